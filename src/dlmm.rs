@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use crate::MeteoraError;
+use crate::types::{DlmmBin, DlmmQuote};
+
+/// Default maximum number of bins a single quote may traverse
+pub const DEFAULT_MAX_BINS: usize = 70;
+
+/// Computes the price of token X in token Y for a bin
+///
+/// # Params
+/// bin_id - The bin identifier
+/// bin_step - The bin step in basis points
+pub fn bin_price(bin_id: i32, bin_step: u16) -> f64 {
+    (1.0 + bin_step as f64 / 10000.0).powi(bin_id)
+}
+
+/// Computes how far a quote's realized rate falls short of the active bin's
+/// price, as a percentage
+///
+/// The fee is excluded, so a quote filled entirely within the active bin has
+/// no price impact beyond rounding.
+///
+/// # Params
+/// quote - A quote from `quote_exact_in`
+/// active_bin_id - The pool's active bin
+/// bin_step - The bin step in basis points
+/// swap_for_y - true when selling X for Y
+pub fn price_impact(quote: &DlmmQuote, active_bin_id: i32, bin_step: u16, swap_for_y: bool) -> f64 {
+    let spot_rate = if swap_for_y {
+        bin_price(active_bin_id, bin_step)
+    } else {
+        1.0 / bin_price(active_bin_id, bin_step)
+    };
+    let amount_in_after_fee = (quote.amount_in - quote.fee_amount) as f64;
+    let realized_rate = quote.amount_out as f64 / amount_in_after_fee;
+    ((1.0 - realized_rate / spot_rate) * 100.0).max(0.0)
+}
+
+/// Quotes an exact-input DLMM swap by consuming liquidity bin-by-bin
+///
+/// Selling X walks bins downward from the active bin consuming Y, selling Y
+/// walks upward consuming X. Bins without liquidity on the output side are
+/// skipped but still count towards `max_bins`.
+///
+/// # Params
+/// bins - The pool's bins, in any order
+/// active_bin_id - The pool's active bin
+/// bin_step - The bin step in basis points
+/// fee_bps - The swap fee in basis points, charged on input
+/// amount_in - The input amount
+/// swap_for_y - true when selling X for Y
+/// max_bins - The maximum number of bins to traverse
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::{dlmm, types::DlmmBin};
+/// # fn main() -> Result<(), meteora_sdk::types::MeteoraError> {
+/// # let active_bin_id = 0;
+/// # let bins = vec![DlmmBin { bin_id: 0, amount_x: 5_000_000, amount_y: 5_000_000 }];
+/// let quote = dlmm::quote_exact_in(&bins, active_bin_id, 25, 20, 1_000_000, true, 70)?;
+/// println!("Average price: {}", quote.average_price);
+/// # Ok(())
+/// # }
+/// ```
+pub fn quote_exact_in(
+    bins: &[DlmmBin],
+    active_bin_id: i32,
+    bin_step: u16,
+    fee_bps: u64,
+    amount_in: u64,
+    swap_for_y: bool,
+    max_bins: usize,
+) -> Result<DlmmQuote, MeteoraError> {
+    if amount_in == 0 {
+        return Err(MeteoraError::InvalidInput(
+            "Amount cannot be zero".to_string(),
+        ));
+    }
+    if fee_bps >= 10000 {
+        return Err(MeteoraError::InvalidInput("Fee too high".to_string()));
+    }
+    let bins_by_id: HashMap<i32, &DlmmBin> = bins.iter().map(|b| (b.bin_id, b)).collect();
+    let fee_amount = (amount_in as u128 * fee_bps as u128).div_ceil(10000) as u64;
+    let mut remaining_in = (amount_in - fee_amount) as f64;
+    let mut amount_out = 0f64;
+    let mut bin_id = active_bin_id;
+    let mut bins_crossed = 0;
+    while remaining_in > 0.0 {
+        if bins_crossed >= max_bins {
+            return Err(MeteoraError::CalculationError(format!(
+                "Swap exceeds {} bins of liquidity",
+                max_bins
+            )));
+        }
+        bins_crossed += 1;
+        if let Some(bin) = bins_by_id.get(&bin_id) {
+            let price = bin_price(bin_id, bin_step);
+            let (available_out, rate) = if swap_for_y {
+                (bin.amount_y as f64, price)
+            } else {
+                (bin.amount_x as f64, 1.0 / price)
+            };
+            let max_in = available_out / rate;
+            if remaining_in <= max_in {
+                amount_out += remaining_in * rate;
+                break;
+            }
+            amount_out += available_out;
+            remaining_in -= max_in;
+        }
+        bin_id = if swap_for_y { bin_id - 1 } else { bin_id + 1 };
+    }
+    let amount_out = amount_out.floor() as u64;
+    if amount_out == 0 {
+        return Err(MeteoraError::NoLiquidityPoolFound);
+    }
+    Ok(DlmmQuote {
+        amount_in,
+        amount_out,
+        fee_amount,
+        average_price: amount_out as f64 / amount_in as f64,
+        bins_crossed,
+        end_bin_id: bin_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bins(amount: u64) -> Vec<DlmmBin> {
+        (-5..=5)
+            .map(|bin_id| DlmmBin {
+                bin_id,
+                amount_x: amount,
+                amount_y: amount,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small_trade_fills_in_active_bin() {
+        let quote = quote_exact_in(&bins(1_000), 0, 100, 0, 500, true, DEFAULT_MAX_BINS).unwrap();
+        assert_eq!(quote.amount_out, 500);
+        assert_eq!(quote.bins_crossed, 1);
+        assert_eq!(quote.end_bin_id, 0);
+        assert_eq!(price_impact(&quote, 0, 100, true), 0.0);
+    }
+
+    #[test]
+    fn large_trade_crosses_several_bins() {
+        // Bin 0 takes 1_000 X at 1.0, bin -1 takes 1_010 X at 1/1.01 and the
+        // last 500 X land in bin -2 at 1/1.01^2
+        let quote = quote_exact_in(&bins(1_000), 0, 100, 0, 2_510, true, DEFAULT_MAX_BINS).unwrap();
+        let expected = 1_000.0 + 1_000.0 + 500.0 * bin_price(-2, 100);
+        assert_eq!(quote.amount_out, expected.floor() as u64);
+        assert_eq!(quote.bins_crossed, 3);
+        assert_eq!(quote.end_bin_id, -2);
+        assert!(quote.average_price < 1.0 && quote.average_price > bin_price(-2, 100));
+        assert!(price_impact(&quote, 0, 100, true) > 0.0);
+    }
+
+    #[test]
+    fn selling_y_walks_bins_upward() {
+        let quote =
+            quote_exact_in(&bins(1_000), 0, 100, 0, 2_500, false, DEFAULT_MAX_BINS).unwrap();
+        assert_eq!(quote.bins_crossed, 3);
+        assert_eq!(quote.end_bin_id, 2);
+        assert!(quote.amount_out < 2_500);
+    }
+
+    #[test]
+    fn fee_is_taken_from_input() {
+        let quote = quote_exact_in(&bins(1_000), 0, 100, 100, 500, true, DEFAULT_MAX_BINS).unwrap();
+        assert_eq!(quote.fee_amount, 5);
+        assert_eq!(quote.amount_out, 495);
+    }
+
+    #[test]
+    fn bins_beyond_the_cap_are_not_traversed() {
+        let result = quote_exact_in(&bins(1_000), 0, 100, 0, 5_000, true, 3);
+        assert!(matches!(result, Err(MeteoraError::CalculationError(_))));
+    }
+
+    #[test]
+    fn empty_bins_are_skipped() {
+        let bins = vec![
+            DlmmBin {
+                bin_id: 0,
+                amount_x: 0,
+                amount_y: 0,
+            },
+            DlmmBin {
+                bin_id: -2,
+                amount_x: 0,
+                amount_y: 1_000,
+            },
+        ];
+        let quote = quote_exact_in(&bins, 0, 100, 0, 100, true, DEFAULT_MAX_BINS).unwrap();
+        assert_eq!(quote.bins_crossed, 3);
+        assert_eq!(quote.end_bin_id, -2);
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+
+/// Consecutive failures after which the current endpoint is demoted
+const FAILOVER_THRESHOLD: u32 = 3;
+/// How long a demoted endpoint is skipped when picking the next one
+const DEMOTION_PERIOD: Duration = Duration::from_secs(30);
+
+struct Endpoint {
+    url: String,
+    client: Arc<RpcClient>,
+}
+
+#[derive(Default)]
+struct EndpointHealth {
+    failures: u32,
+    demoted_until: Option<Instant>,
+}
+
+struct PoolState {
+    current: usize,
+    health: Vec<EndpointHealth>,
+}
+
+/// Ranked list of RPC endpoints with failover on repeated errors and
+/// failback once a demoted endpoint's demotion period has passed
+pub(crate) struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+    state: Mutex<PoolState>,
+}
+
+impl EndpointPool {
+    /// Creates a pool from `(url, client)` pairs, highest priority first
+    pub(crate) fn new(endpoints: Vec<(String, Arc<RpcClient>)>) -> Self {
+        let health = endpoints
+            .iter()
+            .map(|_| EndpointHealth::default())
+            .collect();
+        Self {
+            endpoints: endpoints
+                .into_iter()
+                .map(|(url, client)| Endpoint { url, client })
+                .collect(),
+            state: Mutex::new(PoolState { current: 0, health }),
+        }
+    }
+
+    /// Returns the index and client of the endpoint requests should use
+    pub(crate) fn current(&self) -> (usize, Arc<RpcClient>) {
+        let index = self.select();
+        (index, self.endpoints[index].client.clone())
+    }
+
+    /// Returns the URL of the endpoint requests currently go to
+    pub(crate) fn current_url(&self) -> String {
+        self.endpoints[self.select()].url.clone()
+    }
+
+    /// Fails back to a higher ranked endpoint whose demotion has expired and
+    /// returns the index of the endpoint to use
+    fn select(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let current = state.current;
+        let Some(recovered) = state.health[..current]
+            .iter()
+            .position(|health| health.demoted_until.is_none_or(|until| until <= now))
+        else {
+            return current;
+        };
+        log::info!(
+            "RPC endpoint {} recovered, failing back from {}",
+            self.endpoints[recovered].url,
+            self.endpoints[current].url
+        );
+        state.health[recovered].demoted_until = None;
+        state.current = recovered;
+        recovered
+    }
+
+    pub(crate) fn record_success(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.health[index].failures = 0;
+    }
+
+    /// Counts a failure against an endpoint, rotating away from it once it
+    /// has failed `FAILOVER_THRESHOLD` times in a row
+    pub(crate) fn record_failure(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.health[index].failures += 1;
+        if self.endpoints.len() < 2
+            || index != state.current
+            || state.health[index].failures < FAILOVER_THRESHOLD
+        {
+            return;
+        }
+        let now = Instant::now();
+        state.health[index].failures = 0;
+        state.health[index].demoted_until = Some(now + DEMOTION_PERIOD);
+        // Prefer the highest ranked endpoint that isn't demoted, otherwise
+        // just move on to the next one in order.
+        let next = state
+            .health
+            .iter()
+            .position(|health| health.demoted_until.is_none_or(|until| until <= now))
+            .unwrap_or((index + 1) % self.endpoints.len());
+        log::warn!(
+            "RPC endpoint {} failed {} times in a row, failing over to {}",
+            self.endpoints[index].url,
+            FAILOVER_THRESHOLD,
+            self.endpoints[next].url
+        );
+        state.current = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> EndpointPool {
+        EndpointPool::new(
+            ["http://primary:8899", "http://backup:8899"]
+                .into_iter()
+                .map(|url| (url.to_string(), Arc::new(RpcClient::new(url.to_string()))))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn repeated_failures_fail_over_to_the_next_endpoint() {
+        let pool = pool();
+        for _ in 0..FAILOVER_THRESHOLD - 1 {
+            pool.record_failure(0);
+        }
+        assert_eq!(pool.current().0, 0);
+        pool.record_failure(0);
+        assert_eq!(pool.current().0, 1);
+        assert_eq!(pool.current_url(), "http://backup:8899");
+    }
+
+    #[test]
+    fn recovered_primary_is_reselected_once_its_demotion_expires() {
+        let pool = pool();
+        for _ in 0..FAILOVER_THRESHOLD {
+            pool.record_failure(0);
+        }
+        assert_eq!(pool.current().0, 1);
+        pool.state.lock().unwrap().health[0].demoted_until = Some(Instant::now());
+        assert_eq!(pool.current().0, 0);
+        assert_eq!(pool.current_url(), "http://primary:8899");
+    }
+}
@@ -1,15 +1,84 @@
-use crate::{MeteoraClient, MeteoraError, price::PriceFeed, types::TokenPrice};
-use log::error;
-use solana_sdk::pubkey::Pubkey;
-use std::collections::HashMap;
+use crate::{
+    MeteoraClient, MeteoraError,
+    global::MAX_SIGNATURES_PER_REQUEST,
+    pool::best_pool,
+    price::{PriceFeed, decode_pool_swap},
+    types::{
+        CandleStick, ListenerConfig, ListenerEvent, MarketEvent, MarketEventKind, PoolInfo,
+        TimeFrame, TokenPrice,
+    },
+};
+use chrono::Utc;
+use futures::stream::{StreamExt, select_all};
+use log::{error, warn};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::broadcast;
-use tokio::time::{Duration, sleep};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch};
+use tokio::time::sleep;
+
+/// How often the WebSocket listener refreshes its SOL/USD rate
+const SOL_USD_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Subscription {
+    sender: broadcast::Sender<TokenPrice>,
+    /// Capacity of `sender`, to detect sends that overflow it
+    capacity: usize,
+    /// Overrides the listener's change threshold for this token
+    change_threshold: Option<f64>,
+}
+
+struct EventSubscription {
+    sender: broadcast::Sender<MarketEvent>,
+    kinds: HashSet<MarketEventKind>,
+}
+
+/// Prices seen so far and tokens whose last pricing attempt failed
+#[derive(Default)]
+struct ListenerState {
+    last_prices: HashMap<Pubkey, f64>,
+    failing: HashSet<Pubkey>,
+    /// LP supply of each pool watched for liquidity changes at the last check
+    lp_supplies: HashMap<Pubkey, u64>,
+    /// Newest signature already checked for large swaps, per pool
+    last_signatures: HashMap<Pubkey, Signature>,
+    /// Candle of the current bucket, per token and time frame
+    open_candles: HashMap<(Pubkey, TimeFrame), CandleStick>,
+    /// Prices dropped from full subscriber channels, per token
+    lagged: HashMap<Pubkey, u64>,
+}
 
 /// A listener for monitoring token price changes and notifying subscribers
 pub struct PriceListener {
-    client: Arc<MeteoraClient>,
-    subscriptions: HashMap<Pubkey, broadcast::Sender<TokenPrice>>,
+    price_feed: Arc<PriceFeed>,
+    config: ListenerConfig,
+    subscriptions: HashMap<Pubkey, Subscription>,
+    event_subscriptions: HashMap<Pubkey, EventSubscription>,
+    candle_subscriptions: HashMap<(Pubkey, TimeFrame), broadcast::Sender<CandleStick>>,
+    latest_subscriptions: HashMap<Pubkey, watch::Sender<Option<TokenPrice>>>,
+    status: broadcast::Sender<ListenerEvent>,
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+/// Stops a running `PriceListener` from another task
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+impl ShutdownHandle {
+    /// Asks the listener to stop
+    ///
+    /// The listener returns `Ok(())` as soon as it sees the request, or right
+    /// away if it hasn't started yet. A price
+    /// fetch that is in flight at that moment is cancelled, not awaited, so no
+    /// update is sent after `stop` is observed.
+    pub fn stop(&self) {
+        self.shutdown.send_replace(true);
+    }
 }
 
 impl PriceListener {
@@ -19,20 +88,140 @@ impl PriceListener {
     /// client - MeteoraClient instance for fetching price data
     ///
     /// # Example
-    /// ```
-    /// use meteora_client::MeteoraClient;
-    /// use events::PriceListener;
+    /// ```no_run
+    /// use std::sync::Arc;
+    /// use meteora_sdk::MeteoraClient;
+    /// use meteora_sdk::events::PriceListener;
     ///
-    /// let client = MeteoraClient::new();
+    /// # fn main() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = Arc::new(MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?);
     /// let price_listener = PriceListener::new(client);
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn new(client: Arc<MeteoraClient>) -> Self {
+        Self::new_with_config(client, ListenerConfig::default())
+    }
+
+    /// Creates a new PriceListener with a custom poll interval and change threshold
+    ///
+    /// # Params
+    /// client - MeteoraClient instance for fetching price data
+    /// config - Poll interval and default change threshold
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use meteora_sdk::types::ListenerConfig;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// let config = ListenerConfig {
+    ///     poll_interval: Duration::from_millis(500),
+    ///     change_threshold: 0.001,
+    ///     max_price_age: Some(Duration::from_secs(30)),
+    ///     ..ListenerConfig::default()
+    /// };
+    /// let price_listener = PriceListener::new_with_config(client, config);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_config(client: Arc<MeteoraClient>, config: ListenerConfig) -> Self {
+        Self::new_with_price_feed(Arc::new(PriceFeed::new(client)), config)
+    }
+
+    /// Creates a new PriceListener that prices tokens through an existing PriceFeed
+    ///
+    /// The feed's pool cache is reused on every tick and shared with any other
+    /// users of the same feed, e.g. one created with `PriceFeed::new_with_oracle`.
+    ///
+    /// # Params
+    /// price_feed - The PriceFeed to price subscribed tokens with
+    /// config - Poll interval and default change threshold
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::{price::PriceFeed, types::ListenerConfig};
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pyth_sol_usd = solana_sdk::pubkey!("H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG");
+    /// let price_feed = Arc::new(PriceFeed::new_with_oracle(client, pyth_sol_usd));
+    /// let price_listener =
+    ///     PriceListener::new_with_price_feed(price_feed.clone(), ListenerConfig::default());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_price_feed(price_feed: Arc<PriceFeed>, config: ListenerConfig) -> Self {
+        let (status, _) = broadcast::channel(100);
+        let (shutdown, _) = watch::channel(false);
         Self {
-            client,
+            price_feed,
+            config,
             subscriptions: HashMap::new(),
+            event_subscriptions: HashMap::new(),
+            candle_subscriptions: HashMap::new(),
+            latest_subscriptions: HashMap::new(),
+            status,
+            shutdown: Arc::new(shutdown),
+        }
+    }
+
+    /// Returns a handle that stops `start_listening` from another task
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
+    /// let shutdown = price_listener.shutdown_handle();
+    /// let task = tokio::spawn(async move { price_listener.start_listening().await });
+    /// shutdown.stop();
+    /// task.await.unwrap()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            shutdown: self.shutdown.clone(),
         }
     }
 
+    /// Returns a receiver for the listener's lifecycle events
+    ///
+    /// Lifecycle events are delivered separately from price updates.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::ListenerEvent;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
+    /// let mut status = price_listener.status_receiver();
+    /// tokio::spawn(async move {
+    ///     while let Ok(event) = status.recv().await {
+    ///         if let ListenerEvent::Error { token_mint, message } = event {
+    ///             eprintln!("Pricing {} failed: {}", token_mint, message);
+    ///         }
+    ///     }
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn status_receiver(&self) -> broadcast::Receiver<ListenerEvent> {
+        self.status.subscribe()
+    }
+
     /// Subscribes to price updates for a specific token mint
     ///
     /// # Params
@@ -40,17 +229,248 @@ impl PriceListener {
     /// token_mint - The Pubkey of the token mint to monitor
     ///
     /// # Example
-    /// ```
+    /// ```no_run
     /// use solana_sdk::pubkey;
     ///
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
     /// let mut price_listener = PriceListener::new(client);
     /// let token_mint = pubkey!("So11111111111111111111111111111111111111112");
     /// let mut receiver = price_listener.subscribe(token_mint);
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn subscribe(&mut self, token_mint: Pubkey) -> broadcast::Receiver<TokenPrice> {
-        let (tx, rx) = broadcast::channel(100);
-        self.subscriptions.insert(token_mint, tx);
-        rx
+        self.insert_subscription(token_mint, None)
+    }
+
+    /// Subscribes to price updates for a token with its own change threshold
+    ///
+    /// # Params
+    /// token_mint - The Pubkey of the token mint to monitor
+    /// threshold - Relative price change that triggers an update, e.g. `0.001` for 0.1%
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
+    /// # let token_mint = spl_token::native_mint::ID;
+    /// let mut receiver = price_listener.subscribe_with_threshold(token_mint, 0.001);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscribe_with_threshold(
+        &mut self,
+        token_mint: Pubkey,
+        threshold: f64,
+    ) -> broadcast::Receiver<TokenPrice> {
+        self.insert_subscription(token_mint, Some(threshold))
+    }
+
+    /// Subscribes to price updates for several tokens on a single channel
+    ///
+    /// Each `TokenPrice` carries its `token_mint`, so updates can be told
+    /// apart. The tokens stay separate subscriptions sharing one sender:
+    /// `unsubscribe` stops a single token without closing the channel for the
+    /// others, and subscribing to one of them again moves it to a new channel.
+    ///
+    /// # Params
+    /// mints - The token mints to monitor
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
+    /// # let sol_mint = spl_token::native_mint::ID;
+    /// # let bonk_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+    /// let mut receiver = price_listener.subscribe_many(&[sol_mint, bonk_mint]);
+    /// while let Ok(price) = receiver.recv().await {
+    ///     println!("{}: {}", price.token_mint, price.usd_price);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscribe_many(&mut self, mints: &[Pubkey]) -> broadcast::Receiver<TokenPrice> {
+        let capacity = self.config.channel_capacity * mints.len().max(1);
+        let (sender, receiver) = broadcast::channel(capacity);
+        for token_mint in mints {
+            self.subscriptions.insert(
+                *token_mint,
+                Subscription {
+                    sender: sender.clone(),
+                    capacity,
+                    change_threshold: None,
+                },
+            );
+        }
+        receiver
+    }
+
+    fn insert_subscription(
+        &mut self,
+        token_mint: Pubkey,
+        change_threshold: Option<f64>,
+    ) -> broadcast::Receiver<TokenPrice> {
+        let (sender, receiver) = broadcast::channel(self.config.channel_capacity);
+        self.subscriptions.insert(
+            token_mint,
+            Subscription {
+                sender,
+                capacity: self.config.channel_capacity,
+                change_threshold,
+            },
+        );
+        receiver
+    }
+
+    /// Subscribes to the most recent price of a token, without a backlog
+    ///
+    /// The receiver holds only the latest price that moved past the change
+    /// threshold, `None` until the first one, so a slow consumer skips
+    /// straight to the current state instead of working through queued
+    /// updates or lagging. Dropping every receiver ends the subscription.
+    ///
+    /// # Params
+    /// token_mint - The Pubkey of the token mint to monitor
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
+    /// # let token_mint = spl_token::native_mint::ID;
+    /// let mut latest = price_listener.subscribe_latest_only(token_mint);
+    /// while latest.changed().await.is_ok() {
+    ///     if let Some(price) = latest.borrow_and_update().clone() {
+    ///         println!("{}", price.usd_price);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscribe_latest_only(
+        &mut self,
+        token_mint: Pubkey,
+    ) -> watch::Receiver<Option<TokenPrice>> {
+        let (sender, receiver) = watch::channel(None);
+        self.latest_subscriptions.insert(token_mint, sender);
+        receiver
+    }
+
+    /// Subscribes to market events of the given kinds for a token
+    ///
+    /// Price changes follow the same threshold as `subscribe`. Liquidity
+    /// changes are detected from the LP supply of the token's deepest pool
+    /// between checks and valued at the pool's current TVL. Large swaps are
+    /// found by decoding the pool's transactions since the previous check.
+    /// Both are reported from the first check after subscribing onwards, using
+    /// the USD thresholds in `ListenerConfig`. A token has at most one event
+    /// subscription; subscribing again replaces it.
+    ///
+    /// # Params
+    /// token_mint - The Pubkey of the token mint to monitor
+    /// kinds - The kinds of events to receive
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::MarketEventKind;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
+    /// # let token_mint = spl_token::native_mint::ID;
+    /// let mut events = price_listener.subscribe_events(
+    ///     token_mint,
+    ///     &[MarketEventKind::LiquidityChange, MarketEventKind::LargeSwap],
+    /// );
+    /// while let Ok(event) = events.recv().await {
+    ///     println!("{:?}", event);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscribe_events(
+        &mut self,
+        token_mint: Pubkey,
+        kinds: &[MarketEventKind],
+    ) -> broadcast::Receiver<MarketEvent> {
+        let (sender, receiver) = broadcast::channel(self.config.channel_capacity);
+        self.event_subscriptions.insert(
+            token_mint,
+            EventSubscription {
+                sender,
+                kinds: kinds.iter().copied().collect(),
+            },
+        );
+        receiver
+    }
+
+    /// Stops market events for a token, leaving its price subscription in place
+    pub fn unsubscribe_events(&mut self, token_mint: &Pubkey) {
+        self.event_subscriptions.remove(token_mint);
+    }
+
+    /// Subscribes to candles of a token, sent as each one closes
+    ///
+    /// Candles are built from the USD prices the listener observes, so their
+    /// resolution is the poll interval, or the reserve updates when listening
+    /// over WebSocket, and their volume is always zero. A candle is sent on
+    /// the first price check after its bucket ends. Buckets without any price
+    /// are sent as flat candles at the previous close, so every bucket is
+    /// reported once the first price has been seen.
+    ///
+    /// # Params
+    /// token_mint - The Pubkey of the token mint to build candles for
+    /// timeframe - The time frame of the candles
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::TimeFrame;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
+    /// # let token_mint = spl_token::native_mint::ID;
+    /// let mut candles = price_listener.subscribe_candles(token_mint, TimeFrame::M1);
+    /// while let Ok(candle) = candles.recv().await {
+    ///     println!("{} closed at {}", candle.timestamp, candle.close);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscribe_candles(
+        &mut self,
+        token_mint: Pubkey,
+        timeframe: TimeFrame,
+    ) -> broadcast::Receiver<CandleStick> {
+        let (sender, receiver) = broadcast::channel(self.config.channel_capacity);
+        self.candle_subscriptions
+            .insert((token_mint, timeframe), sender);
+        receiver
+    }
+
+    /// Stops candles of one time frame for a token
+    pub fn unsubscribe_candles(&mut self, token_mint: &Pubkey, timeframe: &TimeFrame) {
+        self.candle_subscriptions
+            .remove(&(*token_mint, timeframe.clone()));
     }
 
     /// Unsubscribes from price updates for a specific token mint
@@ -59,76 +479,1103 @@ impl PriceListener {
     /// token_mint - The Pubkey of the token mint to stop monitoring
     ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use solana_sdk::pubkey;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
     /// let token_mint = pubkey!("So11111111111111111111111111111111111111112");
     /// price_listener.unsubscribe(&token_mint);
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn unsubscribe(&mut self, token_mint: &Pubkey) {
         self.subscriptions.remove(token_mint);
+        self.latest_subscriptions.remove(token_mint);
     }
 
     /// Starts listening for price changes and notifying subscribers
     ///
-    /// This method runs in an infinite loop, checking prices every 5 seconds
-    /// and notifying subscribers when price changes exceed 1%
+    /// This method runs in an infinite loop, checking prices every
+    /// `poll_interval` and notifying subscribers when the price moves by more
+    /// than the subscription's threshold, or the configured one. All subscribed
+    /// tokens are priced together from one pool snapshot per cycle. Subscriptions
+    /// without receivers are pruned, and lifecycle events are published on the
+    /// status channel. The loop returns `Ok(())` once `stop` is called on a
+    /// handle from `shutdown_handle`.
     ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
     /// // Typically run in a separate task
     /// tokio::spawn(async move {
     ///     price_listener.start_listening().await.unwrap();
     /// });
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn start_listening(&mut self) -> Result<(), MeteoraError> {
-        let mut last_prices: HashMap<Pubkey, f64> = HashMap::new();
+        let price_feed = self.price_feed.clone();
+        let mut state = ListenerState::default();
+        let mut shutdown = self.shutdown.subscribe();
+        self.emit_status(ListenerEvent::Started);
+
+        while !self
+            .poll_and_wait(&price_feed, &mut state, &mut shutdown)
+            .await
+        {}
+        self.finish();
+        Ok(())
+    }
+
+    /// Starts listening for price changes over a WebSocket connection
+    ///
+    /// Instead of polling, the reserve accounts of each token's deepest pool
+    /// are watched with `accountSubscribe`, and a token is only repriced when
+    /// one of them changes. Thresholds, pruning and lifecycle events work as in
+    /// `start_listening`. When the connection can't be opened or drops, the
+    /// listener emits `Disconnected`, polls once, waits `poll_interval` and
+    /// reconnects. The SOL/USD rate is refreshed at most every
+    /// `SOL_USD_REFRESH_INTERVAL`. Subscriptions can't be added while the
+    /// listener runs, so once no token is watched it closes the connection
+    /// and only waits for `stop`.
+    ///
+    /// # Params
+    /// ws_url - The WebSocket URL of the RPC node, e.g. `wss://api.mainnet-beta.solana.com`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
+    /// tokio::spawn(async move {
+    ///     price_listener
+    ///         .start_listening_ws("wss://api.mainnet-beta.solana.com")
+    ///         .await
+    ///         .unwrap();
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn start_listening_ws(&mut self, ws_url: &str) -> Result<(), MeteoraError> {
+        let price_feed = self.price_feed.clone();
+        let mut state = ListenerState::default();
+        let mut shutdown = self.shutdown.subscribe();
+        self.emit_status(ListenerEvent::Started);
 
         loop {
-            for (token_mint, sender) in &self.subscriptions {
-                match self.get_current_price(token_mint).await {
-                    Ok(current_price) => {
-                        let should_notify = match last_prices.get(token_mint) {
-                            Some(&last_price) => {
-                                let change =
-                                    (current_price.sol_price - last_price).abs() / last_price;
-                                change > 0.01 // 1%  
-                            }
-                            None => true,
-                        };
-                        if should_notify {
-                            if sender.receiver_count() > 0 {
-                                let _ = sender.send(current_price.clone());
-                            }
-                            last_prices.insert(*token_mint, current_price.sol_price);
-                        }
+            self.prune_subscriptions();
+            if self.watched_mints().is_empty() {
+                let _ = shutdown.wait_for(|stopped| *stopped).await;
+                break;
+            }
+            let stopped = match PubsubClient::new(ws_url).await {
+                Ok(pubsub) => {
+                    let stopped = self
+                        .run_ws_session(&pubsub, &price_feed, &mut state, &mut shutdown)
+                        .await;
+                    if let Err(e) = pubsub.shutdown().await {
+                        warn!("Failed to close WebSocket connection: {:?}", e);
+                    }
+                    stopped
+                }
+                Err(e) => {
+                    self.emit_status(ListenerEvent::Disconnected {
+                        message: format!("{:?}", e),
+                    });
+                    false
+                }
+            };
+            if stopped
+                || self
+                    .poll_and_wait(&price_feed, &mut state, &mut shutdown)
+                    .await
+            {
+                break;
+            }
+        }
+        self.finish();
+        Ok(())
+    }
+
+    /// Prices every subscription once, then sleeps for `poll_interval`
+    ///
+    /// Returns `true` if a shutdown was requested in the meantime.
+    async fn poll_and_wait(
+        &mut self,
+        price_feed: &PriceFeed,
+        state: &mut ListenerState,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> bool {
+        self.prune_subscriptions();
+        let token_mints = self.watched_mints();
+        let prices = tokio::select! {
+            prices = price_feed.get_current_prices(&token_mints) => prices,
+            _ = shutdown.wait_for(|stopped| *stopped) => return true,
+        };
+        let usd_prices: HashMap<Pubkey, f64> = prices
+            .iter()
+            .filter_map(|(token_mint, price)| Some((*token_mint, price.as_ref().ok()?.usd_price)))
+            .collect();
+        for (token_mint, price) in prices {
+            self.publish_price(state, token_mint, price);
+        }
+        tokio::select! {
+            _ = self.check_pool_events(price_feed, state, &usd_prices) => {}
+            _ = shutdown.wait_for(|stopped| *stopped) => return true,
+        }
+        tokio::select! {
+            _ = sleep(self.config.poll_interval) => false,
+            _ = shutdown.wait_for(|stopped| *stopped) => true,
+        }
+    }
+
+    /// Subscribes to the reserves of each token's deepest pool and reprices
+    /// tokens as they change, until the connection drops or a shutdown is
+    /// requested
+    ///
+    /// Returns `true` if a shutdown was requested.
+    async fn run_ws_session(
+        &mut self,
+        pubsub: &PubsubClient,
+        price_feed: &PriceFeed,
+        state: &mut ListenerState,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> bool {
+        self.prune_subscriptions();
+        let pool_manager = price_feed.pool_manager();
+        let pools = match pool_manager.get_all_pool_infos().await {
+            Ok(pools) => pools,
+            Err(e) => {
+                error!("Failed to load pools for WebSocket listener: {:?}", e);
+                return false;
+            }
+        };
+        let mut sol_usd = price_feed.sol_usd_price_for_pools(&pools).await;
+        let mut sol_usd_updated = Instant::now();
+        // Pool address -> tokens priced from that pool
+        let mut watched: HashMap<Pubkey, (PoolInfo, Vec<Pubkey>)> = HashMap::new();
+        for token_mint in self.watched_mints() {
+            let price = price_feed.price_from_pools(&token_mint, &pools, sol_usd);
+            if let Some(pool_info) = best_pool(&token_mint, &pools) {
+                watched
+                    .entry(pool_info.address)
+                    .or_insert_with(|| (pool_info.clone(), Vec::new()))
+                    .1
+                    .push(token_mint);
+            }
+            self.publish_price(state, token_mint, price);
+        }
+        if watched.is_empty() {
+            return false;
+        }
+
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(price_feed.client().commitment),
+            ..Default::default()
+        };
+        let mut streams = Vec::new();
+        let mut unsubscribes = Vec::new();
+        for (pool_address, (pool_info, _)) in &watched {
+            for reserve in [pool_info.token_a_reserve, pool_info.token_b_reserve] {
+                match pubsub
+                    .account_subscribe(&reserve, Some(config.clone()))
+                    .await
+                {
+                    Ok((stream, unsubscribe)) => {
+                        let pool_address = *pool_address;
+                        streams.push(stream.map(move |_| pool_address).boxed());
+                        unsubscribes.push(unsubscribe);
                     }
                     Err(e) => {
-                        error!("Failed to get price for {:?}: {:?}", token_mint, e);
+                        self.emit_status(ListenerEvent::Disconnected {
+                            message: format!("{:?}", e),
+                        });
+                        return false;
                     }
                 }
             }
+        }
+
+        let mut notifications = select_all(streams);
+        let stopped = loop {
+            let pool_address = tokio::select! {
+                notification = notifications.next() => notification,
+                _ = shutdown.wait_for(|stopped| *stopped) => break true,
+            };
+            let Some(pool_address) = pool_address else {
+                self.emit_status(ListenerEvent::Disconnected {
+                    message: "WebSocket subscription closed".to_string(),
+                });
+                break false;
+            };
+            if sol_usd_updated.elapsed() >= SOL_USD_REFRESH_INTERVAL {
+                sol_usd = price_feed.get_sol_usd_price().await;
+                sol_usd_updated = Instant::now();
+            }
+            let Some((_, token_mints)) = watched.get(&pool_address) else {
+                continue;
+            };
+            let pool_info = pool_manager.get_pool_info(&pool_address).await;
+            for token_mint in token_mints {
+                let price = pool_info.clone().and_then(|pool_info| {
+                    price_feed.price_from_pools(
+                        token_mint,
+                        std::slice::from_ref(&pool_info),
+                        sol_usd,
+                    )
+                });
+                let usd_price = price.as_ref().ok().map(|price| price.usd_price);
+                self.publish_price(state, *token_mint, price);
+                if let (Ok(pool_info), Some(usd_price)) = (&pool_info, usd_price) {
+                    self.detect_pool_events(price_feed, state, *token_mint, pool_info, usd_price)
+                        .await;
+                }
+            }
+        };
+        drop(notifications);
+        for unsubscribe in unsubscribes {
+            unsubscribe().await;
+        }
+        stopped
+    }
 
-            sleep(Duration::from_secs(5)).await;
+    /// Sends a price to its subscribers if it moved past the threshold, and
+    /// tracks errors and recoveries on the status channel
+    fn publish_price(
+        &self,
+        state: &mut ListenerState,
+        token_mint: Pubkey,
+        price: Result<TokenPrice, MeteoraError>,
+    ) {
+        let usd_price = price
+            .as_ref()
+            .ok()
+            .filter(|price| {
+                self.config
+                    .max_price_age
+                    .is_none_or(|max_age| !price.is_stale(max_age))
+            })
+            .map(|price| price.usd_price);
+        self.update_candles(state, token_mint, usd_price, Utc::now().timestamp());
+        let subscription = self.subscriptions.get(&token_mint);
+        if subscription.is_none()
+            && !self.event_subscriptions.contains_key(&token_mint)
+            && !self.latest_subscriptions.contains_key(&token_mint)
+        {
+            return;
+        }
+        let threshold = subscription
+            .and_then(|subscription| subscription.change_threshold)
+            .unwrap_or(self.config.change_threshold);
+        match price {
+            Ok(current_price) => {
+                if state.failing.remove(&token_mint) {
+                    self.emit_status(ListenerEvent::Reconnected { token_mint });
+                }
+                if let Some(max_age) = self.config.max_price_age
+                    && current_price.is_stale(max_age)
+                {
+                    warn!(
+                        "Skipping stale price for {:?} from {}",
+                        token_mint, current_price.timestamp
+                    );
+                    return;
+                }
+                let should_notify = match state.last_prices.get(&token_mint) {
+                    Some(&last_price) => {
+                        let change = (current_price.sol_price - last_price).abs() / last_price;
+                        change > threshold
+                    }
+                    None => true,
+                };
+                if should_notify {
+                    if let Some(subscription) = subscription
+                        && subscription.sender.receiver_count() > 0
+                    {
+                        if self.config.track_lag
+                            && subscription.sender.len() >= subscription.capacity
+                        {
+                            self.record_lag(state, token_mint);
+                        }
+                        let _ = subscription.sender.send(current_price.clone());
+                    }
+                    if let Some(latest) = self.latest_subscriptions.get(&token_mint) {
+                        latest.send_replace(Some(current_price.clone()));
+                    }
+                    self.send_event(&token_mint, MarketEvent::PriceChange(current_price.clone()));
+                    state
+                        .last_prices
+                        .insert(token_mint, current_price.sol_price);
+                }
+            }
+            Err(e) => {
+                error!("Failed to get price for {:?}: {:?}", token_mint, e);
+                state.failing.insert(token_mint);
+                self.emit_status(ListenerEvent::Error {
+                    token_mint,
+                    message: format!("{:?}", e),
+                });
+            }
         }
     }
 
-    /// Gets the current price for a token mint
+    /// Counts a price dropped from a full channel and reports it
+    fn record_lag(&self, state: &mut ListenerState, token_mint: Pubkey) {
+        let dropped = state.lagged.entry(token_mint).or_default();
+        *dropped += 1;
+        warn!(
+            "Subscriber of {:?} is lagging, {} prices dropped so far",
+            token_mint, dropped
+        );
+        self.emit_status(ListenerEvent::SubscriberLagged {
+            token_mint,
+            dropped: *dropped,
+        });
+    }
+
+    /// Every token with a price, event or candle subscription
+    fn watched_mints(&self) -> Vec<Pubkey> {
+        let token_mints: HashSet<Pubkey> = self
+            .subscriptions
+            .keys()
+            .chain(self.event_subscriptions.keys())
+            .chain(self.latest_subscriptions.keys())
+            .chain(
+                self.candle_subscriptions
+                    .keys()
+                    .map(|(token_mint, _)| token_mint),
+            )
+            .copied()
+            .collect();
+        token_mints.into_iter().collect()
+    }
+
+    /// Adds a price observed at `now` to the token's open candles and sends
+    /// the candles that closed before `now`
     ///
-    /// # Params
-    /// token_mint - The Pubkey of the token mint
+    /// `usd_price` is `None` when pricing failed, which still closes candles
+    /// whose bucket has ended.
+    fn update_candles(
+        &self,
+        state: &mut ListenerState,
+        token_mint: Pubkey,
+        usd_price: Option<f64>,
+        now: i64,
+    ) {
+        for ((mint, time_frame), sender) in &self.candle_subscriptions {
+            if *mint != token_mint {
+                continue;
+            }
+            let bucket = self.price_feed.bucket_start(now, time_frame);
+            let timeframe_seconds = self.price_feed.get_timeframe_seconds(time_frame);
+            let open_candle = state.open_candles.remove(&(token_mint, time_frame.clone()));
+            let (closed, open_candle) = roll_candle(
+                open_candle,
+                bucket,
+                timeframe_seconds,
+                time_frame,
+                usd_price,
+            );
+            for candle in closed {
+                if sender.receiver_count() > 0 {
+                    let _ = sender.send(candle);
+                }
+            }
+            if let Some(open_candle) = open_candle {
+                state
+                    .open_candles
+                    .insert((token_mint, time_frame.clone()), open_candle);
+            }
+        }
+    }
+
+    /// Sends an event to the token's event subscription if it asked for its kind
+    fn send_event(&self, token_mint: &Pubkey, event: MarketEvent) {
+        if let Some(subscription) = self.event_subscriptions.get(token_mint)
+            && subscription.kinds.contains(&event.kind())
+            && subscription.sender.receiver_count() > 0
+        {
+            let _ = subscription.sender.send(event);
+        }
+    }
+
+    /// Checks the deepest pool of every token subscribed to liquidity or
+    /// large swap events, see `detect_pool_events`
+    async fn check_pool_events(
+        &self,
+        price_feed: &PriceFeed,
+        state: &mut ListenerState,
+        usd_prices: &HashMap<Pubkey, f64>,
+    ) {
+        let token_mints: Vec<Pubkey> = self
+            .event_subscriptions
+            .iter()
+            .filter(|(_, subscription)| {
+                subscription
+                    .kinds
+                    .contains(&MarketEventKind::LiquidityChange)
+                    || subscription.kinds.contains(&MarketEventKind::LargeSwap)
+            })
+            .map(|(token_mint, _)| *token_mint)
+            .collect();
+        if token_mints.is_empty() {
+            return;
+        }
+        let pool_manager = price_feed.pool_manager();
+        let pools = match pool_manager.get_all_pool_infos().await {
+            Ok(pools) => pools,
+            Err(e) => {
+                warn!("Failed to load pools for market events: {:?}", e);
+                return;
+            }
+        };
+        for token_mint in token_mints {
+            let (Some(&usd_price), Some(pool_info)) =
+                (usd_prices.get(&token_mint), best_pool(&token_mint, &pools))
+            else {
+                continue;
+            };
+            // The snapshot may be cached, so read the reserves and LP supply fresh
+            match pool_manager.get_pool_info(&pool_info.address).await {
+                Ok(pool_info) => {
+                    self.detect_pool_events(price_feed, state, token_mint, &pool_info, usd_price)
+                        .await
+                }
+                Err(e) => warn!("Failed to load pool {}: {:?}", pool_info.address, e),
+            }
+        }
+    }
+
+    /// Emits liquidity changes and large swaps of `token_mint` in `pool_info`
+    /// since the pool was last checked
     ///
-    async fn get_current_price(&self, token_mint: &Pubkey) -> Result<TokenPrice, MeteoraError> {
-        let price_feed = PriceFeed::new(self.client.clone());
-        price_feed.get_current_price(token_mint).await
+    /// The first check of a pool only records its LP supply and newest
+    /// signature.
+    async fn detect_pool_events(
+        &self,
+        price_feed: &PriceFeed,
+        state: &mut ListenerState,
+        token_mint: Pubkey,
+        pool_info: &PoolInfo,
+        usd_price: f64,
+    ) {
+        let Some(subscription) = self.event_subscriptions.get(&token_mint) else {
+            return;
+        };
+        let token_decimals = if pool_info.token_a_mint == token_mint {
+            pool_info.token_a_decimals
+        } else {
+            pool_info.token_b_decimals
+        };
+        let to_usd = |amount: u64| amount as f64 / 10f64.powi(token_decimals as i32) * usd_price;
+
+        if subscription
+            .kinds
+            .contains(&MarketEventKind::LiquidityChange)
+            && let Some(previous) = state
+                .lp_supplies
+                .insert(pool_info.address, pool_info.lp_supply)
+            && previous != pool_info.lp_supply
+            && pool_info.lp_supply > 0
+        {
+            let tvl_usd = to_usd(pool_info.liquidity_in(&token_mint).unwrap_or_default());
+            let share = (pool_info.lp_supply as f64 - previous as f64) / pool_info.lp_supply as f64;
+            let delta_usd = tvl_usd * share;
+            if delta_usd.abs() >= self.config.liquidity_change_threshold_usd {
+                self.send_event(
+                    &token_mint,
+                    MarketEvent::LiquidityChange {
+                        pool: pool_info.address,
+                        delta_usd,
+                    },
+                );
+            }
+        }
+
+        if !subscription.kinds.contains(&MarketEventKind::LargeSwap) {
+            return;
+        }
+        let client = price_feed.client();
+        let until = state.last_signatures.get(&pool_info.address).copied();
+        let signatures = match client
+            .get_signatures_for_address(&pool_info.address, None, until, MAX_SIGNATURES_PER_REQUEST)
+            .await
+        {
+            Ok(signatures) => signatures,
+            Err(e) => {
+                warn!(
+                    "Failed to get signatures for {}: {:?}",
+                    pool_info.address, e
+                );
+                return;
+            }
+        };
+        if let Some(newest) = signatures
+            .first()
+            .and_then(|status| status.signature.parse().ok())
+        {
+            state.last_signatures.insert(pool_info.address, newest);
+        }
+        if until.is_none() {
+            return;
+        }
+        for status in signatures.iter().filter(|status| status.err.is_none()) {
+            let Ok(signature) = status.signature.parse::<Signature>() else {
+                continue;
+            };
+            let Ok(tx) = client.get_transaction(&signature).await else {
+                continue;
+            };
+            // The swap is valued from the watched token's amount and price,
+            // so its quote token price isn't needed
+            let Ok(swap) = decode_pool_swap(&tx, pool_info, &token_mint, 0.0) else {
+                continue;
+            };
+            let token_amount = if swap.input_mint == token_mint {
+                swap.input_amount
+            } else {
+                swap.output_amount
+            };
+            let amount_usd = to_usd(token_amount);
+            if amount_usd >= self.config.large_swap_threshold_usd {
+                self.send_event(
+                    &token_mint,
+                    MarketEvent::LargeSwap {
+                        pool: pool_info.address,
+                        amount_usd,
+                    },
+                );
+            }
+        }
+    }
+
+    fn finish(&self) {
+        // Clear the request so the listener can be started again
+        self.shutdown.send_replace(false);
+        self.emit_status(ListenerEvent::Stopped);
+    }
+
+    /// Removes subscriptions whose receivers have all been dropped
+    fn prune_subscriptions(&mut self) {
+        let pruned: Vec<Pubkey> = self
+            .subscriptions
+            .iter()
+            .filter(|(_, subscription)| subscription.sender.receiver_count() == 0)
+            .map(|(token_mint, _)| *token_mint)
+            .collect();
+        for token_mint in pruned {
+            self.subscriptions.remove(&token_mint);
+            self.emit_status(ListenerEvent::SubscriptionPruned { token_mint });
+        }
+        let pruned: Vec<Pubkey> = self
+            .event_subscriptions
+            .iter()
+            .filter(|(_, subscription)| subscription.sender.receiver_count() == 0)
+            .map(|(token_mint, _)| *token_mint)
+            .collect();
+        for token_mint in pruned {
+            self.event_subscriptions.remove(&token_mint);
+            self.emit_status(ListenerEvent::SubscriptionPruned { token_mint });
+        }
+        let pruned: Vec<Pubkey> = self
+            .latest_subscriptions
+            .iter()
+            .filter(|(_, sender)| sender.is_closed())
+            .map(|(token_mint, _)| *token_mint)
+            .collect();
+        for token_mint in pruned {
+            self.latest_subscriptions.remove(&token_mint);
+            self.emit_status(ListenerEvent::SubscriptionPruned { token_mint });
+        }
+        let pruned: Vec<(Pubkey, TimeFrame)> = self
+            .candle_subscriptions
+            .iter()
+            .filter(|(_, sender)| sender.receiver_count() == 0)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for (token_mint, time_frame) in pruned {
+            self.candle_subscriptions.remove(&(token_mint, time_frame));
+            self.emit_status(ListenerEvent::SubscriptionPruned { token_mint });
+        }
+    }
+
+    fn emit_status(&self, event: ListenerEvent) {
+        if self.status.receiver_count() > 0 {
+            let _ = self.status.send(event);
+        }
     }
 
     /// Gets the number of active subscriptions
     ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, events::PriceListener};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut price_listener = PriceListener::new(client);
     /// let subscription_count = price_listener.get_subscription_count();
     /// println!("Monitoring {} tokens", subscription_count);
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn get_subscription_count(&self) -> usize {
         self.subscriptions.len()
     }
 }
+
+/// Moves a candle forward to the bucket starting at `bucket`
+///
+/// Returns the candles that closed, oldest first, and the candle of the
+/// current bucket. When `open_candle` is from an earlier bucket it is closed,
+/// and any buckets skipped in between are filled with flat candles at its
+/// close. `usd_price`, if any, is then added to the current bucket's candle.
+fn roll_candle(
+    open_candle: Option<CandleStick>,
+    bucket: i64,
+    timeframe_seconds: i64,
+    time_frame: &TimeFrame,
+    usd_price: Option<f64>,
+) -> (Vec<CandleStick>, Option<CandleStick>) {
+    let flat_candle = |price: f64, timestamp: i64| CandleStick {
+        open: price,
+        high: price,
+        low: price,
+        close: price,
+        volume: 0.0,
+        timestamp,
+        time_frame: time_frame.clone(),
+    };
+    let mut closed = Vec::new();
+    let mut current = match open_candle {
+        Some(candle) if candle.timestamp < bucket => {
+            let close = candle.close;
+            let mut timestamp = candle.timestamp + timeframe_seconds;
+            closed.push(candle);
+            while timestamp < bucket {
+                closed.push(flat_candle(close, timestamp));
+                timestamp += timeframe_seconds;
+            }
+            Some(flat_candle(close, bucket))
+        }
+        open_candle => open_candle,
+    };
+    if let Some(price) = usd_price {
+        match &mut current {
+            Some(candle) => {
+                candle.high = candle.high.max(price);
+                candle.low = candle.low.min(price);
+                candle.close = price;
+            }
+            None => current = Some(flat_candle(price, bucket)),
+        }
+    }
+    (closed, current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::vault_address;
+    use crate::test_util::{MockRpc, mint_account, token_account};
+    use crate::types::PriceSource;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+
+    fn listener(rpc: &MockRpc) -> PriceListener {
+        PriceListener::new_with_config(
+            rpc.client(),
+            ListenerConfig {
+                poll_interval: Duration::from_millis(10),
+                ..ListenerConfig::default()
+            },
+        )
+    }
+
+    /// Accepts WebSocket connections and drops them right away, counting them
+    async fn dropping_server() -> (String, Arc<AtomicUsize>) {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", server.local_addr().unwrap());
+        let accepts = Arc::new(AtomicUsize::new(0));
+        let counter = accepts.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = server.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                drop(stream);
+            }
+        });
+        (url, accepts)
+    }
+
+    #[tokio::test]
+    async fn ws_listener_reconnects_after_a_dropped_connection() {
+        let rpc = MockRpc::new();
+        let mut price_listener = listener(&rpc);
+        let _receiver = price_listener.subscribe(Pubkey::new_unique());
+        let mut status = price_listener.status_receiver();
+        let shutdown = price_listener.shutdown_handle();
+        let (url, accepts) = dropping_server().await;
+
+        let task = tokio::spawn(async move { price_listener.start_listening_ws(&url).await });
+        let mut disconnected = false;
+        while !disconnected || accepts.load(Ordering::SeqCst) < 2 {
+            match tokio::time::timeout(Duration::from_secs(5), status.recv()).await {
+                Ok(Ok(ListenerEvent::Disconnected { .. })) => disconnected = true,
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => panic!("status channel closed: {:?}", e),
+                Err(_) => panic!("listener didn't reconnect"),
+            }
+        }
+        shutdown.stop();
+        task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_pricing_emits_an_error_event() {
+        let rpc = MockRpc::new();
+        let mut price_listener = listener(&rpc);
+        // No pool holds the token, so pricing it fails
+        let token_mint = Pubkey::new_unique();
+        let _receiver = price_listener.subscribe(token_mint);
+        let mut status = price_listener.status_receiver();
+        let shutdown = price_listener.shutdown_handle();
+
+        let task = tokio::spawn(async move { price_listener.start_listening().await });
+        let mut events = Vec::new();
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), status.recv()).await {
+                Ok(Ok(ListenerEvent::Error {
+                    token_mint: failed, ..
+                })) => {
+                    assert_eq!(failed, token_mint);
+                    break;
+                }
+                Ok(Ok(event)) => events.push(event),
+                Ok(Err(e)) => panic!("status channel closed: {:?}", e),
+                Err(_) => panic!("no error event, got {:?}", events),
+            }
+        }
+        assert!(matches!(events.first(), Some(ListenerEvent::Started)));
+        shutdown.stop();
+        task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn each_poll_reports_changes_above_the_subscriptions_threshold() {
+        let rpc = MockRpc::new();
+        let wsol = spl_token::native_mint::ID;
+        let (token, coarse_token) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pools = [
+            rpc.add_pool(token, wsol, 1_000_000, 2_000_000),
+            rpc.add_pool(coarse_token, wsol, 1_000_000, 2_000_000),
+        ];
+        let mut price_listener = listener(&rpc);
+        let mut receiver = price_listener.subscribe(token);
+        let mut coarse_receiver = price_listener.subscribe_with_threshold(coarse_token, 0.5);
+        let shutdown = price_listener.shutdown_handle();
+        let task = tokio::spawn(async move { price_listener.start_listening().await });
+
+        // Raise both prices by 2% after each update, so every poll sees a change
+        let mut sol_reserve = 2_000_000u64;
+        for _ in 0..5 {
+            let price = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .expect("no update within the poll interval")
+                .unwrap();
+            assert_eq!(price.sol_price, sol_reserve as f64 / 1_000_000.0);
+            sol_reserve = sol_reserve * 102 / 100;
+            for pool in &pools {
+                rpc.set_account(
+                    pool.token_b_reserve,
+                    token_account(wsol, vault_address(&wsol), sol_reserve),
+                );
+            }
+        }
+        // The last change is reported, then unchanged prices are not
+        receiver.recv().await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        shutdown.stop();
+        task.await.unwrap().unwrap();
+        assert!(receiver.try_recv().is_err());
+        // A 10% move overall never crosses the 50% threshold after the first price
+        assert!(coarse_receiver.try_recv().is_ok());
+        assert!(coarse_receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn stop_ends_a_listener_waiting_for_its_next_poll() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        rpc.add_pool(token, spl_token::native_mint::ID, 1_000_000, 2_000_000);
+        let mut price_listener = PriceListener::new_with_config(
+            rpc.client(),
+            ListenerConfig {
+                poll_interval: Duration::from_secs(3_600),
+                ..ListenerConfig::default()
+            },
+        );
+        let mut receiver = price_listener.subscribe(token);
+        let mut status = price_listener.status_receiver();
+        let shutdown = price_listener.shutdown_handle();
+        let task = tokio::spawn(async move { price_listener.start_listening().await });
+
+        // The first poll has been published, so the listener is now sleeping
+        receiver.recv().await.unwrap();
+        shutdown.stop();
+        tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .expect("listener didn't stop")
+            .unwrap()
+            .unwrap();
+        let mut events = Vec::new();
+        while let Ok(event) = status.try_recv() {
+            events.push(event);
+        }
+        assert!(matches!(events.last(), Some(ListenerEvent::Stopped)));
+    }
+
+    #[tokio::test]
+    async fn polls_reuse_the_pool_scan_of_the_first() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        rpc.add_pool(token, spl_token::native_mint::ID, 1_000_000, 2_000_000);
+        let mut price_listener = listener(&rpc);
+        let mut receiver = price_listener.subscribe(token);
+        let shutdown = price_listener.shutdown_handle();
+        let task = tokio::spawn(async move { price_listener.start_listening().await });
+
+        let polled = |polls: usize| {
+            let rpc = rpc.clone();
+            async move {
+                while rpc.call_count("getMultipleAccounts") < polls {
+                    sleep(Duration::from_millis(5)).await;
+                }
+            }
+        };
+        receiver.recv().await.unwrap();
+        // Once the second poll reads reserves, the first has finished
+        let first_reads = rpc.call_count("getMultipleAccounts");
+        polled(first_reads + 1).await;
+        let first_scans = rpc.call_count("getProgramAccounts");
+        polled(first_reads + 11).await;
+        shutdown.stop();
+        task.await.unwrap().unwrap();
+        // Ten more polls read fresh reserves without scanning for pools again
+        assert!(first_scans > 0);
+        assert_eq!(rpc.call_count("getProgramAccounts"), first_scans);
+    }
+
+    #[tokio::test]
+    async fn subscribe_many_merges_tokens_into_one_channel() {
+        let rpc = MockRpc::new();
+        let wsol = spl_token::native_mint::ID;
+        let mints = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        for mint in mints {
+            rpc.add_pool(mint, wsol, 1_000_000, 2_000_000);
+        }
+        let mut price_listener = listener(&rpc);
+        let mut receiver = price_listener.subscribe_many(&mints);
+        // Dropping one token leaves the channel open for the others
+        price_listener.unsubscribe(&mints[2]);
+        assert_eq!(price_listener.get_subscription_count(), 2);
+        let shutdown = price_listener.shutdown_handle();
+        let task = tokio::spawn(async move { price_listener.start_listening().await });
+
+        let mut received = HashSet::new();
+        while received.len() < 2 {
+            let price = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .expect("no update within the poll interval")
+                .unwrap();
+            received.insert(price.token_mint);
+        }
+        sleep(Duration::from_millis(50)).await;
+        shutdown.stop();
+        task.await.unwrap().unwrap();
+        assert_eq!(received, HashSet::from([mints[0], mints[1]]));
+        // Unchanged prices aren't resent, and the dropped token never arrives
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn lp_supply_drop_emits_a_liquidity_change() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        let pool = rpc.add_pool(token, spl_token::native_mint::ID, 1_000_000, 2_000_000);
+        let mut price_listener = PriceListener::new_with_config(
+            rpc.client(),
+            ListenerConfig {
+                liquidity_change_threshold_usd: 50.0,
+                ..ListenerConfig::default()
+            },
+        );
+        let mut events =
+            price_listener.subscribe_events(token, &[MarketEventKind::LiquidityChange]);
+        let price_feed = price_listener.price_feed.clone();
+        let usd_prices = HashMap::from([(token, 200.0)]);
+        let mut state = ListenerState::default();
+        let withdraw = |token_reserve: u64, lp_supply: u64| {
+            rpc.set_account(
+                pool.token_a_reserve,
+                token_account(token, vault_address(&token), token_reserve),
+            );
+            rpc.set_account(pool.lp_mint, mint_account(6, lp_supply));
+        };
+
+        // The first check only records the LP supply
+        price_listener
+            .check_pool_events(&price_feed, &mut state, &usd_prices)
+            .await;
+        assert!(events.try_recv().is_err());
+
+        // Half the LP tokens are burned, taking half of the pool's $400 TVL
+        withdraw(500_000, 500_000);
+        price_listener
+            .check_pool_events(&price_feed, &mut state, &usd_prices)
+            .await;
+        match events.try_recv().unwrap() {
+            MarketEvent::LiquidityChange {
+                pool: address,
+                delta_usd,
+            } => {
+                assert_eq!(address, pool.address);
+                assert!((delta_usd + 200.0).abs() < 1e-9, "{}", delta_usd);
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // A $2 deposit stays below the threshold
+        withdraw(505_000, 505_000);
+        price_listener
+            .check_pool_events(&price_feed, &mut state, &usd_prices)
+            .await;
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn candles_close_at_bucket_boundaries_and_fill_gaps() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        let mut price_listener = listener(&rpc);
+        let mut candles = price_listener.subscribe_candles(token, TimeFrame::M1);
+        let mut state = ListenerState::default();
+        let mut observe = |usd_price: Option<f64>, now: i64| {
+            price_listener.update_candles(&mut state, token, usd_price, now);
+            let mut closed = Vec::new();
+            while let Ok(candle) = candles.try_recv() {
+                closed.push((
+                    candle.timestamp,
+                    candle.open,
+                    candle.high,
+                    candle.low,
+                    candle.close,
+                ));
+            }
+            closed
+        };
+
+        // Prices within one minute only update its open candle
+        assert!(observe(Some(10.0), 60).is_empty());
+        assert!(observe(Some(12.0), 90).is_empty());
+        assert!(observe(Some(9.0), 119).is_empty());
+        // The first price of the next minute closes it
+        assert_eq!(observe(Some(10.0), 120), vec![(60, 10.0, 12.0, 9.0, 9.0)]);
+        // A failed price still rolls the candle forward
+        assert_eq!(observe(None, 185), vec![(120, 9.0, 10.0, 9.0, 10.0)]);
+        // Skipped minutes become flat candles at the previous close
+        assert_eq!(
+            observe(Some(11.0), 300),
+            vec![(180, 10.0, 10.0, 10.0, 10.0), (240, 10.0, 10.0, 10.0, 10.0)]
+        );
+        assert_eq!(observe(None, 360), vec![(300, 10.0, 11.0, 10.0, 11.0)]);
+    }
+
+    #[test]
+    fn slow_subscribers_are_reported_as_lagging() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        let mut price_listener = PriceListener::new_with_config(
+            rpc.client(),
+            ListenerConfig {
+                channel_capacity: 2,
+                track_lag: true,
+                ..ListenerConfig::default()
+            },
+        );
+        let mut receiver = price_listener.subscribe(token);
+        let latest = price_listener.subscribe_latest_only(token);
+        let mut status = price_listener.status_receiver();
+        let mut state = ListenerState::default();
+
+        // Five 10% moves while nobody reads, each one notified
+        let sol_prices = [1.0, 1.1, 1.21, 1.331, 1.4641];
+        for sol_price in sol_prices {
+            let price = TokenPrice {
+                token_mint: token,
+                sol_price,
+                usd_price: sol_price * 150.0,
+                timestamp: Utc::now().timestamp(),
+                liquidity: 1_000_000,
+                confidence: 1.0,
+                source: PriceSource::Override,
+            };
+            price_listener.publish_price(&mut state, token, Ok(price));
+        }
+
+        // Each send into the full channel is reported with a running count
+        let mut dropped = Vec::new();
+        while let Ok(event) = status.try_recv() {
+            if let ListenerEvent::SubscriberLagged {
+                token_mint,
+                dropped: count,
+            } = event
+            {
+                assert_eq!(token_mint, token);
+                dropped.push(count);
+            }
+        }
+        assert_eq!(dropped, vec![1, 2, 3]);
+        // The slow receiver skips the overwritten prices and keeps the newest
+        assert!(matches!(
+            receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Lagged(3))
+        ));
+        assert_eq!(receiver.try_recv().unwrap().sol_price, 1.331);
+        assert_eq!(receiver.try_recv().unwrap().sol_price, 1.4641);
+        // A latest-only subscriber never lags, it just sees the last price
+        assert_eq!(latest.borrow().as_ref().unwrap().sol_price, 1.4641);
+    }
+
+    #[tokio::test]
+    async fn ws_listener_without_subscriptions_waits_for_stop() {
+        let rpc = MockRpc::new();
+        let mut price_listener = listener(&rpc);
+        let shutdown = price_listener.shutdown_handle();
+        let (url, accepts) = dropping_server().await;
+
+        let task = tokio::spawn(async move { price_listener.start_listening_ws(&url).await });
+        sleep(Duration::from_millis(100)).await;
+        assert!(!task.is_finished());
+        shutdown.stop();
+        task.await.unwrap().unwrap();
+        assert_eq!(accepts.load(Ordering::SeqCst), 0);
+    }
+}
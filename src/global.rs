@@ -1,6 +1,18 @@
 /// Meteora program ID on Solana mainnet
 pub const METEORA_PROGRAM_ID: &str = "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB";
+/// Meteora vault program ID on Solana mainnet, which holds pool liquidity
+pub const METEORA_VAULT_PROGRAM_ID: &str = "24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi";
+/// Base key the Meteora vault program derives vault addresses from
+pub const METEORA_VAULT_BASE_KEY: &str = "HWzXGcGHy4tcpYfaRDCyLNzXqBTv3E6BttpCH2vJxArv";
 /// USDC mint address on Solana mainnet
 pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
 /// metaplex program id
 pub const METAPLEX_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+/// Maximum number of signatures returned by a single getSignaturesForAddress request
+pub const MAX_SIGNATURES_PER_REQUEST: usize = 1000;
+/// Maximum number of accounts returned by a single getMultipleAccounts request
+pub const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+/// Default maximum number of concurrent RPC requests per client
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 16;
+/// Compute budget program id
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
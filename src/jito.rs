@@ -0,0 +1,175 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde_json::{Value, json};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, transaction::Transaction};
+
+use crate::types::MeteoraError;
+
+/// Mainnet block engine endpoint for `sendBundle`
+pub const DEFAULT_JITO_BLOCK_ENGINE_URL: &str =
+    "https://mainnet.block-engine.jito.wtf/api/v1/bundles";
+
+/// Accounts the Jito tip program accepts tips to
+///
+/// Any of them works; picking one at random spreads write locks between
+/// concurrent bundles.
+pub const JITO_TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+/// Builds the transfer that pays a Jito tip
+///
+/// # Params
+/// payer - The account paying the tip
+/// tip_account - One of `JITO_TIP_ACCOUNTS`
+/// tip_lamports - The tip amount
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::jito::build_tip_instruction;
+/// # use solana_sdk::{pubkey, signature::{Keypair, Signer}};
+/// # let keypair = Keypair::new();
+/// let tip_account = pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5");
+/// let tip = build_tip_instruction(&keypair.pubkey(), &tip_account, 10_000);
+/// ```
+pub fn build_tip_instruction(
+    payer: &Pubkey,
+    tip_account: &Pubkey,
+    tip_lamports: u64,
+) -> Instruction {
+    solana_system_interface::instruction::transfer(payer, tip_account, tip_lamports)
+}
+
+/// Appends the tip transfer to `instructions`, so it is only paid if every
+/// instruction before it succeeds
+///
+/// # Params
+/// instructions - The instructions to run
+/// payer - The account paying the tip
+/// tip_account - One of `JITO_TIP_ACCOUNTS`
+/// tip_lamports - The tip amount
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::jito::{JITO_TIP_ACCOUNTS, with_tip};
+/// # use solana_sdk::{pubkey::Pubkey, signature::{Keypair, Signer}};
+/// # use std::str::FromStr;
+/// # let keypair = Keypair::new();
+/// # let tip_account = Pubkey::from_str(JITO_TIP_ACCOUNTS[0]).unwrap();
+/// # let instructions = Vec::new();
+/// let instructions = with_tip(instructions, &keypair.pubkey(), &tip_account, 10_000);
+/// ```
+pub fn with_tip(
+    mut instructions: Vec<Instruction>,
+    payer: &Pubkey,
+    tip_account: &Pubkey,
+    tip_lamports: u64,
+) -> Vec<Instruction> {
+    instructions.push(build_tip_instruction(payer, tip_account, tip_lamports));
+    instructions
+}
+
+/// Submits signed transactions as one bundle to a Jito block engine
+///
+/// The transactions are executed in order and atomically, and are never
+/// visible in the public mempool. Returns the bundle id.
+///
+/// # Params
+/// block_engine_url - The block engine's bundles endpoint
+/// transactions - The signed transactions, at most 5
+///
+/// # Example
+/// ```no_run
+/// # use meteora_sdk::jito::{DEFAULT_JITO_BLOCK_ENGINE_URL, send_bundle};
+/// # async fn example(
+/// #     transaction: solana_sdk::transaction::Transaction,
+/// # ) -> Result<(), meteora_sdk::types::MeteoraError> {
+/// let bundle_id = send_bundle(DEFAULT_JITO_BLOCK_ENGINE_URL, &[transaction]).await?;
+/// println!("Bundle {}", bundle_id);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn send_bundle(
+    block_engine_url: &str,
+    transactions: &[Transaction],
+) -> Result<String, MeteoraError> {
+    let encoded = transactions
+        .iter()
+        .map(|transaction| {
+            bincode::serialize(transaction)
+                .map(|bytes| STANDARD.encode(bytes))
+                .map_err(|e| MeteoraError::Error(format!("Failed to serialize transaction: {}", e)))
+        })
+        .collect::<Result<Vec<String>, MeteoraError>>()?;
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "sendBundle",
+        "params": [encoded, { "encoding": "base64" }],
+    });
+    let response: Value = reqwest::Client::new()
+        .post(block_engine_url)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| MeteoraError::from_rpc_error(e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| MeteoraError::DeserializationError(e.to_string()))?;
+    if let Some(error) = response.get("error") {
+        return Err(MeteoraError::from_rpc_error(error.to_string()));
+    }
+    response
+        .get("result")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            MeteoraError::DeserializationError(format!(
+                "Unexpected sendBundle response {}",
+                response
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn tip_instruction_is_a_system_transfer() {
+        let payer = Pubkey::new_unique();
+        let tip_account = Pubkey::from_str(JITO_TIP_ACCOUNTS[0]).unwrap();
+        let instruction = build_tip_instruction(&payer, &tip_account, 10_000);
+
+        assert_eq!(instruction.program_id, solana_system_interface::program::ID);
+        // SystemInstruction::Transfer is variant 2, followed by the lamports
+        assert_eq!(instruction.data[..4], 2u32.to_le_bytes());
+        assert_eq!(instruction.data[4..], 10_000u64.to_le_bytes());
+        assert_eq!(instruction.accounts.len(), 2);
+        assert_eq!(instruction.accounts[0].pubkey, payer);
+        assert!(instruction.accounts[0].is_signer && instruction.accounts[0].is_writable);
+        assert_eq!(instruction.accounts[1].pubkey, tip_account);
+        assert!(instruction.accounts[1].is_writable);
+    }
+
+    #[test]
+    fn tip_is_appended_last() {
+        let payer = Pubkey::new_unique();
+        let tip_account = Pubkey::from_str(JITO_TIP_ACCOUNTS[1]).unwrap();
+        let swap = Instruction::new_with_bytes(Pubkey::new_unique(), &[1, 2, 3], vec![]);
+        let instructions = with_tip(vec![swap.clone(), swap], &payer, &tip_account, 5_000);
+
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(
+            instructions.last(),
+            Some(&build_tip_instruction(&payer, &tip_account, 5_000))
+        );
+    }
+}
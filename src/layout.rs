@@ -0,0 +1,309 @@
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::math::BPS_DENOMINATOR;
+use crate::types::{CurveType, MeteoraError, PoolInfo};
+
+/// Offset of the token A mint, shared by every layout
+pub const TOKEN_A_MINT_OFFSET: usize = 8;
+/// Offset of the token B mint, shared by every layout
+pub const TOKEN_B_MINT_OFFSET: usize = 40;
+
+/// Known versions of the Meteora pool account layout
+///
+/// The version is identified by the 8-byte Anchor account discriminator at
+/// the start of the account data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolLayout {
+    /// Mints, reserves, LP mint and fee account followed by the trade fee
+    V1,
+    /// Same as `V1` with a protocol fee account inserted before the trade fee
+    V2,
+}
+
+impl PoolLayout {
+    /// Discriminator of `V1` pool accounts (`sha256("account:Pool")[..8]`)
+    pub const V1_DISCRIMINATOR: [u8; 8] = [241, 154, 109, 4, 17, 177, 109, 188];
+    /// Discriminator of `V2` pool accounts (`sha256("account:PoolV2")[..8]`)
+    pub const V2_DISCRIMINATOR: [u8; 8] = [91, 12, 214, 87, 7, 185, 167, 55];
+
+    /// Detects the layout of a pool account from its discriminator
+    pub fn from_account_data(data: &[u8]) -> Result<Self, MeteoraError> {
+        let discriminator: [u8; 8] = data
+            .get(..8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| {
+                MeteoraError::InvalidPoolData(format!(
+                    "Pool account is {} bytes, too short for a discriminator",
+                    data.len()
+                ))
+            })?;
+        match discriminator {
+            Self::V1_DISCRIMINATOR => Ok(PoolLayout::V1),
+            Self::V2_DISCRIMINATOR => Ok(PoolLayout::V2),
+            other => Err(MeteoraError::InvalidPoolData(format!(
+                "Unknown pool account discriminator {:?}",
+                other
+            ))),
+        }
+    }
+
+    pub fn discriminator(&self) -> [u8; 8] {
+        match self {
+            PoolLayout::V1 => Self::V1_DISCRIMINATOR,
+            PoolLayout::V2 => Self::V2_DISCRIMINATOR,
+        }
+    }
+
+    /// Every known layout, newest last
+    pub const ALL: [PoolLayout; 2] = [PoolLayout::V1, PoolLayout::V2];
+
+    /// Size in bytes of pool accounts with this layout
+    pub fn account_size(&self) -> usize {
+        match self {
+            PoolLayout::V1 => 300,
+            PoolLayout::V2 => 332,
+        }
+    }
+
+    /// `getProgramAccounts` filters matching only pool accounts with this layout
+    pub fn filters(&self) -> Vec<RpcFilterType> {
+        vec![
+            RpcFilterType::DataSize(self.account_size() as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &self.discriminator())),
+        ]
+    }
+
+    /// `filters` narrowed to pools whose token A and/or token B mint match
+    pub fn filters_for_mints(
+        &self,
+        token_a_mint: Option<&Pubkey>,
+        token_b_mint: Option<&Pubkey>,
+    ) -> Vec<RpcFilterType> {
+        let mut filters = self.filters();
+        if let Some(mint) = token_a_mint {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                TOKEN_A_MINT_OFFSET,
+                &mint.to_bytes(),
+            )));
+        }
+        if let Some(mint) = token_b_mint {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                TOKEN_B_MINT_OFFSET,
+                &mint.to_bytes(),
+            )));
+        }
+        filters
+    }
+
+    /// Offset of the trade fee numerator, followed by the denominator
+    fn trade_fee_offset(&self) -> usize {
+        match self {
+            PoolLayout::V1 => 200,
+            PoolLayout::V2 => 232,
+        }
+    }
+
+    /// Offset of the curve type tag, right after the trade fee
+    ///
+    /// Tag 0 is a constant-product curve. Tag 1 is a stable curve, and its
+    /// amplification coefficient follows the tag as a u64.
+    fn curve_type_offset(&self) -> usize {
+        self.trade_fee_offset() + 16
+    }
+}
+
+/// Parses the static fields of a pool account
+///
+/// Only fields stored in the pool account itself are filled in. The address,
+/// decimals, reserve amounts, LP supply and fetch time live in other accounts
+/// and are left zeroed for the caller to populate.
+///
+/// # Params
+/// data - Raw pool account data, including the discriminator
+///
+/// # Example
+/// ```no_run
+/// # use meteora_sdk::layout::parse_pool_account;
+/// # use meteora_sdk::MeteoraClient;
+/// # use solana_network_sdk::types::Mode;
+/// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+/// # let client = MeteoraClient::new(Mode::MAIN)?;
+/// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+/// let data = client.get_account_data(&pool_address).await?;
+/// let pool = parse_pool_account(&data)?;
+/// println!("{} / {}", pool.token_a_mint, pool.token_b_mint);
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_pool_account(data: &[u8]) -> Result<PoolInfo, MeteoraError> {
+    let layout = PoolLayout::from_account_data(data)?;
+    if data.len() < layout.account_size() {
+        return Err(MeteoraError::InvalidPoolData(format!(
+            "{:?} pool account is {} bytes, expected at least {}",
+            layout,
+            data.len(),
+            layout.account_size()
+        )));
+    }
+    let fee_offset = layout.trade_fee_offset();
+    let trade_fee_bps = fee_to_bps(read_u64(data, fee_offset)?, read_u64(data, fee_offset + 8)?)?;
+    let curve_offset = layout.curve_type_offset();
+    let curve_type = match data.get(curve_offset) {
+        Some(0) => CurveType::ConstantProduct,
+        Some(1) => CurveType::Stable {
+            amp: read_u64(data, curve_offset + 1)?,
+        },
+        tag => {
+            return Err(MeteoraError::InvalidPoolData(format!(
+                "Unknown curve type {:?}",
+                tag
+            )));
+        }
+    };
+    Ok(PoolInfo {
+        address: Pubkey::default(),
+        token_a_mint: read_pubkey(data, TOKEN_A_MINT_OFFSET)?,
+        token_b_mint: read_pubkey(data, TOKEN_B_MINT_OFFSET)?,
+        token_a_reserve: read_pubkey(data, 72)?,
+        token_b_reserve: read_pubkey(data, 104)?,
+        lp_mint: read_pubkey(data, 136)?,
+        fee_account: read_pubkey(data, 168)?,
+        trade_fee_bps,
+        token_a_decimals: 0,
+        token_b_decimals: 0,
+        token_a_reserve_amount: 0,
+        token_b_reserve_amount: 0,
+        lp_supply: 0,
+        lp_decimals: 0,
+        curve_type,
+        fetched_at: 0,
+    })
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey, MeteoraError> {
+    data.get(offset..offset + 32)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(Pubkey::new_from_array)
+        .ok_or_else(|| {
+            MeteoraError::InvalidPoolData(format!("No pubkey at offset {} in pool account", offset))
+        })
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, MeteoraError> {
+    data.get(offset..offset + 8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or_else(|| {
+            MeteoraError::InvalidPoolData(format!("No u64 at offset {} in pool account", offset))
+        })
+}
+
+/// Converts a pool's trade fee fraction into basis points
+///
+/// Rounds up, so a fee below 1 bps is still charged in quotes rather than
+/// quoting a free swap the pool won't honor.
+fn fee_to_bps(numerator: u64, denominator: u64) -> Result<u64, MeteoraError> {
+    if denominator == 0 || numerator > denominator {
+        return Err(MeteoraError::InvalidPoolData(format!(
+            "Invalid trade fee {}/{}",
+            numerator, denominator
+        )));
+    }
+    Ok((numerator as u128 * BPS_DENOMINATOR as u128).div_ceil(denominator as u128) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::PoolFixture;
+
+    #[test]
+    fn trade_fee_is_read_from_each_layouts_offset() {
+        let pool = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        for (layout, offset) in [(PoolLayout::V1, 200), (PoolLayout::V2, 232)] {
+            let mut data = pool.account_data(layout, 0, 1);
+            data[offset..offset + 8].copy_from_slice(&1u64.to_le_bytes());
+            data[offset + 8..offset + 16].copy_from_slice(&10_000u64.to_le_bytes());
+            assert_eq!(parse_pool_account(&data).unwrap().trade_fee_bps, 1);
+        }
+    }
+
+    #[test]
+    fn fractional_trade_fees_round_up_to_whole_bps() {
+        let pool = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        for ((numerator, denominator), bps) in [
+            ((0, 10_000), 0),
+            ((1, 100_000), 1),
+            ((25, 100_000), 3),
+            ((25, 10_000), 25),
+        ] {
+            let data = pool.account_data(PoolLayout::V1, numerator, denominator);
+            assert_eq!(parse_pool_account(&data).unwrap().trade_fee_bps, bps);
+        }
+    }
+
+    #[test]
+    fn invalid_trade_fee_is_rejected() {
+        let pool = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        for (numerator, denominator) in [(1, 0), (2, 1)] {
+            let data = pool.account_data(PoolLayout::V1, numerator, denominator);
+            assert!(matches!(
+                parse_pool_account(&data),
+                Err(MeteoraError::InvalidPoolData(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn both_layouts_parse_every_static_field() {
+        let pool = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        for (layout, size) in [(PoolLayout::V1, 300), (PoolLayout::V2, 332)] {
+            let data = pool.account_data(layout, 25, 10_000);
+            assert_eq!(data.len(), size);
+            assert_eq!(PoolLayout::from_account_data(&data).unwrap(), layout);
+            let info = parse_pool_account(&data).unwrap();
+            assert_eq!(info.token_a_mint, pool.token_a_mint);
+            assert_eq!(info.token_b_mint, pool.token_b_mint);
+            assert_eq!(info.token_a_reserve, pool.token_a_reserve);
+            assert_eq!(info.token_b_reserve, pool.token_b_reserve);
+            assert_eq!(info.lp_mint, pool.lp_mint);
+            assert_eq!(info.fee_account, pool.fee_account);
+            assert_eq!(info.trade_fee_bps, 25);
+            assert_eq!(info.curve_type, CurveType::ConstantProduct);
+        }
+    }
+
+    #[test]
+    fn stable_curve_reads_its_amplification() {
+        let pool = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = pool.account_data(PoolLayout::V2, 4, 10_000);
+        data[248] = 1;
+        data[249..257].copy_from_slice(&100u64.to_le_bytes());
+        assert_eq!(
+            parse_pool_account(&data).unwrap().curve_type,
+            CurveType::Stable { amp: 100 }
+        );
+    }
+
+    #[test]
+    fn unknown_discriminator_is_rejected() {
+        let pool = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = pool.account_data(PoolLayout::V1, 25, 10_000);
+        data[..8].copy_from_slice(&[0; 8]);
+        assert!(matches!(
+            parse_pool_account(&data),
+            Err(MeteoraError::InvalidPoolData(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_account_is_rejected() {
+        let pool = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let data = pool.account_data(PoolLayout::V2, 25, 10_000);
+        assert!(matches!(
+            parse_pool_account(&data[..300]),
+            Err(MeteoraError::InvalidPoolData(_))
+        ));
+    }
+}
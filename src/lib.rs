@@ -1,18 +1,51 @@
+use borsh::BorshDeserialize;
+use futures::{Stream, StreamExt, future, stream};
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
 use solana_client::{
-    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    client_error::ClientError,
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig},
     rpc_filter::{Memcmp, RpcFilterType},
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
 };
 use solana_commitment_config::CommitmentConfig;
 use solana_network_sdk::Solana;
-use solana_sdk::{account::Account, pubkey::Pubkey};
+use solana_sdk::{
+    account::Account,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
+use spl_token::state::Account as TokenAccount;
+use spl_token_2022_interface::extension::AccountType;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
 
-use crate::types::MeteoraError;
+use crate::endpoint::EndpointPool;
+use crate::global::{
+    DEFAULT_MAX_CONCURRENT_REQUESTS, MAX_ACCOUNTS_PER_REQUEST, MAX_SIGNATURES_PER_REQUEST,
+};
+use crate::rate_limit::RateLimiter;
+use crate::types::{MeteoraError, RetryConfig};
 use solana_network_sdk::types::Mode;
+pub mod dlmm;
+mod endpoint;
 pub mod events;
 pub mod global;
+#[cfg(feature = "jito")]
+pub mod jito;
+pub mod layout;
+pub mod math;
+pub mod oracle;
 pub mod pool;
 pub mod price;
+mod rate_limit;
+#[cfg(test)]
+mod test_util;
 pub mod token;
 pub mod trade;
 pub mod types;
@@ -22,27 +55,29 @@ pub mod types;
 pub struct MeteoraClient {
     pub solana: Arc<Solana>,
     pub commitment: CommitmentConfig,
+    rpc_permits: Arc<Semaphore>,
+    retry_config: RetryConfig,
+    endpoints: EndpointPool,
+    request_timeout: Option<Duration>,
+    rate_limiter: Option<RateLimiter>,
 }
 
 impl MeteoraClient {
     /// Creates a new MeteoraClient with the default confirmed commitment
     ///
+    /// Use `MeteoraClient::builder` to configure anything else.
+    ///
     /// # Params
     /// mode - Solana Network Mode
     ///
     /// # Example
-    /// ```
-    /// use meteora_client::MeteoraClient;
+    /// ```no_run
+    /// use meteora_sdk::MeteoraClient;
     ///
     /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
     /// ```
     pub fn new(mode: Mode) -> Result<Self, MeteoraError> {
-        Ok(Self {
-            solana: Arc::new(
-                Solana::new(mode).map_err(|e| MeteoraError::Error(format!("{:?}", e)))?,
-            ),
-            commitment: CommitmentConfig::confirmed(),
-        })
+        Self::builder(mode).build()
     }
 
     /// Creates a new MeteoraClient with a custom commitment level
@@ -52,22 +87,341 @@ impl MeteoraClient {
     /// commitment - The commitment level for queries
     ///
     /// # Example
-    /// ```
-    /// use meteora_client::MeteoraClient;
+    /// ```no_run
+    /// use meteora_sdk::MeteoraClient;
     /// use solana_commitment_config::CommitmentConfig;
     ///
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// let client = MeteoraClient::new_with_commitment(
+    ///     solana_network_sdk::types::Mode::MAIN,
+    ///     CommitmentConfig::finalized(),
+    /// );
     /// ```
     pub fn new_with_commitment(
         mode: Mode,
         commitment: CommitmentConfig,
     ) -> Result<Self, MeteoraError> {
-        Ok(Self {
-            solana: Arc::new(
-                Solana::new(mode).map_err(|e| MeteoraError::Error(format!("{:?}", e)))?,
-            ),
-            commitment: CommitmentConfig::confirmed(),
+        Self::builder(mode).commitment(commitment).build()
+    }
+
+    /// Creates a new MeteoraClient connected to an arbitrary RPC endpoint
+    ///
+    /// The URL bypasses the `Mode` presets entirely, so it can point at a
+    /// private validator or a paid RPC provider. Shorthand for
+    /// `MeteoraClient::builder` with `url` and `commitment`.
+    ///
+    /// # Params
+    /// url - HTTP(S) URL of the RPC endpoint
+    /// commitment - The commitment level for queries
+    ///
+    /// # Example
+    /// ```no_run
+    /// use meteora_sdk::MeteoraClient;
+    /// use solana_commitment_config::CommitmentConfig;
+    ///
+    /// let client = MeteoraClient::new_with_url(
+    ///     "https://mainnet.helius-rpc.com/?api-key=<key>",
+    ///     CommitmentConfig::confirmed(),
+    /// );
+    /// ```
+    #[must_use = "the client is only useful once stored"]
+    pub fn new_with_url(url: &str, commitment: CommitmentConfig) -> Result<Self, MeteoraError> {
+        Self::builder(Mode::MAIN)
+            .url(url)
+            .commitment(commitment)
+            .build()
+    }
+
+    /// Creates a new MeteoraClient that fails over between several RPC endpoints
+    ///
+    /// Requests go to the first URL until it returns repeated RPC errors, at
+    /// which point it is temporarily demoted and the next healthy URL is used.
+    /// Shorthand for `MeteoraClient::builder` with `endpoints` and `commitment`.
+    ///
+    /// # Params
+    /// urls - HTTP(S) URLs of the RPC endpoints, highest priority first
+    /// commitment - The commitment level for queries
+    ///
+    /// # Example
+    /// ```no_run
+    /// use meteora_sdk::MeteoraClient;
+    /// use solana_commitment_config::CommitmentConfig;
+    ///
+    /// # fn main() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::new_with_endpoints(
+    ///     vec![
+    ///         "https://my-validator.example.com".to_string(),
+    ///         "https://api.mainnet-beta.solana.com".to_string(),
+    ///     ],
+    ///     CommitmentConfig::confirmed(),
+    /// )?;
+    /// println!("Using {}", client.current_endpoint());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use = "the client is only useful once stored"]
+    pub fn new_with_endpoints(
+        urls: Vec<String>,
+        commitment: CommitmentConfig,
+    ) -> Result<Self, MeteoraError> {
+        if urls.is_empty() {
+            return Err(MeteoraError::InvalidInput(
+                "At least one RPC endpoint is required".to_string(),
+            ));
+        }
+        Self::builder(Mode::MAIN)
+            .endpoints(urls)
+            .commitment(commitment)
+            .build()
+    }
+
+    /// Creates a new MeteoraClient that never has more than `max_concurrent_requests`
+    /// RPC requests in flight
+    ///
+    /// The limit is shared by every manager built on top of this client.
+    /// Shorthand for `MeteoraClient::builder` with `max_concurrent_requests`.
+    ///
+    /// # Params
+    /// mode - Solana Network Mode
+    /// commitment - The commitment level for queries
+    /// max_concurrent_requests - Maximum number of concurrent RPC requests
+    ///
+    /// # Example
+    /// ```no_run
+    /// use meteora_sdk::MeteoraClient;
+    /// use solana_commitment_config::CommitmentConfig;
+    ///
+    /// let client = MeteoraClient::new_with_concurrency(
+    ///     solana_network_sdk::types::Mode::MAIN,
+    ///     CommitmentConfig::confirmed(),
+    ///     8,
+    /// );
+    /// ```
+    #[must_use = "the client is only useful once stored"]
+    pub fn new_with_concurrency(
+        mode: Mode,
+        commitment: CommitmentConfig,
+        max_concurrent_requests: usize,
+    ) -> Result<Self, MeteoraError> {
+        Self::builder(mode)
+            .commitment(commitment)
+            .max_concurrent_requests(max_concurrent_requests)
+            .build()
+    }
+
+    /// Creates a new MeteoraClient whose RPC requests fail after `timeout`
+    ///
+    /// Shorthand for `MeteoraClient::builder` with `timeout`, see
+    /// `MeteoraClientBuilder::timeout` for how retries extend the total.
+    ///
+    /// # Params
+    /// mode - Solana Network Mode
+    /// commitment - The commitment level for queries
+    /// timeout - Maximum duration of a single RPC request
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use meteora_sdk::MeteoraClient;
+    /// use solana_commitment_config::CommitmentConfig;
+    ///
+    /// let client = MeteoraClient::new_with_timeout(
+    ///     solana_network_sdk::types::Mode::MAIN,
+    ///     CommitmentConfig::confirmed(),
+    ///     Duration::from_secs(5),
+    /// );
+    /// ```
+    #[must_use = "the client is only useful once stored"]
+    pub fn new_with_timeout(
+        mode: Mode,
+        commitment: CommitmentConfig,
+        timeout: Duration,
+    ) -> Result<Self, MeteoraError> {
+        Self::builder(mode)
+            .commitment(commitment)
+            .timeout(timeout)
+            .build()
+    }
+
+    /// Creates a new MeteoraClient that starts at most `max_requests_per_second`
+    /// RPC requests per second
+    ///
+    /// Shorthand for `MeteoraClient::builder` with `rate_limit`.
+    ///
+    /// # Params
+    /// mode - Solana Network Mode
+    /// commitment - The commitment level for queries
+    /// max_requests_per_second - Maximum number of RPC requests started per second
+    ///
+    /// # Example
+    /// ```no_run
+    /// use meteora_sdk::MeteoraClient;
+    /// use solana_commitment_config::CommitmentConfig;
+    ///
+    /// let client = MeteoraClient::new_with_rate_limit(
+    ///     solana_network_sdk::types::Mode::MAIN,
+    ///     CommitmentConfig::confirmed(),
+    ///     10,
+    /// );
+    /// ```
+    #[must_use = "the client is only useful once stored"]
+    pub fn new_with_rate_limit(
+        mode: Mode,
+        commitment: CommitmentConfig,
+        max_requests_per_second: u32,
+    ) -> Result<Self, MeteoraError> {
+        Self::builder(mode)
+            .commitment(commitment)
+            .rate_limit(max_requests_per_second)
+            .build()
+    }
+
+    /// Creates a new MeteoraClient with a custom retry policy for transient RPC failures
+    ///
+    /// Shorthand for `MeteoraClient::builder` with `retry_config`.
+    ///
+    /// # Params
+    /// mode - Solana Network Mode
+    /// commitment - The commitment level for queries
+    /// retry_config - How often and how long to back off before retrying
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use meteora_sdk::{MeteoraClient, types::RetryConfig};
+    /// use solana_commitment_config::CommitmentConfig;
+    ///
+    /// let client = MeteoraClient::new_with_retry(
+    ///     solana_network_sdk::types::Mode::MAIN,
+    ///     CommitmentConfig::confirmed(),
+    ///     RetryConfig {
+    ///         max_retries: 5,
+    ///         base_delay: Duration::from_millis(100),
+    ///         max_delay: Duration::from_secs(2),
+    ///     },
+    /// );
+    /// ```
+    #[must_use = "the client is only useful once stored"]
+    pub fn new_with_retry(
+        mode: Mode,
+        commitment: CommitmentConfig,
+        retry_config: RetryConfig,
+    ) -> Result<Self, MeteoraError> {
+        Self::builder(mode)
+            .commitment(commitment)
+            .retry_config(retry_config)
+            .build()
+    }
+
+    /// Starts building a MeteoraClient for `mode` with default settings
+    ///
+    /// # Params
+    /// mode - Solana Network Mode, whose RPC endpoint is used unless a URL
+    /// or endpoints are set on the builder
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use meteora_sdk::MeteoraClient;
+    ///
+    /// # fn main() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::builder(solana_network_sdk::types::Mode::MAIN)
+    ///     .url("https://mainnet.helius-rpc.com/?api-key=<key>")
+    ///     .timeout(Duration::from_secs(5))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder(mode: Mode) -> MeteoraClientBuilder {
+        MeteoraClientBuilder::new(mode)
+    }
+
+    /// Returns the URL of the RPC endpoint requests are currently sent to
+    pub fn current_endpoint(&self) -> String {
+        self.endpoints.current_url()
+    }
+
+    /// Returns the WebSocket URL matching the current RPC endpoint
+    ///
+    /// Follows the Solana convention of swapping `http(s)` for `ws(s)` and,
+    /// for a local validator, port 8899 for 8900.
+    pub fn current_ws_endpoint(&self) -> String {
+        let url = self.current_endpoint();
+        let url = if let Some(rest) = url.strip_prefix("https://") {
+            format!("wss://{}", rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            format!("ws://{}", rest)
+        } else {
+            url
+        };
+        url.replace(":8899", ":8900")
+    }
+
+    /// Runs an RPC request while holding one of the client's concurrency permits
+    ///
+    /// Every outbound RPC call in the crate goes through this method. Transient
+    /// failures are retried according to the client's `RetryConfig`. With a
+    /// request timeout, each attempt that takes longer fails with a retryable
+    /// `RpcError`. With a rate limit, each attempt first waits for its slot.
+    pub(crate) async fn rpc<T, F, Fut>(&self, request: F) -> Result<T, MeteoraError>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        self.with_retry(|| async {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+            let _permit = self
+                .rpc_permits
+                .acquire()
+                .await
+                .map_err(|e| MeteoraError::Error(e.to_string()))?;
+            let (index, rpc) = self.endpoints.current();
+            let result = match self.request_timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, request(rpc)).await {
+                    Ok(result) => result.map_err(MeteoraError::from),
+                    Err(_) => Err(MeteoraError::RpcError(format!(
+                        "RPC request timed out after {:?}",
+                        timeout
+                    ))),
+                },
+                None => request(rpc).await.map_err(MeteoraError::from),
+            };
+            match &result {
+                Ok(_) => self.endpoints.record_success(index),
+                Err(e) if e.is_retryable() => self.endpoints.record_failure(index),
+                Err(_) => {}
+            }
+            result
         })
+        .await
+    }
+
+    /// Runs `operation` until it succeeds, fails with a non-retryable error, or
+    /// `max_retries` retries have been used, backing off exponentially in between
+    async fn with_retry<T, F, Fut>(&self, operation: F) -> Result<T, MeteoraError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, MeteoraError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match operation().await {
+                Err(e) if e.is_retryable() && attempt < self.retry_config.max_retries => {
+                    let delay = self.retry_config.backoff_delay(attempt);
+                    log::debug!(
+                        "RPC request failed ({:?}), retrying in {:?} ({}/{})",
+                        e,
+                        delay,
+                        attempt + 1,
+                        self.retry_config.max_retries
+                    );
+                    attempt += 1;
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
     }
 
     /// Fetches the raw account data for a given address
@@ -76,38 +430,168 @@ impl MeteoraClient {
     /// address - The Pubkey of the account to fetch
     ///
     /// # Example
-    /// ```
+    /// ```no_run
     /// use solana_sdk::pubkey;
-    /// use meteora_client::MeteoraClient;
+    /// use meteora_sdk::MeteoraClient;
     ///
-    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?;
     /// let account_pubkey = pubkey!("So11111111111111111111111111111111111111112");
-    /// let account_data = client.get_account_data(&account_pubkey)?;
+    /// let account_data = client.get_account_data(&account_pubkey).await?;
     /// # Ok(())
     /// # }
     /// ```
     pub async fn get_account_data(&self, address: &Pubkey) -> Result<Vec<u8>, MeteoraError> {
-        match self
-            .solana
-            .client
-            .clone()
-            .unwrap()
-            .get_account_with_commitment(address, self.commitment)
+        Ok(self.get_account(address).await?.data)
+    }
+
+    /// Fetches an account, including its owner and lamports
+    ///
+    /// # Params
+    /// address - The Pubkey of the account to fetch
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::MeteoraClient;
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = MeteoraClient::new(Mode::MAIN)?;
+    /// # let mint = solana_sdk::pubkey!("So11111111111111111111111111111111111111112");
+    /// let account = client.get_account(&mint).await?;
+    /// println!("Owned by {}", account.owner);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_account(&self, address: &Pubkey) -> Result<Account, MeteoraError> {
+        let account = self
+            .rpc(|rpc| async move {
+                rpc.get_account_with_commitment(address, self.commitment)
+                    .await
+            })
+            .await?;
+        account
+            .value
+            .ok_or_else(|| MeteoraError::AccountNotFound(format!("Account {} not found", address)))
+    }
+
+    /// Fetches an account and unpacks its data as a `Pack` type
+    ///
+    /// The data must be exactly `T::LEN` bytes and initialized, so Token-2022
+    /// accounts with extensions are rejected; use `token::unpack_mint` and
+    /// `token::unpack_token_account` for those.
+    ///
+    /// # Params
+    /// address - The Pubkey of the account to fetch
+    ///
+    /// # Example
+    /// ```no_run
+    /// use spl_token::state::Mint;
+    ///
+    /// # use meteora_sdk::MeteoraClient;
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = MeteoraClient::new(Mode::MAIN)?;
+    /// # let mint_address = solana_sdk::pubkey!("So11111111111111111111111111111111111111112");
+    /// let mint: Mint = client.get_account_typed(&mint_address).await?;
+    /// println!("Decimals: {}", mint.decimals);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_account_typed<T: Pack + IsInitialized>(
+        &self,
+        address: &Pubkey,
+    ) -> Result<T, MeteoraError> {
+        let data = self.get_account_data(address).await?;
+        T::unpack(&data).map_err(|e| {
+            MeteoraError::DeserializationError(format!(
+                "Account {} is not a valid {}: {}",
+                address,
+                std::any::type_name::<T>(),
+                e
+            ))
+        })
+    }
+
+    /// Fetches an account and deserializes the start of its data with borsh
+    ///
+    /// Bytes after the decoded value are ignored, since Anchor and Metaplex
+    /// accounts are often padded beyond their current layout.
+    ///
+    /// # Params
+    /// address - The Pubkey of the account to fetch
+    ///
+    /// # Example
+    /// ```no_run
+    /// #[derive(borsh::BorshDeserialize)]
+    /// struct Counter {
+    ///     discriminator: [u8; 8],
+    ///     count: u64,
+    /// }
+    ///
+    /// # use meteora_sdk::MeteoraClient;
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = MeteoraClient::new(Mode::MAIN)?;
+    /// # let counter_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let counter: Counter = client.get_account_borsh(&counter_address).await?;
+    /// println!("Count: {}", counter.count);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_account_borsh<T: BorshDeserialize>(
+        &self,
+        address: &Pubkey,
+    ) -> Result<T, MeteoraError> {
+        let data = self.get_account_data(address).await?;
+        T::deserialize(&mut data.as_slice()).map_err(|e| {
+            MeteoraError::DeserializationError(format!(
+                "Account {} is not a valid {}: {}",
+                address,
+                std::any::type_name::<T>(),
+                e
+            ))
+        })
+    }
+
+    /// Checks that the RPC node reports itself healthy
+    ///
+    /// Calls `getHealth`; a node that is behind or otherwise unhealthy yields
+    /// an `RpcError` describing why.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::MeteoraClient;
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = MeteoraClient::new(Mode::MAIN)?;
+    /// if let Err(e) = client.health_check().await {
+    ///     eprintln!("RPC unavailable: {:?}", e);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn health_check(&self) -> Result<(), MeteoraError> {
+        self.rpc(|rpc| async move { rpc.get_health().await }).await
+    }
+
+    /// Gets the slot the RPC node has reached at the client's commitment
+    ///
+    /// Polling this and checking that it advances detects a stalled node.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::MeteoraClient;
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = MeteoraClient::new(Mode::MAIN)?;
+    /// let slot = client.get_slot().await?;
+    /// println!("Current slot: {}", slot);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_slot(&self) -> Result<u64, MeteoraError> {
+        self.rpc(|rpc| async move { rpc.get_slot_with_commitment(self.commitment).await })
             .await
-        {
-            Ok(account) => {
-                if let Some(account) = account.value {
-                    Ok(account.data)
-                } else {
-                    Err(MeteoraError::AccountNotFound(format!(
-                        "Account {} not found",
-                        address
-                    )))
-                }
-            }
-            Err(e) => Err(MeteoraError::RpcError(e.to_string())),
-        }
     }
 
     /// Fetches raw account data for multiple addresses in a single request
@@ -116,17 +600,17 @@ impl MeteoraClient {
     /// addresses - Slice of Pubkeys to fetch
     ///
     /// # Example
-    /// ```
+    /// ```no_run
     /// use solana_sdk::pubkey;
-    /// use meteora_client::MeteoraClient;
+    /// use meteora_sdk::MeteoraClient;
     ///
-    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?;
     /// let addresses = vec![
     ///     pubkey!("So11111111111111111111111111111111111111112"),
     ///     pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"),
     /// ];
-    /// let accounts_data = client.get_multiple_accounts_data(&addresses)?;
+    /// let accounts_data = client.get_multiple_accounts_data(&addresses).await?;
     /// # Ok(())
     /// # }
     /// ```
@@ -134,27 +618,108 @@ impl MeteoraClient {
         &self,
         addresses: &[Pubkey],
     ) -> Result<Vec<Vec<u8>>, MeteoraError> {
-        match self
-            .solana
-            .client
-            .clone()
-            .unwrap()
-            .get_multiple_accounts_with_commitment(addresses, self.commitment)
-            .await
-        {
-            Ok(accounts) => {
-                let mut results = Vec::new();
-                for account in accounts.value {
-                    if let Some(account) = account {
-                        results.push(account.data);
-                    } else {
-                        results.push(Vec::new());
-                    }
-                }
-                Ok(results)
-            }
-            Err(e) => Err(MeteoraError::RpcError(e.to_string())),
+        let accounts = self.get_multiple_accounts(addresses).await?;
+        Ok(accounts
+            .into_iter()
+            .map(|account| account.map(|account| account.data).unwrap_or_default())
+            .collect())
+    }
+
+    /// Fetches multiple accounts, keeping missing accounts as `None`
+    ///
+    /// Addresses are requested in chunks of `MAX_ACCOUNTS_PER_REQUEST`.
+    ///
+    /// # Params
+    /// addresses - Slice of Pubkeys to fetch
+    ///
+    /// # Example
+    /// ```no_run
+    /// use solana_sdk::pubkey;
+    /// use meteora_sdk::MeteoraClient;
+    ///
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?;
+    /// let addresses = vec![pubkey!("So11111111111111111111111111111111111111112")];
+    /// let accounts = client.get_multiple_accounts(&addresses).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_multiple_accounts(
+        &self,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<Option<Account>>, MeteoraError> {
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for chunk in addresses.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+            let response = self
+                .rpc(|rpc| async move {
+                    rpc.get_multiple_accounts_with_commitment(chunk, self.commitment)
+                        .await
+                })
+                .await?;
+            accounts.extend(response.value);
         }
+        Ok(accounts)
+    }
+
+    /// Streams the accounts owned by a program that match `filters`
+    ///
+    /// Only the matching keys are fetched up front, with account data sliced
+    /// to zero bytes. Accounts are then loaded `MAX_ACCOUNTS_PER_REQUEST` at a
+    /// time as the stream is polled, so at most one chunk of account data is
+    /// held in memory and callers can stop early. Accounts closed between the
+    /// two steps are skipped.
+    ///
+    /// # Params
+    /// program_id - The program ID to query
+    /// filters - Filters to apply to the query
+    ///
+    /// # Example
+    /// ```no_run
+    /// use futures::StreamExt;
+    ///
+    /// # use meteora_sdk::MeteoraClient;
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = MeteoraClient::new(Mode::MAIN)?;
+    /// # let program_id = spl_token::id();
+    /// # let filters = vec![solana_client::rpc_filter::RpcFilterType::DataSize(82)];
+    /// let mut accounts = Box::pin(client.stream_program_accounts(&program_id, filters));
+    /// while let Some(result) = accounts.next().await {
+    ///     let (address, account) = result?;
+    ///     println!("{}: {} bytes", address, account.data.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stream_program_accounts<'a>(
+        &'a self,
+        program_id: &'a Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> impl Stream<Item = Result<(Pubkey, Account), MeteoraError>> + 'a {
+        stream::once(self.get_program_account_keys(program_id, filters))
+            .map(move |keys| match keys {
+                Ok(keys) => {
+                    let chunks: Vec<Vec<Pubkey>> = keys
+                        .chunks(MAX_ACCOUNTS_PER_REQUEST)
+                        .map(<[Pubkey]>::to_vec)
+                        .collect();
+                    stream::iter(chunks)
+                        .then(move |chunk| async move {
+                            match self.get_multiple_accounts(&chunk).await {
+                                Ok(accounts) => chunk
+                                    .into_iter()
+                                    .zip(accounts)
+                                    .filter_map(|(key, account)| Some(Ok((key, account?))))
+                                    .collect(),
+                                Err(e) => vec![Err(e)],
+                            }
+                        })
+                        .flat_map(stream::iter)
+                        .left_stream()
+                }
+                Err(e) => stream::once(future::ready(Err(e))).right_stream(),
+            })
+            .flatten()
     }
 
     /// Fetches all accounts owned by a program with optional filters
@@ -164,16 +729,16 @@ impl MeteoraClient {
     /// filters - Optional filters to apply to the query
     ///
     /// # Example
-    /// ```
+    /// ```no_run
     /// use solana_sdk::pubkey;
     /// use solana_client::rpc_filter::RpcFilterType;
-    /// use meteora_client::MeteoraClient;
+    /// use meteora_sdk::MeteoraClient;
     ///
-    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?;
     /// let program_id = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
     /// let filters = vec![RpcFilterType::DataSize(165)];
-    /// let program_accounts = client.get_program_accounts(&program_id, Some(filters))?;
+    /// let program_accounts = client.get_program_accounts(&program_id, Some(filters)).await?;
     /// # Ok(())
     /// # }
     /// ```
@@ -193,45 +758,778 @@ impl MeteoraClient {
             with_context: None,
             sort_results: None,
         };
-        match self
-            .solana
-            .client
-            .clone()
-            .unwrap()
-            .get_program_accounts_with_config(program_id, config)
-            .await
-        {
-            Ok(accounts) => Ok(accounts),
-            Err(e) => Err(MeteoraError::RpcError(e.to_string())),
-        }
+        self.rpc(|rpc| {
+            let config = config.clone();
+            async move {
+                rpc.get_program_accounts_with_config(program_id, config)
+                    .await
+            }
+        })
+        .await
+    }
+
+    /// Fetches only the addresses of accounts owned by a program that match `filters`
+    ///
+    /// Account data is sliced to zero bytes on the RPC node, so this is much
+    /// cheaper than `get_program_accounts` when only the keys are needed.
+    ///
+    /// # Params
+    /// program_id - The program ID to query
+    /// filters - Filters to apply to the query
+    ///
+    /// # Example
+    /// ```no_run
+    /// use solana_sdk::pubkey;
+    /// use solana_client::rpc_filter::RpcFilterType;
+    /// use meteora_sdk::MeteoraClient;
+    ///
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?;
+    /// let program_id = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+    /// let keys = client
+    ///     .get_program_account_keys(&program_id, vec![RpcFilterType::DataSize(82)])
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_program_account_keys(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<Pubkey>, MeteoraError> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                commitment: Some(self.commitment),
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice: Some(UiDataSliceConfig {
+                    offset: 0,
+                    length: 0,
+                }),
+                min_context_slot: None,
+            },
+            with_context: None,
+            sort_results: None,
+        };
+        let accounts = self
+            .rpc(|rpc| {
+                let config = config.clone();
+                async move {
+                    rpc.get_program_accounts_with_config(program_id, config)
+                        .await
+                }
+            })
+            .await?;
+        Ok(accounts.into_iter().map(|(pubkey, _)| pubkey).collect())
     }
 
     /// Fetches all SPL token accounts for a specific mint address
     ///
+    /// Accounts of both the SPL Token and the Token-2022 program are returned.
+    ///
     /// # Params
     /// mint - The mint address of the token
     ///
     /// # Example
-    /// ```
+    /// ```no_run
     /// use solana_sdk::pubkey;
-    /// use meteora_client::MeteoraClient;
+    /// use meteora_sdk::MeteoraClient;
     ///
-    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?;
     /// let usdc_mint = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
-    /// let token_accounts = client.get_spl_token_accounts_by_mint(&usdc_mint)?;
-    /// Ok(())
-    /// }
+    /// let token_accounts = client.get_spl_token_accounts_by_mint(&usdc_mint).await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_spl_token_accounts_by_mint(
         &self,
         mint: &Pubkey,
     ) -> Result<Vec<(Pubkey, Account)>, MeteoraError> {
-        let filters = vec![
-            RpcFilterType::DataSize(165),
-            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &mint.to_bytes())),
-        ];
-        self.get_program_accounts(&spl_token::id(), Some(filters))
+        let mut accounts = Vec::new();
+        for (program_id, filters) in token_account_queries(mint) {
+            accounts.extend(
+                self.get_program_accounts(&program_id, Some(filters))
+                    .await?,
+            );
+        }
+        Ok(accounts)
+    }
+
+    /// Fetches the addresses of all token accounts for a mint, without their data
+    ///
+    /// Same accounts as `get_spl_token_accounts_by_mint`, but the RPC only
+    /// returns keys, which is much cheaper for widely held tokens.
+    pub(crate) async fn get_spl_token_account_keys_by_mint(
+        &self,
+        mint: &Pubkey,
+    ) -> Result<Vec<Pubkey>, MeteoraError> {
+        let mut keys = Vec::new();
+        for (program_id, filters) in token_account_queries(mint) {
+            keys.extend(self.get_program_account_keys(&program_id, filters).await?);
+        }
+        Ok(keys)
+    }
+
+    /// Fetches a confirmed transaction with its status metadata, JSON encoded
+    ///
+    /// Versioned transactions are supported; addresses they load from lookup
+    /// tables are reported in the metadata's `loaded_addresses`.
+    ///
+    /// # Params
+    /// signature - The transaction signature
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::MeteoraClient;
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = MeteoraClient::new(Mode::MAIN)?;
+    /// # let signature = solana_sdk::signature::Signature::default();
+    /// let tx = client.get_transaction(&signature).await?;
+    /// println!("Landed in slot {}", tx.slot);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> Result<EncodedConfirmedTransactionWithStatusMeta, MeteoraError> {
+        // getTransaction doesn't support processed commitment
+        let commitment = if self.commitment == CommitmentConfig::processed() {
+            CommitmentConfig::confirmed()
+        } else {
+            self.commitment
+        };
+        self.rpc(|rpc| async move {
+            rpc.get_transaction_with_config(
+                signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Json),
+                    commitment: Some(commitment),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
             .await
+        })
+        .await
+    }
+
+    /// Fetches one page of transaction signatures for an address, newest first
+    ///
+    /// # Params
+    /// address - The address to query
+    /// before - Only return signatures older than this one
+    /// until - Only return signatures newer than this one
+    /// limit - Maximum number of signatures to return, at most 1000
+    ///
+    /// # Example
+    /// ```no_run
+    /// use solana_sdk::pubkey;
+    /// use meteora_sdk::MeteoraClient;
+    ///
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?;
+    /// let pool = pubkey!("So11111111111111111111111111111111111111112");
+    /// let signatures = client.get_signatures_for_address(&pool, None, None, 1000).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, MeteoraError> {
+        self.rpc(|rpc| {
+            let config = GetConfirmedSignaturesForAddress2Config {
+                before,
+                until,
+                limit: Some(limit),
+                commitment: Some(self.commitment),
+            };
+            async move {
+                rpc.get_signatures_for_address_with_config(address, config)
+                    .await
+            }
+        })
+        .await
+    }
+
+    /// Fetches up to `limit` transaction signatures for an address, newest first
+    ///
+    /// Unlike `get_signatures_for_address`, `limit` may exceed the RPC's page
+    /// size of `MAX_SIGNATURES_PER_REQUEST`: pages are requested one after the
+    /// other, each continuing before the oldest signature of the previous one,
+    /// until `limit` signatures are collected or the history runs out.
+    ///
+    /// # Params
+    /// address - The address to query
+    /// until - Only return signatures newer than this one
+    /// limit - Maximum number of signatures to return
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::MeteoraClient;
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = MeteoraClient::new(Mode::MAIN)?;
+    /// # let pool = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let signatures = client
+    ///     .get_signatures_for_address_paginated(&pool, None, 5_000)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_signatures_for_address_paginated(
+        &self,
+        address: &Pubkey,
+        until: Option<Signature>,
+        limit: usize,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, MeteoraError> {
+        let mut signatures: Vec<RpcConfirmedTransactionStatusWithSignature> = Vec::new();
+        let mut before = None;
+        while signatures.len() < limit {
+            let page_limit = (limit - signatures.len()).min(MAX_SIGNATURES_PER_REQUEST);
+            let page = self
+                .get_signatures_for_address(address, before, until, page_limit)
+                .await?;
+            let page_len = page.len();
+            signatures.extend(page);
+            if page_len < page_limit {
+                break;
+            }
+            before = match signatures.last().map(|sig| sig.signature.parse()) {
+                Some(Ok(signature)) => Some(signature),
+                Some(Err(e)) => {
+                    return Err(MeteoraError::DeserializationError(format!("{:?}", e)));
+                }
+                None => break,
+            };
+        }
+        Ok(signatures)
+    }
+}
+
+/// `getProgramAccounts` queries that together match every token account of a mint
+///
+/// Token-2022 accounts with extensions are larger than the base layout and
+/// are matched by their account type byte instead of their size.
+fn token_account_queries(mint: &Pubkey) -> [(Pubkey, Vec<RpcFilterType>); 3] {
+    let mint_filter = RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &mint.to_bytes()));
+    let base_size = RpcFilterType::DataSize(TokenAccount::LEN as u64);
+    [
+        (
+            spl_token::id(),
+            vec![base_size.clone(), mint_filter.clone()],
+        ),
+        (
+            spl_token_2022_interface::id(),
+            vec![base_size, mint_filter.clone()],
+        ),
+        (
+            spl_token_2022_interface::id(),
+            vec![
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    TokenAccount::LEN,
+                    &[AccountType::Account as u8],
+                )),
+                mint_filter,
+            ],
+        ),
+    ]
+}
+
+/// Builds a `MeteoraClient` from any combination of connection settings
+///
+/// Settings that aren't set keep the defaults of `MeteoraClient::new`.
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use meteora_sdk::MeteoraClient;
+/// use solana_commitment_config::CommitmentConfig;
+///
+/// # fn main() -> Result<(), meteora_sdk::types::MeteoraError> {
+/// let client = MeteoraClient::builder(solana_network_sdk::types::Mode::MAIN)
+///     .endpoints(vec![
+///         "https://my-validator.example.com".to_string(),
+///         "https://api.mainnet-beta.solana.com".to_string(),
+///     ])
+///     .commitment(CommitmentConfig::finalized())
+///     .max_concurrent_requests(8)
+///     .timeout(Duration::from_secs(5))
+///     .rate_limit(10)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MeteoraClientBuilder {
+    mode: Mode,
+    urls: Vec<String>,
+    commitment: CommitmentConfig,
+    max_concurrent_requests: usize,
+    retry_config: RetryConfig,
+    timeout: Option<Duration>,
+    max_requests_per_second: Option<u32>,
+    #[cfg(test)]
+    rpc_client: Option<Arc<RpcClient>>,
+}
+
+impl MeteoraClientBuilder {
+    /// Starts a builder for `mode` with default settings, see `MeteoraClient::builder`
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            urls: Vec::new(),
+            commitment: CommitmentConfig::confirmed(),
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            retry_config: RetryConfig::default(),
+            timeout: None,
+            max_requests_per_second: None,
+            #[cfg(test)]
+            rpc_client: None,
+        }
+    }
+
+    /// Connects to an arbitrary RPC endpoint instead of the `Mode` preset
+    ///
+    /// The URL can point at a private validator or a paid RPC provider.
+    ///
+    /// # Params
+    /// url - HTTP(S) URL of the RPC endpoint
+    pub fn url(self, url: &str) -> Self {
+        self.endpoints(vec![url.to_string()])
+    }
+
+    /// Fails over between several RPC endpoints instead of the `Mode` preset
+    ///
+    /// Requests go to the first URL until it returns repeated RPC errors, at
+    /// which point it is temporarily demoted and the next healthy URL is used
+    /// until the demotion ends.
+    ///
+    /// # Params
+    /// urls - HTTP(S) URLs of the RPC endpoints, highest priority first
+    pub fn endpoints(mut self, urls: Vec<String>) -> Self {
+        self.urls = urls;
+        self
+    }
+
+    /// Sets the commitment level for queries, confirmed by default
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    /// Limits the number of RPC requests in flight at once
+    ///
+    /// The limit is shared by every manager built on top of the client.
+    /// Defaults to `DEFAULT_MAX_CONCURRENT_REQUESTS`.
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests;
+        self
+    }
+
+    /// Sets how often and how long to back off before retrying transient RPC failures
+    pub fn retry_config(mut self, retry_config: RetryConfig) -> Self {
+        self.retry_config = retry_config;
+        self
+    }
+
+    /// Fails RPC requests that take longer than `timeout`
+    ///
+    /// The timeout bounds each attempt, so a hung endpoint returns an
+    /// `RpcError` instead of blocking the call. Timed out attempts are retried
+    /// like other transient failures, so a call can take up to
+    /// `(max_retries + 1) * timeout` plus backoff in total.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Starts at most `max_requests_per_second` RPC requests per second
+    ///
+    /// Requests are spaced evenly, and retries count against the limit too,
+    /// so a provider quota holds however many tasks share the client.
+    pub fn rate_limit(mut self, max_requests_per_second: u32) -> Self {
+        self.max_requests_per_second = Some(max_requests_per_second);
+        self
+    }
+
+    /// Sends every request to `rpc_client` instead of a real endpoint
+    #[cfg(test)]
+    pub(crate) fn rpc_client(mut self, rpc_client: Arc<RpcClient>) -> Self {
+        self.rpc_client = Some(rpc_client);
+        self
+    }
+
+    /// Creates the client
+    ///
+    /// Fails with `InvalidInput` for a URL that isn't HTTP(S), a
+    /// concurrency limit of 0 or a rate limit of 0.
+    pub fn build(self) -> Result<MeteoraClient, MeteoraError> {
+        if self.max_concurrent_requests == 0 {
+            return Err(MeteoraError::InvalidInput(
+                "Concurrency limit must be at least 1".to_string(),
+            ));
+        }
+        if self.max_requests_per_second == Some(0) {
+            return Err(MeteoraError::InvalidInput(
+                "Rate limit must be at least 1 request per second".to_string(),
+            ));
+        }
+        let mut solana =
+            Solana::new(self.mode).map_err(|e| MeteoraError::Error(format!("{:?}", e)))?;
+        let urls = if self.urls.is_empty() {
+            vec![solana.client_arc().url()]
+        } else {
+            self.urls
+        };
+        let mut endpoints = Vec::with_capacity(urls.len());
+        for url in urls {
+            validate_rpc_url(&url)?;
+            let client = match self.timeout {
+                Some(timeout) => RpcClient::new_with_timeout_and_commitment(
+                    url.clone(),
+                    timeout,
+                    self.commitment,
+                ),
+                None => RpcClient::new_with_commitment(url.clone(), self.commitment),
+            };
+            endpoints.push((url, Arc::new(client)));
+        }
+        #[cfg(test)]
+        if let Some(rpc_client) = self.rpc_client {
+            endpoints = vec![(rpc_client.url(), rpc_client)];
+        }
+        // `Solana` only exposes `Mode` constructors, so swap in the client of
+        // the primary endpoint.
+        solana.client = Some(endpoints[0].1.clone());
+        Ok(MeteoraClient {
+            solana: Arc::new(solana),
+            commitment: self.commitment,
+            rpc_permits: Arc::new(Semaphore::new(self.max_concurrent_requests)),
+            retry_config: self.retry_config,
+            endpoints: EndpointPool::new(endpoints),
+            request_timeout: self.timeout,
+            rate_limiter: self.max_requests_per_second.map(RateLimiter::new),
+        })
+    }
+}
+
+fn validate_rpc_url(url: &str) -> Result<(), MeteoraError> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(MeteoraError::InvalidInput(format!(
+            "RPC URL must start with http:// or https://: {}",
+            url
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{MockReply, MockRpc};
+
+    #[tokio::test(start_paused = true)]
+    async fn request_to_unresponsive_endpoint_times_out() {
+        let rpc = MockRpc::new();
+        rpc.on("getAccountInfo", |_| MockReply::Hang);
+        let client = rpc
+            .client_builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        let started = tokio::time::Instant::now();
+        let result = client.get_account(&Pubkey::new_unique()).await;
+        assert!(matches!(result, Err(MeteoraError::RpcError(_))));
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_requests_never_exceed_the_permit_count() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let rpc = MockRpc::new();
+        let client = rpc
+            .client_builder()
+            .max_concurrent_requests(3)
+            .build()
+            .unwrap();
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let requests = (0..20).map(|_| {
+            client.rpc(|_| async {
+                let running = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(running, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok::<_, ClientError>(())
+            })
+        });
+        for result in futures::future::join_all(requests).await {
+            result.unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_responses_map_to_rate_limited() {
+        let rpc = MockRpc::new();
+        rpc.on("getAccountInfo", |_| {
+            MockReply::Error("HTTP status client error (429 Too Many Requests)".to_string())
+        });
+        rpc.on("getBalance", |_| {
+            MockReply::Error("connection reset by peer".to_string())
+        });
+        let client = rpc.client();
+        let address = Pubkey::new_unique();
+        assert!(matches!(
+            client.get_account(&address).await,
+            Err(MeteoraError::RateLimited)
+        ));
+        assert!(matches!(
+            client
+                .rpc(|rpc| async move { rpc.get_balance(&address).await })
+                .await,
+            Err(MeteoraError::RpcError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn program_accounts_stream_in_chunks_until_exhausted() {
+        let rpc = MockRpc::new();
+        let count = MAX_ACCOUNTS_PER_REQUEST * 2 + 50;
+        for _ in 0..count {
+            rpc.set_account(Pubkey::new_unique(), test_util::mint_account(6, 1));
+        }
+        let client = rpc.client();
+        let filters = vec![RpcFilterType::DataSize(spl_token::state::Mint::LEN as u64)];
+
+        let accounts: Vec<_> = client
+            .stream_program_accounts(&spl_token::id(), filters.clone())
+            .collect()
+            .await;
+        assert_eq!(accounts.len(), count);
+        assert!(accounts.iter().all(|account| account.is_ok()));
+        assert_eq!(rpc.call_count("getMultipleAccounts"), 3);
+
+        // Stopping early leaves the remaining chunks unread
+        let first: Vec<_> = client
+            .stream_program_accounts(&spl_token::id(), filters)
+            .take(5)
+            .collect()
+            .await;
+        assert_eq!(first.len(), 5);
+        assert_eq!(rpc.call_count("getMultipleAccounts"), 4);
+
+        rpc.on("getProgramAccounts", |_| {
+            MockReply::Error("down".to_string())
+        });
+        let failed: Vec<_> = client
+            .stream_program_accounts(&spl_token::id(), vec![])
+            .collect()
+            .await;
+        assert!(matches!(failed[..], [Err(MeteoraError::RpcError(_))]));
+    }
+
+    #[tokio::test]
+    async fn health_check_reports_an_unhealthy_node() {
+        let rpc = MockRpc::new();
+        let client = rpc
+            .client_builder()
+            .commitment(CommitmentConfig::finalized())
+            .build()
+            .unwrap();
+        rpc.on("getHealth", |_| MockReply::Result(serde_json::json!("ok")));
+        client.health_check().await.unwrap();
+        assert_eq!(client.get_slot().await.unwrap(), test_util::MOCK_SLOT);
+        assert_eq!(rpc.calls("getSlot")[0][0]["commitment"], "finalized");
+
+        rpc.on("getHealth", |_| {
+            MockReply::Error("Node is behind by 42 slots".to_string())
+        });
+        match client.health_check().await {
+            Err(MeteoraError::RpcError(message)) => {
+                assert!(message.contains("behind"), "{}", message)
+            }
+            other => panic!("expected an RPC error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn typed_accounts_unpack_or_report_the_expected_type() {
+        use spl_token::state::Mint;
+        let rpc = MockRpc::new();
+        let client = rpc.client();
+        let (mint, holder) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.set_account(mint, test_util::mint_account(9, 5_000));
+        rpc.set_account(
+            holder,
+            test_util::token_account(mint, Pubkey::new_unique(), 1),
+        );
+
+        let state: Mint = client.get_account_typed(&mint).await.unwrap();
+        assert_eq!((state.decimals, state.supply), (9, 5_000));
+        match client.get_account_typed::<Mint>(&holder).await {
+            Err(MeteoraError::DeserializationError(message)) => {
+                assert!(message.contains("Mint"), "{}", message)
+            }
+            other => panic!("expected a deserialization error, got {:?}", other),
+        }
+
+        #[derive(BorshDeserialize)]
+        struct Header {
+            discriminator: [u8; 8],
+            value: u64,
+        }
+        let record = Pubkey::new_unique();
+        let mut data = vec![7; 8];
+        data.extend_from_slice(&42u64.to_le_bytes());
+        // Trailing padding is ignored
+        data.resize(64, 0);
+        rpc.set_account(
+            record,
+            test_util::program_account(Pubkey::new_unique(), data),
+        );
+        let header: Header = client.get_account_borsh(&record).await.unwrap();
+        assert_eq!((header.discriminator, header.value), ([7; 8], 42));
+        rpc.set_account(
+            record,
+            test_util::program_account(Pubkey::new_unique(), vec![7; 12]),
+        );
+        assert!(matches!(
+            client.get_account_borsh::<Header>(&record).await,
+            Err(MeteoraError::DeserializationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn paginated_signatures_walk_back_across_pages() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(Pubkey::new_unique(), Pubkey::new_unique(), 1, 1);
+        // Newest first, as the RPC returns them
+        let history: Vec<String> = (0..1_500)
+            .map(|block_time| rpc.add_swap(&pool, block_time, 1, -1).to_string())
+            .rev()
+            .collect();
+        let client = rpc.client();
+
+        let signatures = client
+            .get_signatures_for_address_paginated(&pool.address, None, 1_200)
+            .await
+            .unwrap();
+        let signatures: Vec<String> = signatures.into_iter().map(|sig| sig.signature).collect();
+        assert_eq!(signatures, history[..1_200]);
+        // The second page continues before the oldest signature of the first
+        let calls = rpc.calls("getSignaturesForAddress");
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0][1]["limit"], 1_000);
+        assert!(calls[0][1]["before"].is_null());
+        assert_eq!(calls[1][1]["limit"], 200);
+        assert_eq!(calls[1][1]["before"], history[999].as_str());
+
+        // A short page ends the walk once the history runs out
+        let signatures = client
+            .get_signatures_for_address_paginated(&pool.address, None, 5_000)
+            .await
+            .unwrap();
+        assert_eq!(signatures.len(), 1_500);
+        assert_eq!(rpc.calls("getSignaturesForAddress").len(), 4);
+    }
+
+    #[test]
+    fn builder_combines_endpoints_with_other_settings() {
+        let client = MeteoraClient::builder(Mode::MAIN)
+            .endpoints(vec![
+                "https://primary.example.com".to_string(),
+                "https://backup.example.com".to_string(),
+            ])
+            .commitment(CommitmentConfig::finalized())
+            .max_concurrent_requests(2)
+            .timeout(Duration::from_secs(5))
+            .rate_limit(10)
+            .build()
+            .unwrap();
+        assert!(client.rate_limiter.is_some());
+        assert_eq!(client.current_endpoint(), "https://primary.example.com");
+        assert_eq!(client.commitment, CommitmentConfig::finalized());
+        assert_eq!(client.rpc_permits.available_permits(), 2);
+        assert_eq!(client.request_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(
+            client.solana.client_arc().url(),
+            "https://primary.example.com"
+        );
+    }
+
+    #[test]
+    fn builder_rejects_invalid_settings() {
+        let invalid_url = MeteoraClient::builder(Mode::MAIN)
+            .url("ftp://example.com")
+            .build();
+        assert!(matches!(invalid_url, Err(MeteoraError::InvalidInput(_))));
+        let no_concurrency = MeteoraClient::builder(Mode::MAIN)
+            .max_concurrent_requests(0)
+            .build();
+        assert!(matches!(no_concurrency, Err(MeteoraError::InvalidInput(_))));
+        let no_rate = MeteoraClient::builder(Mode::MAIN).rate_limit(0).build();
+        assert!(matches!(no_rate, Err(MeteoraError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn shorthand_constructors_apply_their_setting() {
+        let commitment = CommitmentConfig::finalized();
+        let client = MeteoraClient::new_with_url("https://rpc.example.com", commitment).unwrap();
+        assert_eq!(client.current_endpoint(), "https://rpc.example.com");
+        assert_eq!(client.commitment, commitment);
+
+        let client = MeteoraClient::new_with_endpoints(
+            vec![
+                "https://primary.example.com".to_string(),
+                "https://backup.example.com".to_string(),
+            ],
+            commitment,
+        )
+        .unwrap();
+        assert_eq!(client.current_endpoint(), "https://primary.example.com");
+        assert!(matches!(
+            MeteoraClient::new_with_endpoints(Vec::new(), commitment),
+            Err(MeteoraError::InvalidInput(_))
+        ));
+
+        let client = MeteoraClient::new_with_concurrency(Mode::MAIN, commitment, 3).unwrap();
+        assert_eq!(client.rpc_permits.available_permits(), 3);
+
+        let timeout = Duration::from_secs(5);
+        let client = MeteoraClient::new_with_timeout(Mode::MAIN, commitment, timeout).unwrap();
+        assert_eq!(client.request_timeout, Some(timeout));
+
+        let client = MeteoraClient::new_with_rate_limit(Mode::MAIN, commitment, 10).unwrap();
+        assert!(client.rate_limiter.is_some());
+
+        let retry_config = RetryConfig {
+            max_retries: 7,
+            ..RetryConfig::default()
+        };
+        let client = MeteoraClient::new_with_retry(Mode::MAIN, commitment, retry_config).unwrap();
+        assert_eq!(client.retry_config.max_retries, 7);
+        assert_eq!(client.commitment, commitment);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_spaces_requests_evenly() {
+        let rpc = MockRpc::new();
+        let client = rpc.client_builder().rate_limit(10).build().unwrap();
+        let started = tokio::time::Instant::now();
+        let requests = (0..5).map(|_| client.rpc(|rpc| async move { rpc.get_slot().await }));
+        for result in future::join_all(requests).await {
+            result.unwrap();
+        }
+        // The first request starts immediately and each later one 100ms after the previous
+        assert!(started.elapsed() >= Duration::from_millis(400));
+        assert_eq!(rpc.call_count("getSlot"), 5);
     }
 }
@@ -0,0 +1,448 @@
+use spl_math::uint::U256;
+
+/// Denominator for values expressed in basis points
+pub const BPS_DENOMINATOR: u64 = 10000;
+
+/// Computes the output of a constant-product (x * y = k) swap after fees
+///
+/// The fee is deducted from the input before it is applied to the curve and
+/// all intermediate math is done in u128, so any u64 inputs are safe. Returns
+/// 0 when the pool has no liquidity on either side.
+///
+/// # Params
+/// amount_in - The input amount
+/// in_reserve - The pool's reserve of the input token
+/// out_reserve - The pool's reserve of the output token
+/// fee_bps - The trade fee in basis points
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::math;
+/// let amount_out = math::constant_product_out(1_000_000, 50_000_000, 100_000_000, 30);
+/// ```
+pub fn constant_product_out(
+    amount_in: u64,
+    in_reserve: u64,
+    out_reserve: u64,
+    fee_bps: u64,
+) -> u128 {
+    if in_reserve == 0 || out_reserve == 0 || fee_bps >= BPS_DENOMINATOR {
+        return 0;
+    }
+    let amount_in_with_fee =
+        amount_in as u128 * (BPS_DENOMINATOR - fee_bps) as u128 / BPS_DENOMINATOR as u128;
+    let numerator = amount_in_with_fee * out_reserve as u128;
+    let denominator = in_reserve as u128 + amount_in_with_fee;
+    numerator / denominator
+}
+
+/// Computes the output of a two-token StableSwap swap after fees
+///
+/// Amounts and reserves must be in a common precision. As with
+/// `constant_product_out`, the fee is deducted from the input first. The
+/// output is rounded down by one extra unit against the trader. Returns `None`
+/// for empty pools, a zero `amp`, or reserves whose invariant doesn't fit in
+/// u128.
+///
+/// # Params
+/// amount_in - The input amount
+/// in_reserve - The pool's reserve of the input token
+/// out_reserve - The pool's reserve of the output token
+/// amp - The pool's amplification coefficient
+/// fee_bps - The trade fee in basis points
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::math;
+/// let amount_out = math::stable_swap_out(1_000_000, 50_000_000, 50_000_000, 100, 4);
+/// ```
+pub fn stable_swap_out(
+    amount_in: u128,
+    in_reserve: u128,
+    out_reserve: u128,
+    amp: u64,
+    fee_bps: u64,
+) -> Option<u128> {
+    if fee_bps >= BPS_DENOMINATOR {
+        return None;
+    }
+    let amount_in_with_fee =
+        amount_in.checked_mul((BPS_DENOMINATOR - fee_bps) as u128)? / BPS_DENOMINATOR as u128;
+    let d = stable_invariant(in_reserve, out_reserve, amp)?;
+    let new_out_reserve = stable_reserve(in_reserve.checked_add(amount_in_with_fee)?, d, amp)?;
+    Some(
+        out_reserve
+            .saturating_sub(new_out_reserve)
+            .saturating_sub(1),
+    )
+}
+
+/// Iterations after which the StableSwap solvers give up converging
+const STABLE_MAX_ITERATIONS: usize = 256;
+
+/// Solves `4A(x + y) + D = 4AD + D^3 / (4xy)` for `D` by Newton's method
+///
+/// Iterates in U256: `D^3` alone passes u128 for reserves around 1e12.
+fn stable_invariant(x: u128, y: u128, amp: u64) -> Option<u128> {
+    if x == 0 || y == 0 || amp == 0 {
+        return None;
+    }
+    let (x, y) = (U256::from(x), U256::from(y));
+    let ann = U256::from(amp as u128 * 4);
+    let sum = x + y;
+    let mut d = sum;
+    for _ in 0..STABLE_MAX_ITERATIONS {
+        let d_p = d.checked_mul(d)? / (x * 2);
+        let d_p = d_p.checked_mul(d)? / (y * 2);
+        let previous = d;
+        let numerator = ann
+            .checked_mul(sum)?
+            .checked_add(d_p.checked_mul(U256::from(2))?)?
+            .checked_mul(d)?;
+        let denominator = (ann - 1)
+            .checked_mul(d)?
+            .checked_add(d_p.checked_mul(U256::from(3))?)?;
+        d = numerator / denominator;
+        if d.abs_diff(previous) <= U256::one() {
+            return u128::try_from(d).ok();
+        }
+    }
+    None
+}
+
+/// Solves the StableSwap invariant for the other reserve given reserve `x` and `D`
+fn stable_reserve(x: u128, d: u128, amp: u64) -> Option<u128> {
+    let (x, d) = (U256::from(x), U256::from(d));
+    let ann = U256::from(amp as u128 * 4);
+    let c = d.checked_mul(d)? / x.checked_mul(U256::from(2))?;
+    let c = c.checked_mul(d)? / (ann * 2);
+    let b = x.checked_add(d / ann)?;
+    let mut y = d;
+    for _ in 0..STABLE_MAX_ITERATIONS {
+        let previous = y;
+        let denominator = y
+            .checked_mul(U256::from(2))?
+            .checked_add(b)?
+            .checked_sub(d)?;
+        if denominator.is_zero() {
+            return None;
+        }
+        y = y.checked_mul(y)?.checked_add(c)? / denominator;
+        if y.abs_diff(previous) <= U256::one() {
+            return u128::try_from(y).ok();
+        }
+    }
+    None
+}
+
+/// Computes the input needed to receive exactly `amount_out` from a
+/// constant-product swap, the inverse of `constant_product_out`
+///
+/// Both the pre-fee and post-fee input are rounded up, so swapping the
+/// returned amount always yields at least `amount_out`. Returns `None` when
+/// the pool can't provide `amount_out` or the input overflows u128.
+///
+/// # Params
+/// amount_out - The desired output amount
+/// in_reserve - The pool's reserve of the input token
+/// out_reserve - The pool's reserve of the output token
+/// fee_bps - The trade fee in basis points
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::math;
+/// let amount_in = math::constant_product_in(1_000_000, 50_000_000, 100_000_000, 30);
+/// ```
+pub fn constant_product_in(
+    amount_out: u64,
+    in_reserve: u64,
+    out_reserve: u64,
+    fee_bps: u64,
+) -> Option<u128> {
+    if in_reserve == 0 || amount_out >= out_reserve || fee_bps >= BPS_DENOMINATOR {
+        return None;
+    }
+    let numerator = in_reserve as u128 * amount_out as u128;
+    let amount_in_with_fee = numerator.div_ceil((out_reserve - amount_out) as u128);
+    Some(
+        amount_in_with_fee
+            .checked_mul(BPS_DENOMINATOR as u128)?
+            .div_ceil((BPS_DENOMINATOR - fee_bps) as u128),
+    )
+}
+
+/// Computes the fee charged on an input amount
+///
+/// # Params
+/// amount_in - The input amount
+/// fee_bps - The trade fee in basis points
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::math;
+/// let fee = math::fee_amount(1_000_000, 30);
+/// ```
+pub fn fee_amount(amount_in: u64, fee_bps: u64) -> u64 {
+    let fee_bps = fee_bps.min(BPS_DENOMINATOR);
+    (amount_in as u128 * fee_bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+/// Computes the minimum acceptable output for a quoted amount and slippage
+///
+/// The on-chain program floors swap output just like `constant_product_out`,
+/// but rounding of intermediate fee amounts can still differ by a base unit,
+/// so one unit is subtracted before applying slippage to avoid spurious
+/// slippage failures.
+///
+/// # Params
+/// amount_out - The quoted output amount
+/// slippage_bps - The allowed slippage in basis points
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::math;
+/// let min_amount_out = math::min_amount_out(1_000_000, 100);
+/// ```
+pub fn min_amount_out(amount_out: u64, slippage_bps: u16) -> u64 {
+    let slippage_bps = (slippage_bps as u64).min(BPS_DENOMINATOR);
+    let amount_out = amount_out.saturating_sub(1) as u128;
+    (amount_out * (BPS_DENOMINATOR - slippage_bps) as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+/// Computes the maximum input to allow for a quoted input and slippage,
+/// rounded up
+///
+/// # Params
+/// amount_in - The quoted input amount
+/// slippage_bps - The allowed slippage in basis points
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::math;
+/// let max_amount_in = math::max_amount_in(1_000_000, 100);
+/// ```
+pub fn max_amount_in(amount_in: u64, slippage_bps: u16) -> u64 {
+    let max = (amount_in as u128 * (BPS_DENOMINATOR + slippage_bps as u64) as u128)
+        .div_ceil(BPS_DENOMINATOR as u128);
+    u64::try_from(max).unwrap_or(u64::MAX)
+}
+
+/// Computes the price impact of a swap as a percentage
+///
+/// # Params
+/// amount_in - The input amount
+/// in_reserve - The pool's reserve of the input token
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::math;
+/// let impact = math::price_impact(1_000_000, 50_000_000);
+/// ```
+pub fn price_impact(amount_in: u64, in_reserve: u64) -> f64 {
+    if in_reserve == 0 {
+        return 100.0;
+    }
+    amount_in as f64 / (in_reserve as f64 + amount_in as f64) * 100.0
+}
+
+/// Computes the largest input whose `price_impact` does not exceed a target,
+/// rounded down
+///
+/// Solves `impact = amount_in / (in_reserve + amount_in)` for `amount_in`.
+/// Returns 0 for targets at or below 0% and `u64::MAX` for 100% or more.
+///
+/// # Params
+/// in_reserve - The pool's reserve of the input token
+/// max_impact_pct - The target price impact as a percentage
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::math;
+/// let amount_in = math::amount_for_price_impact(50_000_000, 2.0);
+/// ```
+pub fn amount_for_price_impact(in_reserve: u64, max_impact_pct: f64) -> u64 {
+    if max_impact_pct.is_nan() || max_impact_pct <= 0.0 {
+        return 0;
+    }
+    if max_impact_pct >= 100.0 {
+        return u64::MAX;
+    }
+    let mut amount_in =
+        (in_reserve as f64 * max_impact_pct / (100.0 - max_impact_pct)).floor() as u64;
+    // Step back past any float rounding that lands just above the target
+    while amount_in > 0 && price_impact(amount_in, in_reserve) > max_impact_pct {
+        amount_in -= 1;
+    }
+    amount_in
+}
+
+/// Annualizes a day of fees over a pool's TVL, as a percentage
+///
+/// Fees are assumed to recur every day and are not compounded. Returns 0
+/// for a pool without TVL.
+///
+/// # Params
+/// fees_24h_usd - Fees earned over the last 24 hours
+/// tvl_usd - Total value locked in the pool
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::math;
+/// let apr = math::fee_apr(300.0, 1_000_000.0); // 10.95%
+/// ```
+pub fn fee_apr(fees_24h_usd: f64, tvl_usd: f64) -> f64 {
+    if tvl_usd <= 0.0 {
+        return 0.0;
+    }
+    fees_24h_usd * 365.0 / tvl_usd * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_product_out_applies_fee_before_curve() {
+        assert_eq!(
+            constant_product_out(1_000_000, 50_000_000, 100_000_000, 30),
+            1_955_016
+        );
+        // Swapping the whole input reserve without a fee halves the output reserve
+        assert_eq!(
+            constant_product_out(50_000_000, 50_000_000, 100_000_000, 0),
+            50_000_000
+        );
+    }
+
+    #[test]
+    fn constant_product_out_edge_cases() {
+        assert_eq!(constant_product_out(1_000, 0, 100_000_000, 30), 0);
+        assert_eq!(constant_product_out(1_000, 50_000_000, 0, 30), 0);
+        assert_eq!(
+            constant_product_out(1_000, 50_000_000, 100_000_000, 10_000),
+            0
+        );
+        assert_eq!(
+            constant_product_out(u64::MAX, 50_000_000, 100_000_000, 30),
+            99_999_999
+        );
+        assert_eq!(
+            constant_product_out(u64::MAX, u64::MAX, u64::MAX, 0),
+            u64::MAX as u128 / 2
+        );
+    }
+
+    #[test]
+    fn constant_product_in_round_trips_exact_out() {
+        for amount_out in [1, 999, 1_955_016, 50_000_000, 99_999_999] {
+            let amount_in = constant_product_in(amount_out, 50_000_000, 100_000_000, 30).unwrap();
+            let amount_in = u64::try_from(amount_in).unwrap();
+            assert!(
+                constant_product_out(amount_in, 50_000_000, 100_000_000, 30) >= amount_out as u128
+            );
+            assert!(
+                constant_product_out(amount_in - 1, 50_000_000, 100_000_000, 30)
+                    < amount_out as u128
+            );
+        }
+    }
+
+    #[test]
+    fn constant_product_in_edge_cases() {
+        assert_eq!(constant_product_in(1_000, 0, 100_000_000, 30), None);
+        assert_eq!(
+            constant_product_in(100_000_000, 50_000_000, 100_000_000, 30),
+            None
+        );
+        assert_eq!(
+            constant_product_in(1_000, 50_000_000, 100_000_000, 10_000),
+            None
+        );
+        assert_eq!(
+            constant_product_in(u64::MAX - 1, u64::MAX, u64::MAX, 30),
+            None
+        );
+        assert!(constant_product_in(u64::MAX / 2, u64::MAX, u64::MAX, 30).is_some());
+    }
+
+    #[test]
+    fn min_amount_out_subtracts_a_unit_then_slippage() {
+        assert_eq!(min_amount_out(1_000_000, 100), 989_999);
+        assert_eq!(min_amount_out(1_000_000, 0), 999_999);
+        assert_eq!(min_amount_out(0, 100), 0);
+        assert_eq!(min_amount_out(1_000_000, u16::MAX), 0);
+        assert_eq!(min_amount_out(u64::MAX, 0), u64::MAX - 1);
+    }
+
+    #[test]
+    fn stable_invariant_of_balanced_pool_is_its_sum() {
+        let d = stable_invariant(50_000_000, 50_000_000, 100).unwrap();
+        assert!(d.abs_diff(100_000_000) <= 1);
+        assert_eq!(stable_invariant(0, 50_000_000, 100), None);
+        assert_eq!(stable_invariant(50_000_000, 50_000_000, 0), None);
+    }
+
+    #[test]
+    fn stable_swap_out_beats_constant_product_near_peg() {
+        let stable = stable_swap_out(1_000_000, 50_000_000, 50_000_000, 100, 0).unwrap();
+        let constant = constant_product_out(1_000_000, 50_000_000, 50_000_000, 0);
+        assert!(stable < 1_000_000);
+        assert!(stable > 999_000);
+        assert!(stable > constant);
+        let with_fee = stable_swap_out(1_000_000, 50_000_000, 50_000_000, 100, 4).unwrap();
+        assert!(with_fee < stable);
+    }
+
+    #[test]
+    fn stable_swap_out_edge_cases() {
+        assert_eq!(stable_swap_out(1_000, 0, 50_000_000, 100, 4), None);
+        assert_eq!(
+            stable_swap_out(1_000, 50_000_000, 50_000_000, 100, 10_000),
+            None
+        );
+        assert_eq!(
+            stable_swap_out(u128::MAX, 50_000_000, 50_000_000, 100, 4),
+            None
+        );
+        assert_eq!(stable_swap_out(1_000, u128::MAX, u128::MAX, 100, 4), None);
+    }
+
+    #[test]
+    fn stable_math_handles_large_reserves_and_amplification() {
+        for reserve in [1_000_000_000_000u128, u64::MAX as u128 * 1_000] {
+            let d = stable_invariant(reserve, reserve, 5_000).unwrap();
+            assert!(d.abs_diff(2 * reserve) <= 1);
+            let amount_in = reserve / 1_000;
+            let amount_out = stable_swap_out(amount_in, reserve, reserve, 5_000, 0).unwrap();
+            assert!(amount_out < amount_in);
+            assert!(amount_out > amount_in - amount_in / 10_000);
+        }
+        let amount_out =
+            stable_swap_out(1_000_000, 1_000_000_000_000, 3_000_000_000_000, 1_000, 4).unwrap();
+        assert!(amount_out > 1_000_000 * 9_990 / 10_000);
+    }
+
+    #[test]
+    fn amount_for_price_impact_is_the_largest_input_within_target() {
+        let amount_in = amount_for_price_impact(50_000_000, 2.0);
+        assert_eq!(amount_in, 1_020_408);
+        assert!(price_impact(amount_in, 50_000_000) <= 2.0);
+        assert!(price_impact(amount_in + 1, 50_000_000) > 2.0);
+    }
+
+    #[test]
+    fn amount_for_price_impact_edge_cases() {
+        assert_eq!(amount_for_price_impact(50_000_000, 0.0), 0);
+        assert_eq!(amount_for_price_impact(50_000_000, f64::NAN), 0);
+        assert_eq!(amount_for_price_impact(50_000_000, 100.0), u64::MAX);
+        assert_eq!(amount_for_price_impact(0, 2.0), 0);
+    }
+
+    #[test]
+    fn fee_apr_annualizes_daily_fees() {
+        assert!((fee_apr(300.0, 1_000_000.0) - 10.95).abs() < 1e-9);
+        assert_eq!(fee_apr(0.0, 1_000_000.0), 0.0);
+        assert_eq!(fee_apr(300.0, 0.0), 0.0);
+        assert_eq!(fee_apr(300.0, -1.0), 0.0);
+    }
+}
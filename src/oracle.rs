@@ -0,0 +1,138 @@
+use crate::types::MeteoraError;
+
+/// Magic number at the start of every Pyth account
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+/// Account type of Pyth price accounts
+const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3;
+/// Aggregate status meaning the price is currently being published
+const PYTH_STATUS_TRADING: u32 = 1;
+
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPONENT_OFFSET: usize = 20;
+const AGGREGATE_PRICE_OFFSET: usize = 208;
+const AGGREGATE_STATUS_OFFSET: usize = 224;
+const AGGREGATE_PUBLISH_SLOT_OFFSET: usize = 232;
+
+/// Most slots a Pyth aggregate price can lag the current slot, about 10 seconds
+pub const MAX_PYTH_SLOT_LAG: u64 = 25;
+
+/// Parses the aggregate price of a Pyth price account
+///
+/// Only prices with a `Trading` status that were published within
+/// `MAX_PYTH_SLOT_LAG` slots of `current_slot` are accepted, so halted,
+/// unknown or stale feeds are reported as errors rather than returning their
+/// last value.
+///
+/// # Params
+/// data - Raw Pyth price account data
+/// current_slot - The cluster's current slot
+///
+/// # Example
+/// ```no_run
+/// # use meteora_sdk::oracle::parse_pyth_price;
+/// # use meteora_sdk::MeteoraClient;
+/// # use solana_network_sdk::types::Mode;
+/// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+/// # let client = MeteoraClient::new(Mode::MAIN)?;
+/// # let sol_usd_pyth_account = solana_sdk::pubkey!("H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG");
+/// let slot = client.get_slot().await?;
+/// let data = client.get_account_data(&sol_usd_pyth_account).await?;
+/// let sol_usd = parse_pyth_price(&data, slot)?;
+/// println!("SOL/USD: {}", sol_usd);
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_pyth_price(data: &[u8], current_slot: u64) -> Result<f64, MeteoraError> {
+    if read_u32(data, 0)? != PYTH_MAGIC {
+        return Err(MeteoraError::DeserializationError(
+            "Not a Pyth account".to_string(),
+        ));
+    }
+    if read_u32(data, ACCOUNT_TYPE_OFFSET)? != PYTH_PRICE_ACCOUNT_TYPE {
+        return Err(MeteoraError::DeserializationError(
+            "Not a Pyth price account".to_string(),
+        ));
+    }
+    if read_u32(data, AGGREGATE_STATUS_OFFSET)? != PYTH_STATUS_TRADING {
+        return Err(MeteoraError::InvalidPrice);
+    }
+    let publish_slot = read_u64(data, AGGREGATE_PUBLISH_SLOT_OFFSET)?;
+    if current_slot.saturating_sub(publish_slot) > MAX_PYTH_SLOT_LAG {
+        return Err(MeteoraError::InvalidPrice);
+    }
+    let exponent = read_u32(data, EXPONENT_OFFSET)? as i32;
+    let price = read_u64(data, AGGREGATE_PRICE_OFFSET)? as i64;
+    if price <= 0 {
+        return Err(MeteoraError::InvalidPrice);
+    }
+    Ok(price as f64 * 10f64.powi(exponent))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, MeteoraError> {
+    data.get(offset..offset + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or_else(|| {
+            MeteoraError::DeserializationError(format!(
+                "No u32 at offset {} in Pyth account",
+                offset
+            ))
+        })
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, MeteoraError> {
+    data.get(offset..offset + 8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or_else(|| {
+            MeteoraError::DeserializationError(format!(
+                "No u64 at offset {} in Pyth account",
+                offset
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::pyth_price_data;
+
+    /// A SOL/USD price account publishing $150.25 at `publish_slot`
+    fn price_account(publish_slot: u64) -> Vec<u8> {
+        pyth_price_data(15_025_000_000, -8, publish_slot)
+    }
+
+    #[test]
+    fn recent_trading_price_is_parsed() {
+        let price = parse_pyth_price(&price_account(1_000), 1_000).unwrap();
+        assert!((price - 150.25).abs() < 1e-9);
+        assert!(parse_pyth_price(&price_account(1_000), 1_000 + MAX_PYTH_SLOT_LAG).is_ok());
+    }
+
+    #[test]
+    fn stale_price_is_rejected() {
+        assert!(matches!(
+            parse_pyth_price(&price_account(1_000), 1_001 + MAX_PYTH_SLOT_LAG),
+            Err(MeteoraError::InvalidPrice)
+        ));
+    }
+
+    #[test]
+    fn halted_price_is_rejected() {
+        let mut data = price_account(1_000);
+        data[AGGREGATE_STATUS_OFFSET..AGGREGATE_STATUS_OFFSET + 4]
+            .copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            parse_pyth_price(&data, 1_000),
+            Err(MeteoraError::InvalidPrice)
+        ));
+    }
+
+    #[test]
+    fn truncated_account_is_rejected() {
+        assert!(matches!(
+            parse_pyth_price(&price_account(1_000)[..236], 1_000),
+            Err(MeteoraError::DeserializationError(_))
+        ));
+    }
+}
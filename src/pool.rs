@@ -1,62 +1,213 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::global::METEORA_PROGRAM_ID;
-use crate::types::PoolInfo;
+use crate::global::{
+    MAX_SIGNATURES_PER_REQUEST, METEORA_PROGRAM_ID, METEORA_VAULT_BASE_KEY,
+    METEORA_VAULT_PROGRAM_ID, USDC_MINT,
+};
+use crate::layout::{PoolLayout, parse_pool_account};
+use crate::math::{self, BPS_DENOMINATOR};
+use crate::price::{SolUsdResolver, SwapEvent, decode_pool_swap};
+use crate::token::{unpack_mint, unpack_token_account};
+use crate::types::{MatchedPool, PoolCreationTime, PoolInfo, PoolStats, PriceSource};
 use crate::{MeteoraClient, MeteoraError};
-use solana_sdk::program_pack::Pack;
+use futures::future;
+use futures::stream::{self, Stream, StreamExt, select_all};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::account::Account;
 use solana_sdk::pubkey::Pubkey;
-use spl_token::state::{Account, Mint};
-use tokio::time::Instant;
+use solana_sdk::signature::Signature;
+use spl_token::state::Account as TokenAccount;
+use tokio::sync::{broadcast, oneshot};
+use tokio::time::{Instant, sleep};
+
+/// Default lifetime of cached pools and of the cached pool list
+const DEFAULT_CACHE_TTL_SECS: u64 = 300;
+/// How long `watch_pool` waits for the other reserve to update before emitting
+const RESERVE_UPDATE_DEBOUNCE: Duration = Duration::from_millis(250);
+/// Capacity of the channel returned by `watch_pool`
+const POOL_WATCH_CHANNEL_CAPACITY: usize = 100;
+/// Default number of pools loaded at once while scanning
+const DEFAULT_SCAN_CONCURRENCY: usize = 8;
+/// Length of the window covered by `get_pool_stats`
+const SECONDS_PER_DAY: i64 = 86400;
+/// Most pages of signatures `get_pool_creation_time` reads before giving up
+const MAX_CREATION_TIME_PAGES: usize = 20;
+/// Most transactions `get_pool_stats` fetches, see `sample_evenly`
+const MAX_POOL_STATS_TRANSACTIONS: usize = 200;
 
 struct PoolCache {
     pools: HashMap<Pubkey, (PoolInfo, Instant)>,
     all_pools: Vec<Pubkey>,
+    creation_times: HashMap<Pubkey, PoolCreationTime>,
+    index: Option<(PoolIndex, Instant)>,
     last_update: Instant,
-    cache_ttl: Duration,
+    pool_ttl: Duration,
+    all_pools_ttl: Duration,
+}
+
+/// Mapping from each mint to the pools that contain it
+///
+/// Built from a snapshot of pool information, so pools created after the
+/// snapshot are missing until the index is rebuilt.
+#[derive(Debug, Clone, Default)]
+pub struct PoolIndex {
+    by_mint: HashMap<Pubkey, HashSet<Pubkey>>,
+}
+
+impl PoolIndex {
+    /// Indexes `pools` by both of their mints
+    pub fn from_pools(pools: &[PoolInfo]) -> Self {
+        let mut by_mint: HashMap<Pubkey, HashSet<Pubkey>> = HashMap::new();
+        for pool in pools {
+            for mint in [pool.token_a_mint, pool.token_b_mint] {
+                by_mint.entry(mint).or_default().insert(pool.address);
+            }
+        }
+        Self { by_mint }
+    }
+
+    /// Addresses of the pools containing `token_mint`
+    pub fn pools_for_mint(&self, token_mint: &Pubkey) -> Vec<Pubkey> {
+        self.by_mint
+            .get(token_mint)
+            .map(|pools| pools.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Addresses of the pools containing both `token_a` and `token_b`
+    pub fn pools_for_pair(&self, token_a: &Pubkey, token_b: &Pubkey) -> Vec<Pubkey> {
+        match (self.by_mint.get(token_a), self.by_mint.get(token_b)) {
+            (Some(a_pools), Some(b_pools)) => a_pools.intersection(b_pools).copied().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Number of distinct mints in the index
+    pub fn mint_count(&self) -> usize {
+        self.by_mint.len()
+    }
 }
 
 /// Manages Meteora pools with caching capabilities
+///
+/// Clones share the same cache.
+#[derive(Clone)]
 pub struct PoolManager {
     client: Arc<MeteoraClient>,
     cache: Arc<Mutex<PoolCache>>,
+    scan_concurrency: usize,
+    sol_usd: Arc<SolUsdResolver>,
 }
 
 impl PoolManager {
     /// Creates a new PoolManager instance
     pub fn new(client: Arc<MeteoraClient>) -> Self {
+        Self::new_with_ttl(
+            client,
+            Duration::from_secs(DEFAULT_CACHE_TTL_SECS),
+            Duration::from_secs(DEFAULT_CACHE_TTL_SECS),
+        )
+    }
+
+    /// Creates a new PoolManager with custom cache lifetimes
+    ///
+    /// # Params
+    /// client - The Meteora client
+    /// pool_ttl - How long a pool's information is cached
+    /// all_pools_ttl - How long the list of all pools is cached
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// let pool_manager = PoolManager::new_with_ttl(
+    ///     client,
+    ///     Duration::from_secs(2),
+    ///     Duration::from_secs(3600),
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_ttl(
+        client: Arc<MeteoraClient>,
+        pool_ttl: Duration,
+        all_pools_ttl: Duration,
+    ) -> Self {
         Self {
             client,
             cache: Arc::new(Mutex::new(PoolCache {
                 pools: HashMap::new(),
                 all_pools: Vec::new(),
-                last_update: Instant::now() - Duration::from_secs(3600),
-                cache_ttl: Duration::from_secs(300),
+                creation_times: HashMap::new(),
+                index: None,
+                last_update: Instant::now(),
+                pool_ttl,
+                all_pools_ttl,
             })),
+            scan_concurrency: DEFAULT_SCAN_CONCURRENCY,
+            sol_usd: Arc::new(SolUsdResolver::default()),
         }
     }
-    /// Retrieves all pool addresses with caching
+
+    /// Sets how many pools scans such as `find_pools_by_tokens` load at once
+    ///
+    /// Values below 1 are treated as 1. Every load still goes through the
+    /// client, so its concurrency and rate limits apply on top.
     ///
     /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// let mut pool_manager = PoolManager::new(client);
+    /// pool_manager.set_scan_concurrency(16);
+    /// # Ok(())
+    /// # }
     /// ```
+    pub fn set_scan_concurrency(&mut self, scan_concurrency: usize) {
+        self.scan_concurrency = scan_concurrency.max(1);
+    }
+
+    /// Resolves SOL/USD through `sol_usd`, shared with the `PriceFeed` that owns this manager
+    pub(crate) fn set_sol_usd_resolver(&mut self, sol_usd: Arc<SolUsdResolver>) {
+        self.sol_usd = sol_usd;
+    }
+
+    pub(crate) fn sol_usd_resolver(&self) -> &SolUsdResolver {
+        &self.sol_usd
+    }
+    /// Retrieves all pool addresses with caching
+    ///
+    /// # Example
+    /// ```no_run
     /// use std::sync::Arc;
-    /// use meteora_client::{MeteoraClient, PoolManager};
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = Arc::new(MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?);
     /// let pool_manager = PoolManager::new(client);
     /// let pools = pool_manager.find_all_pools_cached().await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn find_all_pools_cached(&self) -> Result<Vec<Pubkey>, MeteoraError> {
-        let mut cache = self.cache.lock().unwrap();
-        if cache.last_update.elapsed() < cache.cache_ttl && !cache.all_pools.is_empty() {
-            return Ok(cache.all_pools.clone());
+        {
+            let cache = self.cache.lock().unwrap();
+            if cache.last_update.elapsed() < cache.all_pools_ttl && !cache.all_pools.is_empty() {
+                return Ok(cache.all_pools.clone());
+            }
         }
-        let accounts = self
-            .client
-            .get_program_accounts(&Pubkey::from_str(METEORA_PROGRAM_ID).unwrap(), None)
-            .await?;
-        let pools: Vec<Pubkey> = accounts.into_iter().map(|(pubkey, _)| pubkey).collect();
+        let pools = self.find_all_pools_filtered().await?;
+        let mut cache = self.cache.lock().unwrap();
         cache.all_pools = pools.clone();
         cache.last_update = Instant::now();
         Ok(pools)
@@ -65,181 +216,2301 @@ impl PoolManager {
     /// Retrieves pool information with caching
     ///
     /// # Example
-    /// ```
+    /// ```no_run
     /// use std::sync::Arc;
     /// use solana_sdk::pubkey::Pubkey;
-    /// use meteora_client::{MeteoraClient, PoolManager};
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = Arc::new(MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?);
     /// let pool_manager = PoolManager::new(client);
     /// let pool_address = Pubkey::new_unique();
     /// let pool_info = pool_manager.get_pool_info_cached(&pool_address).await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_pool_info_cached(
         &self,
         pool_address: &Pubkey,
     ) -> Result<PoolInfo, MeteoraError> {
-        let mut cache = self.cache.lock().unwrap();
-        if let Some((cached_info, timestamp)) = cache.pools.get(pool_address) {
-            if timestamp.elapsed() < cache.cache_ttl {
+        {
+            let cache = self.cache.lock().unwrap();
+            if let Some((cached_info, timestamp)) = cache.pools.get(pool_address)
+                && timestamp.elapsed() < cache.pool_ttl
+            {
                 return Ok(cached_info.clone());
             }
         }
+        self.refresh_pool(pool_address).await
+    }
+
+    /// Fetches a pool from RPC and replaces its cached entry
+    ///
+    /// Useful right after a pool is known to have changed, e.g. when your own
+    /// swap lands, to avoid reading stale reserves until the TTL expires.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let pool_info = pool_manager.refresh_pool(&pool_address).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn refresh_pool(&self, pool_address: &Pubkey) -> Result<PoolInfo, MeteoraError> {
         let pool_info = self.get_pool_info(pool_address).await?;
-        cache
+        self.cache
+            .lock()
+            .unwrap()
             .pools
             .insert(*pool_address, (pool_info.clone(), Instant::now()));
         Ok(pool_info)
     }
 
+    /// Drops a pool's cached information so the next cached read fetches it again
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// pool_manager.invalidate_pool(&pool_address);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn invalidate_pool(&self, pool_address: &Pubkey) {
+        self.cache.lock().unwrap().pools.remove(pool_address);
+    }
+
+    /// Drops every cached pool and the cached pool list
+    ///
+    /// Pool creation times never change and are kept.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// pool_manager.invalidate_all();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn invalidate_all(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.pools.clear();
+        cache.all_pools.clear();
+        cache.index = None;
+    }
+
+    /// Rebuilds the mint-to-pools index from a snapshot of every pool
+    ///
+    /// While the index is younger than the pool list TTL, `find_token_pools`
+    /// and `find_pools_by_tokens` answer from it instead of scanning the
+    /// program. The snapshot goes through `get_all_pool_infos`, so the pools
+    /// also land in the pool cache.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let token_mint = spl_token::native_mint::ID;
+    /// let index = pool_manager.rebuild_index().await?;
+    /// println!("{} mints indexed", index.mint_count());
+    /// let pools = pool_manager.find_token_pools(&token_mint).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn rebuild_index(&self) -> Result<PoolIndex, MeteoraError> {
+        let pools = self.get_all_pool_infos().await?;
+        let index = PoolIndex::from_pools(&pools);
+        self.cache.lock().unwrap().index = Some((index.clone(), Instant::now()));
+        Ok(index)
+    }
+
+    /// The mint-to-pools index, if it was built within the pool list TTL
+    fn fresh_index(&self) -> Option<PoolIndex> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .index
+            .as_ref()
+            .filter(|(_, built_at)| built_at.elapsed() < cache.all_pools_ttl)
+            .map(|(index, _)| index.clone())
+    }
+
+    /// Streams a pool's information every time its reserves change
+    ///
+    /// The pool's two reserve accounts are watched with WebSocket
+    /// `accountSubscribe` on the endpoint from `current_ws_endpoint`. A swap
+    /// usually updates both reserves in separate notifications, so updates
+    /// arriving within `RESERVE_UPDATE_DEBOUNCE` of each other are merged
+    /// into one emission. Each emitted `PoolInfo` also replaces the cached
+    /// entry. The stream ends when every receiver is dropped or the
+    /// connection closes.
+    ///
+    /// # Params
+    /// pool_address - The pool to watch
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let mut updates = pool_manager.watch_pool(&pool_address).await?;
+    /// while let Ok(pool_info) = updates.recv().await {
+    ///     println!("Reserves: {} / {}", pool_info.token_a_reserve_amount, pool_info.token_b_reserve_amount);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn watch_pool(
+        &self,
+        pool_address: &Pubkey,
+    ) -> Result<broadcast::Receiver<PoolInfo>, MeteoraError> {
+        let pool_info = self.refresh_pool(pool_address).await?;
+        let ws_url = self.client.current_ws_endpoint();
+        let (sender, receiver) = broadcast::channel(POOL_WATCH_CHANNEL_CAPACITY);
+        let (ready_sender, ready) = oneshot::channel();
+        let pool_manager = self.clone();
+        tokio::spawn(async move {
+            pool_manager
+                .run_pool_watch(ws_url, pool_info, sender, ready_sender)
+                .await;
+        });
+        ready
+            .await
+            .map_err(|_| MeteoraError::Error("Pool watcher stopped unexpectedly".to_string()))??;
+        Ok(receiver)
+    }
+
+    /// Body of the `watch_pool` task, reporting whether subscribing worked on `ready`
+    async fn run_pool_watch(
+        self,
+        ws_url: String,
+        pool_info: PoolInfo,
+        sender: broadcast::Sender<PoolInfo>,
+        ready: oneshot::Sender<Result<(), MeteoraError>>,
+    ) {
+        let pubsub = match PubsubClient::new(&ws_url).await {
+            Ok(pubsub) => pubsub,
+            Err(e) => {
+                let _ = ready.send(Err(MeteoraError::RpcError(e.to_string())));
+                return;
+            }
+        };
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(self.client.commitment),
+            ..Default::default()
+        };
+        let mut streams = Vec::new();
+        let mut unsubscribes = Vec::new();
+        for reserve in [pool_info.token_a_reserve, pool_info.token_b_reserve] {
+            match pubsub
+                .account_subscribe(&reserve, Some(config.clone()))
+                .await
+            {
+                Ok((stream, unsubscribe)) => {
+                    streams.push(stream.map(move |response| (reserve, response.value)));
+                    unsubscribes.push(unsubscribe);
+                }
+                Err(e) => {
+                    let _ = ready.send(Err(MeteoraError::RpcError(e.to_string())));
+                    return;
+                }
+            }
+        }
+        let _ = ready.send(Ok(()));
+
+        self.forward_reserve_updates(pool_info, select_all(streams), sender)
+            .await;
+        for unsubscribe in unsubscribes {
+            unsubscribe().await;
+        }
+    }
+
+    /// Applies reserve notifications to `pool_info` and broadcasts the result,
+    /// merging notifications within `RESERVE_UPDATE_DEBOUNCE` of each other
+    ///
+    /// Returns when `updates` ends or every receiver is dropped.
+    async fn forward_reserve_updates(
+        &self,
+        mut pool_info: PoolInfo,
+        mut updates: impl Stream<Item = (Pubkey, UiAccount)> + Unpin,
+        sender: broadcast::Sender<PoolInfo>,
+    ) {
+        while let Some(first) = updates.next().await {
+            let mut pending = vec![first];
+            let debounce = sleep(RESERVE_UPDATE_DEBOUNCE);
+            tokio::pin!(debounce);
+            loop {
+                tokio::select! {
+                    _ = &mut debounce => break,
+                    update = updates.next() => match update {
+                        Some(update) => pending.push(update),
+                        None => break,
+                    },
+                }
+            }
+            for (reserve, ui_account) in pending {
+                let reserve_account = ui_account
+                    .decode::<Account>()
+                    .ok_or_else(|| {
+                        MeteoraError::DeserializationError(format!(
+                            "Undecodable notification for {}",
+                            reserve
+                        ))
+                    })
+                    .and_then(|account| unpack_token_account(&reserve, &account));
+                match reserve_account {
+                    Ok(account) if reserve == pool_info.token_a_reserve => {
+                        pool_info.token_a_reserve_amount = account.amount
+                    }
+                    Ok(account) => pool_info.token_b_reserve_amount = account.amount,
+                    Err(e) => log::warn!("Ignoring reserve update: {:?}", e),
+                }
+            }
+            pool_info.fetched_at = chrono::Utc::now().timestamp();
+            self.cache
+                .lock()
+                .unwrap()
+                .pools
+                .insert(pool_info.address, (pool_info.clone(), Instant::now()));
+            if sender.send(pool_info.clone()).is_err() {
+                break;
+            }
+        }
+    }
+
     /// Retrieves pool information directly from RPC
     pub async fn get_pool_info(&self, pool_address: &Pubkey) -> Result<PoolInfo, MeteoraError> {
         let pool_data = self.client.get_account_data(pool_address).await?;
-        if pool_data.len() < 300 {
-            return Err(MeteoraError::InvalidPoolData);
-        }
-        let token_a_mint = Pubkey::new_from_array(
-            pool_data[8..40]
-                .try_into()
-                .map_err(|_| MeteoraError::InvalidPoolData)?,
-        );
-        let token_b_mint = Pubkey::new_from_array(
-            pool_data[40..72]
-                .try_into()
-                .map_err(|_| MeteoraError::InvalidPoolData)?,
-        );
-        let token_a_reserve = Pubkey::new_from_array(
-            pool_data[72..104]
-                .try_into()
-                .map_err(|_| MeteoraError::InvalidPoolData)?,
-        );
-        let token_b_reserve = Pubkey::new_from_array(
-            pool_data[104..136]
-                .try_into()
-                .map_err(|_| MeteoraError::InvalidPoolData)?,
-        );
-        let lp_mint = Pubkey::new_from_array(
-            pool_data[136..168]
-                .try_into()
-                .map_err(|_| MeteoraError::InvalidPoolData)?,
-        );
-        let fee_account = Pubkey::new_from_array(
-            pool_data[168..200]
-                .try_into()
-                .map_err(|_| MeteoraError::InvalidPoolData)?,
-        );
-        let token_a_decimals = self.get_token_decimals(&token_a_mint).await?;
-        let token_b_decimals = self.get_token_decimals(&token_b_mint).await?;
-        let token_a_reserve_amount = self.get_token_balance(&token_a_reserve).await?;
-        let token_b_reserve_amount = self.get_token_balance(&token_b_reserve).await?;
-        let lp_supply = self.get_token_supply(&lp_mint).await?;
-        Ok(PoolInfo {
-            address: *pool_address,
-            token_a_mint,
-            token_b_mint,
-            token_a_reserve,
-            token_b_reserve,
-            lp_mint,
-            fee_account,
-            trade_fee_bps: 30, // Meteora default fee 0.3%
-            token_a_decimals,
-            token_b_decimals,
-            token_a_reserve_amount,
-            token_b_reserve_amount,
-            lp_supply,
+        self.load_pool_info(pool_address, &pool_data).await
+    }
+
+    /// Checks that a pool account and its reserves belong together
+    ///
+    /// Returns `Ok(false)`, and logs why, when the pool account isn't owned by
+    /// the Meteora program, both reserves are the same account, or either
+    /// reserve holds a different mint than the pool's token or isn't owned by
+    /// the Meteora vault of that mint (see `vault_address`). A fake pool that
+    /// only copies the account layout fails these checks, since it can't make
+    /// the vault program's PDA own its reserves. Errors are returned only when
+    /// the accounts can't be fetched or parsed.
+    ///
+    /// # Params
+    /// pool_address - The pool to verify
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let trusted = pool_manager.verify_pool_integrity(&pool_address).await?;
+    /// println!("Pool {} trusted: {}", pool_address, trusted);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn verify_pool_integrity(&self, pool_address: &Pubkey) -> Result<bool, MeteoraError> {
+        let account = self.client.get_account(pool_address).await?;
+        let program_id = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
+        if account.owner != program_id {
+            log::warn!(
+                "Pool {} is owned by {}, not the Meteora program",
+                pool_address,
+                account.owner
+            );
+            return Ok(false);
+        }
+        let pool = parse_pool_data(pool_address, &account.data)?;
+        if pool.token_a_reserve == pool.token_b_reserve {
+            log::warn!("Pool {} uses one account for both reserves", pool_address);
+            return Ok(false);
+        }
+        let reserves = [
+            (pool.token_a_reserve, pool.token_a_mint),
+            (pool.token_b_reserve, pool.token_b_mint),
+        ];
+        let accounts = self
+            .client
+            .get_multiple_accounts(&reserves.map(|(reserve, _)| reserve))
+            .await?;
+        for ((reserve, expected_mint), account) in reserves.iter().zip(&accounts) {
+            let account = account.as_ref().ok_or_else(|| {
+                MeteoraError::AccountNotFound(format!("Reserve {} not found", reserve))
+            })?;
+            let token_account = unpack_token_account(reserve, account)?;
+            if let Some(problem) = reserve_problem(expected_mint, &token_account) {
+                log::warn!("Reserve {} of pool {} {}", reserve, pool_address, problem);
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Loads already fetched pool accounts, at most `scan_concurrency` at a time
+    ///
+    /// Pools are returned in input order; those that fail to load are skipped.
+    async fn load_pool_accounts(&self, pool_accounts: Vec<(Pubkey, Account)>) -> Vec<PoolInfo> {
+        stream::iter(pool_accounts)
+            .map(|(pool_address, account)| async move {
+                self.load_pool_info(&pool_address, &account.data).await
+            })
+            .buffered(self.scan_concurrency)
+            .filter_map(|pool_info| future::ready(pool_info.ok()))
+            .collect()
+            .await
+    }
+
+    /// Parses pool account data and fetches the fields stored in other accounts
+    async fn load_pool_info(
+        &self,
+        pool_address: &Pubkey,
+        pool_data: &[u8],
+    ) -> Result<PoolInfo, MeteoraError> {
+        let pool = parse_pool_data(pool_address, pool_data)?;
+        let addresses = pool_dependency_addresses(&pool);
+        let accounts = self.client.get_multiple_accounts(&addresses).await?;
+        pool_info_from_accounts(pool_address, pool, &accounts)
+    }
+
+    /// Retrieves several pools, serving fresh entries from the cache
+    ///
+    /// Pools missing from the cache are loaded with two batched requests, one
+    /// for the pool accounts and one for all of their mints and reserves,
+    /// instead of one round of requests per pool, and are then cached. Pools
+    /// are returned in the order of `addresses`; if any of them can't be
+    /// loaded, its error is returned.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_addresses = vec![solana_sdk::pubkey::Pubkey::new_unique()];
+    /// let pool_infos = pool_manager.get_pool_infos(&pool_addresses).await?;
+    /// for pool_info in pool_infos {
+    ///     println!("{}: {} bps", pool_info.address, pool_info.trade_fee_bps);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_pool_infos(
+        &self,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<PoolInfo>, MeteoraError> {
+        self.load_pool_infos(addresses)
+            .await?
+            .into_iter()
+            .map(|(_, pool_info)| pool_info)
+            .collect()
+    }
+
+    /// Cached or batch-loaded pool information for each address, with a
+    /// separate result per pool
+    async fn load_pool_infos(
+        &self,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<(Pubkey, Result<PoolInfo, MeteoraError>)>, MeteoraError> {
+        let mut results: HashMap<Pubkey, Result<PoolInfo, MeteoraError>> = HashMap::new();
+        let mut misses = Vec::new();
+        let mut seen = HashSet::new();
+        {
+            let cache = self.cache.lock().unwrap();
+            for address in addresses {
+                if !seen.insert(*address) {
+                    continue;
+                }
+                match cache.pools.get(address) {
+                    Some((pool_info, timestamp)) if timestamp.elapsed() < cache.pool_ttl => {
+                        results.insert(*address, Ok(pool_info.clone()));
+                    }
+                    _ => misses.push(*address),
+                }
+            }
+        }
+        if !misses.is_empty() {
+            let pool_accounts = self.client.get_multiple_accounts(&misses).await?;
+            let mut parsed = Vec::new();
+            for (address, account) in misses.iter().zip(pool_accounts) {
+                let pool = account
+                    .ok_or_else(|| {
+                        MeteoraError::AccountNotFound(format!("Pool {} not found", address))
+                    })
+                    .and_then(|account| parse_pool_data(address, &account.data));
+                match pool {
+                    Ok(pool) => parsed.push((*address, pool)),
+                    Err(e) => {
+                        results.insert(*address, Err(e));
+                    }
+                }
+            }
+            let dependencies: Vec<Pubkey> = parsed
+                .iter()
+                .flat_map(|(_, pool)| pool_dependency_addresses(pool))
+                .collect();
+            let accounts = self.client.get_multiple_accounts(&dependencies).await?;
+            let mut cache = self.cache.lock().unwrap();
+            for ((address, pool), accounts) in parsed
+                .into_iter()
+                .zip(accounts.chunks(POOL_DEPENDENCY_COUNT))
+            {
+                let pool_info = pool_info_from_accounts(&address, pool, accounts);
+                if let Ok(pool_info) = &pool_info {
+                    cache
+                        .pools
+                        .insert(address, (pool_info.clone(), Instant::now()));
+                }
+                results.insert(address, pool_info);
+            }
+        }
+        Ok(addresses
+            .iter()
+            .map(|address| {
+                let result = results.get(address).cloned().unwrap_or_else(|| {
+                    Err(MeteoraError::AccountNotFound(format!(
+                        "Pool {} not found",
+                        address
+                    )))
+                });
+                (*address, result)
+            })
+            .collect())
+    }
+
+    /// Re-reads the mints, reserves and LP mint of already parsed pools
+    ///
+    /// The pool accounts themselves aren't fetched again, so however old the
+    /// cached entries of `pools` are, their reserves are read in one batched
+    /// request. Refreshed pools replace their cached entries; pools whose
+    /// accounts can't be read are skipped.
+    ///
+    /// # Params
+    /// pools - Pools to refresh, e.g. from `get_all_pool_infos`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// let pools = pool_manager.get_all_pool_infos().await?;
+    /// let pools = pool_manager.refresh_reserves(&pools).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn refresh_reserves(
+        &self,
+        pools: &[PoolInfo],
+    ) -> Result<Vec<PoolInfo>, MeteoraError> {
+        let dependencies: Vec<Pubkey> = pools.iter().flat_map(pool_dependency_addresses).collect();
+        let accounts = self.client.get_multiple_accounts(&dependencies).await?;
+        let mut cache = self.cache.lock().unwrap();
+        Ok(pools
+            .iter()
+            .zip(accounts.chunks(POOL_DEPENDENCY_COUNT))
+            .filter_map(|(pool, accounts)| {
+                let pool_info =
+                    pool_info_from_accounts(&pool.address, pool.clone(), accounts).ok()?;
+                cache
+                    .pools
+                    .insert(pool.address, (pool_info.clone(), Instant::now()));
+                Some(pool_info)
+            })
+            .collect())
+    }
+
+    /// Finds the pool with the most liquidity in a token
+    ///
+    /// Pools are ranked by `PoolInfo::normalized_liquidity_in`, the same
+    /// ranking used for pricing and for picking a direct pool to trade
+    /// through. Pools that fail to load are skipped.
+    ///
+    /// # Params
+    /// token_mint - The token to find a pool for
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let token_mint = spl_token::native_mint::ID;
+    /// let (pool_address, pool_info) = pool_manager.find_best_pool(&token_mint).await?;
+    /// println!("Deepest pool: {} ({} bps fee)", pool_address, pool_info.trade_fee_bps);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn find_best_pool(
+        &self,
+        token_mint: &Pubkey,
+    ) -> Result<(Pubkey, PoolInfo), MeteoraError> {
+        let addresses = self.find_token_pools(token_mint).await?;
+        let pools: Vec<PoolInfo> = self
+            .load_pool_infos(&addresses)
+            .await?
+            .into_iter()
+            .filter_map(|(_, pool_info)| pool_info.ok())
+            .collect();
+        let pool_info = best_pool(token_mint, &pools)
+            .cloned()
+            .ok_or(MeteoraError::NoLiquidityPoolFound)?;
+        Ok((pool_info.address, pool_info))
+    }
+
+    /// Retrieves when a pool was created
+    ///
+    /// Walks the pool's signature history back to its oldest transaction and
+    /// returns that transaction's block time. The result is cached permanently.
+    /// At most `MAX_CREATION_TIME_PAGES` pages of signatures are read; for a
+    /// pool with a longer history the oldest block time read so far is
+    /// returned with `is_lower_bound` set, since the pool is at least that old.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let created_at = pool_manager.get_pool_creation_time(&pool_address).await?;
+    /// if created_at.is_lower_bound {
+    ///     println!("Pool created at or before {}", created_at.timestamp);
+    /// } else {
+    ///     println!("Pool created at {}", created_at.timestamp);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_pool_creation_time(
+        &self,
+        pool_address: &Pubkey,
+    ) -> Result<PoolCreationTime, MeteoraError> {
+        if let Some(created_at) = self.cache.lock().unwrap().creation_times.get(pool_address) {
+            return Ok(*created_at);
+        }
+        let mut before = None;
+        let mut oldest = None;
+        let mut is_lower_bound = false;
+        for page_number in 1.. {
+            if page_number > MAX_CREATION_TIME_PAGES {
+                log::warn!(
+                    "Pool {} has more than {} signatures, its creation time is a lower bound",
+                    pool_address,
+                    MAX_CREATION_TIME_PAGES * MAX_SIGNATURES_PER_REQUEST
+                );
+                is_lower_bound = true;
+                break;
+            }
+            let page = self
+                .client
+                .get_signatures_for_address(pool_address, before, None, MAX_SIGNATURES_PER_REQUEST)
+                .await?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            before = Some(
+                Signature::from_str(&last.signature)
+                    .map_err(|e| MeteoraError::DeserializationError(e.to_string()))?,
+            );
+            oldest = Some(last.clone());
+            if page.len() < MAX_SIGNATURES_PER_REQUEST {
+                break;
+            }
+        }
+        let timestamp = oldest
+            .and_then(|signature| signature.block_time)
+            .ok_or(MeteoraError::NoHistoricalData)?;
+        let created_at = PoolCreationTime {
+            timestamp,
+            is_lower_bound,
+        };
+        self.cache
+            .lock()
+            .unwrap()
+            .creation_times
+            .insert(*pool_address, created_at);
+        Ok(created_at)
+    }
+
+    /// Retrieves how long ago a pool was created
+    ///
+    /// See `get_pool_creation_time`; only the first lookup walks the pool's
+    /// signature history. For a pool whose history is too long to read to
+    /// the start, the age is a lower bound. A creation time in the future
+    /// yields zero.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let age = pool_manager.get_pool_age(&pool_address).await?;
+    /// println!("Pool is {} days old", age.as_secs() / 86400);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_pool_age(&self, pool_address: &Pubkey) -> Result<Duration, MeteoraError> {
+        let created_at = self.get_pool_creation_time(pool_address).await?;
+        Ok(pool_age(
+            created_at.timestamp,
+            chrono::Utc::now().timestamp(),
+        ))
+    }
+
+    /// Finds pools that contain the specified token pair and are at least `min_age` old
+    ///
+    /// A pool whose history is too long to read to the start is kept when
+    /// the lower bound on its age already reaches `min_age`. Pools whose
+    /// history can't be fetched are left out.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let (token_a, token_b) = (spl_token::native_mint::ID, solana_sdk::pubkey::Pubkey::new_unique());
+    /// // Skip pools created within the last day
+    /// let pools = pool_manager
+    ///     .find_pools_by_tokens_with_min_age(&token_a, &token_b, Duration::from_secs(86400))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn find_pools_by_tokens_with_min_age(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+        min_age: Duration,
+    ) -> Result<Vec<PoolInfo>, MeteoraError> {
+        let now = chrono::Utc::now().timestamp();
+        let mut old_enough = Vec::new();
+        for pool_info in self.find_pools_by_tokens(token_a, token_b).await? {
+            match self.get_pool_creation_time(&pool_info.address).await {
+                Ok(created_at) if pool_age(created_at.timestamp, now) >= min_age => {
+                    old_enough.push(pool_info)
+                }
+                Ok(created_at) if created_at.is_lower_bound => log::debug!(
+                    "Skipping pool {} whose age can't be shown to reach {:?}",
+                    pool_info.address,
+                    min_age
+                ),
+                Ok(_) => {}
+                Err(e) => log::debug!(
+                    "Skipping pool {} of unknown age: {:?}",
+                    pool_info.address,
+                    e
+                ),
+            }
+        }
+        Ok(old_enough)
+    }
+
+    /// Pages backwards through a pool's successful signatures whose block time
+    /// falls within `from..=to`
+    pub(crate) async fn get_pool_signatures_in_range(
+        &self,
+        pool_address: &Pubkey,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<String>, MeteoraError> {
+        let mut signatures = Vec::new();
+        let mut before: Option<Signature> = None;
+        loop {
+            let page = self
+                .client
+                .get_signatures_for_address(pool_address, before, None, MAX_SIGNATURES_PER_REQUEST)
+                .await?;
+            let mut reached_start = false;
+            for sig in &page {
+                match sig.block_time {
+                    Some(block_time) if block_time > to => continue,
+                    Some(block_time) if block_time < from => {
+                        reached_start = true;
+                        break;
+                    }
+                    _ => {}
+                }
+                if sig.err.is_none() {
+                    signatures.push(sig.signature.clone());
+                }
+            }
+            if reached_start || page.len() < MAX_SIGNATURES_PER_REQUEST {
+                break;
+            }
+            before = match page.last().and_then(|sig| sig.signature.parse().ok()) {
+                Some(signature) => Some(signature),
+                None => break,
+            };
+        }
+        Ok(signatures)
+    }
+
+    /// Gets a pool's trading volume, fees and fee APR over the last 24 hours
+    ///
+    /// Successful transactions touching the pool in the last day are fetched,
+    /// at most `scan_concurrency` at a time, and decoded from their reserve
+    /// balance changes, so deposits and withdrawals are not counted. A busy
+    /// pool has only `MAX_POOL_STATS_TRANSACTIONS` of its transactions
+    /// fetched, spread evenly over the day, and their volume is scaled up to
+    /// the full transaction count, so the stats are an estimate.
+    /// Volume is the USD value of the swap inputs and fees are the pool's
+    /// trade fee on that volume. A token without a USD price is valued at the
+    /// pool's own rate against the other one. `fee_apr` is a percentage, see
+    /// `math::fee_apr`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let stats = pool_manager.get_pool_stats(&pool_address).await?;
+    /// println!("24h volume: ${:.0}, fee APR: {:.2}%", stats.volume_24h_usd, stats.fee_apr);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_pool_stats(&self, pool_address: &Pubkey) -> Result<PoolStats, MeteoraError> {
+        let pool_info = self.get_pool_info(pool_address).await?;
+        let token_a_price = self.get_token_usd_price(&pool_info.token_a_mint).await.ok();
+        let token_b_price = self.get_token_usd_price(&pool_info.token_b_mint).await.ok();
+        let tvl_usd = pool_info
+            .tvl(token_a_price, token_b_price)
+            .ok_or(MeteoraError::InvalidPrice)?;
+        let (token_a_price, token_b_price) = match (token_a_price, token_b_price) {
+            (Some(price_a), Some(price_b)) => (price_a, price_b),
+            (Some(price_a), None) => (
+                price_a,
+                pool_info.price_of(&pool_info.token_b_mint)? * price_a,
+            ),
+            (None, Some(price_b)) => (
+                pool_info.price_of(&pool_info.token_a_mint)? * price_b,
+                price_b,
+            ),
+            (None, None) => return Err(MeteoraError::InvalidPrice),
+        };
+        let to = chrono::Utc::now().timestamp();
+        let from = to - SECONDS_PER_DAY;
+        let signatures = self
+            .get_pool_signatures_in_range(pool_address, from, to)
+            .await?;
+        let transaction_count = signatures.len();
+        let signatures = sample_evenly(&signatures, MAX_POOL_STATS_TRANSACTIONS);
+        let scale = if signatures.is_empty() {
+            1.0
+        } else {
+            transaction_count as f64 / signatures.len() as f64
+        };
+        let pool_info = &pool_info;
+        let swaps: Vec<SwapEvent> = stream::iter(signatures)
+            .map(|signature| async move {
+                let signature: Signature = signature
+                    .parse()
+                    .map_err(|_| MeteoraError::Error("Invalid signature".to_string()))?;
+                let tx = self.client.get_transaction(&signature).await?;
+                // Volume is valued below from each side's own price, so the
+                // swap's quote token price is left unset
+                decode_pool_swap(&tx, pool_info, &pool_info.token_a_mint, 0.0)
+            })
+            .buffer_unordered(self.scan_concurrency)
+            .filter_map(|swap| future::ready(swap.ok()))
+            .collect()
+            .await;
+        let volume_24h_usd: f64 = swaps
+            .iter()
+            .filter(|swap| swap.timestamp >= from && swap.timestamp <= to)
+            .map(|swap| {
+                let (price, decimals) = if swap.input_mint == pool_info.token_a_mint {
+                    (token_a_price, pool_info.token_a_decimals)
+                } else {
+                    (token_b_price, pool_info.token_b_decimals)
+                };
+                swap.input_amount as f64 / 10f64.powi(decimals as i32) * price
+            })
+            .sum::<f64>()
+            * scale;
+        let fees_24h_usd = volume_24h_usd * pool_info.trade_fee_bps as f64 / BPS_DENOMINATOR as f64;
+        Ok(PoolStats {
+            volume_24h_usd,
+            fees_24h_usd,
+            fee_apr: math::fee_apr(fees_24h_usd, tvl_usd),
+            tvl_usd,
         })
     }
 
     /// Finds pools that contain the specified token pair
     ///
     /// # Example
-    /// ```
+    /// ```no_run
     /// use std::sync::Arc;
     /// use solana_sdk::pubkey::Pubkey;
-    /// use meteora_client::{MeteoraClient, PoolManager};
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = Arc::new(MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?);
     /// let pool_manager = PoolManager::new(client);
     /// let token_a = Pubkey::new_unique();
     /// let token_b = Pubkey::new_unique();
     /// let pools = pool_manager.find_pools_by_tokens(&token_a, &token_b).await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn find_pools_by_tokens(
         &self,
         token_a: &Pubkey,
         token_b: &Pubkey,
     ) -> Result<Vec<PoolInfo>, MeteoraError> {
-        let all_pools = self.find_all_pools().await?;
-        let mut matching_pools = Vec::new();
-        for pool_address in all_pools {
-            if let Ok(pool_info) = self.get_pool_info(&pool_address).await {
-                if (pool_info.token_a_mint == *token_a && pool_info.token_b_mint == *token_b)
-                    || (pool_info.token_a_mint == *token_b && pool_info.token_b_mint == *token_a)
-                {
-                    matching_pools.push(pool_info);
+        if let Some(index) = self.fresh_index() {
+            let addresses = index.pools_for_pair(token_a, token_b);
+            return Ok(self
+                .load_pool_infos(&addresses)
+                .await?
+                .into_iter()
+                .filter_map(|(_, pool_info)| pool_info.ok())
+                .collect());
+        }
+        let program_id = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
+        let mut seen = HashSet::new();
+        let mut pool_accounts = Vec::new();
+        for layout in PoolLayout::ALL {
+            for (mint_a, mint_b) in [(token_a, token_b), (token_b, token_a)] {
+                let accounts = self
+                    .client
+                    .get_program_accounts(
+                        &program_id,
+                        Some(layout.filters_for_mints(Some(mint_a), Some(mint_b))),
+                    )
+                    .await?;
+                pool_accounts.extend(
+                    accounts
+                        .into_iter()
+                        .filter(|(pool_address, _)| seen.insert(*pool_address)),
+                );
+            }
+        }
+        Ok(self.load_pool_accounts(pool_accounts).await)
+    }
+
+    /// Checks whether any pool trades the specified token pair
+    ///
+    /// Only pool keys are requested, with account data sliced to zero bytes, so
+    /// no pool is deserialized or loaded. `getProgramAccounts` filters must all
+    /// match, so one request covers a single layout and mint order: at most
+    /// `2 * PoolLayout::ALL.len()` requests are made, stopping at the first
+    /// that returns a pool.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let (token_a, token_b) = (spl_token::native_mint::ID, solana_sdk::pubkey::Pubkey::new_unique());
+    /// if !pool_manager.pool_exists_for_pair(&token_a, &token_b).await? {
+    ///     println!("No direct pool");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn pool_exists_for_pair(
+        &self,
+        token_a: &Pubkey,
+        token_b: &Pubkey,
+    ) -> Result<bool, MeteoraError> {
+        let program_id = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
+        for layout in PoolLayout::ALL {
+            for (mint_a, mint_b) in [(token_a, token_b), (token_b, token_a)] {
+                let keys = self
+                    .client
+                    .get_program_account_keys(
+                        &program_id,
+                        layout.filters_for_mints(Some(mint_a), Some(mint_b)),
+                    )
+                    .await?;
+                if !keys.is_empty() {
+                    return Ok(true);
                 }
             }
         }
-        Ok(matching_pools)
+        Ok(false)
+    }
+
+    /// Finds pools for a swap from `input_mint` to `output_mint`, tagged with direction
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let (input_mint, output_mint) = (spl_token::native_mint::ID, solana_sdk::pubkey::Pubkey::new_unique());
+    /// let pools = pool_manager.find_matched_pools(&input_mint, &output_mint).await?;
+    /// for matched in pools {
+    ///     println!("{} input is token a: {}", matched.pool.address, matched.input_is_token_a);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn find_matched_pools(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+    ) -> Result<Vec<MatchedPool>, MeteoraError> {
+        let pools = self.find_pools_by_tokens(input_mint, output_mint).await?;
+        Ok(pools
+            .into_iter()
+            .map(|pool| MatchedPool::new(pool, input_mint))
+            .collect())
     }
 
     /// Retrieves all pool addresses without caching
     pub async fn find_all_pools(&self) -> Result<Vec<Pubkey>, MeteoraError> {
-        let accounts = self
-            .client
-            .get_program_accounts(&Pubkey::from_str(METEORA_PROGRAM_ID).unwrap(), None)
-            .await?;
-        Ok(accounts.into_iter().map(|(pubkey, _)| pubkey).collect())
+        self.find_all_pools_filtered().await
+    }
+
+    /// Retrieves all pool addresses, fetching only pool accounts and only their keys
+    ///
+    /// Each known `PoolLayout` is queried with a size and discriminator filter
+    /// and a zero-length data slice, so other program accounts and pool data
+    /// never leave the RPC node.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// let pools = pool_manager.find_all_pools_filtered().await?;
+    /// println!("{} pools", pools.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn find_all_pools_filtered(&self) -> Result<Vec<Pubkey>, MeteoraError> {
+        let program_id = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
+        let mut pools = Vec::new();
+        for layout in PoolLayout::ALL {
+            let keys = self
+                .client
+                .get_program_account_keys(&program_id, layout.filters())
+                .await?;
+            pools.extend(keys);
+        }
+        Ok(pools)
+    }
+
+    /// Finds the first pool whose account fields satisfy `predicate`
+    ///
+    /// Pool accounts are streamed instead of loaded all at once, and the scan
+    /// stops at the first match. `predicate` only sees the fields stored in the
+    /// pool account, see `parse_pool_account`; the returned pool is fully loaded.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let lp_mint = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let pool = pool_manager
+    ///     .find_pool(|pool| pool.lp_mint == lp_mint)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn find_pool(
+        &self,
+        predicate: impl Fn(&PoolInfo) -> bool,
+    ) -> Result<Option<PoolInfo>, MeteoraError> {
+        let program_id = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
+        for layout in PoolLayout::ALL {
+            let mut accounts = Box::pin(
+                self.client
+                    .stream_program_accounts(&program_id, layout.filters()),
+            );
+            while let Some(result) = accounts.next().await {
+                let (pool_address, account) = result?;
+                let Ok(pool) = parse_pool_account(&account.data) else {
+                    continue;
+                };
+                if predicate(&PoolInfo {
+                    address: pool_address,
+                    ..pool
+                }) {
+                    return self
+                        .load_pool_info(&pool_address, &account.data)
+                        .await
+                        .map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Retrieves a snapshot of every pool's information from a single pool scan
+    ///
+    /// Pools whose information cannot be loaded are skipped.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::sync::Arc;
+    /// use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = Arc::new(MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?);
+    /// let pool_manager = PoolManager::new(client);
+    /// let pools = pool_manager.get_all_pool_infos().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_all_pool_infos(&self) -> Result<Vec<PoolInfo>, MeteoraError> {
+        let all_pools = self.find_all_pools_cached().await?;
+        Ok(self
+            .load_pool_infos(&all_pools)
+            .await?
+            .into_iter()
+            .filter_map(|(_, pool_info)| pool_info.ok())
+            .collect())
     }
 
     /// Finds all pools that contain the specified token
+    ///
+    /// Answered from the index without RPC calls while it is fresh, see
+    /// `rebuild_index`.
     pub async fn find_token_pools(&self, token_mint: &Pubkey) -> Result<Vec<Pubkey>, MeteoraError> {
-        let all_pools = self.find_all_pools().await?;
+        if let Some(index) = self.fresh_index() {
+            return Ok(index.pools_for_mint(token_mint));
+        }
+        let program_id = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
         let mut token_pools = Vec::new();
-        for pool_address in all_pools {
-            if let Ok(pool_info) = self.get_pool_info(&pool_address).await {
-                if pool_info.token_a_mint == *token_mint || pool_info.token_b_mint == *token_mint {
-                    token_pools.push(pool_address);
+        for layout in PoolLayout::ALL {
+            for filters in [
+                layout.filters_for_mints(Some(token_mint), None),
+                layout.filters_for_mints(None, Some(token_mint)),
+            ] {
+                for pool_address in self
+                    .client
+                    .get_program_account_keys(&program_id, filters)
+                    .await?
+                {
+                    if !token_pools.contains(&pool_address) {
+                        token_pools.push(pool_address);
+                    }
                 }
             }
         }
         Ok(token_pools)
     }
 
-    /// Calculates total liquidity for a pool
+    /// Calculates total liquidity for a pool in base units of one of its tokens
+    ///
+    /// See `PoolInfo::liquidity_in`. Measuring every pool of a token in that
+    /// token keeps the values comparable, which raw reserve sums are not.
+    ///
+    /// # Params
+    /// pool_address - The pool to measure
+    /// quote_mint - The pool token to express the liquidity in
     ///
     /// # Example
-    /// ```
+    /// ```no_run
     /// use std::sync::Arc;
     /// use solana_sdk::pubkey::Pubkey;
-    /// use meteora_client::{MeteoraClient, PoolManager};
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = Arc::new(MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?);
     /// let pool_manager = PoolManager::new(client);
     /// let pool_address = Pubkey::new_unique();
-    /// let liquidity = pool_manager.get_pool_liquidity(&pool_address).await?;
+    /// # let token_mint = spl_token::native_mint::ID;
+    /// let liquidity = pool_manager.get_pool_liquidity(&pool_address, &token_mint).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_pool_liquidity(
+        &self,
+        pool_address: &Pubkey,
+        quote_mint: &Pubkey,
+    ) -> Result<u64, MeteoraError> {
+        let pool_info = self.get_pool_info(pool_address).await?;
+        pool_info.liquidity_in(quote_mint).ok_or_else(|| {
+            MeteoraError::InvalidInput(format!(
+                "Pool {} does not contain {}",
+                pool_address, quote_mint
+            ))
+        })
+    }
+
+    /// Calculates the total value locked in a pool in USD
+    ///
+    /// Each reserve is converted to UI units and valued with its token's USD
+    /// price from `get_token_usd_price`. If only one token can be priced, the
+    /// other side is valued at the pool's own rate.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let tvl = pool_manager.get_pool_tvl_usd(&pool_address).await?;
+    /// println!("TVL: ${:.2}", tvl);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_pool_tvl_usd(&self, pool_address: &Pubkey) -> Result<f64, MeteoraError> {
+        let pool_info = self.get_pool_info(pool_address).await?;
+        self.pool_tvl_usd(&pool_info).await
+    }
+
+    /// Calculates the USD value of one LP token of a pool
+    ///
+    /// The pool's USD TVL from `get_pool_tvl_usd` divided by the LP supply in
+    /// UI units.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let lp_price = pool_manager.get_lp_token_price_usd(&pool_address).await?;
+    /// println!("LP token: ${:.4}", lp_price);
+    /// # Ok(())
+    /// # }
     /// ```
-    pub async fn get_pool_liquidity(&self, pool_address: &Pubkey) -> Result<u64, MeteoraError> {
+    pub async fn get_lp_token_price_usd(&self, pool_address: &Pubkey) -> Result<f64, MeteoraError> {
         let pool_info = self.get_pool_info(pool_address).await?;
-        let liquidity = pool_info.token_a_reserve_amount + pool_info.token_b_reserve_amount;
-        Ok(liquidity)
+        if pool_info.lp_supply == 0 {
+            return Err(MeteoraError::CalculationError(format!(
+                "Pool {} has no LP supply",
+                pool_address
+            )));
+        }
+        let tvl = self.pool_tvl_usd(&pool_info).await?;
+        Ok(tvl / pool_info.ui_lp_supply())
+    }
+
+    async fn pool_tvl_usd(&self, pool_info: &PoolInfo) -> Result<f64, MeteoraError> {
+        let token_a_price = self.get_token_usd_price(&pool_info.token_a_mint).await.ok();
+        let token_b_price = self.get_token_usd_price(&pool_info.token_b_mint).await.ok();
+        pool_info
+            .tvl(token_a_price, token_b_price)
+            .ok_or(MeteoraError::InvalidPrice)
+    }
+
+    /// Gets the USD price of a token from its deepest USDC or WSOL pool
+    ///
+    /// USDC is priced at 1 and WSOL at the rate of `get_sol_usd_price`, which
+    /// fails with `InvalidPrice` when only the fallback rate is available.
+    /// Other tokens are priced against USDC pools first, then WSOL pools.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// # let token_mint = spl_token::native_mint::ID;
+    /// let price = pool_manager.get_token_usd_price(&token_mint).await?;
+    /// println!("Price: ${}", price);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_token_usd_price(&self, token_mint: &Pubkey) -> Result<f64, MeteoraError> {
+        let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+        let wsol_mint = spl_token::native_mint::ID;
+        if *token_mint == usdc_mint {
+            return Ok(1.0);
+        }
+        if *token_mint == wsol_mint {
+            return self.sol_usd_price().await;
+        }
+        if let Some(price) = self.deepest_pool_price(token_mint, &usdc_mint).await? {
+            return Ok(price);
+        }
+        let sol_price = self
+            .deepest_pool_price(token_mint, &wsol_mint)
+            .await?
+            .ok_or(MeteoraError::NoLiquidityPoolFound)?;
+        Ok(sol_price * self.sol_usd_price().await?)
+    }
+
+    /// Gets the SOL/USD rate and where it came from
+    ///
+    /// Uses the pinned rate of the owning `PriceFeed` if one is set. Otherwise
+    /// prefers the deepest WSOL/USDC pool, then the feed's Pyth oracle if it
+    /// has one, and only then a hardcoded fallback rate, see
+    /// `PriceFeed::get_sol_usd_price`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, pool::PoolManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = PoolManager::new(client);
+    /// let (sol_usd, source) = pool_manager.get_sol_usd_price().await;
+    /// println!("SOL/USD {} from {:?}", sol_usd, source);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_sol_usd_price(&self) -> (f64, PriceSource) {
+        let pool_price = async {
+            self.get_sol_usd_pool_price().await.unwrap_or_else(|e| {
+                log::warn!("Failed to price SOL/USD from pools: {:?}", e);
+                None
+            })
+        };
+        self.sol_usd.resolve(&self.client, pool_price).await
+    }
+
+    /// SOL/USD from `get_sol_usd_price`, or `InvalidPrice` for the fallback rate
+    async fn sol_usd_price(&self) -> Result<f64, MeteoraError> {
+        match self.get_sol_usd_price().await {
+            (_, PriceSource::Fallback) => Err(MeteoraError::InvalidPrice),
+            (price, _) => Ok(price),
+        }
+    }
+
+    /// Gets the SOL/USD rate from the deepest WSOL/USDC pool, if there is one
+    pub async fn get_sol_usd_pool_price(&self) -> Result<Option<f64>, MeteoraError> {
+        let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+        self.deepest_pool_price(&spl_token::native_mint::ID, &usdc_mint)
+            .await
+    }
+
+    /// Price of `token_mint` in `quote_mint` from the pool holding the most `quote_mint`
+    async fn deepest_pool_price(
+        &self,
+        token_mint: &Pubkey,
+        quote_mint: &Pubkey,
+    ) -> Result<Option<f64>, MeteoraError> {
+        let pools = self.find_pools_by_tokens(token_mint, quote_mint).await?;
+        let quote_reserve = |pool: &PoolInfo| {
+            if pool.token_a_mint == *quote_mint {
+                pool.token_a_reserve_amount
+            } else {
+                pool.token_b_reserve_amount
+            }
+        };
+        Ok(pools
+            .iter()
+            .max_by_key(|pool| quote_reserve(pool))
+            .and_then(|pool| pool.price_of(token_mint).ok())
+            .filter(|price| price.is_finite() && *price > 0.0))
+    }
+}
+
+/// Picks at most `max` of `items`, evenly spaced and in order
+///
+/// Returns all of `items` when there are no more than `max`.
+fn sample_evenly<T: Clone>(items: &[T], max: usize) -> Vec<T> {
+    if items.len() <= max {
+        return items.to_vec();
     }
+    (0..max)
+        .map(|i| items[i * items.len() / max].clone())
+        .collect()
+}
+
+/// Number of accounts a pool's information is built from, see `pool_dependency_addresses`
+const POOL_DEPENDENCY_COUNT: usize = 5;
+
+/// Describes why a pool reserve for `expected_mint` can't be trusted, if it can't
+///
+/// A genuine reserve holds `expected_mint` and is owned by the Meteora vault
+/// of that mint.
+fn reserve_problem(expected_mint: &Pubkey, reserve: &TokenAccount) -> Option<String> {
+    if reserve.mint != *expected_mint {
+        return Some(format!(
+            "holds {}, expected {}",
+            reserve.mint, expected_mint
+        ));
+    }
+    let vault = vault_address(expected_mint);
+    if reserve.owner != vault {
+        return Some(format!(
+            "is owned by {}, not the vault {}",
+            reserve.owner, vault
+        ));
+    }
+    None
+}
+
+/// The Meteora vault holding a pool's `mint` tokens
+///
+/// Derived from `["vault", mint, METEORA_VAULT_BASE_KEY]` under the vault program.
+pub fn vault_address(mint: &Pubkey) -> Pubkey {
+    let program_id = Pubkey::from_str(METEORA_VAULT_PROGRAM_ID).unwrap();
+    let base = Pubkey::from_str(METEORA_VAULT_BASE_KEY).unwrap();
+    Pubkey::find_program_address(&[b"vault", mint.as_ref(), base.as_ref()], &program_id).0
+}
+
+/// The token account a vault keeps its tokens in, owned by the vault
+pub fn vault_token_account(vault: &Pubkey) -> Pubkey {
+    let program_id = Pubkey::from_str(METEORA_VAULT_PROGRAM_ID).unwrap();
+    Pubkey::find_program_address(&[b"token_vault", vault.as_ref()], &program_id).0
+}
+
+/// The mint of a vault's LP token
+pub fn vault_lp_mint(vault: &Pubkey) -> Pubkey {
+    let program_id = Pubkey::from_str(METEORA_VAULT_PROGRAM_ID).unwrap();
+    Pubkey::find_program_address(&[b"lp_mint", vault.as_ref()], &program_id).0
+}
+
+/// The account holding a pool's LP tokens of `vault`
+pub fn pool_vault_lp_account(vault: &Pubkey, pool_address: &Pubkey) -> Pubkey {
+    let program_id = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
+    Pubkey::find_program_address(&[vault.as_ref(), pool_address.as_ref()], &program_id).0
+}
 
-    async fn get_token_balance(&self, token_account: &Pubkey) -> Result<u64, MeteoraError> {
-        let account_data = self.client.get_account_data(token_account).await?;
-        let token_account = Account::unpack(&account_data)
-            .map_err(|e| MeteoraError::DeserializationError(e.to_string()))?;
-        Ok(token_account.amount)
+/// The account a pool collects the protocol fee in when swapping from `input_mint`
+pub fn protocol_fee_account(input_mint: &Pubkey, pool_address: &Pubkey) -> Pubkey {
+    let program_id = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
+    Pubkey::find_program_address(
+        &[b"fee", input_mint.as_ref(), pool_address.as_ref()],
+        &program_id,
+    )
+    .0
+}
+
+/// Time elapsed between `created_at` and `now`, both unix timestamps
+fn pool_age(created_at: i64, now: i64) -> Duration {
+    Duration::from_secs(now.saturating_sub(created_at).max(0) as u64)
+}
+
+/// The mints, reserves and LP mint a pool's information is read from
+fn pool_dependency_addresses(pool: &PoolInfo) -> [Pubkey; POOL_DEPENDENCY_COUNT] {
+    [
+        pool.token_a_mint,
+        pool.token_b_mint,
+        pool.token_a_reserve,
+        pool.token_b_reserve,
+        pool.lp_mint,
+    ]
+}
+
+/// Completes a parsed pool account with its decimals, reserve amounts and
+/// LP supply from the accounts at `pool_dependency_addresses`, fetched now
+fn pool_info_from_accounts(
+    pool_address: &Pubkey,
+    pool: PoolInfo,
+    accounts: &[Option<Account>],
+) -> Result<PoolInfo, MeteoraError> {
+    let addresses = pool_dependency_addresses(&pool);
+    let account = |index: usize| {
+        accounts.get(index).and_then(Option::as_ref).ok_or_else(|| {
+            MeteoraError::AccountNotFound(format!("Account {} not found", addresses[index]))
+        })
+    };
+    let token_a_mint = unpack_mint(&addresses[0], account(0)?)?;
+    let token_b_mint = unpack_mint(&addresses[1], account(1)?)?;
+    let token_a_reserve = unpack_token_account(&addresses[2], account(2)?)?;
+    let token_b_reserve = unpack_token_account(&addresses[3], account(3)?)?;
+    let lp_mint = unpack_mint(&addresses[4], account(4)?)?;
+    Ok(PoolInfo {
+        address: *pool_address,
+        token_a_decimals: token_a_mint.decimals,
+        token_b_decimals: token_b_mint.decimals,
+        token_a_reserve_amount: token_a_reserve.amount,
+        token_b_reserve_amount: token_b_reserve.amount,
+        lp_supply: lp_mint.supply,
+        lp_decimals: lp_mint.decimals,
+        fetched_at: chrono::Utc::now().timestamp(),
+        ..pool
+    })
+}
+
+/// The pool with the most normalized liquidity in `token_mint` among `pools`
+///
+/// Every candidate holds `token_mint`, so valuing each pool at its own rate
+/// makes its USD TVL twice its `token_mint` reserve times that token's price:
+/// this ranks pools exactly as `get_pool_tvl_usd` would, without pricing
+/// anything. Pools that don't contain the token or hold none of it are never
+/// picked.
+pub(crate) fn best_pool<'a>(
+    token_mint: &Pubkey,
+    pools: impl IntoIterator<Item = &'a PoolInfo>,
+) -> Option<&'a PoolInfo> {
+    pools
+        .into_iter()
+        .filter_map(|p| Some((p, p.normalized_liquidity_in(token_mint)?)))
+        .filter(|(_, liquidity)| *liquidity > 0.0)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(p, _)| p)
+}
+
+/// Parses a pool account, reporting an account without data as not found
+///
+/// Empty data means the address holds no pool at all, e.g. a closed account
+/// or a missing one read through `get_multiple_accounts_data`, rather than a
+/// pool with an unexpected layout.
+fn parse_pool_data(pool_address: &Pubkey, pool_data: &[u8]) -> Result<PoolInfo, MeteoraError> {
+    if pool_data.is_empty() {
+        return Err(MeteoraError::AccountNotFound(format!(
+            "Pool {} has no account data",
+            pool_address
+        )));
     }
+    parse_pool_account(pool_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::PoolLayout;
+    use crate::price::PriceFeed;
+    use crate::test_util::{MockReply, MockRpc, PoolFixture, mint_account, token_account};
+    use crate::trade::Trade;
+    use crate::types::TradeParams;
+    use serde_json::{Value, json};
+    use solana_client::rpc_filter::RpcFilterType;
+
+    #[test]
+    fn empty_pool_data_is_not_found() {
+        assert!(matches!(
+            parse_pool_data(&Pubkey::new_unique(), &[]),
+            Err(MeteoraError::AccountNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn short_pool_data_is_invalid() {
+        let pool = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let data = pool.account_data(PoolLayout::V1, 25, 10_000);
+        for len in [4, 100] {
+            assert!(matches!(
+                parse_pool_data(&pool.address, &data[..len]),
+                Err(MeteoraError::InvalidPoolData(_))
+            ));
+        }
+        assert!(parse_pool_data(&pool.address, &data).is_ok());
+    }
+
+    fn reserve(mint: Pubkey, owner: Pubkey) -> TokenAccount {
+        TokenAccount {
+            mint,
+            owner,
+            state: spl_token::state::AccountState::Initialized,
+            ..TokenAccount::default()
+        }
+    }
+
+    #[test]
+    fn reserve_must_hold_its_mint_and_belong_to_the_mints_vault() {
+        let mint = Pubkey::new_unique();
+        let vault = vault_address(&mint);
+        assert_eq!(reserve_problem(&mint, &reserve(mint, vault)), None);
+        assert!(reserve_problem(&mint, &reserve(Pubkey::new_unique(), vault)).is_some());
+        assert!(reserve_problem(&mint, &reserve(mint, Pubkey::new_unique())).is_some());
+        // The vault of another mint doesn't own this mint's reserves
+        let other_vault = vault_address(&Pubkey::new_unique());
+        assert!(reserve_problem(&mint, &reserve(mint, other_vault)).is_some());
+    }
+
+    #[tokio::test]
+    async fn pool_with_a_mismatched_reserve_mint_fails_integrity() {
+        let rpc = MockRpc::new();
+        let pool_manager = PoolManager::new(rpc.client());
+        let pool = rpc.add_pool(Pubkey::new_unique(), Pubkey::new_unique(), 1_000, 2_000);
+        assert!(
+            pool_manager
+                .verify_pool_integrity(&pool.address)
+                .await
+                .unwrap()
+        );
+
+        let vault = vault_address(&pool.token_b_mint);
+        rpc.set_account(
+            pool.token_b_reserve,
+            token_account(Pubkey::new_unique(), vault, 2_000),
+        );
+        assert!(
+            !pool_manager
+                .verify_pool_integrity(&pool.address)
+                .await
+                .unwrap()
+        );
+    }
+
+    /// Answers `getSignaturesForAddress` for each `(address, total, newest)`
+    /// with `total` signatures, newest first, where signature `i` has block
+    /// time `newest - i`; other addresses have no signatures
+    fn signature_histories(rpc: &MockRpc, histories: Vec<(Pubkey, usize, i64)>) {
+        let histories: HashMap<String, (Vec<String>, i64)> = histories
+            .into_iter()
+            .map(|(address, total, newest)| {
+                let signatures = (0..total)
+                    .map(|_| Signature::new_unique().to_string())
+                    .collect();
+                (address.to_string(), (signatures, newest))
+            })
+            .collect();
+        rpc.on("getSignaturesForAddress", move |params| {
+            let Some((signatures, newest)) = histories.get(params[0].as_str().unwrap()) else {
+                return MockReply::Result(json!([]));
+            };
+            let start = match params[1]["before"].as_str() {
+                Some(before) => signatures.iter().position(|s| s == before).unwrap() + 1,
+                None => 0,
+            };
+            let limit = params[1]["limit"].as_u64().unwrap() as usize;
+            let page: Vec<Value> = (start..signatures.len().min(start + limit))
+                .map(|i| {
+                    json!({
+                        "signature": signatures[i],
+                        "slot": 1,
+                        "err": null,
+                        "memo": null,
+                        "blockTime": newest - i as i64,
+                        "confirmationStatus": "finalized",
+                    })
+                })
+                .collect();
+            MockReply::Result(json!(page))
+        });
+    }
+
+    #[tokio::test]
+    async fn creation_time_is_the_oldest_signatures_block_time() {
+        let rpc = MockRpc::new();
+        let pool_address = Pubkey::new_unique();
+        let total = MAX_SIGNATURES_PER_REQUEST + 10;
+        signature_histories(&rpc, vec![(pool_address, total, 1_000_000)]);
+        let pool_manager = PoolManager::new(rpc.client());
+        let created_at = pool_manager
+            .get_pool_creation_time(&pool_address)
+            .await
+            .unwrap();
+        assert_eq!(
+            created_at,
+            PoolCreationTime {
+                timestamp: 1_000_000 - (total as i64 - 1),
+                is_lower_bound: false,
+            }
+        );
+        assert_eq!(rpc.call_count("getSignaturesForAddress"), 2);
+
+        // Cached after the first lookup
+        pool_manager
+            .get_pool_creation_time(&pool_address)
+            .await
+            .unwrap();
+        assert_eq!(rpc.call_count("getSignaturesForAddress"), 2);
+    }
+
+    #[tokio::test]
+    async fn creation_time_is_a_lower_bound_after_the_page_cap() {
+        let rpc = MockRpc::new();
+        let pool_address = Pubkey::new_unique();
+        signature_histories(
+            &rpc,
+            vec![(
+                pool_address,
+                (MAX_CREATION_TIME_PAGES + 1) * MAX_SIGNATURES_PER_REQUEST,
+                1_000_000,
+            )],
+        );
+        let pool_manager = PoolManager::new(rpc.client());
+        let created_at = pool_manager
+            .get_pool_creation_time(&pool_address)
+            .await
+            .unwrap();
+        assert_eq!(
+            created_at,
+            PoolCreationTime {
+                timestamp: 1_000_000
+                    - (MAX_CREATION_TIME_PAGES * MAX_SIGNATURES_PER_REQUEST) as i64
+                    + 1,
+                is_lower_bound: true,
+            }
+        );
+        assert_eq!(
+            rpc.call_count("getSignaturesForAddress"),
+            MAX_CREATION_TIME_PAGES
+        );
+    }
+
+    #[tokio::test]
+    async fn min_age_keeps_old_pools_and_pools_old_enough_at_the_page_cap() {
+        let rpc = MockRpc::new();
+        let (token_a, token_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let young = rpc.add_pool(token_a, token_b, 1_000, 1_000);
+        let old = rpc.add_pool(token_a, token_b, 1_000, 1_000);
+        let capped = rpc.add_pool(token_a, token_b, 1_000, 1_000);
+        let now = chrono::Utc::now().timestamp();
+        signature_histories(
+            &rpc,
+            vec![
+                (young.address, 10, now - 60),
+                (old.address, 10, now - 2 * SECONDS_PER_DAY),
+                // Only the newest hours of its history are read
+                (
+                    capped.address,
+                    (MAX_CREATION_TIME_PAGES + 1) * MAX_SIGNATURES_PER_REQUEST,
+                    now,
+                ),
+            ],
+        );
+        let pool_manager = PoolManager::new(rpc.client());
+        let mut pools: Vec<Pubkey> = pool_manager
+            .find_pools_by_tokens_with_min_age(&token_a, &token_b, Duration::from_secs(3600))
+            .await
+            .unwrap()
+            .iter()
+            .map(|pool| pool.address)
+            .collect();
+        pools.sort();
+        let mut expected = vec![old.address, capped.address];
+        expected.sort();
+        assert_eq!(pools, expected);
+    }
+
+    /// The filters of every `getProgramAccounts` request made so far
+    fn program_account_filters(rpc: &MockRpc) -> Vec<Vec<RpcFilterType>> {
+        rpc.calls("getProgramAccounts")
+            .iter()
+            .map(|params| serde_json::from_value(params[1]["filters"].clone()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn find_all_pools_requests_only_pool_keys() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(Pubkey::new_unique(), Pubkey::new_unique(), 1_000, 2_000);
+        // Another account of the program, which isn't a pool
+        let program_id = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
+        rpc.set_account(
+            Pubkey::new_unique(),
+            crate::test_util::program_account(program_id, vec![0; 300]),
+        );
+        let pool_manager = PoolManager::new(rpc.client());
+        assert_eq!(
+            pool_manager.find_all_pools_filtered().await.unwrap(),
+            [pool.address]
+        );
+
+        let expected: Vec<Vec<RpcFilterType>> = PoolLayout::ALL
+            .iter()
+            .map(|layout| layout.filters())
+            .collect();
+        assert_eq!(program_account_filters(&rpc), expected);
+        for (filters, layout) in expected.iter().zip(PoolLayout::ALL) {
+            assert_eq!(
+                filters[0],
+                RpcFilterType::DataSize(layout.account_size() as u64)
+            );
+            let RpcFilterType::Memcmp(memcmp) = &filters[1] else {
+                panic!("expected a discriminator memcmp, got {:?}", filters[1]);
+            };
+            assert_eq!(memcmp.offset(), 0);
+            assert_eq!(memcmp.bytes().unwrap().as_slice(), layout.discriminator());
+        }
+        for params in rpc.calls("getProgramAccounts") {
+            assert_eq!(params[1]["dataSlice"]["length"], 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn pools_by_tokens_are_filtered_on_both_mint_offsets() {
+        let rpc = MockRpc::new();
+        let (token_a, token_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let forward = rpc.add_pool(token_a, token_b, 1_000, 2_000);
+        let reverse = rpc.add_pool(token_b, token_a, 3_000, 4_000);
+        rpc.add_pool(token_a, Pubkey::new_unique(), 5_000, 6_000);
+        let pool_manager = PoolManager::new(rpc.client());
+        let mut pools: Vec<Pubkey> = pool_manager
+            .find_pools_by_tokens(&token_a, &token_b)
+            .await
+            .unwrap()
+            .iter()
+            .map(|pool| pool.address)
+            .collect();
+        pools.sort();
+        let mut expected = vec![forward.address, reverse.address];
+        expected.sort();
+        assert_eq!(pools, expected);
+
+        let mint_filters: Vec<Vec<(usize, Pubkey)>> = program_account_filters(&rpc)
+            .iter()
+            .map(|filters| {
+                filters[2..]
+                    .iter()
+                    .map(|filter| match filter {
+                        RpcFilterType::Memcmp(memcmp) => (
+                            memcmp.offset(),
+                            Pubkey::try_from(memcmp.bytes().unwrap().as_slice()).unwrap(),
+                        ),
+                        other => panic!("expected a mint memcmp, got {:?}", other),
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(mint_filters.len(), 2 * PoolLayout::ALL.len());
+        for pair in mint_filters.chunks(2) {
+            assert_eq!(pair[0], [(8, token_a), (40, token_b)]);
+            assert_eq!(pair[1], [(8, token_b), (40, token_a)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn pool_exists_for_pair_only_requests_pool_keys() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(Pubkey::new_unique(), Pubkey::new_unique(), 1_000, 2_000);
+        let pool_manager = PoolManager::new(rpc.client());
+        for (token_a, token_b) in [
+            (pool.token_a_mint, pool.token_b_mint),
+            (pool.token_b_mint, pool.token_a_mint),
+        ] {
+            assert!(
+                pool_manager
+                    .pool_exists_for_pair(&token_a, &token_b)
+                    .await
+                    .unwrap()
+            );
+        }
+        let queries = rpc.call_count("getProgramAccounts");
+        assert!(
+            !pool_manager
+                .pool_exists_for_pair(&pool.token_a_mint, &Pubkey::new_unique())
+                .await
+                .unwrap()
+        );
+        // Every layout and mint order is tried before giving up
+        assert_eq!(
+            rpc.call_count("getProgramAccounts") - queries,
+            2 * PoolLayout::ALL.len()
+        );
+
+        // No pool was loaded, and every query sliced the pool data away
+        assert_eq!(rpc.call_count("getAccountInfo"), 0);
+        assert_eq!(rpc.call_count("getMultipleAccounts"), 0);
+        for params in rpc.calls("getProgramAccounts") {
+            assert_eq!(params[1]["dataSlice"]["length"], 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn tvl_normalizes_each_reserve_by_its_decimals() {
+        let rpc = MockRpc::new();
+        let usdc = Pubkey::from_str(USDC_MINT).unwrap();
+        // 100 USDC (6 decimals) against 2 tokens of 9 decimals, so 1 token = 50 USDC
+        let pool = rpc.add_pool(usdc, Pubkey::new_unique(), 100_000_000, 2_000_000_000);
+        rpc.set_account(pool.token_b_mint, mint_account(9, 1_000_000_000_000));
+        let pool_manager = PoolManager::new(rpc.client());
+        assert_eq!(
+            pool_manager
+                .get_token_usd_price(&pool.token_b_mint)
+                .await
+                .unwrap(),
+            50.0
+        );
+        assert_eq!(
+            pool_manager.get_pool_tvl_usd(&pool.address).await.unwrap(),
+            200.0
+        );
+    }
+
+    #[tokio::test]
+    async fn matched_pools_flag_which_side_is_the_input() {
+        let rpc = MockRpc::new();
+        let (input_mint, output_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let forward = rpc.add_pool(input_mint, output_mint, 1_000_000, 2_000_000);
+        let reverse = rpc.add_pool(output_mint, input_mint, 3_000_000, 4_000_000);
+        let pool_manager = PoolManager::new(rpc.client());
+        let matched = pool_manager
+            .find_matched_pools(&input_mint, &output_mint)
+            .await
+            .unwrap();
+        assert_eq!(matched.len(), 2);
+        for pool in &matched {
+            assert_eq!(pool.input_is_token_a, pool.pool.address == forward.address);
+            assert_eq!(pool.input_mint(), input_mint);
+            assert_eq!(pool.output_mint(), output_mint);
+        }
+        assert!(
+            matched
+                .iter()
+                .any(|pool| pool.pool.address == reverse.address)
+        );
+    }
+
+    #[tokio::test]
+    async fn pool_info_carries_the_lp_mints_decimals() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(Pubkey::new_unique(), Pubkey::new_unique(), 1_000, 1_000);
+        rpc.set_account(pool.lp_mint, mint_account(9, 2_500_000_000));
+        let pool_info = PoolManager::new(rpc.client())
+            .get_pool_info(&pool.address)
+            .await
+            .unwrap();
+        assert_eq!(pool_info.lp_decimals, 9);
+        assert_eq!(pool_info.lp_supply, 2_500_000_000);
+        assert_eq!(pool_info.ui_lp_supply(), 2.5);
+    }
+
+    #[tokio::test]
+    async fn pool_info_loads_mints_and_reserves_in_one_request() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000,
+            2_000_000,
+        );
+        rpc.set_account(pool.token_b_mint, mint_account(9, 5_000_000_000));
+        let pool_info = PoolManager::new(rpc.client())
+            .get_pool_info(&pool.address)
+            .await
+            .unwrap();
+        assert_eq!(rpc.call_count("getAccountInfo"), 1);
+        let batches = rpc.calls("getMultipleAccounts");
+        assert_eq!(batches.len(), 1);
+        let requested: Vec<Pubkey> = batches[0][0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|address| address.as_str().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(
+            requested,
+            [
+                pool.token_a_mint,
+                pool.token_b_mint,
+                pool.token_a_reserve,
+                pool.token_b_reserve,
+                pool.lp_mint
+            ]
+        );
+        assert_eq!(pool_info.token_a_decimals, 6);
+        assert_eq!(pool_info.token_b_decimals, 9);
+        assert_eq!(pool_info.token_a_reserve_amount, 1_000_000);
+        assert_eq!(pool_info.token_b_reserve_amount, 2_000_000);
+        assert_eq!(pool_info.lp_supply, 1_000_000);
+    }
+
+    #[tokio::test]
+    async fn best_pool_ignores_reserves_of_the_other_token() {
+        let rpc = MockRpc::new();
+        let token_mint = Pubkey::new_unique();
+        // By raw reserve sum the junk pool is by far the deepest
+        let junk = rpc.add_pool(
+            token_mint,
+            Pubkey::new_unique(),
+            1_000_000,
+            1_000_000_000_000_000,
+        );
+        let deep = rpc.add_pool(spl_token::native_mint::ID, token_mint, 1_000_000, 5_000_000);
+        let pool_manager = PoolManager::new(rpc.client());
+
+        let (best, _) = pool_manager.find_best_pool(&token_mint).await.unwrap();
+        assert_eq!(best, deep.address);
+        assert_eq!(
+            pool_manager
+                .get_pool_liquidity(&junk.address, &token_mint)
+                .await
+                .unwrap(),
+            2_000_000
+        );
+        assert_eq!(
+            pool_manager
+                .get_pool_liquidity(&deep.address, &token_mint)
+                .await
+                .unwrap(),
+            10_000_000
+        );
+        assert!(matches!(
+            pool_manager
+                .get_pool_liquidity(&deep.address, &Pubkey::new_unique())
+                .await,
+            Err(MeteoraError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn deepest_pool_is_shared_by_pricing_and_trading() {
+        let rpc = MockRpc::new();
+        let token_mint = Pubkey::new_unique();
+        let wsol = spl_token::native_mint::ID;
+        // The token sits on either side, and the deepest pool has the lowest price
+        let pools = [
+            rpc.add_pool(token_mint, wsol, 1_000_000, 4_000_000),
+            rpc.add_pool(wsol, token_mint, 6_000_000, 5_000_000),
+            rpc.add_pool(token_mint, wsol, 3_000_000, 9_000_000),
+        ];
+        let deepest = &pools[1];
+        let client = rpc.client();
+        let pool_manager = PoolManager::new(client.clone());
+
+        let (best, pool_info) = pool_manager.find_best_pool(&token_mint).await.unwrap();
+        assert_eq!(best, deepest.address);
+        assert_eq!(pool_info.liquidity_in(&token_mint), Some(10_000_000));
+
+        let price = PriceFeed::new(client.clone())
+            .get_current_price(&token_mint)
+            .await
+            .unwrap();
+        assert_eq!(price.sol_price, 1.2);
+        assert_eq!(price.liquidity, 10_000_000);
+
+        let quote = Trade::new(client)
+            .get_quote(&TradeParams {
+                input_mint: token_mint,
+                output_mint: wsol,
+                amount_in: 1_000,
+                slippage_bps: 100,
+                user: Pubkey::new_unique(),
+                deadline: None,
+                referral: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(quote.route, vec![deepest.address]);
+
+        assert!(matches!(
+            pool_manager.find_best_pool(&Pubkey::new_unique()).await,
+            Err(MeteoraError::NoLiquidityPoolFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn invalidated_pools_are_fetched_again() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(Pubkey::new_unique(), Pubkey::new_unique(), 1_000, 2_000);
+        let pool_manager = PoolManager::new(rpc.client());
+        pool_manager
+            .get_pool_info_cached(&pool.address)
+            .await
+            .unwrap();
+        pool_manager
+            .get_pool_info_cached(&pool.address)
+            .await
+            .unwrap();
+        assert_eq!(rpc.call_count("getAccountInfo"), 1);
+
+        rpc.set_account(
+            pool.token_a_reserve,
+            token_account(pool.token_a_mint, vault_address(&pool.token_a_mint), 3_000),
+        );
+        pool_manager.invalidate_pool(&pool.address);
+        let pool_info = pool_manager
+            .get_pool_info_cached(&pool.address)
+            .await
+            .unwrap();
+        assert_eq!(rpc.call_count("getAccountInfo"), 2);
+        assert_eq!(pool_info.token_a_reserve_amount, 3_000);
+
+        pool_manager.invalidate_all();
+        pool_manager
+            .get_pool_info_cached(&pool.address)
+            .await
+            .unwrap();
+        assert_eq!(rpc.call_count("getAccountInfo"), 3);
+
+        // A refresh always fetches and repopulates the cache
+        pool_manager.refresh_pool(&pool.address).await.unwrap();
+        pool_manager
+            .get_pool_info_cached(&pool.address)
+            .await
+            .unwrap();
+        assert_eq!(rpc.call_count("getAccountInfo"), 4);
+    }
+
+    #[tokio::test]
+    async fn pool_and_pool_list_ttls_apply_independently() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(Pubkey::new_unique(), Pubkey::new_unique(), 1_000, 2_000);
+        let pool_manager =
+            PoolManager::new_with_ttl(rpc.client(), Duration::ZERO, Duration::from_secs(3600));
+        for _ in 0..3 {
+            pool_manager
+                .get_pool_info_cached(&pool.address)
+                .await
+                .unwrap();
+            assert_eq!(
+                pool_manager.find_all_pools_cached().await.unwrap(),
+                vec![pool.address]
+            );
+        }
+        assert_eq!(rpc.call_count("getAccountInfo"), 3);
+        let list_scans = rpc.call_count("getProgramAccounts");
+
+        let pool_manager =
+            PoolManager::new_with_ttl(rpc.client(), Duration::from_secs(3600), Duration::ZERO);
+        for _ in 0..3 {
+            pool_manager
+                .get_pool_info_cached(&pool.address)
+                .await
+                .unwrap();
+            pool_manager.find_all_pools_cached().await.unwrap();
+        }
+        assert_eq!(rpc.call_count("getAccountInfo"), 4);
+        assert_eq!(rpc.call_count("getProgramAccounts"), list_scans * 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_swap_touching_both_reserves_emits_one_pool_info() {
+        use futures::channel::mpsc;
+        use solana_account_decoder::encode_ui_account;
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(Pubkey::new_unique(), Pubkey::new_unique(), 1_000, 2_000);
+        let pool_manager = PoolManager::new(rpc.client());
+        let pool_info = pool_manager.get_pool_info(&pool.address).await.unwrap();
+        let reserve_update = |reserve: Pubkey, mint: Pubkey, amount: u64| {
+            let account = token_account(mint, vault_address(&mint), amount);
+            let ui_account =
+                encode_ui_account(&reserve, &account, UiAccountEncoding::Base64, None, None);
+            (reserve, ui_account)
+        };
+        let (updates, notifications) = mpsc::unbounded();
+        let (sender, mut receiver) = broadcast::channel(POOL_WATCH_CHANNEL_CAPACITY);
+        let watcher = tokio::spawn({
+            let pool_manager = pool_manager.clone();
+            async move {
+                pool_manager
+                    .forward_reserve_updates(pool_info, notifications, sender)
+                    .await
+            }
+        });
+
+        updates
+            .unbounded_send(reserve_update(
+                pool.token_a_reserve,
+                pool.token_a_mint,
+                1_100,
+            ))
+            .unwrap();
+        sleep(RESERVE_UPDATE_DEBOUNCE / 2).await;
+        updates
+            .unbounded_send(reserve_update(
+                pool.token_b_reserve,
+                pool.token_b_mint,
+                1_820,
+            ))
+            .unwrap();
+        let emitted = receiver.recv().await.unwrap();
+        assert_eq!(emitted.token_a_reserve_amount, 1_100);
+        assert_eq!(emitted.token_b_reserve_amount, 1_820);
+        sleep(RESERVE_UPDATE_DEBOUNCE * 4).await;
+        assert!(matches!(
+            receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        ));
+        // The emitted reserves also replace the cached pool
+        let cached = pool_manager
+            .get_pool_info_cached(&pool.address)
+            .await
+            .unwrap();
+        assert_eq!(cached.token_a_reserve_amount, 1_100);
+
+        drop(updates);
+        watcher.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn lp_token_price_is_tvl_per_whole_lp_token() {
+        let rpc = MockRpc::new();
+        let usdc = Pubkey::from_str(USDC_MINT).unwrap();
+        // $200 of TVL, see tvl_normalizes_each_reserve_by_its_decimals
+        let pool = rpc.add_pool(usdc, Pubkey::new_unique(), 100_000_000, 2_000_000_000);
+        rpc.set_account(pool.token_b_mint, mint_account(9, 1_000_000_000_000));
+        rpc.set_account(pool.lp_mint, mint_account(6, 4_000_000));
+        let pool_manager = PoolManager::new(rpc.client());
+        assert_eq!(
+            pool_manager
+                .get_lp_token_price_usd(&pool.address)
+                .await
+                .unwrap(),
+            50.0
+        );
+
+        rpc.set_account(pool.lp_mint, mint_account(6, 0));
+        assert!(matches!(
+            pool_manager.get_lp_token_price_usd(&pool.address).await,
+            Err(MeteoraError::CalculationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn pool_infos_batch_the_misses_and_reuse_the_cache() {
+        let rpc = MockRpc::new();
+        let pools: Vec<PoolFixture> = (0..3)
+            .map(|_| rpc.add_pool(Pubkey::new_unique(), Pubkey::new_unique(), 1_000, 2_000))
+            .collect();
+        let pool_manager = PoolManager::new(rpc.client());
+        pool_manager
+            .get_pool_info_cached(&pools[1].address)
+            .await
+            .unwrap();
+        let reads = rpc.call_count("getMultipleAccounts");
+
+        let addresses: Vec<Pubkey> = pools.iter().map(|pool| pool.address).collect();
+        let pool_infos = pool_manager.get_pool_infos(&addresses).await.unwrap();
+        let loaded: Vec<Pubkey> = pool_infos.iter().map(|pool| pool.address).collect();
+        assert_eq!(loaded, addresses);
+        // One request for the two missing pools, one for their accounts
+        let batches = rpc.calls("getMultipleAccounts");
+        assert_eq!(batches.len(), reads + 2);
+        assert_eq!(
+            batches[reads][0],
+            json!([pools[0].address.to_string(), pools[2].address.to_string()])
+        );
+        assert_eq!(
+            batches[reads + 1][0].as_array().unwrap().len(),
+            2 * POOL_DEPENDENCY_COUNT
+        );
+
+        // Everything is cached now
+        pool_manager.get_pool_infos(&addresses).await.unwrap();
+        assert_eq!(rpc.call_count("getMultipleAccounts"), reads + 2);
+
+        let missing = Pubkey::new_unique();
+        assert!(matches!(
+            pool_manager.get_pool_infos(&[addresses[0], missing]).await,
+            Err(MeteoraError::AccountNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn fresh_index_resolves_pools_without_rpc() {
+        let rpc = MockRpc::new();
+        let (token, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let wsol = spl_token::native_mint::ID;
+        let sol_pool = rpc.add_pool(token, wsol, 1_000, 1_000);
+        let other_pool = rpc.add_pool(other, token, 1_000, 1_000);
+        let unrelated = rpc.add_pool(other, wsol, 1_000, 1_000);
+        let pool_manager = PoolManager::new(rpc.client());
+
+        let index = pool_manager.rebuild_index().await.unwrap();
+        assert_eq!(index.mint_count(), 3);
+        let calls = |rpc: &MockRpc| {
+            rpc.call_count("getProgramAccounts") + rpc.call_count("getMultipleAccounts")
+        };
+        let before = calls(&rpc);
+
+        let mut token_pools = pool_manager.find_token_pools(&token).await.unwrap();
+        token_pools.sort();
+        let mut expected = vec![sol_pool.address, other_pool.address];
+        expected.sort();
+        assert_eq!(token_pools, expected);
+        let pair = pool_manager
+            .find_pools_by_tokens(&other, &wsol)
+            .await
+            .unwrap();
+        assert_eq!(pair.len(), 1);
+        assert_eq!(pair[0].address, unrelated.address);
+        assert_eq!(calls(&rpc), before);
+
+        // Without an index, lookups scan the program again
+        let scans = rpc.call_count("getProgramAccounts");
+        pool_manager.invalidate_all();
+        pool_manager.find_token_pools(&token).await.unwrap();
+        assert!(rpc.call_count("getProgramAccounts") > scans);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pool_scans_keep_at_most_scan_concurrency_loads_in_flight() {
+        let rpc = MockRpc::new();
+        let (token_a, token_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut expected: Vec<Pubkey> = (0..20)
+            .map(|_| rpc.add_pool(token_a, token_b, 1_000, 2_000).address)
+            .collect();
+        let latency = Duration::from_millis(100);
+        let started = Arc::new(Mutex::new(Vec::new()));
+        rpc.on("getMultipleAccounts", {
+            let started = started.clone();
+            move |_| {
+                started.lock().unwrap().push(Instant::now());
+                MockReply::Delayed(latency)
+            }
+        });
+        let mut pool_manager = PoolManager::new(rpc.client());
+        pool_manager.set_scan_concurrency(4);
 
-    async fn get_token_decimals(&self, mint: &Pubkey) -> Result<u8, MeteoraError> {
-        let account_data = self.client.get_account_data(mint).await?;
-        let token_mint = Mint::unpack(&account_data)
-            .map_err(|e| MeteoraError::DeserializationError(e.to_string()))?;
-        Ok(token_mint.decimals)
+        let pools = pool_manager
+            .find_pools_by_tokens(&token_a, &token_b)
+            .await
+            .unwrap();
+        let mut found: Vec<Pubkey> = pools.iter().map(|pool| pool.address).collect();
+        found.sort();
+        expected.sort();
+        assert_eq!(found, expected);
+        // Loads started less than `latency` apart were in flight together
+        let started = started.lock().unwrap();
+        let peak = started
+            .iter()
+            .map(|start| {
+                started
+                    .iter()
+                    .filter(|other| **other >= *start && **other < *start + latency)
+                    .count()
+            })
+            .max()
+            .unwrap();
+        assert_eq!(peak, 4);
     }
 
-    async fn get_token_supply(&self, mint: &Pubkey) -> Result<u64, MeteoraError> {
-        let account_data = self.client.get_account_data(mint).await?;
-        let token_mint = Mint::unpack(&account_data)
-            .map_err(|e| MeteoraError::DeserializationError(e.to_string()))?;
-        Ok(token_mint.supply)
+    #[test]
+    fn sample_evenly_spreads_over_all_items() {
+        let items: Vec<usize> = (0..10).collect();
+        assert_eq!(sample_evenly(&items, 20), items);
+        assert_eq!(sample_evenly(&items, 5), vec![0, 2, 4, 6, 8]);
+        assert_eq!(sample_evenly(&items, 3), vec![0, 3, 6]);
+        assert!(sample_evenly(&items, 0).is_empty());
     }
 }
@@ -1,38 +1,206 @@
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
 use tokio::sync::Mutex;
 
-use crate::global::USDC_MINT;
-use crate::types::{CandleStick, PoolInfo, TimeFrame, TokenPrice};
-use crate::{MeteoraClient, MeteoraError, pool::PoolManager};
+use crate::global::{METEORA_PROGRAM_ID, USDC_MINT};
+use crate::oracle::parse_pyth_price;
+use crate::trade::SWAP_DISCRIMINATOR;
+use crate::types::{
+    CandleStick, MatchedPool, PoolInfo, PriceSource, TimeFrame, TokenPrice, TradeSide,
+};
+use crate::{
+    MeteoraClient, MeteoraError,
+    pool::{PoolManager, best_pool},
+};
 use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
 use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiCompiledInstruction,
+    UiInstruction, UiMessage, UiTransactionTokenBalance, option_serializer::OptionSerializer,
+};
 
+/// Confidence assigned to a price read from a single pool
+const SINGLE_POOL_CONFIDENCE: f64 = 0.3;
+/// Relative deviation within which pool prices are considered to agree
+const PRICE_AGREEMENT_TOLERANCE: f64 = 0.02;
+/// Number of agreeing pools needed for full confidence
+const CONFIDENT_POOL_COUNT: usize = 3;
+/// Minimum number of swaps in the window for a TWAP
+const MIN_TWAP_SAMPLES: usize = 2;
+/// SOL/USD rate used when neither a pool nor the oracle is available
+const FALLBACK_SOL_USD_PRICE: f64 = 100.0;
+/// Seconds from the Unix epoch (a Thursday) to the first Monday, 1970-01-05
+const WEEK_START_OFFSET: i64 = 4 * 86400;
+/// Number of candles cached per token and time frame unless configured otherwise
+const DEFAULT_CANDLE_CAPACITY: usize = 1000;
+/// Version of the on-disk cache format, bumped on incompatible changes
+const CACHE_FILE_VERSION: u32 = 1;
+/// Minimum time between writes of a `PriceFeed`'s cache file
+const CACHE_FLUSH_INTERVAL_SECS: i64 = 60;
+
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
-struct SwapEvent {
-    timestamp: i64,
-    input_mint: Pubkey,
-    output_mint: Pubkey,
-    input_amount: u64,
-    output_amount: u64,
-    price: f64,
-    volume_usd: f64,
+pub(crate) struct SwapEvent {
+    pub(crate) timestamp: i64,
+    pub(crate) input_mint: Pubkey,
+    pub(crate) output_mint: Pubkey,
+    pub(crate) input_amount: u64,
+    pub(crate) output_amount: u64,
+    /// Executed USD price of the target token
+    pub(crate) price: f64,
+    pub(crate) volume_usd: f64,
+}
+
+/// Resolves the SOL/USD rate behind every USD price
+///
+/// Tries a pinned rate, then WSOL/USDC pools, then the Pyth oracle if one is
+/// configured, and only then `FALLBACK_SOL_USD_PRICE`. A `PriceFeed` shares
+/// its resolver with its `PoolManager`, so pool TVL and stats follow the
+/// feed's override and oracle.
+#[derive(Default)]
+pub(crate) struct SolUsdResolver {
+    oracle: Option<Pubkey>,
+    override_price: std::sync::RwLock<Option<f64>>,
+}
+
+impl SolUsdResolver {
+    pub(crate) fn new(oracle: Option<Pubkey>) -> Self {
+        Self {
+            oracle,
+            override_price: std::sync::RwLock::new(None),
+        }
+    }
+
+    /// Pins the rate, see `PriceFeed::set_sol_usd_override`
+    pub(crate) fn set_override(&self, price: Option<f64>) {
+        if let Some(price) = price
+            && !(price.is_finite() && price > 0.0)
+        {
+            log::warn!("Ignoring invalid SOL/USD override {}", price);
+            return;
+        }
+        *self.override_price.write().unwrap() = price;
+    }
+
+    /// The SOL/USD rate and where it came from
+    ///
+    /// `pool_price` is only awaited without an override, and the oracle is
+    /// only read when it yields no price.
+    pub(crate) async fn resolve(
+        &self,
+        client: &MeteoraClient,
+        pool_price: impl Future<Output = Option<f64>>,
+    ) -> (f64, PriceSource) {
+        if let Some(price) = *self.override_price.read().unwrap() {
+            return (price, PriceSource::Override);
+        }
+        if let Some(price) = pool_price.await {
+            return (price, PriceSource::Pool);
+        }
+        if let Some(oracle) = &self.oracle {
+            let account =
+                futures::future::try_join(client.get_slot(), client.get_account_data(oracle)).await;
+            match account {
+                Ok((slot, data)) => match parse_pyth_price(&data, slot) {
+                    Ok(price) => return (price, PriceSource::Oracle),
+                    Err(e) => log::warn!("Invalid Pyth price in {}: {:?}", oracle, e),
+                },
+                Err(e) => log::warn!("Failed to read Pyth account {}: {:?}", oracle, e),
+            }
+        }
+        log::warn!(
+            "No SOL/USD pool or oracle price available, using fallback of {}",
+            FALLBACK_SOL_USD_PRICE
+        );
+        (FALLBACK_SOL_USD_PRICE, PriceSource::Fallback)
+    }
 }
 
 #[derive(Clone)]
 pub struct HistoricalCache {
     data: Arc<Mutex<HashMap<Pubkey, VecDeque<CandleStick>>>>,
     last_fetch: Arc<Mutex<HashMap<Pubkey, DateTime<Utc>>>>,
+    capacity: Arc<HashMap<TimeFrame, usize>>,
+}
+
+/// On-disk form of a `HistoricalCache`, keyed by base58 mint addresses
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    candles: HashMap<String, VecDeque<CandleStick>>,
+    /// Unix timestamps of the last fetch per mint
+    last_fetch: HashMap<String, i64>,
+}
+
+impl Default for HistoricalCache {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HistoricalCache {
     pub fn new() -> Self {
+        Self::new_with_capacity(HashMap::new())
+    }
+
+    /// Creates a cache that keeps at most `cap_per_timeframe` candles of each
+    /// time frame per token
+    ///
+    /// Time frames missing from the map keep `DEFAULT_CANDLE_CAPACITY` (1000)
+    /// candles. When a time frame is full, its oldest candles are evicted.
+    ///
+    /// # Params
+    /// cap_per_timeframe - Candles kept per token for each time frame
+    ///
+    /// # Example
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use meteora_sdk::{price::HistoricalCache, types::TimeFrame};
+    /// let cache = HistoricalCache::new_with_capacity(HashMap::from([
+    ///     (TimeFrame::M1, 10_080),
+    ///     (TimeFrame::D1, 365),
+    /// ]));
+    /// # assert_eq!(cache.capacity(&TimeFrame::D1), 365);
+    /// ```
+    pub fn new_with_capacity(cap_per_timeframe: HashMap<TimeFrame, usize>) -> Self {
         Self {
             data: Arc::new(Mutex::new(HashMap::new())),
             last_fetch: Arc::new(Mutex::new(HashMap::new())),
+            capacity: Arc::new(cap_per_timeframe),
+        }
+    }
+
+    /// Maximum number of candles of `time_frame` kept per token
+    pub fn capacity(&self, time_frame: &TimeFrame) -> usize {
+        self.capacity
+            .get(time_frame)
+            .copied()
+            .unwrap_or(DEFAULT_CANDLE_CAPACITY)
+    }
+
+    /// Drops the oldest candles of `time_frame` beyond its capacity
+    fn evict(&self, candles: &mut VecDeque<CandleStick>, time_frame: &TimeFrame) {
+        let mut timestamps: Vec<i64> = candles
+            .iter()
+            .filter(|c| c.time_frame == *time_frame)
+            .map(|c| c.timestamp)
+            .collect();
+        let capacity = self.capacity(time_frame);
+        if timestamps.len() <= capacity {
+            return;
         }
+        timestamps.sort_unstable();
+        let excess = timestamps.len() - capacity;
+        let Some(&oldest_kept) = timestamps.get(excess) else {
+            // Capacity of zero, nothing is kept
+            candles.retain(|c| c.time_frame != *time_frame);
+            return;
+        };
+        candles.retain(|c| c.time_frame != *time_frame || c.timestamp >= oldest_kept);
     }
 
     pub async fn get_cached_prices(
@@ -70,9 +238,7 @@ impl HistoricalCache {
             });
             entry.push_back(candle.clone());
         }
-        while entry.len() > 1000 {
-            entry.pop_front();
-        }
+        self.evict(entry, time_frame);
         let mut last_fetch = self.last_fetch.lock().await;
         last_fetch.insert(*token_mint, Utc::now());
     }
@@ -84,6 +250,135 @@ impl HistoricalCache {
             None => true,
         }
     }
+
+    /// Loads a cache saved with `save_to_path`
+    ///
+    /// A missing file gives an empty cache, as does a file that can't be
+    /// parsed or was written with another format version, so a stale cache
+    /// never prevents startup.
+    ///
+    /// # Params
+    /// path - The cache file
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::price::HistoricalCache;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let cache = HistoricalCache::load_from_path("candles.json").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn load_from_path(path: impl AsRef<Path>) -> Result<Self, MeteoraError> {
+        let path = path.as_ref();
+        let bytes = match tokio::fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => {
+                return Err(MeteoraError::Error(format!(
+                    "Failed to read cache file {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+        let file = match serde_json::from_slice::<CacheFile>(&bytes) {
+            Ok(file) if file.version == CACHE_FILE_VERSION => file,
+            Ok(file) => {
+                log::warn!(
+                    "Discarding cache file {} with version {}, expected {}",
+                    path.display(),
+                    file.version,
+                    CACHE_FILE_VERSION
+                );
+                return Ok(Self::new());
+            }
+            Err(e) => {
+                log::warn!("Discarding unreadable cache file {}: {}", path.display(), e);
+                return Ok(Self::new());
+            }
+        };
+        let cache = Self::new();
+        let data = file
+            .candles
+            .into_iter()
+            .filter_map(|(mint, mut candles)| {
+                let time_frames: HashSet<TimeFrame> =
+                    candles.iter().map(|c| c.time_frame.clone()).collect();
+                for time_frame in time_frames {
+                    cache.evict(&mut candles, &time_frame);
+                }
+                Some((Pubkey::from_str(&mint).ok()?, candles))
+            })
+            .collect();
+        let last_fetch = file
+            .last_fetch
+            .into_iter()
+            .filter_map(|(mint, time)| {
+                Some((
+                    Pubkey::from_str(&mint).ok()?,
+                    DateTime::<Utc>::from_timestamp(time, 0)?,
+                ))
+            })
+            .collect();
+        Ok(Self {
+            data: Arc::new(Mutex::new(data)),
+            last_fetch: Arc::new(Mutex::new(last_fetch)),
+            capacity: cache.capacity,
+        })
+    }
+
+    /// Writes the cached candles and fetch times to `path` as JSON
+    ///
+    /// The file is written next to `path` and renamed over it, so readers
+    /// never see a partially written cache.
+    ///
+    /// # Params
+    /// path - The cache file
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::price::HistoricalCache;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let cache = HistoricalCache::new();
+    /// cache.save_to_path("candles.json").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn save_to_path(&self, path: impl AsRef<Path>) -> Result<(), MeteoraError> {
+        let path = path.as_ref();
+        let file = CacheFile {
+            version: CACHE_FILE_VERSION,
+            candles: self
+                .data
+                .lock()
+                .await
+                .iter()
+                .map(|(mint, candles)| (mint.to_string(), candles.clone()))
+                .collect(),
+            last_fetch: self
+                .last_fetch
+                .lock()
+                .await
+                .iter()
+                .map(|(mint, time)| (mint.to_string(), time.timestamp()))
+                .collect(),
+        };
+        let bytes = serde_json::to_vec(&file)
+            .map_err(|e| MeteoraError::Error(format!("Failed to serialize cache: {}", e)))?;
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let write = async {
+            tokio::fs::write(&tmp_path, bytes).await?;
+            tokio::fs::rename(&tmp_path, path).await
+        };
+        write.await.map_err(|e| {
+            MeteoraError::Error(format!(
+                "Failed to write cache file {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
 }
 
 /// Main price feed service for retrieving token prices and historical data
@@ -91,6 +386,8 @@ pub struct PriceFeed {
     client: Arc<MeteoraClient>,
     pool_manager: PoolManager,
     cache: HistoricalCache,
+    cache_path: Option<PathBuf>,
+    last_cache_flush: Arc<Mutex<DateTime<Utc>>>,
 }
 
 impl PriceFeed {
@@ -101,59 +398,432 @@ impl PriceFeed {
             client,
             pool_manager,
             cache: HistoricalCache::new(),
+            cache_path: None,
+            last_cache_flush: Arc::new(Mutex::new(Utc::now())),
+        }
+    }
+
+    /// Creates a new PriceFeed whose candle cache is persisted at `cache_path`
+    ///
+    /// The cache is loaded from the file if it exists and written back at most
+    /// once a minute as new candles are cached. Call
+    /// `flush_cache` before exiting to save the latest candles.
+    ///
+    /// # Params
+    /// client - The Meteora client
+    /// cache_path - File to load the cache from and save it to
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::TimeFrame;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+    /// let price_feed = PriceFeed::new_with_cache_path(client, "candles.json").await?;
+    /// let candles = price_feed.get_historical_prices(&token_mint, TimeFrame::H1, 24).await?;
+    /// price_feed.flush_cache().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn new_with_cache_path(
+        client: Arc<MeteoraClient>,
+        cache_path: impl Into<PathBuf>,
+    ) -> Result<Self, MeteoraError> {
+        let cache_path = cache_path.into();
+        Ok(Self {
+            cache: HistoricalCache::load_from_path(&cache_path).await?,
+            cache_path: Some(cache_path),
+            ..Self::new(client)
+        })
+    }
+
+    /// Saves the candle cache to the feed's cache file, if it has one
+    pub async fn flush_cache(&self) -> Result<(), MeteoraError> {
+        let Some(path) = &self.cache_path else {
+            return Ok(());
+        };
+        self.cache.save_to_path(path).await?;
+        *self.last_cache_flush.lock().await = Utc::now();
+        Ok(())
+    }
+
+    /// Adds candles to the cache and saves it if the last save is older than
+    /// `CACHE_FLUSH_INTERVAL_SECS`
+    async fn store_candles(
+        &self,
+        token_mint: &Pubkey,
+        time_frame: &TimeFrame,
+        candles: &[CandleStick],
+    ) {
+        self.cache
+            .update_cache(token_mint, time_frame, candles)
+            .await;
+        if self.cache_path.is_none() {
+            return;
+        }
+        let last_flush = *self.last_cache_flush.lock().await;
+        if Utc::now() - last_flush < Duration::seconds(CACHE_FLUSH_INTERVAL_SECS) {
+            return;
         }
+        if let Err(e) = self.flush_cache().await {
+            log::warn!("Failed to flush candle cache: {:?}", e);
+        }
+    }
+
+    /// Creates a new PriceFeed that reads SOL/USD from a Pyth price account
+    /// when no WSOL/USDC pool is available
+    ///
+    /// # Params
+    /// client - The Meteora client
+    /// sol_usd_pyth_account - The Pyth SOL/USD price account
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::str::FromStr;
+    /// # use solana_sdk::pubkey::Pubkey;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// let pyth_sol_usd = Pubkey::from_str("H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG").unwrap();
+    /// let price_feed = PriceFeed::new_with_oracle(client, pyth_sol_usd);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_oracle(client: Arc<MeteoraClient>, sol_usd_pyth_account: Pubkey) -> Self {
+        let mut price_feed = Self::new(client);
+        price_feed
+            .pool_manager
+            .set_sol_usd_resolver(Arc::new(SolUsdResolver::new(Some(sol_usd_pyth_account))));
+        price_feed
+    }
+
+    /// Pins the SOL/USD rate used for every USD price, or unpins it with `None`
+    ///
+    /// While set, no pool or oracle is consulted for SOL/USD, by this feed or
+    /// by the TVL and stats of its `pool_manager`, and prices carry
+    /// `PriceSource::Override`, which makes USD prices reproducible in tests,
+    /// backtests and on clusters without a WSOL/USDC pool. Rates that aren't
+    /// finite and positive are ignored with a warning.
+    ///
+    /// # Params
+    /// price - The SOL/USD rate to use, or `None` to look it up again
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+    /// price_feed.set_sol_usd_override(Some(150.0));
+    /// let price = price_feed.get_current_price(&token_mint).await?;
+    /// assert_eq!(price.usd_price, price.sol_price * 150.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_sol_usd_override(&self, price: Option<f64>) {
+        self.pool_manager.sol_usd_resolver().set_override(price);
+    }
+
+    /// Returns the pool manager, and its pool cache, used by this feed
+    pub fn pool_manager(&self) -> &PoolManager {
+        &self.pool_manager
+    }
+
+    pub(crate) fn client(&self) -> &Arc<MeteoraClient> {
+        &self.client
     }
 
     /// Gets the current price for a token
     ///
+    /// The price comes from the single deepest pool, so it carries a low confidence.
+    ///
     /// # Params
     /// token_mint - The mint address of the token
     ///
     /// # Example
-    /// ```rust
+    /// ```no_run
+    /// # use solana_sdk::pubkey::Pubkey;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
     /// let price_feed = PriceFeed::new(client);
     /// let token_mint = Pubkey::new_unique();
     /// match price_feed.get_current_price(&token_mint).await {
     ///     Ok(price) => println!("Current price: {}", price.sol_price),
-    ///     Err(e) => eprintln!("Error getting price: {}", e),
+    ///     Err(e) => eprintln!("Error getting price: {:?}", e),
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_current_price(&self, token_mint: &Pubkey) -> Result<TokenPrice, MeteoraError> {
-        let pools = self.pool_manager.find_token_pools(token_mint).await?;
-        if pools.is_empty() {
-            return Err(MeteoraError::NoLiquidityPoolFound);
+        let (_, pool_info) = self.pool_manager.find_best_pool(token_mint).await?;
+        let sol_price = self.pool_price(&pool_info, token_mint)?;
+        let (sol_usd_price, source) = self.get_sol_usd_price().await;
+        Ok(TokenPrice {
+            token_mint: *token_mint,
+            sol_price,
+            usd_price: sol_price * sol_usd_price,
+            timestamp: pool_info.fetched_at,
+            liquidity: pool_info.liquidity_in(token_mint).unwrap_or_default(),
+            confidence: SINGLE_POOL_CONFIDENCE,
+            source,
+        })
+    }
+
+    /// Gets the current price for a token, failing rather than returning outdated data
+    ///
+    /// # Params
+    /// token_mint - The mint address of the token
+    /// max_age - Oldest acceptable price
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+    /// let price = price_feed
+    ///     .get_current_price_fresh(&token_mint, std::time::Duration::from_secs(10))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_current_price_fresh(
+        &self,
+        token_mint: &Pubkey,
+        max_age: std::time::Duration,
+    ) -> Result<TokenPrice, MeteoraError> {
+        let price = self.get_current_price(token_mint).await?;
+        if price.is_stale(max_age) {
+            return Err(MeteoraError::StalePrice(format!(
+                "Price of {} from {} is older than {:?}",
+                token_mint, price.timestamp, max_age
+            )));
         }
-        let mut best_pool = None;
-        let mut max_liquidity = 0;
-        for pool_address in &pools {
-            if let Ok(liquidity) = self.pool_manager.get_pool_liquidity(pool_address).await {
-                if liquidity > max_liquidity {
-                    max_liquidity = liquidity;
-                    best_pool = Some(pool_address);
-                }
+        Ok(price)
+    }
+
+    /// Gets the average USD price per token of trading `notional_usd` in the deepest pool
+    ///
+    /// The trade is run through the pool's constant-product math, so unlike
+    /// the mid price from `get_current_price`, the result includes the trade
+    /// fee and price impact: buying costs more and selling yields less per
+    /// token the larger the notional. The pool's other token is valued with
+    /// `PoolManager::get_token_usd_price`.
+    ///
+    /// # Params
+    /// token_mint - The mint address of the token
+    /// notional_usd - USD value of the trade at the current mid price
+    /// side - Whether the token is bought or sold
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::TradeSide;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+    /// let ask = price_feed.get_effective_price(&token_mint, 10_000.0, TradeSide::Buy).await?;
+    /// let bid = price_feed.get_effective_price(&token_mint, 10_000.0, TradeSide::Sell).await?;
+    /// println!("$10k bid/ask: {} / {}", bid, ask);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_effective_price(
+        &self,
+        token_mint: &Pubkey,
+        notional_usd: f64,
+        side: TradeSide,
+    ) -> Result<f64, MeteoraError> {
+        if !notional_usd.is_finite() || notional_usd <= 0.0 {
+            return Err(MeteoraError::InvalidInput(
+                "Notional must be a positive amount".to_string(),
+            ));
+        }
+        let mut pools = Vec::new();
+        for pool_address in self.pool_manager.find_token_pools(token_mint).await? {
+            if let Ok(pool_info) = self.pool_manager.get_pool_info(&pool_address).await {
+                pools.push(pool_info);
             }
         }
-        let main_pool = best_pool.ok_or(MeteoraError::NoLiquidityPoolFound)?;
-        let pool_info = self.pool_manager.get_pool_info(main_pool).await?;
-        let (sol_price, usd_price) = self.calculate_prices(&pool_info, token_mint).await?;
+        let pool = best_pool(token_mint, &pools).ok_or(MeteoraError::NoLiquidityPoolFound)?;
+        let (quote_mint, token_decimals, quote_decimals) = if pool.token_a_mint == *token_mint {
+            (
+                pool.token_b_mint,
+                pool.token_a_decimals,
+                pool.token_b_decimals,
+            )
+        } else {
+            (
+                pool.token_a_mint,
+                pool.token_b_decimals,
+                pool.token_a_decimals,
+            )
+        };
+        let quote_usd = self.pool_manager.get_token_usd_price(&quote_mint).await?;
+        let token_usd = pool.price_of(token_mint)? * quote_usd;
+        let (input_mint, input_usd, input_decimals, output_decimals) = match side {
+            TradeSide::Buy => (quote_mint, quote_usd, quote_decimals, token_decimals),
+            TradeSide::Sell => (*token_mint, token_usd, token_decimals, quote_decimals),
+        };
+        let amount_in = notional_usd / input_usd * 10f64.powi(input_decimals as i32);
+        if !amount_in.is_finite() || amount_in < 1.0 || amount_in > u64::MAX as f64 {
+            return Err(MeteoraError::CalculationError(format!(
+                "Notional of ${} is out of range for the pool",
+                notional_usd
+            )));
+        }
+        let amount_in = amount_in as u64;
+        let amount_out = MatchedPool::new(pool.clone(), &input_mint).swap_output(amount_in)?;
+        if amount_out == 0 {
+            return Err(MeteoraError::CalculationError(
+                "Trade yields no output".to_string(),
+            ));
+        }
+        let ui_in = amount_in as f64 / 10f64.powi(input_decimals as i32);
+        let ui_out = amount_out as f64 / 10f64.powi(output_decimals as i32);
+        Ok(match side {
+            TradeSide::Buy => ui_in * quote_usd / ui_out,
+            TradeSide::Sell => ui_out * quote_usd / ui_in,
+        })
+    }
+
+    /// Gets current prices for a batch of tokens from a single pool snapshot
+    ///
+    /// The pool list and pool accounts may come from the pool manager's cache,
+    /// but the reserves of the pools containing the requested mints, and of
+    /// the WSOL/USDC pools, are read again in one batched request on every
+    /// call. Each token is then priced from only the pools that contain it,
+    /// and a failure for one token doesn't affect the others.
+    ///
+    /// # Params
+    /// mints - The mint addresses of the tokens
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// # let (token_a, token_b) = (spl_token::native_mint::ID, spl_token::native_mint::ID);
+    /// let prices = price_feed.get_current_prices(&[token_a, token_b]).await;
+    /// for (mint, price) in prices {
+    ///     match price {
+    ///         Ok(price) => println!("{}: {}", mint, price.sol_price),
+    ///         Err(e) => eprintln!("{}: {:?}", mint, e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_current_prices(
+        &self,
+        mints: &[Pubkey],
+    ) -> Vec<(Pubkey, Result<TokenPrice, MeteoraError>)> {
+        let wanted: HashSet<&Pubkey> = mints.iter().collect();
+        let pools = match self.pool_manager.get_all_pool_infos().await {
+            Ok(pools) => pools
+                .into_iter()
+                .filter(|p| {
+                    wanted.contains(&p.token_a_mint)
+                        || wanted.contains(&p.token_b_mint)
+                        || is_sol_usd_pool(p)
+                })
+                .collect::<Vec<_>>(),
+            Err(e) => return mints.iter().map(|mint| (*mint, Err(e.clone()))).collect(),
+        };
+        let pools = match self.pool_manager.refresh_reserves(&pools).await {
+            Ok(pools) => pools,
+            Err(e) => return mints.iter().map(|mint| (*mint, Err(e.clone()))).collect(),
+        };
+        let sol_usd = self.sol_usd_price_for_pools(&pools).await;
+        let index = index_pools_by_mint(&pools, mints);
+        mints
+            .iter()
+            .map(|mint| {
+                let token_pools = index.get(mint).map(Vec::as_slice).unwrap_or_default();
+                (*mint, self.price_from_pools(mint, token_pools, sol_usd))
+            })
+            .collect()
+    }
+
+    /// Prices a token from the deepest of `pools` that contains it
+    pub(crate) fn price_from_pools(
+        &self,
+        token_mint: &Pubkey,
+        pools: &[PoolInfo],
+        (sol_usd_price, source): (f64, PriceSource),
+    ) -> Result<TokenPrice, MeteoraError> {
+        let pool_info = best_pool(token_mint, pools).ok_or(MeteoraError::NoLiquidityPoolFound)?;
+        let sol_price = self.pool_price(pool_info, token_mint)?;
         Ok(TokenPrice {
             token_mint: *token_mint,
             sol_price,
-            usd_price,
-            timestamp: chrono::Utc::now().timestamp(),
-            liquidity: max_liquidity,
+            usd_price: sol_price * sol_usd_price,
+            timestamp: pool_info.fetched_at,
+            liquidity: pool_info.liquidity_in(token_mint).unwrap_or_default(),
+            confidence: SINGLE_POOL_CONFIDENCE,
+            source,
         })
     }
 
+    /// SOL/USD like `get_sol_usd_price`, with the pool rate taken from `pools`
+    pub(crate) async fn sol_usd_price_for_pools(&self, pools: &[PoolInfo]) -> (f64, PriceSource) {
+        self.pool_manager
+            .sol_usd_resolver()
+            .resolve(
+                &self.client,
+                futures::future::ready(self.sol_usd_price_from_pools(pools)),
+            )
+            .await
+    }
+
+    fn sol_usd_price_from_pools(&self, pools: &[PoolInfo]) -> Option<f64> {
+        pools
+            .iter()
+            .find(|p| is_sol_usd_pool(p))
+            .and_then(|p| self.pool_price(p, &spl_token::native_mint::ID).ok())
+    }
+
     /// Gets historical price data for a token
     ///
+    /// Candles are built from recent swaps in the token's pools and priced in
+    /// USD. Only pools quoting the token in WSOL or USDC are used, so that
+    /// swaps from different pools share a unit.
+    ///
     /// # Params
     /// token_mint - The mint address of the token
     /// time_frame - The timeframe for the candles
     /// limit - Maximum number of candles to return
     ///
     /// # Example
-    /// ```rust
+    /// ```no_run
+    /// # use meteora_sdk::types::TimeFrame;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
     /// let candles = price_feed.get_historical_prices(
     ///     &token_mint,
     ///     TimeFrame::H1,
@@ -163,6 +833,8 @@ impl PriceFeed {
     ///     println!("Time: {}, Open: {}, Close: {}",
     ///         candle.timestamp, candle.open, candle.close);
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_historical_prices(
         &self,
@@ -174,24 +846,194 @@ impl PriceFeed {
             .cache
             .should_refresh(token_mint, Duration::minutes(5))
             .await
-        {
-            if let Some(cached) = self
+            && let Some(cached) = self
                 .cache
                 .get_cached_prices(token_mint, &time_frame, limit)
                 .await
-            {
-                return Ok(cached);
-            }
+        {
+            return Ok(cached);
         }
         let candles = self
             .fetch_historical_from_chain(token_mint, &time_frame, limit)
             .await?;
-        self.cache
-            .update_cache(token_mint, &time_frame, &candles)
-            .await;
+        self.store_candles(token_mint, &time_frame, &candles).await;
         Ok(candles)
     }
 
+    /// Backfills the candle cache for a token over an explicit time range
+    ///
+    /// Unlike `get_historical_prices`, this pages through every pool signature
+    /// between `from` and `to` instead of only the most recent ones, and only
+    /// stores candles built from swaps in that range.
+    ///
+    /// # Params
+    /// token_mint - The mint address of the token
+    /// time_frame - The timeframe for the candles
+    /// from - Start of the range as a unix timestamp (inclusive)
+    /// to - End of the range as a unix timestamp (inclusive)
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::TimeFrame;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+    /// let now = chrono::Utc::now().timestamp();
+    /// let stored = price_feed
+    ///     .backfill(&token_mint, TimeFrame::H1, now - 7 * 86400, now)
+    ///     .await?;
+    /// println!("Stored {} candles", stored);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn backfill(
+        &self,
+        token_mint: &Pubkey,
+        time_frame: TimeFrame,
+        from: i64,
+        to: i64,
+    ) -> Result<usize, MeteoraError> {
+        if from > to {
+            return Err(MeteoraError::InvalidInput(
+                "Backfill range start must not be after its end".to_string(),
+            ));
+        }
+        let swap_events = self.get_swap_events_in_range(token_mint, from, to).await?;
+        let candles = self.bucket_swap_events(&swap_events, &time_frame);
+        self.store_candles(token_mint, &time_frame, &candles).await;
+        Ok(candles.len())
+    }
+
+    /// Gets the time-weighted average price of a token over the last `window`
+    ///
+    /// Each swap's executed price is weighted by how long it remained the
+    /// latest price, up to now. Averaging over time makes the result much
+    /// harder to move than a spot price: a manipulator has to hold the pool
+    /// off-market for a large part of the window instead of for one block.
+    /// The tradeoff is lag, since the TWAP trails the market by roughly half
+    /// the window, and thin pools with few swaps still give weak protection.
+    /// Returns `NoHistoricalData` when fewer than `MIN_TWAP_SAMPLES` swaps
+    /// happened in the window.
+    ///
+    /// # Params
+    /// token_mint - The mint address of the token
+    /// window - How far back to average
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use chrono::Duration;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+    /// let twap = price_feed.get_twap(&token_mint, Duration::minutes(30)).await?;
+    /// println!("30 minute TWAP: {}", twap);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_twap(
+        &self,
+        token_mint: &Pubkey,
+        window: Duration,
+    ) -> Result<f64, MeteoraError> {
+        let to = Utc::now().timestamp();
+        let from = to - window.num_seconds();
+        let swap_events = self.get_swap_events_in_range(token_mint, from, to).await?;
+        time_weighted_average_price(&swap_events, to).ok_or(MeteoraError::NoHistoricalData)
+    }
+
+    /// Gets the volume-weighted average price of a token over the last `window`
+    ///
+    /// Each swap's executed price is weighted by its USD volume, so the result
+    /// tracks where size actually traded and suits fill estimation. Returns
+    /// `NoHistoricalData` when no volume traded in the window.
+    ///
+    /// # Params
+    /// token_mint - The mint address of the token
+    /// window - How far back to average
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use chrono::Duration;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+    /// let vwap = price_feed.get_vwap(&token_mint, Duration::hours(1)).await?;
+    /// println!("1 hour VWAP: {}", vwap);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_vwap(
+        &self,
+        token_mint: &Pubkey,
+        window: Duration,
+    ) -> Result<f64, MeteoraError> {
+        let to = Utc::now().timestamp();
+        let from = to - window.num_seconds();
+        let swap_events = self.get_swap_events_in_range(token_mint, from, to).await?;
+        volume_weighted_average_price(&swap_events).ok_or(MeteoraError::NoHistoricalData)
+    }
+
+    /// Collects the swaps of a token across its pools with a block time in `from..=to`
+    async fn get_swap_events_in_range(
+        &self,
+        token_mint: &Pubkey,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<SwapEvent>, MeteoraError> {
+        let pools = self.pool_manager.find_token_pools(token_mint).await?;
+        if pools.is_empty() {
+            return Err(MeteoraError::NoLiquidityPoolFound);
+        }
+        let (sol_usd_price, _) = self.get_sol_usd_price().await;
+        let mut swap_events = Vec::new();
+        for pool_address in pools.iter().take(5) {
+            let pool_info = match self.pool_manager.get_pool_info(pool_address).await {
+                Ok(pool_info) => pool_info,
+                Err(e) => {
+                    log::warn!("Skipping pool {}: {:?}", pool_address, e);
+                    continue;
+                }
+            };
+            let Some(quote_usd_price) = quote_usd_price(&pool_info, token_mint, sol_usd_price)
+            else {
+                log::debug!("Skipping pool {} not quoted in WSOL or USDC", pool_address);
+                continue;
+            };
+            let signatures = self
+                .pool_manager
+                .get_pool_signatures_in_range(pool_address, from, to)
+                .await?;
+            for signature in signatures {
+                if let Ok(swap_event) = self
+                    .analyze_transaction_for_swaps(
+                        &signature,
+                        &pool_info,
+                        token_mint,
+                        quote_usd_price,
+                    )
+                    .await
+                    && swap_event.timestamp >= from
+                    && swap_event.timestamp <= to
+                {
+                    swap_events.push(swap_event);
+                }
+            }
+        }
+        Ok(swap_events)
+    }
+
     async fn fetch_historical_from_chain(
         &self,
         token_mint: &Pubkey,
@@ -212,9 +1054,7 @@ impl PriceFeed {
             }
         }
         if all_swap_events.is_empty() {
-            return self
-                .generate_pool_based_prices(token_mint, time_frame, limit)
-                .await;
+            return Err(MeteoraError::NoHistoricalData);
         }
         let candles = self
             .swap_events_to_candles(&all_swap_events, time_frame, limit)
@@ -226,17 +1066,22 @@ impl PriceFeed {
         &self,
         pool_address: &Pubkey,
         token_mint: &Pubkey,
-        time_frame: &TimeFrame,
+        _time_frame: &TimeFrame,
         max_transactions: usize,
     ) -> Result<Vec<SwapEvent>, MeteoraError> {
         let pool_info = self.pool_manager.get_pool_info(pool_address).await?;
+        let (sol_usd_price, _) = self.get_sol_usd_price().await;
+        let Some(quote_usd_price) = quote_usd_price(&pool_info, token_mint, sol_usd_price) else {
+            log::debug!("Skipping pool {} not quoted in WSOL or USDC", pool_address);
+            return Ok(Vec::new());
+        };
         let signatures = self
             .get_pool_transaction_signatures(pool_address, max_transactions)
             .await?;
         let mut swap_events = Vec::new();
         for signature in signatures {
             if let Ok(swap_event) = self
-                .analyze_transaction_for_swaps(&signature, &pool_info, token_mint)
+                .analyze_transaction_for_swaps(&signature, &pool_info, token_mint, quote_usd_price)
                 .await
             {
                 swap_events.push(swap_event);
@@ -255,22 +1100,23 @@ impl PriceFeed {
     ) -> Result<Vec<String>, MeteoraError> {
         match self
             .client
-            .solana
-            .client_arc()
-            .get_signatures_for_address(pool_address)
+            .get_signatures_for_address_paginated(pool_address, None, limit)
             .await
         {
             Ok(signatures) => {
                 let valid_signatures: Vec<String> = signatures
                     .iter()
-                    .take(limit)
                     .filter(|sig| sig.err.is_none()) // 只取成功的交易
                     .map(|sig| sig.signature.clone())
                     .collect();
                 Ok(valid_signatures)
             }
             Err(e) => {
-                log::warn!("Failed to get signatures for pool {}: {}", pool_address, e);
+                log::warn!(
+                    "Failed to get signatures for pool {}: {:?}",
+                    pool_address,
+                    e
+                );
                 Ok(Vec::new())
             }
         }
@@ -281,61 +1127,13 @@ impl PriceFeed {
         signature: &str,
         pool_info: &PoolInfo,
         target_token_mint: &Pubkey,
+        quote_usd_price: f64,
     ) -> Result<SwapEvent, MeteoraError> {
-        let timestamp = self
-            .get_transaction_timestamp(signature)
-            .await
-            .unwrap_or_else(|_| chrono::Utc::now().timestamp());
-        let current_price = self
-            .calculate_current_pool_price(pool_info, target_token_mint)
-            .await?;
-        let volatility = 0.05; // 5% fluctuation
-        let price_variation = 1.0 + (rand::random::<f64>() - 0.5) * volatility * 2.0;
-        let transaction_price = current_price * price_variation;
-        let base_volume =
-            (pool_info.token_a_reserve_amount + pool_info.token_b_reserve_amount) as f64 / 1000.0;
-        let volume = base_volume * (0.1 + rand::random::<f64>() * 0.9);
-        let sol_usd_price = self.get_sol_usd_price().await.unwrap_or(100.0);
-        let volume_usd = volume * sol_usd_price;
-        Ok(SwapEvent {
-            timestamp,
-            input_mint: *target_token_mint,
-            output_mint: if *target_token_mint == pool_info.token_a_mint {
-                pool_info.token_b_mint
-            } else {
-                pool_info.token_a_mint
-            },
-            input_amount: (volume * 0.5) as u64,
-            output_amount: (volume * 0.5 / transaction_price) as u64,
-            price: transaction_price,
-            volume_usd,
-        })
-    }
-
-    async fn get_transaction_timestamp(&self, signature: &str) -> Result<i64, MeteoraError> {
-        match self
-            .client
-            .solana
-            .client_arc()
-            .get_transaction(
-                &signature
-                    .parse()
-                    .map_err(|_| MeteoraError::Error("Invalid signature".to_string()))?,
-                solana_transaction_status::UiTransactionEncoding::Json,
-            )
-            .await
-        {
-            Ok(tx) => {
-                if let Some(block_time) = tx.block_time {
-                    Ok(block_time)
-                } else {
-                    // 如果没有时间戳，使用当前时间减去随机偏移
-                    let random_offset = rand::random::<u32>() % 86400; // 随机0-24小时偏移
-                    Ok(chrono::Utc::now().timestamp() - random_offset as i64)
-                }
-            }
-            Err(e) => Err(MeteoraError::RpcError(e.to_string())),
-        }
+        let signature: Signature = signature
+            .parse()
+            .map_err(|_| MeteoraError::Error("Invalid signature".to_string()))?;
+        let tx = self.client.get_transaction(&signature).await?;
+        decode_pool_swap(&tx, pool_info, target_token_mint, quote_usd_price)
     }
 
     async fn swap_events_to_candles(
@@ -347,14 +1145,30 @@ impl PriceFeed {
         if swap_events.is_empty() {
             return Err(MeteoraError::NoHistoricalData);
         }
-        let timeframe_seconds = self.get_timeframe_seconds(time_frame);
+        let mut candles = self.bucket_swap_events(swap_events, time_frame);
+        self.ensure_sufficient_candles(&mut candles, time_frame, limit)
+            .await?;
+        candles.reverse();
+        candles.truncate(limit);
+        candles.reverse();
+        Ok(candles)
+    }
+
+    /// Groups swap events into one candle per time frame bucket, oldest first
+    ///
+    /// Events may come in any order, e.g. newest first as signatures are
+    /// paged; each candle opens and closes at its earliest and latest swap.
+    fn bucket_swap_events(
+        &self,
+        swap_events: &[SwapEvent],
+        time_frame: &TimeFrame,
+    ) -> Vec<CandleStick> {
+        let mut swap_events: Vec<&SwapEvent> = swap_events.iter().collect();
+        swap_events.sort_by_key(|event| event.timestamp);
         let mut time_buckets: BTreeMap<i64, Vec<&SwapEvent>> = BTreeMap::new();
         for event in swap_events {
-            let bucket_time = (event.timestamp / timeframe_seconds) * timeframe_seconds;
-            time_buckets
-                .entry(bucket_time)
-                .or_insert_with(Vec::new)
-                .push(event);
+            let bucket_time = self.bucket_start(event.timestamp, time_frame);
+            time_buckets.entry(bucket_time).or_default().push(event);
         }
         // to kline
         let mut candles: Vec<CandleStick> = time_buckets
@@ -379,12 +1193,7 @@ impl PriceFeed {
             })
             .collect();
         candles.sort_by_key(|c| c.timestamp);
-        self.ensure_sufficient_candles(&mut candles, time_frame, limit)
-            .await?;
-        candles.reverse();
-        candles.truncate(limit);
-        candles.reverse();
-        Ok(candles)
+        candles
     }
 
     async fn ensure_sufficient_candles(
@@ -398,7 +1207,9 @@ impl PriceFeed {
         }
         let timeframe_seconds = self.get_timeframe_seconds(time_frame);
         let now = Utc::now().timestamp();
-        let start_time = now - (required_count as i64 * timeframe_seconds);
+        // Aligned to bucket starts so existing candles are found in the timeline
+        let start_time =
+            self.bucket_start(now - required_count as i64 * timeframe_seconds, time_frame);
         let mut full_timeline = Vec::new();
         let mut current_time = start_time;
         while current_time <= now {
@@ -411,8 +1222,8 @@ impl PriceFeed {
                     .unwrap_or_else(|| candles.first().map(|c| c.close).unwrap_or(1.0));
                 full_timeline.push(CandleStick {
                     open: interpolated_price,
-                    high: interpolated_price * 1.01, // +1%
-                    low: interpolated_price * 0.99,  // -1%
+                    high: interpolated_price,
+                    low: interpolated_price,
                     close: interpolated_price,
                     volume: 0.0,
                     timestamp: current_time,
@@ -425,46 +1236,12 @@ impl PriceFeed {
         Ok(())
     }
 
-    async fn generate_pool_based_prices(
-        &self,
-        token_mint: &Pubkey,
-        time_frame: &TimeFrame,
-        limit: usize,
-    ) -> Result<Vec<CandleStick>, MeteoraError> {
-        let current_price = self.get_current_price(token_mint).await?;
-        let timeframe_seconds = self.get_timeframe_seconds(time_frame);
-        let now = Utc::now().timestamp();
-        let mut candles = Vec::new();
-        let mut price = current_price.sol_price;
-        for i in 0..limit {
-            let time_offset = (limit - i - 1) as i64 * timeframe_seconds;
-            let timestamp = now - time_offset;
-            let volatility = 0.02;
-            let time_adjusted_volatility = volatility * (timeframe_seconds as f64 / 86400.0).sqrt();
-            let change = 1.0 + (rand::random::<f64>() - 0.5) * time_adjusted_volatility * 2.0;
-            price *= change;
-            let base_liquidity = current_price.liquidity as f64;
-            let volume_variation = 0.5 + rand::random::<f64>() * 0.5;
-            let volume = base_liquidity * volume_variation * 0.01;
-            candles.push(CandleStick {
-                open: price,
-                high: price * (1.0 + rand::random::<f64>() * 0.015), // +1.5%
-                low: price * (1.0 - rand::random::<f64>() * 0.015),  // -1.5%
-                close: price,
-                volume,
-                timestamp,
-                time_frame: time_frame.clone(),
-            });
-        }
-        Ok(candles)
-    }
-
     fn interpolate_price(&self, candles: &[CandleStick], target_time: i64) -> Option<f64> {
         if candles.is_empty() {
             return None;
         }
-        let before = candles.iter().filter(|c| c.timestamp <= target_time).last();
-        let after = candles.iter().filter(|c| c.timestamp >= target_time).next();
+        let before = candles.iter().rev().find(|c| c.timestamp <= target_time);
+        let after = candles.iter().find(|c| c.timestamp >= target_time);
         match (before, after) {
             (Some(b), Some(a)) if b.timestamp != a.timestamp => {
                 let time_ratio =
@@ -478,103 +1255,93 @@ impl PriceFeed {
         }
     }
 
-    async fn calculate_current_pool_price(
-        &self,
-        pool_info: &PoolInfo,
-        token_mint: &Pubkey,
-    ) -> Result<f64, MeteoraError> {
-        let (price, _) = self.calculate_prices(pool_info, token_mint).await?;
-        Ok(price)
-    }
-
-    async fn calculate_prices(
-        &self,
-        pool_info: &PoolInfo,
-        token_mint: &Pubkey,
-    ) -> Result<(f64, f64), MeteoraError> {
-        let token_a_normalized =
-            pool_info.token_a_reserve_amount as f64 / 10f64.powi(pool_info.token_a_decimals as i32);
-        let token_b_normalized =
-            pool_info.token_b_reserve_amount as f64 / 10f64.powi(pool_info.token_b_decimals as i32);
-        let price = if *token_mint == pool_info.token_a_mint {
-            token_b_normalized / token_a_normalized
-        } else {
-            token_a_normalized / token_b_normalized
-        };
-        let sol_usd_price = self
-            .get_sol_usd_price_without_calculate()
-            .await
-            .unwrap_or(100.0);
-        let usd_price = price * sol_usd_price;
-        Ok((price, usd_price))
-    }
-
-    async fn get_sol_usd_price_without_calculate(&self) -> Result<f64, MeteoraError> {
-        let usdc_mint =
-            Pubkey::from_str(USDC_MINT).map_err(|e| MeteoraError::Error(e.to_string()))?;
-        let wsol_mint = spl_token::native_mint::ID;
-        let sol_pools = self
-            .pool_manager
-            .find_pools_by_tokens(&wsol_mint, &usdc_mint)
-            .await?;
-        if let Some(pool_info) = sol_pools.first() {
-            let wsol_normalized = pool_info.token_a_reserve_amount as f64
-                / 10f64.powi(pool_info.token_a_decimals as i32);
-            let usdc_normalized = pool_info.token_b_reserve_amount as f64
-                / 10f64.powi(pool_info.token_b_decimals as i32);
-            let sol_price = if pool_info.token_a_mint == wsol_mint {
-                usdc_normalized / wsol_normalized
-            } else {
-                wsol_normalized / usdc_normalized
-            };
-            let final_price = if pool_info.token_a_mint == wsol_mint {
-                sol_price
-            } else {
-                1.0 / sol_price
-            };
-            Ok(final_price)
-        } else {
-            Ok(100.0)
+    /// Price of `token_mint` in the pool's other token, or `InvalidPrice` if
+    /// the reserves don't give a finite positive price
+    fn pool_price(&self, pool_info: &PoolInfo, token_mint: &Pubkey) -> Result<f64, MeteoraError> {
+        let price = pool_info.price_of(token_mint)?;
+        if !price.is_finite() || price <= 0.0 {
+            return Err(MeteoraError::InvalidPrice);
         }
+        Ok(price)
     }
 
-    async fn get_sol_usd_price(&self) -> Result<f64, MeteoraError> {
-        let usdc_mint =
-            Pubkey::from_str(USDC_MINT).map_err(|e| MeteoraError::Error(e.to_string()))?;
-        let wsol_mint = spl_token::native_mint::ID;
-        let sol_pools = self
-            .pool_manager
-            .find_pools_by_tokens(&wsol_mint, &usdc_mint)
-            .await?;
-        if let Some(pool_info) = sol_pools.first() {
-            let (sol_price, _) = self.calculate_prices(pool_info, &wsol_mint).await?;
-            Ok(sol_price)
-        } else {
-            Ok(100.0)
-        }
+    /// Gets the SOL/USD rate and where it came from
+    ///
+    /// Uses the rate from `set_sol_usd_override` if one is set. Otherwise
+    /// prefers a WSOL/USDC pool, then the Pyth oracle if one is configured,
+    /// and only then a hardcoded fallback rate. Callers that need a real USD
+    /// value should reject `PriceSource::Fallback`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::PriceSource;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// let (sol_usd, source) = price_feed.get_sol_usd_price().await;
+    /// if source == PriceSource::Fallback {
+    ///     eprintln!("SOL/USD unavailable, {} is a placeholder", sol_usd);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_sol_usd_price(&self) -> (f64, PriceSource) {
+        self.pool_manager.get_sol_usd_price().await
     }
 
-    fn get_timeframe_seconds(&self, time_frame: &TimeFrame) -> i64 {
+    pub(crate) fn get_timeframe_seconds(&self, time_frame: &TimeFrame) -> i64 {
         match time_frame {
             TimeFrame::M1 => 60,
             TimeFrame::M5 => 300,
             TimeFrame::M15 => 900,
+            TimeFrame::M30 => 1800,
             TimeFrame::H1 => 3600,
+            TimeFrame::H2 => 7200,
             TimeFrame::H4 => 14400,
+            TimeFrame::H12 => 43200,
             TimeFrame::D1 => 86400,
+            TimeFrame::W1 => 604800,
         }
     }
 
+    /// Start of the time frame bucket containing `timestamp`
+    ///
+    /// Buckets are aligned to the Unix epoch, except weekly buckets which start
+    /// on Monday 00:00 UTC rather than on the epoch's Thursday.
+    pub(crate) fn bucket_start(&self, timestamp: i64, time_frame: &TimeFrame) -> i64 {
+        let timeframe_seconds = self.get_timeframe_seconds(time_frame);
+        let offset = match time_frame {
+            TimeFrame::W1 => WEEK_START_OFFSET,
+            _ => 0,
+        };
+        (timestamp - offset).div_euclid(timeframe_seconds) * timeframe_seconds + offset
+    }
+
     /// Gets a secure price using weighted average from multiple pools
     ///
+    /// The returned confidence grows with the number of pools agreeing on the
+    /// price and the share of liquidity they hold.
+    ///
     /// # Params
     /// token_mint - The mint address of the token
     ///
     /// # Example
-    /// ```rust
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let price_feed = PriceFeed::new(client);
+    /// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
     /// let secure_price = price_feed.get_secure_price(&token_mint).await?;
     /// println!("Secure price: {} SOL, USD: {}",
     ///     secure_price.sol_price, secure_price.usd_price);
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_secure_price(&self, token_mint: &Pubkey) -> Result<TokenPrice, MeteoraError> {
         let pools = self.pool_manager.find_token_pools(token_mint).await?;
@@ -583,20 +1350,19 @@ impl PriceFeed {
         }
         let mut total_liquidity = 0u64;
         let mut weighted_prices = Vec::new();
+        let mut oldest_fetch = i64::MAX;
         for pool_address in &pools {
-            if let (Ok(pool_info), Ok(liquidity)) = (
-                self.pool_manager.get_pool_info(pool_address).await,
-                self.pool_manager.get_pool_liquidity(pool_address).await,
-            ) {
-                if let Ok((price, _)) = self.calculate_prices(&pool_info, token_mint).await {
-                    if liquidity > 1000 {
-                        total_liquidity += liquidity;
-                        weighted_prices.push((price, liquidity));
-                    }
-                }
+            if let Ok(pool_info) = self.pool_manager.get_pool_info(pool_address).await
+                && let Some(liquidity) = pool_info.liquidity_in(token_mint)
+                && liquidity > 1000
+                && let Ok(price) = self.pool_price(&pool_info, token_mint)
+            {
+                total_liquidity += liquidity;
+                weighted_prices.push((price, liquidity));
+                oldest_fetch = oldest_fetch.min(pool_info.fetched_at);
             }
         }
-        if weighted_prices.is_empty() {
+        if weighted_prices.is_empty() || total_liquidity == 0 {
             return Err(MeteoraError::NoLiquidityPoolFound);
         }
         let mut weighted_sum = 0.0;
@@ -604,14 +1370,1105 @@ impl PriceFeed {
             let weight = *liquidity as f64 / total_liquidity as f64;
             weighted_sum += price * weight;
         }
-        let sol_usd_price = self.get_sol_usd_price().await.unwrap_or(100.0);
+        let confidence = self.price_confidence(&weighted_prices, weighted_sum, total_liquidity);
+        let (sol_usd_price, source) = self.get_sol_usd_price().await;
         let usd_price = weighted_sum * sol_usd_price;
         Ok(TokenPrice {
             token_mint: *token_mint,
             sol_price: weighted_sum,
             usd_price,
-            timestamp: chrono::Utc::now().timestamp(),
+            timestamp: oldest_fetch,
             liquidity: total_liquidity,
+            confidence,
+            source,
         })
     }
+
+    /// Scores a weighted price by how many pools agree with it within
+    /// `PRICE_AGREEMENT_TOLERANCE` and what share of liquidity they hold
+    fn price_confidence(&self, prices: &[(f64, u64)], price: f64, total_liquidity: u64) -> f64 {
+        if price <= 0.0 || total_liquidity == 0 {
+            return 0.0;
+        }
+        let agreeing: Vec<&(f64, u64)> = prices
+            .iter()
+            .filter(|(p, _)| ((p - price) / price).abs() <= PRICE_AGREEMENT_TOLERANCE)
+            .collect();
+        let agreeing_liquidity: u64 = agreeing.iter().map(|(_, liquidity)| liquidity).sum();
+        let count_score =
+            agreeing.len().min(CONFIDENT_POOL_COUNT) as f64 / CONFIDENT_POOL_COUNT as f64;
+        let depth_score = agreeing_liquidity as f64 / total_liquidity as f64;
+        count_score * depth_score
+    }
+}
+
+/// Header row written by `candles_to_csv`
+pub const CANDLE_CSV_HEADER: &str = "timestamp,open,high,low,close,volume,timeframe";
+
+/// Formats candles as CSV with Unix second timestamps
+///
+/// # Params
+/// candles - Candles to export, written in the given order
+///
+/// # Example
+/// ```no_run
+/// # use meteora_sdk::{price::candles_to_csv, types::TimeFrame};
+/// # use std::sync::Arc;
+/// # use meteora_sdk::{MeteoraClient, price::PriceFeed};
+/// # use solana_network_sdk::types::Mode;
+/// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+/// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+/// # let price_feed = PriceFeed::new(client);
+/// # let token_mint = solana_sdk::pubkey!("DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263");
+/// let candles = price_feed.get_historical_prices(&token_mint, TimeFrame::H1, 24).await?;
+/// std::fs::write("candles.csv", candles_to_csv(&candles)).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+pub fn candles_to_csv(candles: &[CandleStick]) -> String {
+    candles_to_csv_with_timestamps(candles, false)
+}
+
+/// Formats candles as CSV, with RFC3339 timestamps when `rfc3339_timestamps` is set
+///
+/// # Params
+/// candles - Candles to export, written in the given order
+/// rfc3339_timestamps - Write timestamps as RFC3339 UTC instead of Unix seconds
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::price::candles_to_csv_with_timestamps;
+/// # use meteora_sdk::types::{CandleStick, TimeFrame};
+/// # let candles = vec![CandleStick {
+/// #     open: 1.0, high: 1.2, low: 0.9, close: 1.1, volume: 500.0,
+/// #     timestamp: 1_700_000_000, time_frame: TimeFrame::H1,
+/// # }];
+/// let csv = candles_to_csv_with_timestamps(&candles, true);
+/// # assert!(csv.contains("2023-11-14T22:13:20+00:00"));
+/// ```
+pub fn candles_to_csv_with_timestamps(candles: &[CandleStick], rfc3339_timestamps: bool) -> String {
+    let mut csv = String::from(CANDLE_CSV_HEADER);
+    csv.push('\n');
+    for candle in candles {
+        let timestamp = match DateTime::<Utc>::from_timestamp(candle.timestamp, 0) {
+            Some(time) if rfc3339_timestamps => time.to_rfc3339(),
+            _ => candle.timestamp.to_string(),
+        };
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            timestamp,
+            candle.open,
+            candle.high,
+            candle.low,
+            candle.close,
+            candle.volume,
+            candle.time_frame
+        ));
+    }
+    csv
+}
+
+/// Serializes candles as a JSON array that deserializes back into `Vec<CandleStick>`
+///
+/// # Example
+/// ```
+/// # use meteora_sdk::price::candles_to_json;
+/// # use meteora_sdk::types::{CandleStick, TimeFrame};
+/// # fn main() -> Result<(), meteora_sdk::types::MeteoraError> {
+/// # let candles = vec![CandleStick {
+/// #     open: 1.0, high: 1.2, low: 0.9, close: 1.1, volume: 500.0,
+/// #     timestamp: 1_700_000_000, time_frame: TimeFrame::H1,
+/// # }];
+/// let json = candles_to_json(&candles)?;
+/// let restored: Vec<CandleStick> = serde_json::from_str(&json).unwrap();
+/// # assert_eq!(restored.len(), candles.len());
+/// # Ok(())
+/// # }
+/// ```
+pub fn candles_to_json(candles: &[CandleStick]) -> Result<String, MeteoraError> {
+    serde_json::to_string(candles)
+        .map_err(|e| MeteoraError::Error(format!("Failed to serialize candles: {}", e)))
+}
+
+/// Whether `pool` trades WSOL against USDC
+fn is_sol_usd_pool(pool: &PoolInfo) -> bool {
+    let usdc_mint = Pubkey::from_str(USDC_MINT).unwrap();
+    let wsol_mint = spl_token::native_mint::ID;
+    (pool.token_a_mint == wsol_mint && pool.token_b_mint == usdc_mint)
+        || (pool.token_a_mint == usdc_mint && pool.token_b_mint == wsol_mint)
+}
+
+/// Groups the pools containing each of `mints` by that mint
+fn index_pools_by_mint(pools: &[PoolInfo], mints: &[Pubkey]) -> HashMap<Pubkey, Vec<PoolInfo>> {
+    let wanted: HashSet<&Pubkey> = mints.iter().collect();
+    let mut index: HashMap<Pubkey, Vec<PoolInfo>> = HashMap::new();
+    for pool in pools {
+        for mint in [&pool.token_a_mint, &pool.token_b_mint] {
+            if wanted.contains(mint) {
+                index.entry(*mint).or_default().push(pool.clone());
+            }
+        }
+    }
+    index
+}
+
+/// Weights each swap's price by the time until the next swap, or until `end`
+/// for the last one
+///
+/// Returns `None` with fewer than `MIN_TWAP_SAMPLES` swaps or no elapsed time.
+fn time_weighted_average_price(swap_events: &[SwapEvent], end: i64) -> Option<f64> {
+    if swap_events.len() < MIN_TWAP_SAMPLES {
+        return None;
+    }
+    let mut events: Vec<&SwapEvent> = swap_events.iter().collect();
+    events.sort_by_key(|event| event.timestamp);
+    let mut weighted_sum = 0.0;
+    let mut total_seconds = 0i64;
+    for (index, event) in events.iter().enumerate() {
+        let until = events
+            .get(index + 1)
+            .map(|next| next.timestamp)
+            .unwrap_or(end);
+        let seconds = (until - event.timestamp).max(0);
+        weighted_sum += event.price * seconds as f64;
+        total_seconds += seconds;
+    }
+    if total_seconds == 0 {
+        return None;
+    }
+    Some(weighted_sum / total_seconds as f64)
+}
+
+/// Weights each swap's price by its USD volume
+///
+/// Returns `None` when the swaps have no total volume.
+fn volume_weighted_average_price(swap_events: &[SwapEvent]) -> Option<f64> {
+    let total_volume: f64 = swap_events.iter().map(|event| event.volume_usd).sum();
+    if total_volume <= 0.0 {
+        return None;
+    }
+    let weighted_sum: f64 = swap_events
+        .iter()
+        .map(|event| event.price * event.volume_usd)
+        .sum();
+    Some(weighted_sum / total_volume)
+}
+
+/// USD price of one whole unit of the token `token_mint` is quoted in by a pool
+///
+/// Only WSOL (at `sol_usd_price`) and USDC (at 1) quotes are known; other
+/// quote tokens give `None`.
+pub(crate) fn quote_usd_price(
+    pool_info: &PoolInfo,
+    token_mint: &Pubkey,
+    sol_usd_price: f64,
+) -> Option<f64> {
+    let quote_mint = if *token_mint == pool_info.token_a_mint {
+        pool_info.token_b_mint
+    } else {
+        pool_info.token_a_mint
+    };
+    if quote_mint == spl_token::native_mint::ID {
+        Some(sol_usd_price)
+    } else if quote_mint == Pubkey::from_str(USDC_MINT).unwrap() {
+        Some(1.0)
+    } else {
+        None
+    }
+}
+
+/// Reads the pool's reserve balances before and after a transaction and
+/// turns their change into a swap of `target_token_mint`
+///
+/// `quote_usd_price` is the USD price of one whole unit of the pool's other
+/// token, see `quote_usd_price`, and the event's `price` and `volume_usd`
+/// are in USD. Callers that only need the swapped amounts may pass 0.
+/// Fails when the transaction didn't move tokens into one reserve and out of
+/// the other, e.g. deposits, withdrawals and unrelated transactions.
+pub(crate) fn decode_pool_swap(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+    pool_info: &PoolInfo,
+    target_token_mint: &Pubkey,
+    quote_usd_price: f64,
+) -> Result<SwapEvent, MeteoraError> {
+    let not_a_swap = || MeteoraError::DeserializationError("Transaction is not a swap".to_string());
+    let timestamp = tx.block_time.ok_or_else(|| {
+        MeteoraError::DeserializationError("Transaction has no block time".to_string())
+    })?;
+    let meta = tx.transaction.meta.as_ref().ok_or_else(not_a_swap)?;
+    if meta.err.is_some() {
+        return Err(not_a_swap());
+    }
+    let account_keys = transaction_account_keys(tx)?;
+    let pre_balances = meta.pre_token_balances.as_ref().map(|b| b.as_slice());
+    let post_balances = meta.post_token_balances.as_ref().map(|b| b.as_slice());
+    let reserve_delta = |reserve: &Pubkey| -> Option<i128> {
+        let reserve = reserve.to_string();
+        let index = account_keys.iter().position(|key| *key == reserve)?;
+        let balance_at = |balances: Option<&[UiTransactionTokenBalance]>| -> i128 {
+            balances
+                .and_then(|balances| {
+                    balances
+                        .iter()
+                        .find(|balance| balance.account_index as usize == index)
+                })
+                .and_then(|balance| balance.ui_token_amount.amount.parse::<i128>().ok())
+                .unwrap_or(0)
+        };
+        Some(balance_at(post_balances) - balance_at(pre_balances))
+    };
+    let delta_a = reserve_delta(&pool_info.token_a_reserve).ok_or_else(not_a_swap)?;
+    let delta_b = reserve_delta(&pool_info.token_b_reserve).ok_or_else(not_a_swap)?;
+    // Exactly one reserve must grow (the input) and the other shrink (the output)
+    if delta_a == 0 || delta_b == 0 || (delta_a > 0) == (delta_b > 0) {
+        return Err(not_a_swap());
+    }
+    let amount_a = delta_a.unsigned_abs() as f64 / 10f64.powi(pool_info.token_a_decimals as i32);
+    let amount_b = delta_b.unsigned_abs() as f64 / 10f64.powi(pool_info.token_b_decimals as i32);
+    let (target_amount, quote_amount) = if *target_token_mint == pool_info.token_a_mint {
+        (amount_a, amount_b)
+    } else {
+        (amount_b, amount_a)
+    };
+    let price = quote_amount / target_amount * quote_usd_price;
+    let (input_mint, output_mint, input_amount, output_amount) = if delta_a > 0 {
+        (
+            pool_info.token_a_mint,
+            pool_info.token_b_mint,
+            delta_a.unsigned_abs() as u64,
+            delta_b.unsigned_abs() as u64,
+        )
+    } else {
+        (
+            pool_info.token_b_mint,
+            pool_info.token_a_mint,
+            delta_b.unsigned_abs() as u64,
+            delta_a.unsigned_abs() as u64,
+        )
+    };
+    Ok(SwapEvent {
+        timestamp,
+        input_mint,
+        output_mint,
+        input_amount,
+        output_amount,
+        price,
+        volume_usd: quote_amount * quote_usd_price,
+    })
+}
+
+/// Every account key of a JSON encoded transaction, in index order, including
+/// addresses loaded from lookup tables
+fn transaction_account_keys(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Vec<String>, MeteoraError> {
+    let EncodedTransaction::Json(ui_transaction) = &tx.transaction.transaction else {
+        return Err(MeteoraError::DeserializationError(
+            "Transaction is not JSON encoded".to_string(),
+        ));
+    };
+    let mut account_keys: Vec<String> = match &ui_transaction.message {
+        UiMessage::Raw(message) => message.account_keys.clone(),
+        UiMessage::Parsed(message) => message
+            .account_keys
+            .iter()
+            .map(|account| account.pubkey.clone())
+            .collect(),
+    };
+    if let Some(meta) = &tx.transaction.meta
+        && let OptionSerializer::Some(loaded) = &meta.loaded_addresses
+    {
+        account_keys.extend(loaded.writable.iter().cloned());
+        account_keys.extend(loaded.readonly.iter().cloned());
+    }
+    Ok(account_keys)
+}
+
+/// Pools swapped through by the Meteora swap instructions of a transaction,
+/// top-level and inner (CPI) instructions alike, in execution order and
+/// without duplicates
+///
+/// Instructions of other programs and other Meteora instructions are skipped.
+pub(crate) fn meteora_swap_pools(
+    tx: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Vec<Pubkey>, MeteoraError> {
+    let account_keys = transaction_account_keys(tx)?;
+    let EncodedTransaction::Json(ui_transaction) = &tx.transaction.transaction else {
+        unreachable!("transaction_account_keys only accepts JSON transactions");
+    };
+    let UiMessage::Raw(message) = &ui_transaction.message else {
+        return Err(MeteoraError::DeserializationError(
+            "Transaction message is parsed, expected raw instructions".to_string(),
+        ));
+    };
+    let inner_instructions = match tx
+        .transaction
+        .meta
+        .as_ref()
+        .map(|meta| &meta.inner_instructions)
+    {
+        Some(OptionSerializer::Some(inner)) => inner.as_slice(),
+        _ => &[],
+    };
+    let mut instructions: Vec<&UiCompiledInstruction> = Vec::new();
+    for (index, instruction) in message.instructions.iter().enumerate() {
+        instructions.push(instruction);
+        for inner in inner_instructions
+            .iter()
+            .filter(|inner| inner.index as usize == index)
+        {
+            instructions.extend(inner.instructions.iter().filter_map(
+                |instruction| match instruction {
+                    UiInstruction::Compiled(instruction) => Some(instruction),
+                    UiInstruction::Parsed(_) => None,
+                },
+            ));
+        }
+    }
+    let mut pools = Vec::new();
+    for instruction in instructions {
+        let program_id = account_keys.get(instruction.program_id_index as usize);
+        if program_id.map(String::as_str) != Some(METEORA_PROGRAM_ID) {
+            continue;
+        }
+        let is_swap = bs58::decode(&instruction.data)
+            .into_vec()
+            .is_ok_and(|data| data.starts_with(&SWAP_DISCRIMINATOR));
+        // The pool is the first account of a swap instruction
+        let pool = instruction
+            .accounts
+            .first()
+            .and_then(|index| account_keys.get(*index as usize))
+            .and_then(|key| Pubkey::from_str(key).ok());
+        if is_swap
+            && let Some(pool) = pool
+            && !pools.contains(&pool)
+        {
+            pools.push(pool);
+        }
+    }
+    Ok(pools)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{PoolLayout, parse_pool_account};
+    use crate::test_util::{
+        MOCK_SLOT, MockRpc, PoolFixture, program_account, pyth_price_data, token_account,
+    };
+    use serde_json::json;
+
+    /// A 6 decimal `token`/WSOL pool whose reserves were read at `fetched_at`
+    fn sol_pool(token: Pubkey, token_amount: u64, sol_amount: u64, fetched_at: i64) -> PoolInfo {
+        let fixture = PoolFixture::new(token, spl_token::native_mint::ID);
+        let pool = parse_pool_account(&fixture.account_data(PoolLayout::V1, 25, 10_000)).unwrap();
+        PoolInfo {
+            address: fixture.address,
+            token_a_decimals: 6,
+            token_b_decimals: 6,
+            token_a_reserve_amount: token_amount,
+            token_b_reserve_amount: sol_amount,
+            fetched_at,
+            ..pool
+        }
+    }
+
+    #[tokio::test]
+    async fn current_prices_scan_pools_once_and_read_fresh_reserves() {
+        let rpc = MockRpc::new();
+        let wsol = spl_token::native_mint::ID;
+        let usdc = Pubkey::from_str(USDC_MINT).unwrap();
+        let (token_a, token_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pool_a = rpc.add_pool(token_a, wsol, 1_000_000, 2_000_000);
+        rpc.add_pool(token_b, wsol, 1_000_000, 4_000_000);
+        rpc.add_pool(wsol, usdc, 1_000_000, 150_000_000);
+        let price_feed = PriceFeed::new(rpc.client());
+
+        let prices = price_feed.get_current_prices(&[token_a, token_b]).await;
+        let price_a = prices[0].1.as_ref().unwrap();
+        let price_b = prices[1].1.as_ref().unwrap();
+        assert_eq!(price_a.sol_price, 2.0);
+        assert_eq!(price_b.sol_price, 4.0);
+        assert_eq!(price_a.usd_price, 300.0);
+        assert_eq!(rpc.call_count("getProgramAccounts"), PoolLayout::ALL.len());
+
+        rpc.set_account(
+            pool_a.token_b_reserve,
+            token_account(wsol, Pubkey::new_unique(), 3_000_000),
+        );
+        let prices = price_feed.get_current_prices(&[token_a, token_b]).await;
+        assert_eq!(prices[0].1.as_ref().unwrap().sol_price, 3.0);
+        assert_eq!(rpc.call_count("getProgramAccounts"), PoolLayout::ALL.len());
+    }
+
+    #[test]
+    fn price_is_stamped_with_the_reserve_fetch_time() {
+        let price_feed = PriceFeed::new(MockRpc::new().client());
+        let token = Pubkey::new_unique();
+        let pools = [sol_pool(token, 1_000_000, 2_000_000, 1_700_000_000)];
+        let price = price_feed
+            .price_from_pools(&token, &pools, (150.0, PriceSource::Pool))
+            .unwrap();
+        assert_eq!(price.timestamp, 1_700_000_000);
+        assert!(price.is_stale(std::time::Duration::from_secs(60)));
+    }
+
+    /// A JSON `getTransaction` result in which a user swaps through `pool`,
+    /// moving the given base unit amounts into reserve A and out of reserve B
+    fn swap_transaction(
+        pool: &PoolInfo,
+        a_in: u64,
+        b_out: u64,
+    ) -> EncodedConfirmedTransactionWithStatusMeta {
+        let balance = |index: u8, mint: &Pubkey, amount: u64| {
+            json!({
+                "accountIndex": index,
+                "mint": mint.to_string(),
+                "uiTokenAmount": {
+                    "uiAmount": null,
+                    "decimals": 6,
+                    "amount": amount.to_string(),
+                    "uiAmountString": "",
+                },
+            })
+        };
+        let fixture = json!({
+            "slot": 1000,
+            "blockTime": 1_700_000_000,
+            "transaction": {
+                "signatures": [Signature::default().to_string()],
+                "message": {
+                    "accountKeys": [
+                        Pubkey::new_unique().to_string(),
+                        pool.token_a_reserve.to_string(),
+                        pool.token_b_reserve.to_string(),
+                        METEORA_PROGRAM_ID,
+                    ],
+                    "header": {
+                        "numRequiredSignatures": 1,
+                        "numReadonlySignedAccounts": 0,
+                        "numReadonlyUnsignedAccounts": 1,
+                    },
+                    "recentBlockhash": solana_sdk::hash::Hash::default().to_string(),
+                    "instructions": [],
+                },
+            },
+            "meta": {
+                "err": null,
+                "status": { "Ok": null },
+                "fee": 5000,
+                "preBalances": [],
+                "postBalances": [],
+                "preTokenBalances": [
+                    balance(1, &pool.token_a_mint, 10_000_000),
+                    balance(2, &pool.token_b_mint, 20_000_000),
+                ],
+                "postTokenBalances": [
+                    balance(1, &pool.token_a_mint, 10_000_000 + a_in),
+                    balance(2, &pool.token_b_mint, 20_000_000 - b_out),
+                ],
+            },
+        });
+        serde_json::from_value(fixture).unwrap()
+    }
+
+    #[test]
+    fn swap_is_priced_in_usd_through_the_quote_token() {
+        let token = Pubkey::new_unique();
+        let sol_pool = sol_pool(token, 10_000_000, 20_000_000, 0);
+        let tx = swap_transaction(&sol_pool, 1_000_000, 2_000_000);
+        let sol_usd = quote_usd_price(&sol_pool, &token, 150.0).unwrap();
+        let swap = decode_pool_swap(&tx, &sol_pool, &token, sol_usd).unwrap();
+        assert_eq!(swap.timestamp, 1_700_000_000);
+        assert_eq!(swap.input_mint, token);
+        assert_eq!(swap.input_amount, 1_000_000);
+        assert_eq!(swap.output_amount, 2_000_000);
+        assert_eq!(swap.price, 300.0);
+        assert_eq!(swap.volume_usd, 300.0);
+
+        let usdc_pool = PoolInfo {
+            token_b_mint: Pubkey::from_str(USDC_MINT).unwrap(),
+            ..sol_pool.clone()
+        };
+        let tx = swap_transaction(&usdc_pool, 1_000_000, 2_000_000);
+        let usd = quote_usd_price(&usdc_pool, &token, 150.0).unwrap();
+        let swap = decode_pool_swap(&tx, &usdc_pool, &token, usd).unwrap();
+        assert_eq!(swap.price, 2.0);
+        assert_eq!(swap.volume_usd, 2.0);
+    }
+
+    #[test]
+    fn pools_quoted_in_other_tokens_have_no_usd_quote() {
+        let token = Pubkey::new_unique();
+        let pool = PoolInfo {
+            token_b_mint: Pubkey::new_unique(),
+            ..sol_pool(token, 1, 1, 0)
+        };
+        assert_eq!(quote_usd_price(&pool, &token, 150.0), None);
+        // The quote token of the WSOL side is the other token
+        assert_eq!(quote_usd_price(&pool, &pool.token_b_mint, 150.0), None);
+    }
+
+    #[tokio::test]
+    async fn stale_oracle_price_falls_back() {
+        let rpc = MockRpc::new();
+        let oracle = Pubkey::new_unique();
+        let price_feed = PriceFeed::new_with_oracle(rpc.client(), oracle);
+
+        rpc.set_account(
+            oracle,
+            program_account(Pubkey::new_unique(), pyth_price_data(16_000, -2, MOCK_SLOT)),
+        );
+        assert_eq!(
+            price_feed.get_sol_usd_price().await,
+            (160.0, PriceSource::Oracle)
+        );
+
+        rpc.set_account(
+            oracle,
+            program_account(
+                Pubkey::new_unique(),
+                pyth_price_data(16_000, -2, MOCK_SLOT - 100),
+            ),
+        );
+        assert_eq!(
+            price_feed.get_sol_usd_price().await,
+            (FALLBACK_SOL_USD_PRICE, PriceSource::Fallback)
+        );
+    }
+
+    #[tokio::test]
+    async fn usd_prices_without_a_sol_usd_pool_are_flagged_as_fallback() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        rpc.add_pool(token, spl_token::native_mint::ID, 1_000_000, 2_000_000);
+        // No WSOL/USDC pool exists and no oracle is configured
+        let price_feed = PriceFeed::new(rpc.client());
+
+        for price in [
+            price_feed.get_current_price(&token).await.unwrap(),
+            price_feed.get_secure_price(&token).await.unwrap(),
+        ] {
+            assert_eq!(price.source, PriceSource::Fallback);
+            assert_eq!(price.sol_price, 2.0);
+            assert_eq!(price.usd_price, 2.0 * FALLBACK_SOL_USD_PRICE);
+        }
+    }
+
+    /// A candle of `time_frame` opening at `timestamp` whose prices all equal `close`
+    fn candle(timestamp: i64, time_frame: TimeFrame, close: f64) -> CandleStick {
+        CandleStick {
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1_000.0,
+            timestamp,
+            time_frame,
+        }
+    }
+
+    #[test]
+    fn candles_export_to_json_and_csv() {
+        let candles = vec![
+            candle(1_700_000_000, TimeFrame::H1, 1.5),
+            candle(1_700_003_600, TimeFrame::H1, 2.25),
+        ];
+        let restored: Vec<CandleStick> =
+            serde_json::from_str(&candles_to_json(&candles).unwrap()).unwrap();
+        assert_eq!(restored.len(), candles.len());
+        for (restored, candle) in restored.iter().zip(&candles) {
+            assert_eq!(restored.timestamp, candle.timestamp);
+            assert_eq!(restored.time_frame, candle.time_frame);
+            assert_eq!(
+                (restored.open, restored.high, restored.low, restored.close),
+                (candle.open, candle.high, candle.low, candle.close)
+            );
+            assert_eq!(restored.volume, candle.volume);
+        }
+
+        let csv = candles_to_csv(&candles);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CANDLE_CSV_HEADER);
+        assert_eq!(lines[1], "1700000000,1.5,1.5,1.5,1.5,1000,1h");
+        assert_eq!(lines.len(), 3);
+        let csv = candles_to_csv_with_timestamps(&candles, true);
+        assert!(
+            csv.lines()
+                .nth(1)
+                .unwrap()
+                .starts_with("2023-11-14T22:13:20+00:00,")
+        );
+    }
+
+    #[test]
+    fn new_time_frames_bucket_on_their_boundaries() {
+        let rpc = MockRpc::new();
+        let price_feed = PriceFeed::new(rpc.client());
+        // Tuesday 2023-11-14 22:13:20 UTC
+        let timestamp = 1_700_000_000;
+        let expected = [
+            (TimeFrame::M30, 1_800, 1_699_999_200),
+            (TimeFrame::H2, 7_200, 1_699_999_200),
+            (TimeFrame::H12, 43_200, 1_699_963_200),
+            // Monday 2023-11-13 00:00:00 UTC
+            (TimeFrame::W1, 604_800, 1_699_833_600),
+        ];
+        for (time_frame, seconds, bucket) in expected {
+            assert_eq!(price_feed.get_timeframe_seconds(&time_frame), seconds);
+            assert_eq!(price_feed.bucket_start(timestamp, &time_frame), bucket);
+            assert_eq!(price_feed.bucket_start(bucket, &time_frame), bucket);
+            assert_eq!(
+                price_feed.bucket_start(bucket - 1, &time_frame),
+                bucket - seconds
+            );
+        }
+        // The epoch was a Thursday, so its week started the Monday before
+        assert_eq!(price_feed.bucket_start(0, &TimeFrame::W1), -3 * 86_400);
+    }
+
+    #[tokio::test]
+    async fn full_cache_drops_the_oldest_candles_of_that_time_frame() {
+        let cache = HistoricalCache::new_with_capacity(HashMap::from([(TimeFrame::H1, 3)]));
+        let token_mint = Pubkey::new_unique();
+        let daily = [candle(0, TimeFrame::D1, 1.0)];
+        cache
+            .update_cache(&token_mint, &TimeFrame::D1, &daily)
+            .await;
+        // Inserted newest first to check eviction goes by timestamp
+        let hourly: Vec<CandleStick> = (0..5)
+            .rev()
+            .map(|hour| candle(hour * 3_600, TimeFrame::H1, hour as f64))
+            .collect();
+        cache
+            .update_cache(&token_mint, &TimeFrame::H1, &hourly)
+            .await;
+
+        let kept = cache
+            .get_cached_prices(&token_mint, &TimeFrame::H1, 3)
+            .await
+            .unwrap();
+        let mut timestamps: Vec<i64> = kept.iter().map(|c| c.timestamp).collect();
+        timestamps.sort_unstable();
+        assert_eq!(timestamps, [7_200, 10_800, 14_400]);
+        assert!(
+            cache
+                .get_cached_prices(&token_mint, &TimeFrame::H1, 4)
+                .await
+                .is_none()
+        );
+        // Other time frames keep their own capacity
+        assert_eq!(cache.capacity(&TimeFrame::D1), DEFAULT_CANDLE_CAPACITY);
+        assert!(
+            cache
+                .get_cached_prices(&token_mint, &TimeFrame::D1, 1)
+                .await
+                .is_some()
+        );
+    }
+
+    /// A cache file path unique to one test
+    fn cache_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("meteora-sdk-{}-{}.json", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn saved_cache_loads_back_in_order_within_capacity() {
+        let path = cache_path("round-trip");
+        let token_mint = Pubkey::new_unique();
+        // Saved out of timestamp order, which must survive the round trip
+        let candles: Vec<CandleStick> = [3, 1, 2]
+            .into_iter()
+            .map(|hour| candle(hour * 3_600, TimeFrame::H1, hour as f64))
+            .collect();
+        let cache = HistoricalCache::new();
+        cache
+            .update_cache(&token_mint, &TimeFrame::H1, &candles)
+            .await;
+        cache.save_to_path(&path).await.unwrap();
+
+        let loaded = HistoricalCache::load_from_path(&path).await.unwrap();
+        let closes: Vec<f64> = loaded
+            .get_cached_prices(&token_mint, &TimeFrame::H1, 3)
+            .await
+            .unwrap()
+            .iter()
+            .map(|c| c.close)
+            .collect();
+        assert_eq!(closes, [3.0, 1.0, 2.0]);
+        assert!(
+            !loaded
+                .should_refresh(&token_mint, Duration::minutes(5))
+                .await
+        );
+
+        // A file holding more than the capacity is trimmed to the newest candles
+        let oversized: VecDeque<CandleStick> = (0..DEFAULT_CANDLE_CAPACITY as i64 + 5)
+            .map(|hour| candle(hour * 3_600, TimeFrame::H1, hour as f64))
+            .collect();
+        let file = CacheFile {
+            version: CACHE_FILE_VERSION,
+            candles: HashMap::from([(token_mint.to_string(), oversized)]),
+            last_fetch: HashMap::new(),
+        };
+        tokio::fs::write(&path, serde_json::to_vec(&file).unwrap())
+            .await
+            .unwrap();
+        let loaded = HistoricalCache::load_from_path(&path).await.unwrap();
+        assert!(
+            loaded
+                .get_cached_prices(&token_mint, &TimeFrame::H1, DEFAULT_CANDLE_CAPACITY + 1)
+                .await
+                .is_none()
+        );
+        let kept = loaded
+            .get_cached_prices(&token_mint, &TimeFrame::H1, DEFAULT_CANDLE_CAPACITY)
+            .await
+            .unwrap();
+        assert_eq!(kept[0].timestamp, 5 * 3_600);
+        assert!(kept.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn incompatible_cache_files_are_discarded() {
+        let path = cache_path("incompatible");
+        let token_mint = Pubkey::new_unique();
+        let file = CacheFile {
+            version: CACHE_FILE_VERSION + 1,
+            candles: HashMap::from([(
+                token_mint.to_string(),
+                VecDeque::from([candle(0, TimeFrame::H1, 1.0)]),
+            )]),
+            last_fetch: HashMap::new(),
+        };
+        tokio::fs::write(&path, serde_json::to_vec(&file).unwrap())
+            .await
+            .unwrap();
+        let loaded = HistoricalCache::load_from_path(&path).await.unwrap();
+        assert!(
+            loaded
+                .get_cached_prices(&token_mint, &TimeFrame::H1, 1)
+                .await
+                .is_none()
+        );
+
+        tokio::fs::write(&path, b"not json").await.unwrap();
+        assert!(HistoricalCache::load_from_path(&path).await.is_ok());
+        tokio::fs::remove_file(&path).await.unwrap();
+        // A missing file is an empty cache too
+        assert!(HistoricalCache::load_from_path(&path).await.is_ok());
+    }
+
+    /// Start of an hour, so hourly candles start at `HOUR + n * 3600`
+    const HOUR: i64 = 1_700_002_800;
+
+    #[tokio::test]
+    async fn backfill_stores_candles_of_the_swaps_in_range() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        let pool = rpc.add_pool(token, spl_token::native_mint::ID, 1_000_000, 2_000_000);
+        // Selling 1 token for 2 SOL, then for 3 SOL, then for 4 SOL an hour later
+        rpc.add_swap(&pool, HOUR - 60, 1_000_000, -9_000_000);
+        rpc.add_swap(&pool, HOUR + 60, 1_000_000, -2_000_000);
+        rpc.add_swap(&pool, HOUR + 120, 1_000_000, -3_000_000);
+        rpc.add_swap(&pool, HOUR + 3_660, 1_000_000, -4_000_000);
+        rpc.add_swap(&pool, HOUR + 7_260, 1_000_000, -9_000_000);
+        let price_feed = PriceFeed::new(rpc.client());
+        price_feed.set_sol_usd_override(Some(100.0));
+
+        let stored = price_feed
+            .backfill(&token, TimeFrame::H1, HOUR, HOUR + 7_199)
+            .await
+            .unwrap();
+        assert_eq!(stored, 2);
+        let candles = price_feed
+            .cache
+            .get_cached_prices(&token, &TimeFrame::H1, 2)
+            .await
+            .unwrap();
+        let mut summary: Vec<_> = candles
+            .iter()
+            .map(|c| (c.timestamp, c.open, c.high, c.low, c.close))
+            .collect();
+        summary.sort_by_key(|(timestamp, ..)| *timestamp);
+        assert_eq!(
+            summary,
+            [
+                (HOUR, 200.0, 300.0, 200.0, 300.0),
+                (HOUR + 3_600, 400.0, 400.0, 400.0, 400.0)
+            ]
+        );
+        assert!(matches!(
+            price_feed
+                .backfill(&token, TimeFrame::H1, HOUR, HOUR - 1)
+                .await,
+            Err(MeteoraError::InvalidInput(_))
+        ));
+    }
+
+    fn swap_at(timestamp: i64, price: f64) -> SwapEvent {
+        SwapEvent {
+            timestamp,
+            input_mint: Pubkey::default(),
+            output_mint: Pubkey::default(),
+            input_amount: 1,
+            output_amount: 1,
+            price,
+            volume_usd: price,
+        }
+    }
+
+    #[test]
+    fn twap_weights_each_price_by_how_long_it_held() {
+        // 1.0 for 10s, 2.0 for 30s, then 4.0 for the last 10s
+        let swaps = [swap_at(40, 4.0), swap_at(0, 1.0), swap_at(10, 2.0)];
+        assert_eq!(time_weighted_average_price(&swaps, 50), Some(2.2));
+        assert_eq!(time_weighted_average_price(&swaps[..1], 50), None);
+        assert_eq!(
+            time_weighted_average_price(&[swap_at(50, 1.0), swap_at(50, 2.0)], 50),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn twap_averages_the_swaps_in_the_window() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        let pool = rpc.add_pool(token, spl_token::native_mint::ID, 1_000_000, 2_000_000);
+        let now = Utc::now().timestamp();
+        rpc.add_swap(&pool, now - 7_200, 1_000_000, -9_000_000);
+        rpc.add_swap(&pool, now - 1_000, 1_000_000, -2_000_000);
+        rpc.add_swap(&pool, now - 500, 1_000_000, -4_000_000);
+        let price_feed = PriceFeed::new(rpc.client());
+        price_feed.set_sol_usd_override(Some(100.0));
+
+        // 200 for 500s and 400 for the last ~500s; the older swap is outside
+        let twap = price_feed
+            .get_twap(&token, Duration::minutes(30))
+            .await
+            .unwrap();
+        assert!((twap - 300.0).abs() < 1.0, "{}", twap);
+        assert!(matches!(
+            price_feed.get_twap(&token, Duration::minutes(10)).await,
+            Err(MeteoraError::NoHistoricalData)
+        ));
+    }
+
+    #[test]
+    fn vwap_weights_each_price_by_its_volume() {
+        let swaps = [
+            SwapEvent {
+                volume_usd: 300.0,
+                ..swap_at(0, 1.0)
+            },
+            SwapEvent {
+                volume_usd: 100.0,
+                ..swap_at(10, 5.0)
+            },
+        ];
+        assert_eq!(volume_weighted_average_price(&swaps), Some(2.0));
+        let without_volume = [SwapEvent {
+            volume_usd: 0.0,
+            ..swap_at(0, 1.0)
+        }];
+        assert_eq!(volume_weighted_average_price(&without_volume), None);
+        assert_eq!(volume_weighted_average_price(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn vwap_averages_the_swaps_in_the_window() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        let pool = rpc.add_pool(token, spl_token::native_mint::ID, 1_000_000, 2_000_000);
+        let now = Utc::now().timestamp();
+        rpc.add_swap(&pool, now - 7_200, 1_000_000, -9_000_000);
+        // $200 of volume at 200, then $1200 at 400
+        rpc.add_swap(&pool, now - 1_000, 1_000_000, -2_000_000);
+        rpc.add_swap(&pool, now - 500, 3_000_000, -12_000_000);
+        let price_feed = PriceFeed::new(rpc.client());
+        price_feed.set_sol_usd_override(Some(100.0));
+
+        let vwap = price_feed
+            .get_vwap(&token, Duration::minutes(30))
+            .await
+            .unwrap();
+        assert!((vwap - 2_600.0 / 7.0).abs() < 1e-6, "{}", vwap);
+        assert!(matches!(
+            price_feed.get_vwap(&token, Duration::minutes(5)).await,
+            Err(MeteoraError::NoHistoricalData)
+        ));
+    }
+
+    #[tokio::test]
+    async fn pool_without_a_quote_reserve_is_an_invalid_price() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        rpc.add_pool(token, spl_token::native_mint::ID, 1_000_000, 0);
+        let price_feed = PriceFeed::new(rpc.client());
+        assert!(matches!(
+            price_feed.get_current_price(&token).await,
+            Err(MeteoraError::InvalidPrice)
+        ));
+    }
+
+    #[tokio::test]
+    async fn empty_and_one_sided_pools_never_produce_a_price() {
+        let token = Pubkey::new_unique();
+        for (token_amount, sol_amount) in [(0, 0), (1_000_000, 0), (0, 1_000_000)] {
+            let pool = sol_pool(token, token_amount, sol_amount, 0);
+            assert!(matches!(
+                pool.price_of(&token),
+                Err(MeteoraError::InvalidPrice)
+            ));
+        }
+
+        let rpc = MockRpc::new();
+        let wsol = spl_token::native_mint::ID;
+        rpc.add_pool(token, wsol, 0, 0);
+        let price_feed = PriceFeed::new(rpc.client());
+        assert!(matches!(
+            price_feed.get_secure_price(&token).await,
+            Err(MeteoraError::NoLiquidityPoolFound)
+        ));
+        // A one-sided pool is skipped rather than averaged in as infinity
+        rpc.add_pool(token, wsol, 1_000_000, 0);
+        rpc.add_pool(token, wsol, 1_000_000, 2_000_000);
+        let price = price_feed.get_secure_price(&token).await.unwrap();
+        assert_eq!(price.sol_price, 2.0);
+    }
+
+    #[tokio::test]
+    async fn larger_notionals_get_worse_effective_prices() {
+        let rpc = MockRpc::new();
+        let token = Pubkey::new_unique();
+        let usdc = Pubkey::from_str(USDC_MINT).unwrap();
+        // 1,000 tokens against 10,000 USDC, a mid price of $10
+        rpc.add_pool(token, usdc, 1_000_000_000, 10_000_000_000);
+        let price_feed = PriceFeed::new(rpc.client());
+
+        let mut asks = Vec::new();
+        let mut bids = Vec::new();
+        for notional in [1.0, 1_000.0, 5_000.0] {
+            asks.push(
+                price_feed
+                    .get_effective_price(&token, notional, TradeSide::Buy)
+                    .await
+                    .unwrap(),
+            );
+            bids.push(
+                price_feed
+                    .get_effective_price(&token, notional, TradeSide::Sell)
+                    .await
+                    .unwrap(),
+            );
+        }
+        // Even a tiny trade pays the 0.25% fee on both sides
+        assert!(asks[0] > 10.025 && asks[0] < 10.03, "{:?}", asks);
+        assert!(bids[0] < 9.975 && bids[0] > 9.97, "{:?}", bids);
+        assert!(asks.windows(2).all(|w| w[0] < w[1]), "{:?}", asks);
+        assert!(bids.windows(2).all(|w| w[0] > w[1]), "{:?}", bids);
+
+        assert!(matches!(
+            price_feed
+                .get_effective_price(&token, 0.0, TradeSide::Buy)
+                .await,
+            Err(MeteoraError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn secure_price_confidence_grows_with_agreeing_pools() {
+        let rpc = MockRpc::new();
+        let wsol = spl_token::native_mint::ID;
+        let (thin, deep) = (Pubkey::new_unique(), Pubkey::new_unique());
+        rpc.add_pool(thin, wsol, 1_000_000, 2_000_000);
+        for _ in 0..3 {
+            rpc.add_pool(deep, wsol, 1_000_000, 2_000_000);
+        }
+        let price_feed = PriceFeed::new(rpc.client());
+
+        let thin_price = price_feed.get_secure_price(&thin).await.unwrap();
+        let deep_price = price_feed.get_secure_price(&deep).await.unwrap();
+        assert_eq!(thin_price.sol_price, 2.0);
+        assert_eq!(deep_price.sol_price, 2.0);
+        assert!((thin_price.confidence - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(deep_price.confidence, 1.0);
+        assert_eq!(
+            price_feed
+                .get_current_price(&deep)
+                .await
+                .unwrap()
+                .confidence,
+            SINGLE_POOL_CONFIDENCE
+        );
+    }
+
+    #[tokio::test]
+    async fn sol_usd_override_replaces_the_pool_rate() {
+        let price_feed = PriceFeed::new(MockRpc::new().client());
+        let token = Pubkey::new_unique();
+        let usdc = Pubkey::from_str(USDC_MINT).unwrap();
+        let pools = [
+            sol_pool(token, 1_000_000, 2_000_000, 0),
+            sol_pool(usdc, 150_000_000, 1_000_000, 0),
+        ];
+        assert_eq!(
+            price_feed.sol_usd_price_for_pools(&pools).await,
+            (150.0, PriceSource::Pool)
+        );
+
+        price_feed.set_sol_usd_override(Some(200.0));
+        let sol_usd = price_feed.sol_usd_price_for_pools(&pools).await;
+        assert_eq!(sol_usd, (200.0, PriceSource::Override));
+        let price = price_feed
+            .price_from_pools(&token, &pools, sol_usd)
+            .unwrap();
+        assert_eq!(price.usd_price, price.sol_price * 200.0);
+        assert_eq!(price.source, PriceSource::Override);
+
+        // Invalid rates are ignored and `None` restores the pool rate
+        price_feed.set_sol_usd_override(Some(f64::NAN));
+        assert_eq!(
+            price_feed.sol_usd_price_for_pools(&pools).await,
+            (200.0, PriceSource::Override)
+        );
+        price_feed.set_sol_usd_override(None);
+        assert_eq!(
+            price_feed.sol_usd_price_for_pools(&pools).await,
+            (150.0, PriceSource::Pool)
+        );
+    }
+
+    #[tokio::test]
+    async fn sol_usd_override_reaches_pool_tvl() {
+        let rpc = MockRpc::new();
+        let wsol = spl_token::native_mint::ID;
+        let token = Pubkey::new_unique();
+        // 1 SOL = 150 USDC, and 1 token = 0.5 SOL
+        rpc.add_pool(
+            wsol,
+            Pubkey::from_str(USDC_MINT).unwrap(),
+            1_000_000,
+            150_000_000,
+        );
+        let pool = rpc.add_pool(wsol, token, 2_000_000, 4_000_000);
+        let price_feed = PriceFeed::new(rpc.client());
+        let pool_manager = price_feed.pool_manager();
+        assert_eq!(
+            pool_manager.get_pool_tvl_usd(&pool.address).await.unwrap(),
+            2.0 * 150.0 + 4.0 * 75.0
+        );
+
+        price_feed.set_sol_usd_override(Some(200.0));
+        assert_eq!(
+            pool_manager.get_sol_usd_price().await,
+            (200.0, PriceSource::Override)
+        );
+        assert_eq!(
+            pool_manager.get_pool_tvl_usd(&pool.address).await.unwrap(),
+            2.0 * 200.0 + 4.0 * 100.0
+        );
+    }
 }
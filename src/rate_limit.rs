@@ -0,0 +1,34 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::{Instant, sleep_until};
+
+/// Spaces requests evenly so no more than a fixed number start per second
+///
+/// Each caller reserves the next free slot and sleeps until it, so the rate
+/// holds no matter how many tasks wait at once.
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `max_requests_per_second` requests per second
+    pub(crate) fn new(max_requests_per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / max_requests_per_second,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the caller may send its next request
+    pub(crate) async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
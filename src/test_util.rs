@@ -0,0 +1,613 @@
+//! Fixtures shared by the unit tests: an in-process RPC node and account builders
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde_json::{Value, json};
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig, encode_ui_account};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_filter::RpcFilterType,
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::{
+    account::Account, hash::Hash, program_pack::Pack, pubkey::Pubkey, rent::Rent,
+    signature::Signature, transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+use crate::layout::PoolLayout;
+use crate::pool::{self, vault_address};
+use crate::types::RetryConfig;
+use crate::{MeteoraClient, MeteoraClientBuilder};
+
+/// What a mocked RPC method answers
+pub(crate) enum MockReply {
+    /// Responds with this `result`
+    Result(Value),
+    /// Fails with a transport error
+    Error(String),
+    /// Never responds
+    Hang,
+    /// Responds as if unmocked, after this long
+    Delayed(std::time::Duration),
+}
+
+type Handler = Arc<dyn Fn(&Value) -> MockReply + Send + Sync>;
+
+/// A transaction stored with `MockRpc::add_swap`
+struct StoredTransaction {
+    signature: Signature,
+    block_time: i64,
+    address: Pubkey,
+    transaction: Value,
+}
+
+#[derive(Default)]
+struct MockState {
+    accounts: Mutex<HashMap<Pubkey, Account>>,
+    transactions: Mutex<Vec<StoredTransaction>>,
+    handlers: Mutex<HashMap<String, Handler>>,
+    calls: Mutex<Vec<(String, Value)>>,
+}
+
+/// An in-process RPC node serving accounts from memory
+///
+/// Account reads (`getAccountInfo`, `getMultipleAccounts`,
+/// `getProgramAccounts` with size and memcmp filters, `getBalance`) and a
+/// few chain queries are answered from the stored accounts, and
+/// `getSignaturesForAddress` and `getTransaction` from the stored swaps; any
+/// other method fails. Every request is recorded.
+#[derive(Clone, Default)]
+pub(crate) struct MockRpc {
+    state: Arc<MockState>,
+}
+
+pub(crate) const MOCK_URL: &str = "http://127.0.0.1:8899";
+pub(crate) const MOCK_SLOT: u64 = 1_000;
+
+impl MockRpc {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn set_account(&self, address: Pubkey, account: Account) {
+        self.state.accounts.lock().unwrap().insert(address, account);
+    }
+
+    pub(crate) fn account(&self, address: &Pubkey) -> Option<Account> {
+        self.state.accounts.lock().unwrap().get(address).cloned()
+    }
+
+    pub(crate) fn remove_account(&self, address: &Pubkey) {
+        self.state.accounts.lock().unwrap().remove(address);
+    }
+
+    /// Answers `method` with `handler` instead of the built-in behavior
+    pub(crate) fn on(
+        &self,
+        method: &str,
+        handler: impl Fn(&Value) -> MockReply + Send + Sync + 'static,
+    ) {
+        self.state
+            .handlers
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), Arc::new(handler));
+    }
+
+    /// Transactions submitted with `sendTransaction`, oldest first
+    pub(crate) fn sent_transactions(&self) -> Vec<Transaction> {
+        self.calls("sendTransaction")
+            .iter()
+            .map(|params| decode_transaction(&params[0]))
+            .collect()
+    }
+
+    /// Parameters of every request made for `method`, oldest first
+    pub(crate) fn calls(&self, method: &str) -> Vec<Value> {
+        self.state
+            .calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| name == method)
+            .map(|(_, params)| params.clone())
+            .collect()
+    }
+
+    pub(crate) fn call_count(&self, method: &str) -> usize {
+        self.calls(method).len()
+    }
+
+    /// A client talking to this node that gives up after the first failure
+    pub(crate) fn client(&self) -> Arc<MeteoraClient> {
+        Arc::new(self.client_builder().build().unwrap())
+    }
+
+    /// A builder for a client backed by this mock that doesn't retry
+    pub(crate) fn client_builder(&self) -> MeteoraClientBuilder {
+        MeteoraClient::builder(solana_network_sdk::types::Mode::MAIN)
+            .retry_config(RetryConfig {
+                max_retries: 0,
+                ..RetryConfig::default()
+            })
+            .rpc_client(self.rpc_client())
+    }
+
+    pub(crate) fn rpc_client(&self) -> Arc<RpcClient> {
+        Arc::new(RpcClient::new_sender(
+            self.clone(),
+            RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+        ))
+    }
+
+    fn reply(&self, method: &str, params: &Value) -> MockReply {
+        let handler = self.state.handlers.lock().unwrap().get(method).cloned();
+        if let Some(handler) = handler {
+            return handler(params);
+        }
+        self.default_reply(method, params)
+    }
+
+    /// The built-in answer to `method`, ignoring handlers set with `on`
+    fn default_reply(&self, method: &str, params: &Value) -> MockReply {
+        let accounts = self.state.accounts.lock().unwrap();
+        let context = json!({ "slot": MOCK_SLOT });
+        let ui_account = |address: &Pubkey, slice: Option<UiDataSliceConfig>| {
+            accounts.get(address).map(|account| {
+                encode_ui_account(address, account, UiAccountEncoding::Base64, None, slice)
+            })
+        };
+        match method {
+            "getAccountInfo" => {
+                let address = pubkey_param(&params[0]);
+                MockReply::Result(
+                    json!({ "context": context, "value": ui_account(&address, None) }),
+                )
+            }
+            "getMultipleAccounts" => {
+                let value: Vec<_> = params[0]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|address| ui_account(&pubkey_param(address), None))
+                    .collect();
+                MockReply::Result(json!({ "context": context, "value": value }))
+            }
+            "getProgramAccounts" => {
+                let program_id = pubkey_param(&params[0]);
+                let config = &params[1];
+                let filters: Vec<RpcFilterType> =
+                    serde_json::from_value(config["filters"].clone()).unwrap_or_default();
+                let slice: Option<UiDataSliceConfig> =
+                    serde_json::from_value(config["dataSlice"].clone()).unwrap_or_default();
+                let mut matches: Vec<_> = accounts
+                    .iter()
+                    .filter(|(_, account)| account.owner == program_id)
+                    .filter(|(_, account)| filters.iter().all(|f| filter_allows(f, &account.data)))
+                    .map(|(address, _)| {
+                        json!({
+                            "pubkey": address.to_string(),
+                            "account": ui_account(address, slice),
+                        })
+                    })
+                    .collect();
+                matches.sort_by_key(|keyed| keyed["pubkey"].as_str().unwrap().to_string());
+                MockReply::Result(json!(matches))
+            }
+            "getBalance" => {
+                let lamports = accounts
+                    .get(&pubkey_param(&params[0]))
+                    .map_or(0, |account| account.lamports);
+                MockReply::Result(json!({ "context": context, "value": lamports }))
+            }
+            "getSlot" => MockReply::Result(json!(MOCK_SLOT)),
+            "getLatestBlockhash" => {
+                // A new blockhash per request, so resent transactions differ
+                let count = self.call_count("getLatestBlockhash") as u8;
+                MockReply::Result(json!({
+                    "context": context,
+                    "value": {
+                        "blockhash": Hash::new_from_array([count; 32]).to_string(),
+                        "lastValidBlockHeight": MOCK_SLOT + 150,
+                    },
+                }))
+            }
+            "sendTransaction" => MockReply::Result(json!(
+                decode_transaction(&params[0]).signatures[0].to_string()
+            )),
+            "getSignatureStatuses" => {
+                let statuses = vec![Value::Null; params[0].as_array().unwrap().len()];
+                MockReply::Result(json!({ "context": context, "value": statuses }))
+            }
+            "isBlockhashValid" => MockReply::Result(json!({ "context": context, "value": true })),
+            "getSignaturesForAddress" => {
+                let address = pubkey_param(&params[0]);
+                let transactions = self.state.transactions.lock().unwrap();
+                let mut history: Vec<&StoredTransaction> = transactions
+                    .iter()
+                    .filter(|stored| stored.address == address)
+                    .collect();
+                history.sort_by_key(|stored| std::cmp::Reverse(stored.block_time));
+                let start = match params[1]["before"].as_str() {
+                    Some(before) => {
+                        history
+                            .iter()
+                            .position(|stored| stored.signature.to_string() == before)
+                            .unwrap()
+                            + 1
+                    }
+                    None => 0,
+                };
+                let limit = params[1]["limit"].as_u64().unwrap_or(1_000) as usize;
+                let page: Vec<Value> = history
+                    .iter()
+                    .skip(start)
+                    .take(limit)
+                    .map(|stored| {
+                        json!({
+                            "signature": stored.signature.to_string(),
+                            "slot": MOCK_SLOT,
+                            "err": null,
+                            "memo": null,
+                            "blockTime": stored.block_time,
+                            "confirmationStatus": "finalized",
+                        })
+                    })
+                    .collect();
+                MockReply::Result(json!(page))
+            }
+            "getTransaction" => {
+                let signature: Signature = params[0].as_str().unwrap().parse().unwrap();
+                let transactions = self.state.transactions.lock().unwrap();
+                let transaction = transactions
+                    .iter()
+                    .find(|stored| stored.signature == signature)
+                    .map(|stored| stored.transaction.clone());
+                MockReply::Result(transaction.unwrap_or(Value::Null))
+            }
+            "getMinimumBalanceForRentExemption" => {
+                let size = params[0].as_u64().unwrap() as usize;
+                MockReply::Result(json!(Rent::default().minimum_balance(size)))
+            }
+            _ => MockReply::Error(format!("{} is not mocked", method)),
+        }
+    }
+}
+
+#[async_trait]
+impl RpcSender for MockRpc {
+    async fn send(&self, request: RpcRequest, params: Value) -> Result<Value, ClientError> {
+        let method = request.to_string();
+        self.state
+            .calls
+            .lock()
+            .unwrap()
+            .push((method.clone(), params.clone()));
+        match self.reply(&method, &params) {
+            MockReply::Result(result) => Ok(result),
+            MockReply::Error(message) => Err(ClientErrorKind::Custom(message).into()),
+            MockReply::Hang => std::future::pending().await,
+            MockReply::Delayed(delay) => {
+                tokio::time::sleep(delay).await;
+                match self.default_reply(&method, &params) {
+                    MockReply::Result(result) => Ok(result),
+                    MockReply::Error(message) => Err(ClientErrorKind::Custom(message).into()),
+                    _ => unreachable!("built-in replies are immediate"),
+                }
+            }
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        MOCK_URL.to_string()
+    }
+}
+
+fn decode_transaction(value: &Value) -> Transaction {
+    let bytes = STANDARD.decode(value.as_str().unwrap()).unwrap();
+    bincode::deserialize(&bytes).unwrap()
+}
+
+/// A `getSignatureStatuses` reply for one successful transaction that has
+/// reached `commitment`, e.g. `"processed"` or `"confirmed"`
+pub(crate) fn signature_status(commitment: &str) -> MockReply {
+    MockReply::Result(json!({
+        "context": { "slot": MOCK_SLOT },
+        "value": [{
+            "slot": MOCK_SLOT,
+            "confirmations": if commitment == "finalized" { Value::Null } else { json!(0) },
+            "err": null,
+            "status": { "Ok": null },
+            "confirmationStatus": commitment,
+        }],
+    }))
+}
+
+fn pubkey_param(value: &Value) -> Pubkey {
+    value.as_str().unwrap().parse().unwrap()
+}
+
+fn filter_allows(filter: &RpcFilterType, data: &[u8]) -> bool {
+    match filter {
+        RpcFilterType::DataSize(size) => data.len() as u64 == *size,
+        RpcFilterType::Memcmp(memcmp) => memcmp.bytes_match(data),
+        RpcFilterType::TokenAccountState => true,
+    }
+}
+
+/// A `getTransaction` result in which a user swaps through `pool` at
+/// `block_time`, changing reserve A by `a_delta` and reserve B by `b_delta`
+/// base units
+fn swap_transaction(
+    pool: &PoolFixture,
+    signature: Signature,
+    block_time: i64,
+    a_delta: i64,
+    b_delta: i64,
+) -> Value {
+    const RESERVE_BEFORE: u64 = 1_000_000_000_000;
+    let balance = |index: u8, mint: &Pubkey, amount: u64| {
+        json!({
+            "accountIndex": index,
+            "mint": mint.to_string(),
+            "uiTokenAmount": {
+                "uiAmount": null,
+                "decimals": 6,
+                "amount": amount.to_string(),
+                "uiAmountString": "",
+            },
+        })
+    };
+    json!({
+        "slot": MOCK_SLOT,
+        "blockTime": block_time,
+        "transaction": {
+            "signatures": [signature.to_string()],
+            "message": {
+                "accountKeys": [
+                    Pubkey::new_unique().to_string(),
+                    pool.token_a_reserve.to_string(),
+                    pool.token_b_reserve.to_string(),
+                    crate::global::METEORA_PROGRAM_ID,
+                ],
+                "header": {
+                    "numRequiredSignatures": 1,
+                    "numReadonlySignedAccounts": 0,
+                    "numReadonlyUnsignedAccounts": 1,
+                },
+                "recentBlockhash": Hash::default().to_string(),
+                "instructions": [],
+            },
+        },
+        "meta": {
+            "err": null,
+            "status": { "Ok": null },
+            "fee": 5000,
+            "preBalances": [],
+            "postBalances": [],
+            "preTokenBalances": [
+                balance(1, &pool.token_a_mint, RESERVE_BEFORE),
+                balance(2, &pool.token_b_mint, RESERVE_BEFORE),
+            ],
+            "postTokenBalances": [
+                balance(1, &pool.token_a_mint, RESERVE_BEFORE.saturating_add_signed(a_delta)),
+                balance(2, &pool.token_b_mint, RESERVE_BEFORE.saturating_add_signed(b_delta)),
+            ],
+        },
+    })
+}
+
+/// An SPL Token mint account
+pub(crate) fn mint_account(decimals: u8, supply: u64) -> Account {
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            supply,
+            decimals,
+            is_initialized: true,
+            ..Mint::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_account(spl_token::id(), data)
+}
+
+/// An SPL Token account holding `amount` of `mint`
+pub(crate) fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_account(spl_token::id(), data)
+}
+
+/// A rent-exempt account owned by `owner`
+pub(crate) fn program_account(owner: Pubkey, data: Vec<u8>) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Pyth price account data publishing `price * 10^exponent` at `publish_slot`
+/// with a `Trading` status
+pub(crate) fn pyth_price_data(price: i64, exponent: i32, publish_slot: u64) -> Vec<u8> {
+    let mut data = vec![0u8; 240];
+    data[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    // Price account type
+    data[8..12].copy_from_slice(&3u32.to_le_bytes());
+    data[20..24].copy_from_slice(&exponent.to_le_bytes());
+    data[208..216].copy_from_slice(&price.to_le_bytes());
+    // Trading status
+    data[224..228].copy_from_slice(&1u32.to_le_bytes());
+    data[232..240].copy_from_slice(&publish_slot.to_le_bytes());
+    data
+}
+
+/// Addresses of a pool stored with `MockRpc::add_pool`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PoolFixture {
+    pub address: Pubkey,
+    pub token_a_mint: Pubkey,
+    pub token_b_mint: Pubkey,
+    pub token_a_reserve: Pubkey,
+    pub token_b_reserve: Pubkey,
+    pub lp_mint: Pubkey,
+    pub fee_account: Pubkey,
+}
+
+impl PoolFixture {
+    pub(crate) fn new(token_a_mint: Pubkey, token_b_mint: Pubkey) -> Self {
+        Self {
+            address: Pubkey::new_unique(),
+            token_a_mint,
+            token_b_mint,
+            token_a_reserve: Pubkey::new_unique(),
+            token_b_reserve: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            fee_account: Pubkey::new_unique(),
+        }
+    }
+
+    /// Raw `layout` pool account data with a `fee_numerator / fee_denominator`
+    /// trade fee on a constant-product curve
+    pub(crate) fn account_data(
+        &self,
+        layout: PoolLayout,
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> Vec<u8> {
+        let mut data = vec![0; layout.account_size()];
+        data[..8].copy_from_slice(&layout.discriminator());
+        for (offset, key) in [
+            (8, self.token_a_mint),
+            (40, self.token_b_mint),
+            (72, self.token_a_reserve),
+            (104, self.token_b_reserve),
+            (136, self.lp_mint),
+            (168, self.fee_account),
+        ] {
+            data[offset..offset + 32].copy_from_slice(key.as_ref());
+        }
+        let fee_offset = match layout {
+            PoolLayout::V1 => 200,
+            PoolLayout::V2 => 232,
+        };
+        data[fee_offset..fee_offset + 8].copy_from_slice(&fee_numerator.to_le_bytes());
+        data[fee_offset + 8..fee_offset + 16].copy_from_slice(&fee_denominator.to_le_bytes());
+        data
+    }
+}
+
+impl MockRpc {
+    /// Stores a 0.25% fee V1 pool of two 6 decimal tokens with the given reserves
+    ///
+    /// Mints that aren't stored yet are created. The reserves are owned by
+    /// the vaults of their mints, as on chain.
+    pub(crate) fn add_pool(
+        &self,
+        token_a_mint: Pubkey,
+        token_b_mint: Pubkey,
+        token_a_amount: u64,
+        token_b_amount: u64,
+    ) -> PoolFixture {
+        let pool = PoolFixture::new(token_a_mint, token_b_mint);
+        let program_id: Pubkey = crate::global::METEORA_PROGRAM_ID.parse().unwrap();
+        self.set_account(
+            pool.address,
+            program_account(program_id, pool.account_data(PoolLayout::V1, 25, 10_000)),
+        );
+        for mint in [token_a_mint, token_b_mint] {
+            let mut accounts = self.state.accounts.lock().unwrap();
+            accounts
+                .entry(mint)
+                .or_insert_with(|| mint_account(6, 1_000_000_000_000));
+        }
+        self.set_account(
+            pool.token_a_reserve,
+            token_account(token_a_mint, vault_address(&token_a_mint), token_a_amount),
+        );
+        self.set_account(
+            pool.token_b_reserve,
+            token_account(token_b_mint, vault_address(&token_b_mint), token_b_amount),
+        );
+        self.set_account(pool.lp_mint, mint_account(6, 1_000_000));
+        pool
+    }
+
+    /// Stores a swap through `pool` at `block_time` in the pool's history,
+    /// see `swap_transaction`
+    pub(crate) fn add_swap(
+        &self,
+        pool: &PoolFixture,
+        block_time: i64,
+        a_delta: i64,
+        b_delta: i64,
+    ) -> Signature {
+        let signature = Signature::new_unique();
+        self.state
+            .transactions
+            .lock()
+            .unwrap()
+            .push(StoredTransaction {
+                signature,
+                block_time,
+                address: pool.address,
+                transaction: swap_transaction(pool, signature, block_time, a_delta, b_delta),
+            });
+        signature
+    }
+
+    /// Stores the other accounts a swap through `pool` references: its fee
+    /// account, the vaults of both mints with their token accounts and LP
+    /// mints, the pool's vault LP accounts and both protocol fee accounts
+    pub(crate) fn add_swap_accounts(&self, pool: &PoolFixture) {
+        let vault_program: Pubkey = crate::global::METEORA_VAULT_PROGRAM_ID.parse().unwrap();
+        self.set_account(
+            pool.fee_account,
+            token_account(pool.token_a_mint, pool.address, 0),
+        );
+        for mint in [pool.token_a_mint, pool.token_b_mint] {
+            let vault = vault_address(&mint);
+            let lp_mint = pool::vault_lp_mint(&vault);
+            self.set_account(vault, program_account(vault_program, vec![0; 8]));
+            self.set_account(
+                pool::vault_token_account(&vault),
+                token_account(mint, vault, 0),
+            );
+            self.set_account(lp_mint, mint_account(6, 0));
+            self.set_account(
+                pool::pool_vault_lp_account(&vault, &pool.address),
+                token_account(lp_mint, pool.address, 0),
+            );
+            self.set_account(
+                pool::protocol_fee_account(&mint, &pool.address),
+                token_account(mint, pool.address, 0),
+            );
+        }
+    }
+}
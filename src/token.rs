@@ -1,11 +1,43 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use crate::global::METAPLEX_PROGRAM_ID;
-use crate::types::{TokenInfo, TokenMetadata};
+use crate::types::{Creator, TokenInfo, TokenMetadata};
 use crate::{MeteoraClient, MeteoraError};
+use borsh::BorshDeserialize;
+use futures::future::join_all;
+use solana_sdk::account::Account;
 use solana_sdk::program_pack::Pack;
 use solana_sdk::pubkey::Pubkey;
-use spl_token::state::Mint;
+use spl_token::state::{Account as TokenAccount, Mint};
+use spl_token_2022_interface::extension::StateWithExtensions;
+
+/// Leading fields of a Metaplex metadata account, in borsh order
+///
+/// Only the fields up to `creators` are read, anything after them is ignored.
+#[derive(BorshDeserialize)]
+struct MetadataAccount {
+    _key: u8,
+    _update_authority: [u8; 32],
+    _mint: [u8; 32],
+    data: MetadataData,
+}
+
+#[derive(BorshDeserialize)]
+struct MetadataData {
+    name: String,
+    symbol: String,
+    uri: String,
+    seller_fee_basis_points: u16,
+    creators: Option<Vec<MetadataCreator>>,
+}
+
+#[derive(BorshDeserialize)]
+struct MetadataCreator {
+    address: [u8; 32],
+    verified: bool,
+    share: u8,
+}
 
 /// Manages token-related operations including fetching token information,
 /// holder counts, and metadata.
@@ -17,12 +49,15 @@ impl TokenManager {
     /// Creates a new TokenManager instance.
     ///
     /// # Example
-    /// ```
-    /// use meteora_client::MeteoraClient;
-    /// use meteora_client::token::TokenManager;
+    /// ```no_run
+    /// use meteora_sdk::MeteoraClient;
+    /// use meteora_sdk::token::TokenManager;
     ///
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// # fn main() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN)?;
     /// let token_manager = TokenManager::new(client);
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn new(client: MeteoraClient) -> Self {
         Self { client }
@@ -34,33 +69,113 @@ impl TokenManager {
     /// mint - The mint address of the token
     ///
     /// # Example
-    /// ```
-    /// use solana_sdk::pubkey::Pubkey;
-    /// use meteora_client::token::TokenManager;
-    /// use meteora_client::MeteoraClient;
+    /// ```no_run
+    /// use solana_sdk::pubkey;
+    /// use meteora_sdk::token::TokenManager;
+    /// use meteora_sdk::MeteoraClient;
     ///
     /// #[tokio::main]
     /// async fn main() {
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN).unwrap();
     /// let token_manager = TokenManager::new(client);
-    /// let usdc_mint = Pubkey::new_from_array([/* USDC mint address */]);
+    /// let usdc_mint = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
     /// match token_manager.get_token_info(&usdc_mint).await {
     ///     Ok(token_info) => println!("Token decimals: {}", token_info.decimals),
-    ///     Err(e) => eprintln!("Error fetching token info: {}", e),
+    ///     Err(e) => eprintln!("Error fetching token info: {:?}", e),
     /// }
     /// }
     /// ```
     pub async fn get_token_info(&self, mint: &Pubkey) -> Result<TokenInfo, MeteoraError> {
-        let mint_account_data = self.client.get_account_data(mint).await?;
-        let (decimals, supply) = self.parse_mint_account(&mint_account_data)?;
-        let holder_count = self.get_holder_count(mint).await?;
-        let metadata = self.get_token_metadata(mint).await.ok();
+        let mint_account = self.client.get_account(mint).await?;
+        let holder_count = self.get_holder_count(mint).await;
+        let metadata_address = self.get_metadata_account(mint);
+        let metadata_account = self.client.get_account(&metadata_address).await.ok();
+        self.token_info_from_accounts(mint, Some(mint_account), metadata_account, holder_count)
+    }
+
+    /// Fetches information about a batch of tokens.
+    ///
+    /// Mint and metadata accounts are each read in one batched request, and
+    /// holder counts are fetched concurrently. Results keep the order of
+    /// `mints`, and a failure for one mint doesn't affect the others.
+    ///
+    /// # Params
+    /// mints - The mint addresses of the tokens
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::{MeteoraClient, token::TokenManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let token_manager = TokenManager::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let (mint_a, mint_b) = (spl_token::native_mint::ID, spl_token::native_mint::ID);
+    /// let infos = token_manager.get_token_infos(&[mint_a, mint_b]).await;
+    /// for (mint, info) in infos {
+    ///     match info {
+    ///         Ok(info) => println!("{}: {} decimals", mint, info.decimals),
+    ///         Err(e) => eprintln!("{}: {:?}", mint, e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_token_infos(
+        &self,
+        mints: &[Pubkey],
+    ) -> Vec<(Pubkey, Result<TokenInfo, MeteoraError>)> {
+        let metadata_addresses: Vec<Pubkey> = mints
+            .iter()
+            .map(|mint| self.get_metadata_account(mint))
+            .collect();
+        let (mint_accounts, metadata_accounts, holder_counts) = tokio::join!(
+            self.client.get_multiple_accounts(mints),
+            self.client.get_multiple_accounts(&metadata_addresses),
+            join_all(mints.iter().map(|mint| self.get_holder_count(mint))),
+        );
+        let mint_accounts = match mint_accounts {
+            Ok(accounts) => accounts,
+            Err(e) => return mints.iter().map(|mint| (*mint, Err(e.clone()))).collect(),
+        };
+        let metadata_accounts = metadata_accounts.unwrap_or_else(|e| {
+            log::warn!("Failed to fetch token metadata: {:?}", e);
+            vec![None; mints.len()]
+        });
+        mints
+            .iter()
+            .zip(mint_accounts)
+            .zip(metadata_accounts)
+            .zip(holder_counts)
+            .map(|(((mint, mint_account), metadata_account), holder_count)| {
+                let info = self.token_info_from_accounts(
+                    mint,
+                    mint_account,
+                    metadata_account,
+                    holder_count,
+                );
+                (*mint, info)
+            })
+            .collect()
+    }
+
+    fn token_info_from_accounts(
+        &self,
+        mint: &Pubkey,
+        mint_account: Option<Account>,
+        metadata_account: Option<Account>,
+        holder_count: Result<u64, MeteoraError>,
+    ) -> Result<TokenInfo, MeteoraError> {
+        let mint_account = mint_account
+            .ok_or_else(|| MeteoraError::AccountNotFound(format!("Mint {} not found", mint)))?;
+        let mint_state = unpack_mint(mint, &mint_account)?;
         Ok(TokenInfo {
             mint: *mint,
-            decimals,
-            supply,
-            holder_count,
-            metadata,
+            decimals: mint_state.decimals,
+            supply: mint_state.supply,
+            holder_count: holder_count?,
+            metadata: metadata_account
+                .and_then(|account| self.parse_metadata_account(&account.data).ok()),
+            mint_authority: mint_state.mint_authority.into(),
+            freeze_authority: mint_state.freeze_authority.into(),
         })
     }
 
@@ -70,46 +185,126 @@ impl TokenManager {
     /// mint - The mint address of the token
     ///
     /// # Example
-    /// ```
-    /// use solana_sdk::pubkey::Pubkey;
-    /// use meteora_client::token::TokenManager;
-    /// use meteora_client::MeteoraClient;
+    /// ```no_run
+    /// use solana_sdk::pubkey;
+    /// use meteora_sdk::token::TokenManager;
+    /// use meteora_sdk::MeteoraClient;
     ///
     /// #[tokio::main]
     /// async fn main() {
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN).unwrap();
     /// let token_manager = TokenManager::new(client);
-    /// let mint = Pubkey::new_from_array([/* token mint address */]);
+    /// let mint = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
     /// match token_manager.get_holder_count(&mint).await {
     ///     Ok(count) => println!("Token has {} holders", count),
-    ///     Err(e) => eprintln!("Error fetching holder count: {}", e),
+    ///     Err(e) => eprintln!("Error fetching holder count: {:?}", e),
     /// }
     /// }
     /// ```
     pub async fn get_holder_count(&self, mint: &Pubkey) -> Result<u64, MeteoraError> {
-        let accounts = self.client.get_spl_token_accounts_by_mint(mint).await?;
+        let accounts = self.client.get_spl_token_account_keys_by_mint(mint).await?;
         Ok(accounts.len() as u64)
     }
 
-    /// Fetches token metadata from the Metaplex metadata account.
+    /// Fetches the largest holders of a token, largest first.
+    ///
+    /// Balances of token accounts with the same owner are added together, and
+    /// empty accounts are skipped.
+    ///
+    /// # Params
+    /// mint - The mint address of the token
+    /// limit - Maximum number of holders to return
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::{MeteoraClient, token::TokenManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let token_manager = TokenManager::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mint = spl_token::native_mint::ID;
+    /// let holders = token_manager.get_top_holders(&mint, 10).await?;
+    /// for (owner, balance) in holders {
+    ///     println!("{}: {}", owner, balance);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_top_holders(
+        &self,
+        mint: &Pubkey,
+        limit: usize,
+    ) -> Result<Vec<(Pubkey, u64)>, MeteoraError> {
+        let accounts = self.client.get_spl_token_accounts_by_mint(mint).await?;
+        let mut balances: HashMap<Pubkey, u64> = HashMap::new();
+        for (address, account) in &accounts {
+            let token_account = unpack_token_account(address, account)?;
+            if token_account.amount > 0 {
+                let balance = balances.entry(token_account.owner).or_default();
+                *balance = balance.saturating_add(token_account.amount);
+            }
+        }
+        let mut holders: Vec<(Pubkey, u64)> = balances.into_iter().collect();
+        holders.sort_by_key(|(_, balance)| std::cmp::Reverse(*balance));
+        holders.truncate(limit);
+        Ok(holders)
+    }
+
+    /// Fetches the fraction of a token's supply held by its `top_n` largest holders.
     ///
     /// # Params
     /// mint - The mint address of the token
+    /// top_n - Number of largest holders to include
     ///
     /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::{MeteoraClient, token::TokenManager};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let token_manager = TokenManager::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mint = spl_token::native_mint::ID;
+    /// let concentration = token_manager.top_holder_concentration(&mint, 10).await?;
+    /// println!("Top 10 hold {:.1}% of supply", concentration * 100.0);
+    /// # Ok(())
+    /// # }
     /// ```
-    /// use solana_sdk::pubkey::Pubkey;
-    /// use meteora_client::token::TokenManager;
-    /// use meteora_client::MeteoraClient;
+    pub async fn top_holder_concentration(
+        &self,
+        mint: &Pubkey,
+        top_n: usize,
+    ) -> Result<f64, MeteoraError> {
+        let mint_account = self.client.get_account(mint).await?;
+        let supply = unpack_mint(mint, &mint_account)?.supply;
+        if supply == 0 {
+            return Ok(0.0);
+        }
+        let held: u128 = self
+            .get_top_holders(mint, top_n)
+            .await?
+            .iter()
+            .map(|(_, balance)| *balance as u128)
+            .sum();
+        Ok(held as f64 / supply as f64)
+    }
+
+    /// Fetches token metadata from the Metaplex metadata account.
+    ///
+    /// # Params
+    /// mint - The mint address of the token
+    ///
+    /// # Example
+    /// ```no_run
+    /// use solana_sdk::pubkey;
+    /// use meteora_sdk::token::TokenManager;
+    /// use meteora_sdk::MeteoraClient;
     ///
     /// #[tokio::main]
     /// async fn main() {
-    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN);
+    /// let client = MeteoraClient::new(solana_network_sdk::types::Mode::MAIN).unwrap();
     /// let token_manager = TokenManager::new(client);
-    /// let mint = Pubkey::new_from_array([/* token mint address */]);
+    /// let mint = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
     /// match token_manager.get_token_metadata(&mint).await {
     ///     Ok(metadata) => println!("Token name: {}", metadata.name),
-    ///     Err(e) => eprintln!("Error fetching metadata: {}", e),
+    ///     Err(e) => eprintln!("Error fetching metadata: {:?}", e),
     /// }
     /// }
     /// ```
@@ -123,12 +318,6 @@ impl TokenManager {
         }
     }
 
-    fn parse_mint_account(&self, data: &[u8]) -> Result<(u8, u64), MeteoraError> {
-        let token_mint =
-            Mint::unpack(data).map_err(|e| MeteoraError::DeserializationError(e.to_string()))?;
-        Ok((token_mint.decimals, token_mint.supply))
-    }
-
     fn get_metadata_account(&self, mint: &Pubkey) -> Pubkey {
         let metaplex_program_id =
             Pubkey::from_str(METAPLEX_PROGRAM_ID).expect("Failed to parse Metaplex program ID");
@@ -137,30 +326,379 @@ impl TokenManager {
     }
 
     fn parse_metadata_account(&self, data: &[u8]) -> Result<TokenMetadata, MeteoraError> {
-        if data.len() < 100 {
-            return Err(MeteoraError::InvalidAccountData);
-        }
-        let name_start = 1 + 32 + 32; // key + update auth + mint
-        let name_length = data[name_start] as usize;
-        let name_end = name_start + 1 + name_length;
-        if name_end >= data.len() {
-            return Err(MeteoraError::InvalidAccountData);
+        let account = MetadataAccount::deserialize(&mut &data[..])
+            .map_err(|e| MeteoraError::DeserializationError(e.to_string()))?;
+        let metadata = account.data;
+        Ok(TokenMetadata {
+            name: trim_null_padding(&metadata.name),
+            symbol: trim_null_padding(&metadata.symbol),
+            uri: trim_null_padding(&metadata.uri),
+            seller_fee_basis_points: metadata.seller_fee_basis_points,
+            creators: metadata
+                .creators
+                .unwrap_or_default()
+                .into_iter()
+                .map(|creator| Creator {
+                    address: Pubkey::new_from_array(creator.address),
+                    verified: creator.verified,
+                    share: creator.share,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Metaplex pads names, symbols and URIs to a fixed length with null bytes
+fn trim_null_padding(value: &str) -> String {
+    value.trim_end_matches('\0').to_string()
+}
+
+/// Unpacks a mint owned by either the SPL Token or the Token-2022 program
+///
+/// Token-2022 mints start with the SPL Token layout and append extensions, so
+/// after validating them as Token-2022 state only the base is decoded.
+///
+/// # Params
+/// address - Address of the mint, used in errors
+/// account - The fetched mint account
+///
+/// # Example
+/// ```no_run
+/// # use meteora_sdk::token::unpack_mint;
+/// # use meteora_sdk::MeteoraClient;
+/// # use solana_network_sdk::types::Mode;
+/// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+/// # let client = MeteoraClient::new(Mode::MAIN)?;
+/// # let mint = spl_token::native_mint::ID;
+/// let account = client.get_account(&mint).await?;
+/// let decimals = unpack_mint(&mint, &account)?.decimals;
+/// # Ok(())
+/// # }
+/// ```
+pub fn unpack_mint(address: &Pubkey, account: &Account) -> Result<Mint, MeteoraError> {
+    if account.owner == spl_token::id() {
+        Ok(Mint::unpack(&account.data)?)
+    } else if account.owner == spl_token_2022_interface::id() {
+        StateWithExtensions::<spl_token_2022_interface::state::Mint>::unpack(&account.data)?;
+        Ok(Mint::unpack(&account.data[..Mint::LEN])?)
+    } else {
+        Err(MeteoraError::InvalidAccountOwner(format!(
+            "Mint {} is owned by {}, not a token program",
+            address, account.owner
+        )))
+    }
+}
+
+/// Unpacks a token account owned by either the SPL Token or the Token-2022 program
+///
+/// # Params
+/// address - Address of the token account, used in errors
+/// account - The fetched token account
+///
+/// # Example
+/// ```no_run
+/// # use meteora_sdk::token::unpack_token_account;
+/// # use meteora_sdk::MeteoraClient;
+/// # use solana_network_sdk::types::Mode;
+/// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+/// # let client = MeteoraClient::new(Mode::MAIN)?;
+/// # let token_account = solana_sdk::pubkey::Pubkey::new_unique();
+/// let account = client.get_account(&token_account).await?;
+/// let balance = unpack_token_account(&token_account, &account)?.amount;
+/// # Ok(())
+/// # }
+/// ```
+pub fn unpack_token_account(
+    address: &Pubkey,
+    account: &Account,
+) -> Result<TokenAccount, MeteoraError> {
+    if account.owner == spl_token::id() {
+        Ok(TokenAccount::unpack(&account.data)?)
+    } else if account.owner == spl_token_2022_interface::id() {
+        StateWithExtensions::<spl_token_2022_interface::state::Account>::unpack(&account.data)?;
+        Ok(TokenAccount::unpack(&account.data[..TokenAccount::LEN])?)
+    } else {
+        Err(MeteoraError::InvalidAccountOwner(format!(
+            "Token account {} is owned by {}, not a token program",
+            address, account.owner
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{MockRpc, mint_account, program_account, token_account};
+    use spl_token_2022_interface::extension::{
+        BaseState, BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+        transfer_fee::{TransferFeeAmount, TransferFeeConfig},
+    };
+
+    /// Appends a Borsh string padded with nulls to `len` bytes, as Metaplex stores it
+    fn push_padded(data: &mut Vec<u8>, value: &str, len: usize) {
+        data.extend_from_slice(&(len as u32).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+        data.resize(data.len() + len - value.len(), 0);
+    }
+
+    /// A Metaplex metadata account as stored on chain, with one verified creator
+    fn metadata_account_data(mint: &Pubkey, creator: &Pubkey) -> Vec<u8> {
+        let mut data = vec![4];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(mint.as_ref());
+        push_padded(&mut data, "Wrapped SOL", 32);
+        push_padded(&mut data, "SOL", 10);
+        push_padded(&mut data, "https://example.com/sol.json", 200);
+        data.extend_from_slice(&500u16.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(creator.as_ref());
+        data.extend_from_slice(&[1, 100]);
+        // primary_sale_happened, is_mutable, then the optional trailing fields
+        data.extend_from_slice(&[0, 1]);
+        data.resize(679, 0);
+        data
+    }
+
+    #[tokio::test]
+    async fn metadata_strings_are_returned_without_padding() {
+        let rpc = MockRpc::new();
+        let token_manager = TokenManager::new(rpc.client_builder().build().unwrap());
+        let (mint, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let metaplex_program = Pubkey::from_str(METAPLEX_PROGRAM_ID).unwrap();
+        rpc.set_account(
+            token_manager.get_metadata_account(&mint),
+            program_account(metaplex_program, metadata_account_data(&mint, &creator)),
+        );
+
+        let metadata = token_manager.get_token_metadata(&mint).await.unwrap();
+        assert_eq!(metadata.name, "Wrapped SOL");
+        assert_eq!(metadata.symbol, "SOL");
+        assert_eq!(metadata.uri, "https://example.com/sol.json");
+        assert_eq!(metadata.seller_fee_basis_points, 500);
+        assert_eq!(metadata.creators.len(), 1);
+        assert_eq!(metadata.creators[0].address, creator);
+        assert!(metadata.creators[0].verified);
+        assert_eq!(metadata.creators[0].share, 100);
+    }
+
+    #[tokio::test]
+    async fn top_holders_are_sorted_by_balance_without_empty_accounts() {
+        let rpc = MockRpc::new();
+        let token_manager = TokenManager::new(rpc.client_builder().build().unwrap());
+        let mint = Pubkey::new_unique();
+        rpc.set_account(mint, mint_account(6, 1_000));
+        let (small, whale, split, empty) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        for (owner, amount) in [
+            (small, 50),
+            (whale, 500),
+            (split, 150),
+            (split, 150),
+            (empty, 0),
+        ] {
+            rpc.set_account(Pubkey::new_unique(), token_account(mint, owner, amount));
         }
-        let name = String::from_utf8_lossy(&data[name_start + 1..name_end]).to_string();
-        let symbol_start = name_end + 4; // +4 for URI length prefix
-        let symbol_length = data[symbol_start] as usize;
-        let symbol_end = symbol_start + 1 + symbol_length;
-        if symbol_end >= data.len() {
-            return Err(MeteoraError::InvalidAccountData);
+        // Accounts of another mint are not counted
+        rpc.set_account(
+            Pubkey::new_unique(),
+            token_account(Pubkey::new_unique(), small, 10_000),
+        );
+
+        let holders = token_manager.get_top_holders(&mint, 10).await.unwrap();
+        assert_eq!(holders, vec![(whale, 500), (split, 300), (small, 50)]);
+        let top_two = token_manager.get_top_holders(&mint, 2).await.unwrap();
+        assert_eq!(top_two, vec![(whale, 500), (split, 300)]);
+
+        let concentration = token_manager
+            .top_holder_concentration(&mint, 2)
+            .await
+            .unwrap();
+        assert!((concentration - 0.8).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn holder_count_requests_keys_without_account_data() {
+        let rpc = MockRpc::new();
+        let token_manager = TokenManager::new(rpc.client_builder().build().unwrap());
+        let mint = Pubkey::new_unique();
+        for amount in [1, 2, 3] {
+            rpc.set_account(
+                Pubkey::new_unique(),
+                token_account(mint, Pubkey::new_unique(), amount),
+            );
         }
-        let symbol = String::from_utf8_lossy(&data[symbol_start + 1..symbol_end]).to_string();
-        let uri_start = symbol_end + 4; // +4 for URI length prefix
-        let uri_length = data[uri_start] as usize;
-        let uri_end = uri_start + 1 + uri_length;
-        if uri_end > data.len() {
-            return Err(MeteoraError::InvalidAccountData);
+
+        assert_eq!(token_manager.get_holder_count(&mint).await.unwrap(), 3);
+        let calls = rpc.calls("getProgramAccounts");
+        assert!(!calls.is_empty());
+        for params in calls {
+            assert_eq!(params[1]["dataSlice"]["offset"], 0);
+            assert_eq!(params[1]["dataSlice"]["length"], 0);
         }
-        let uri = String::from_utf8_lossy(&data[uri_start + 1..uri_end]).to_string();
-        Ok(TokenMetadata { name, symbol, uri })
+    }
+
+    #[tokio::test]
+    async fn token_infos_keep_input_order_and_fail_per_mint() {
+        let rpc = MockRpc::new();
+        let token_manager = TokenManager::new(rpc.client_builder().build().unwrap());
+        let (listed, missing, bare) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        rpc.set_account(listed, mint_account(9, 5_000));
+        rpc.set_account(bare, mint_account(6, 7_000));
+        let metaplex_program = Pubkey::from_str(METAPLEX_PROGRAM_ID).unwrap();
+        rpc.set_account(
+            token_manager.get_metadata_account(&listed),
+            program_account(
+                metaplex_program,
+                metadata_account_data(&listed, &Pubkey::new_unique()),
+            ),
+        );
+        rpc.set_account(
+            Pubkey::new_unique(),
+            token_account(listed, Pubkey::new_unique(), 5_000),
+        );
+
+        let infos = token_manager
+            .get_token_infos(&[listed, missing, bare])
+            .await;
+        let mints: Vec<Pubkey> = infos.iter().map(|(mint, _)| *mint).collect();
+        assert_eq!(mints, vec![listed, missing, bare]);
+
+        let listed_info = infos[0].1.as_ref().unwrap();
+        assert_eq!(listed_info.decimals, 9);
+        assert_eq!(listed_info.holder_count, 1);
+        assert_eq!(listed_info.metadata.as_ref().unwrap().symbol, "SOL");
+        assert!(matches!(infos[1].1, Err(MeteoraError::AccountNotFound(_))));
+        let bare_info = infos[2].1.as_ref().unwrap();
+        assert_eq!(bare_info.supply, 7_000);
+        assert_eq!(bare_info.holder_count, 0);
+        assert!(bare_info.metadata.is_none());
+
+        // Mints and metadata are each read in a single request
+        assert_eq!(rpc.call_count("getMultipleAccounts"), 2);
+        assert_eq!(rpc.call_count("getAccountInfo"), 0);
+    }
+
+    #[tokio::test]
+    async fn token_info_reports_mint_and_freeze_authorities() {
+        use solana_sdk::program_option::COption;
+        let rpc = MockRpc::new();
+        let token_manager = TokenManager::new(rpc.client_builder().build().unwrap());
+        let (fixed, frozen, freezer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        rpc.set_account(fixed, mint_account(6, 2_500_000));
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                supply: 1_000,
+                decimals: 2,
+                is_initialized: true,
+                freeze_authority: COption::Some(freezer),
+                ..Mint::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        rpc.set_account(frozen, program_account(spl_token::id(), data));
+
+        let fixed_info = token_manager.get_token_info(&fixed).await.unwrap();
+        assert_eq!(fixed_info.mint_authority, None);
+        assert_eq!(fixed_info.freeze_authority, None);
+        assert_eq!(fixed_info.ui_supply(), 2.5);
+
+        let frozen_info = token_manager.get_token_info(&frozen).await.unwrap();
+        assert_eq!(frozen_info.mint_authority, None);
+        assert_eq!(frozen_info.freeze_authority, Some(freezer));
+        assert_eq!(frozen_info.ui_supply(), 10.0);
+    }
+
+    /// Token-2022 data for `base` followed by the `extension_type` extension,
+    /// which `init_extension` initializes
+    fn token_2022_data<S: BaseState + Pack>(
+        base: S,
+        extension_type: ExtensionType,
+        init_extension: impl FnOnce(&mut StateWithExtensionsMut<S>),
+    ) -> Vec<u8> {
+        let len = ExtensionType::try_calculate_account_len::<S>(&[extension_type]).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<S>::unpack_uninitialized(&mut data).unwrap();
+        init_extension(&mut state);
+        state.base = base;
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    #[test]
+    fn token_2022_transfer_fee_mint_and_account_unpack() {
+        use spl_token_2022_interface::state::{
+            Account as Account2022, AccountState as AccountState2022, Mint as Mint2022,
+        };
+        let token_2022 = spl_token_2022_interface::id();
+        let mint_address = Pubkey::new_unique();
+        let mint_data = token_2022_data(
+            Mint2022 {
+                decimals: 9,
+                supply: 5_000_000,
+                is_initialized: true,
+                ..Mint2022::default()
+            },
+            ExtensionType::TransferFeeConfig,
+            |state| {
+                let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+                config.newer_transfer_fee.transfer_fee_basis_points = 100.into();
+                config.newer_transfer_fee.maximum_fee = 1_000_000.into();
+            },
+        );
+        assert!(mint_data.len() > Mint::LEN);
+        let mint = unpack_mint(&mint_address, &program_account(token_2022, mint_data)).unwrap();
+        assert_eq!(mint.decimals, 9);
+        assert_eq!(mint.supply, 5_000_000);
+
+        let owner = Pubkey::new_unique();
+        let account_data = token_2022_data(
+            Account2022 {
+                mint: mint_address,
+                owner,
+                amount: 1_234,
+                state: AccountState2022::Initialized,
+                ..Account2022::default()
+            },
+            ExtensionType::TransferFeeAmount,
+            |state| {
+                state
+                    .init_extension::<TransferFeeAmount>(true)
+                    .unwrap()
+                    .withheld_amount = 12.into();
+            },
+        );
+        let account = unpack_token_account(
+            &Pubkey::new_unique(),
+            &program_account(token_2022, account_data),
+        )
+        .unwrap();
+        assert_eq!(account.mint, mint_address);
+        assert_eq!(account.owner, owner);
+        assert_eq!(account.amount, 1_234);
+
+        // The same data under an unrelated owner is rejected
+        assert!(matches!(
+            unpack_mint(
+                &mint_address,
+                &program_account(Pubkey::new_unique(), vec![0; 82])
+            ),
+            Err(MeteoraError::InvalidAccountOwner(_))
+        ));
     }
 }
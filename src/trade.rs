@@ -1,46 +1,461 @@
-use std::{collections::HashMap, str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc};
 
 use crate::{
-    MeteoraClient, MeteoraError,
-    global::METEORA_PROGRAM_ID,
-    pool::PoolManager,
-    types::{PoolInfo, SwapSimulation, TradeParams, TradeQuote},
+    MeteoraClient, MeteoraError, dlmm,
+    global::{COMPUTE_BUDGET_PROGRAM_ID, METEORA_PROGRAM_ID, METEORA_VAULT_PROGRAM_ID, USDC_MINT},
+    math,
+    pool::{self, PoolManager, best_pool},
+    price::{decode_pool_swap, meteora_swap_pools},
+    token::unpack_token_account,
+    types::{
+        ConfirmConfig, CurveType, DecodedSwap, DlmmPool, HopInfo, MatchedPool, PoolInfo,
+        PriorityFeeConfig, SplitAllocation, SplitQuote, SwapSimulation, TradeParams, TradeQuote,
+        ValidationIssue, ValidationReport,
+    },
+};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_config::{
+    RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
 };
 use solana_sdk::{
+    account::Account,
+    hash::Hash,
     instruction::{AccountMeta, Instruction},
+    message::{AddressLookupTableAccount, VersionedMessage, v0},
     program_pack::Pack,
     pubkey::Pubkey,
-    signature::Keypair,
+    signature::{Keypair, Signature},
     signer::Signer,
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
-use solana_transaction::Message;
+use solana_transaction::{Message, versioned::VersionedTransaction};
 use spl_associated_token_account::{
-    get_associated_token_address, instruction::create_associated_token_account,
+    get_associated_token_address,
+    instruction::{create_associated_token_account, create_associated_token_account_idempotent},
 };
 
+/// Seconds before a trade's deadline within which its quote is checked
+/// against fresh reserves before sending
+const DEADLINE_REQUOTE_WINDOW_SECS: i64 = 10;
+
+/// Highest slippage a trade may allow (50%)
+const MAX_SLIPPAGE_BPS: u16 = 5000;
+
+/// Default margin `suggest_slippage_bps` adds on top of the expected price impact
+const DEFAULT_SLIPPAGE_BUFFER_BPS: u16 = 50;
+
+/// Highest referral fee a trade may pay (5%)
+const MAX_REFERRAL_FEE_BPS: u16 = 500;
+
+/// Maximum number of intermediate mints tried when routing through two pools
+const MAX_INTERMEDIATE_CANDIDATES: usize = 8;
+
+/// Anchor discriminator of the swap instruction (`sha256("global:swap")[..8]`)
+pub const SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+
+/// Maximum number of direct pools a split swap is spread across
+const MAX_SPLIT_POOLS: usize = 3;
+
+/// Number of equal chunks a split swap's input is allocated in
+const SPLIT_CHUNKS: u64 = 20;
+
+/// Percentile of recent prioritization fees used by `estimate_priority_fee`
+const PRIORITY_FEE_PERCENTILE: usize = 75;
+
 /// Main trade execution handler for Meteora DEX
 pub struct Trade {
     client: Arc<MeteoraClient>,
     pool_manager: PoolManager,
-    simulation_cache: HashMap<Pubkey, SwapSimulation>,
+    confirm_config: ConfirmConfig,
+    output_guard_program: Option<Pubkey>,
+    priority_fee_config: PriorityFeeConfig,
+    wrap_sol: bool,
+    slippage_buffer_bps: u16,
+    #[cfg(feature = "jito")]
+    jito_block_engine_url: String,
 }
 
 impl Trade {
     /// Creates a new Trade instance
     pub fn new(client: Arc<MeteoraClient>) -> Self {
+        Self::new_with_confirm_config(client, ConfirmConfig::default())
+    }
+
+    /// Creates a new Trade instance with custom submission and confirmation settings
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::ConfirmConfig;
+    /// # use solana_commitment_config::CommitmentConfig;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// let trade = Trade::new_with_confirm_config(
+    ///     client,
+    ///     ConfirmConfig {
+    ///         max_resends: 5,
+    ///         timeout_secs: 60,
+    ///         commitment: CommitmentConfig::finalized(),
+    ///         ..ConfirmConfig::default()
+    ///     },
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_confirm_config(
+        client: Arc<MeteoraClient>,
+        confirm_config: ConfirmConfig,
+    ) -> Self {
         let pool_manager = PoolManager::new(client.clone());
         Self {
             client,
             pool_manager,
-            simulation_cache: HashMap::new(),
+            confirm_config,
+            output_guard_program: None,
+            priority_fee_config: PriorityFeeConfig::default(),
+            wrap_sol: true,
+            slippage_buffer_bps: DEFAULT_SLIPPAGE_BUFFER_BPS,
+            #[cfg(feature = "jito")]
+            jito_block_engine_url: crate::jito::DEFAULT_JITO_BLOCK_ENGINE_URL.to_string(),
         }
     }
 
-    /// Gets a validated trade quote with comprehensive checks
+    /// Enables or disables the on-chain minimum output guard
+    ///
+    /// When set, every swap transaction gets an extra instruction for
+    /// `guard_program` that fails unless the output token account's balance
+    /// grew by at least `min_amount_out`, independently of the Meteora
+    /// program's own slippage check. The guard reads a single token account
+    /// and typically costs around 2,000-5,000 additional compute units,
+    /// depending on the guard program.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let guard_program_id = solana_sdk::pubkey::Pubkey::new_unique();
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// let mut trade = Trade::new(client);
+    /// trade.set_output_guard_program(Some(guard_program_id));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_output_guard_program(&mut self, guard_program: Option<Pubkey>) {
+        self.output_guard_program = guard_program;
+    }
+
+    /// Enables or disables automatic wrapping of native SOL, on by default
+    ///
+    /// When enabled and a swap's input mint is the native mint, the input
+    /// amount is moved from the user's lamports into their WSOL account before
+    /// the swap; when the output mint is the native mint, the WSOL account is
+    /// closed afterwards so the proceeds arrive as lamports. In both cases the
+    /// WSOL account is closed, so any WSOL already held is unwrapped too.
+    /// Disable it to trade directly from and into an existing WSOL balance.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// let mut trade = Trade::new(client);
+    /// trade.set_wrap_sol(false);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_wrap_sol(&mut self, wrap_sol: bool) {
+        self.wrap_sol = wrap_sol;
+    }
+
+    /// Sets the compute unit limit and price added to every swap transaction
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::PriorityFeeConfig;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let mut trade = Trade::new(client);
+    /// let price = trade.estimate_priority_fee(&[pool_address]).await?;
+    /// trade.set_priority_fee_config(PriorityFeeConfig {
+    ///     compute_unit_limit: Some(300_000),
+    ///     compute_unit_price_micro_lamports: Some(price),
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_priority_fee_config(&mut self, priority_fee_config: PriorityFeeConfig) {
+        self.priority_fee_config = priority_fee_config;
+    }
+
+    /// Sets the margin `suggest_slippage_bps` adds on top of the expected price impact
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// let mut trade = Trade::new(client);
+    /// trade.set_slippage_buffer_bps(100); // impact + 1%
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_slippage_buffer_bps(&mut self, slippage_buffer_bps: u16) {
+        self.slippage_buffer_bps = slippage_buffer_bps;
+    }
+
+    /// Recommends a slippage tolerance for a trade
+    ///
+    /// The expected price impact of the route is rounded up to basis points
+    /// and the slippage buffer is added, 0.5% unless set with
+    /// `set_slippage_buffer_bps`. The result is clamped to the 50% maximum
+    /// accepted by `get_quote_with_validation`. `params.slippage_bps` is ignored.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::TradeParams;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// let slippage_bps = trade.suggest_slippage_bps(&params).await?;
+    /// let params = TradeParams { slippage_bps, ..params };
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn suggest_slippage_bps(&self, params: &TradeParams) -> Result<u16, MeteoraError> {
+        let quote = self.get_quote(params).await?;
+        // price_impact is a percentage
+        let impact_bps = (quote.price_impact * 100.0).ceil().max(0.0);
+        let suggested = impact_bps + self.slippage_buffer_bps as f64;
+        Ok(suggested.min(MAX_SLIPPAGE_BPS as f64) as u16)
+    }
+
+    /// Estimates the largest input that keeps price impact within `max_impact_pct`
+    ///
+    /// The best direct pool between the two mints is picked as in
+    /// `get_quote_with_validation`, and the impact formula is solved for the
+    /// input amount against its reserves. The result is clamped to the pool's
+    /// input reserve, which is already a 50% impact.
+    ///
+    /// # Params
+    /// input_mint - The token to spend
+    /// output_mint - The token to receive
+    /// max_impact_pct - The highest acceptable price impact as a percentage
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let (usdc_mint, sol_mint) = (solana_sdk::pubkey::Pubkey::new_unique(), spl_token::native_mint::ID);
+    /// // How much USDC can be sold before moving the price 2%?
+    /// let max_in = trade.max_amount_for_impact(&usdc_mint, &sol_mint, 2.0).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn max_amount_for_impact(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        max_impact_pct: f64,
+    ) -> Result<u64, MeteoraError> {
+        if !(max_impact_pct > 0.0 && max_impact_pct < 100.0) {
+            return Err(MeteoraError::InvalidInput(
+                "Price impact must be between 0% and 100%".to_string(),
+            ));
+        }
+        let pools = self
+            .pool_manager
+            .find_pools_by_tokens(input_mint, output_mint)
+            .await?;
+        let pool_info = best_pool(input_mint, &pools)
+            .cloned()
+            .ok_or(MeteoraError::NoLiquidityPoolFound)?;
+        require_constant_product(&pool_info)?;
+        let matched = MatchedPool::new(pool_info, input_mint);
+        let (input_reserve, _) = matched.reserve_amounts();
+        if input_reserve == 0 {
+            return Err(MeteoraError::CalculationError(
+                "Pool has no liquidity".to_string(),
+            ));
+        }
+        Ok(math::amount_for_price_impact(input_reserve, max_impact_pct).min(input_reserve))
+    }
+
+    /// Sets the block engine endpoint used by `send_transaction_bundle`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let mut trade = Trade::new(client);
+    /// trade.set_jito_block_engine_url("https://ny.mainnet.block-engine.jito.wtf/api/v1/bundles");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "jito")]
+    pub fn set_jito_block_engine_url(&mut self, url: &str) {
+        self.jito_block_engine_url = url.to_string();
+    }
+
+    /// Signs `instructions` with a Jito tip and submits them as a bundle
+    ///
+    /// A transfer of `tip_lamports` to `tip_account` is appended as the last
+    /// instruction, so the tip is only paid if everything before it succeeds.
+    /// The transaction goes to the block engine instead of the RPC node,
+    /// which keeps it out of the public mempool and protects it from
+    /// sandwiching. Returns the bundle id; landing has to be checked
+    /// separately, e.g. with `confirm_transaction`.
+    ///
+    /// # Params
+    /// instructions - The instructions to run, e.g. from `build_swap_transaction`
+    /// keypair - Fee payer and only signer
+    /// tip_lamports - The tip paid to the validator
+    /// tip_account - One of `jito::JITO_TIP_ACCOUNTS`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::str::FromStr;
+    /// # use meteora_sdk::jito;
+    /// # use solana_sdk::pubkey::Pubkey;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let keypair = solana_sdk::signature::Keypair::new();
+    /// # let instructions = Vec::new();
+    /// let tip_account = Pubkey::from_str(jito::JITO_TIP_ACCOUNTS[0]).unwrap();
+    /// let bundle_id = trade
+    ///     .send_transaction_bundle(instructions, &keypair, 10_000, &tip_account)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "jito")]
+    pub async fn send_transaction_bundle(
+        &self,
+        instructions: Vec<Instruction>,
+        keypair: &Keypair,
+        tip_lamports: u64,
+        tip_account: &Pubkey,
+    ) -> Result<String, MeteoraError> {
+        let instructions =
+            crate::jito::with_tip(instructions, &keypair.pubkey(), tip_account, tip_lamports);
+        let recent_blockhash = self.get_recent_blockhash().await?;
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&keypair.pubkey()),
+            &[keypair],
+            recent_blockhash,
+        );
+        crate::jito::send_bundle(&self.jito_block_engine_url, &[transaction]).await
+    }
+
+    /// Sets the resend budget, timeout and commitment used by `execute_swap_safe`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use meteora_sdk::types::ConfirmConfig;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// let mut trade = Trade::new(client);
+    /// trade.set_confirm_config(ConfirmConfig {
+    ///     timeout_secs: 90,
+    ///     poll_interval: Duration::from_secs(2),
+    ///     ..ConfirmConfig::default()
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_confirm_config(&mut self, confirm_config: ConfirmConfig) {
+        self.confirm_config = confirm_config;
+    }
+
+    /// Estimates a compute unit price from fees recently paid by transactions
+    /// that write to `accounts`
+    ///
+    /// Returns the `PRIORITY_FEE_PERCENTILE`th percentile of recent non-zero
+    /// prioritization fees in micro-lamports, or 0 when nobody paid one.
     ///
     /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let price = trade.estimate_priority_fee(&[pool_address]).await?;
+    /// println!("Suggested price: {} micro-lamports per CU", price);
+    /// # Ok(())
+    /// # }
     /// ```
+    pub async fn estimate_priority_fee(&self, accounts: &[Pubkey]) -> Result<u64, MeteoraError> {
+        let recent_fees = self
+            .client
+            .rpc(|rpc| async move { rpc.get_recent_prioritization_fees(accounts).await })
+            .await?;
+        let mut fees: Vec<u64> = recent_fees
+            .iter()
+            .map(|fee| fee.prioritization_fee)
+            .filter(|fee| *fee > 0)
+            .collect();
+        if fees.is_empty() {
+            return Ok(0);
+        }
+        fees.sort_unstable();
+        let index = (fees.len() - 1) * PRIORITY_FEE_PERCENTILE / 100;
+        Ok(fees[index])
+    }
+
+    /// Gets a validated trade quote with comprehensive checks
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use meteora_sdk::types::TradeParams;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let (usdc_mint, sol_mint) = (solana_sdk::pubkey::Pubkey::new_unique(), spl_token::native_mint::ID);
+    /// # let user_pubkey = solana_sdk::pubkey::Pubkey::new_unique();
     /// let trade = Trade::new(client);
     /// let params = TradeParams {
     ///     input_mint: usdc_mint,
@@ -48,58 +463,148 @@ impl Trade {
     ///     amount_in: 100_000_000, // 100 USDC
     ///     slippage_bps: 100, // 1%
     ///     user: user_pubkey,
+    ///     deadline: None,
+    ///     referral: None,
     /// };
     /// let quote = trade.get_quote_with_validation(&params).await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_quote_with_validation(
         &self,
         params: &TradeParams,
     ) -> Result<TradeQuote, MeteoraError> {
-        self.validate_trade_params(params).await?;
+        self.validate_trade_params(params, false).await?;
+        let quote = self.best_quote(params).await?;
+        if quote.price_impact > params.slippage_bps as f64 / 100.0 {
+            return Err(MeteoraError::SlippageExceeded);
+        }
+        Ok(quote)
+    }
+
+    /// Checks trade parameters and routing, collecting every problem found
+    ///
+    /// Unlike `get_quote_with_validation`, which stops at the first failed
+    /// check, all parameter checks run and each failure is reported. The
+    /// route and its price impact are checked too, unless the amount is zero
+    /// or both mints are the same.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// let report = trade.validate_report(&params).await;
+    /// if !report.is_valid() {
+    ///     for issue in &report.issues {
+    ///         println!("{:?}", issue);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn validate_report(&self, params: &TradeParams) -> ValidationReport {
+        let mut issues = trade_param_issues(params);
+        if params.amount_in > 0 && params.input_mint != params.output_mint {
+            match self.best_quote(params).await {
+                Ok(quote) if quote.price_impact > params.slippage_bps as f64 / 100.0 => issues
+                    .push(ValidationIssue::PriceImpactTooHigh {
+                        price_impact: quote.price_impact,
+                        slippage_bps: params.slippage_bps,
+                    }),
+                Ok(_) => {}
+                Err(MeteoraError::NoLiquidityPoolFound) => issues.push(ValidationIssue::NoRoute),
+                Err(e) => issues.push(ValidationIssue::RouteCheckFailed(e)),
+            }
+        }
+        ValidationReport { issues }
+    }
+
+    /// Quotes the deepest direct pool, or the best two-hop route without one
+    async fn best_quote(&self, params: &TradeParams) -> Result<TradeQuote, MeteoraError> {
         let pools = self
-            .find_best_route(&params.input_mint, &params.output_mint)
+            .pool_manager
+            .find_pools_by_tokens(&params.input_mint, &params.output_mint)
             .await?;
         if pools.is_empty() {
-            return Err(MeteoraError::NoLiquidityPoolFound);
+            let hops = self.find_two_hop_route(params).await?;
+            return self.quote_route(params, &hops);
         }
-        let best_pool = self.select_best_pool(&pools).await?;
-        let pool_info = self.pool_manager.get_pool_info(&best_pool).await?;
-        let amount_out =
-            self.calculate_swap_output(params.amount_in, &pool_info, &params.input_mint)?;
-        let price_impact =
-            self.calculate_price_impact(params.amount_in, &pool_info, &params.input_mint)?;
-        if price_impact > params.slippage_bps as f64 / 100.0 {
-            return Err(MeteoraError::SlippageExceeded);
-        }
-        let min_amount_out = amount_out * (10000 - params.slippage_bps as u64) / 10000;
-        let fee_amount = params.amount_in * pool_info.trade_fee_bps / 10000;
-        Ok(TradeQuote {
-            amount_out,
-            min_amount_out,
-            price_impact,
-            fee_amount,
-            route: vec![best_pool],
-        })
+        let pool_info = best_pool(&params.input_mint, &pools)
+            .cloned()
+            .ok_or(MeteoraError::NoLiquidityPoolFound)?;
+        let matched = MatchedPool::new(pool_info, &params.input_mint);
+        self.quote_route(params, &[matched])
     }
 
     /// Executes a swap with comprehensive safety checks
     ///
+    /// The transaction is resent with a fresh blockhash according to the
+    /// trade's `ConfirmConfig`, and the signature of the confirmed attempt is returned.
+    /// If no attempt reaches the configured commitment, `TransactionTimeout` is returned.
+    /// When `params.deadline` is set, nothing is sent after it passes and
+    /// `DeadlineExceeded` is returned. Close to the deadline, the quote is
+    /// checked against fresh pool reserves before sending.
+    ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// # let user_keypair = solana_sdk::signature::Keypair::new();
     /// let signature = trade.execute_swap_safe(&params, &user_keypair).await?;
     /// println!("Swap executed: {}", signature);
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn execute_swap_safe(
         &self,
         params: &TradeParams,
         user_keypair: &Keypair,
     ) -> Result<String, MeteoraError> {
+        // The transaction is built with `params.user` as fee payer and signed
+        // with `user_keypair`, so the two must be the same account.
+        if user_keypair.pubkey() != params.user {
+            return Err(MeteoraError::InvalidInput(format!(
+                "Keypair {} does not match trade user {}",
+                user_keypair.pubkey(),
+                params.user
+            )));
+        }
+        self.check_deadline(params)?;
         let quote = self.get_quote_with_validation(params).await?;
         let simulation = self.simulate_swap(params, &quote).await?;
         if !simulation.success {
-            return Err(MeteoraError::TransactionFailed(
-                "Simulation failed".to_string(),
-            ));
+            let reason = simulation.error.as_deref().unwrap_or("unknown error");
+            let message = match simulation.custom_error_code() {
+                Some(code) => format!("{} (program error code {:#x})", reason, code),
+                None => reason.to_string(),
+            };
+            return Err(MeteoraError::SimulationFailed(message));
         }
         if simulation.actual_output < quote.min_amount_out {
             return Err(MeteoraError::SlippageExceeded);
@@ -107,67 +612,281 @@ impl Trade {
         self.check_user_balance(&params.user, &params.input_mint, params.amount_in)
             .await?;
         let fee_estimate = self.estimate_transaction_fees().await?;
-        let instructions = self.build_swap_instructions(params, &quote).await?;
-        let signature = self
-            .send_transaction(&instructions, user_keypair, fee_estimate)
-            .await?;
-        self.confirm_transaction_with_timeout(&signature, 30)
-            .await?;
-        Ok(signature)
+        log::debug!("Estimated transaction fee: {} lamports", fee_estimate);
+        self.check_deadline(params)?;
+        if let Some(deadline) = params.deadline
+            && deadline - chrono::Utc::now().timestamp() <= DEADLINE_REQUOTE_WINDOW_SECS
+        {
+            let fresh_quote = self.get_quote(params).await?;
+            if fresh_quote.amount_out < quote.min_amount_out {
+                return Err(MeteoraError::SlippageExceeded);
+            }
+        }
+        let transaction = self.build_transaction_for_quote(params, &quote).await?;
+        self.submit_and_confirm(transaction, user_keypair, params.deadline)
+            .await
     }
 
-    async fn validate_trade_params(&self, params: &TradeParams) -> Result<(), MeteoraError> {
-        if params.amount_in == 0 {
-            return Err(MeteoraError::InvalidInput(
-                "Amount cannot be zero".to_string(),
-            ));
+    /// Fails with `DeadlineExceeded` once the trade's deadline has passed
+    fn check_deadline(&self, params: &TradeParams) -> Result<(), MeteoraError> {
+        match params.deadline {
+            Some(deadline) if chrono::Utc::now().timestamp() >= deadline => {
+                Err(MeteoraError::DeadlineExceeded)
+            }
+            _ => Ok(()),
         }
-        if params.slippage_bps > 5000 {
-            // Maximum 50% slippage
-            return Err(MeteoraError::InvalidInput("Slippage too high".to_string()));
+    }
+
+    /// Builds an unsigned swap transaction for signing outside this process
+    ///
+    /// The transaction has a recent blockhash and `params.user` as fee payer,
+    /// so it only needs the user's signature before it can be submitted.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let hardware_signer = solana_sdk::signature::Keypair::new();
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// let mut transaction = trade.build_swap_transaction(&params).await?;
+    /// let blockhash = transaction.message.recent_blockhash;
+    /// transaction.sign(&[&hardware_signer], blockhash);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build_swap_transaction(
+        &self,
+        params: &TradeParams,
+    ) -> Result<Transaction, MeteoraError> {
+        let quote = self.get_quote_with_validation(params).await?;
+        self.build_transaction_for_quote(params, &quote).await
+    }
+
+    /// Builds an unsigned v0 swap transaction that can use address lookup tables
+    ///
+    /// Accounts found in `lookup_tables` are referenced through the tables
+    /// instead of being listed in the message, which keeps multi-hop swaps
+    /// under the transaction size limit. Single-hop swaps fit in a legacy
+    /// transaction, see `build_swap_transaction`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use solana_transaction::versioned::VersionedTransaction;
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// # let lookup_table = solana_sdk::message::AddressLookupTableAccount { key: solana_sdk::pubkey::Pubkey::new_unique(), addresses: Vec::new() };
+    /// # let user_keypair = solana_sdk::signature::Keypair::new();
+    /// let unsigned = trade
+    ///     .build_versioned_swap_transaction(&params, &[lookup_table])
+    ///     .await?;
+    /// let transaction = VersionedTransaction::try_new(unsigned.message, &[&user_keypair]).unwrap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build_versioned_swap_transaction(
+        &self,
+        params: &TradeParams,
+        lookup_tables: &[AddressLookupTableAccount],
+    ) -> Result<VersionedTransaction, MeteoraError> {
+        let quote = self.get_quote_with_validation(params).await?;
+        let instructions = self.build_swap_instructions(params, &quote).await?;
+        let recent_blockhash = self.get_recent_blockhash().await?;
+        let message =
+            v0::Message::try_compile(&params.user, &instructions, lookup_tables, recent_blockhash)
+                .map_err(|e| {
+                    MeteoraError::Error(format!("Failed to compile v0 message: {:?}", e))
+                })?;
+        let message = VersionedMessage::V0(message);
+        Ok(VersionedTransaction {
+            signatures: vec![
+                Signature::default();
+                message.header().num_required_signatures as usize
+            ],
+            message,
+        })
+    }
+
+    async fn build_transaction_for_quote(
+        &self,
+        params: &TradeParams,
+        quote: &TradeQuote,
+    ) -> Result<Transaction, MeteoraError> {
+        let instructions = self.build_swap_instructions(params, quote).await?;
+        let recent_blockhash = self.get_recent_blockhash().await?;
+        let message =
+            Message::new_with_blockhash(&instructions, Some(&params.user), &recent_blockhash);
+        Ok(Transaction::new_unsigned(message))
+    }
+
+    /// Checks `params` before quoting
+    ///
+    /// With `direct_only`, a pair without any pool fails fast with
+    /// `NoLiquidityPoolFound` through `PoolManager::pool_exists_for_pair`,
+    /// before any pool is loaded. Quotes that can route through an
+    /// intermediate mint skip that check.
+    async fn validate_trade_params(
+        &self,
+        params: &TradeParams,
+        direct_only: bool,
+    ) -> Result<(), MeteoraError> {
+        if let Some(issue) = trade_param_issues(params).into_iter().next() {
+            return Err(issue.into());
         }
-        if params.input_mint == params.output_mint {
-            return Err(MeteoraError::InvalidInput(
-                "Cannot swap same token".to_string(),
-            ));
+        if direct_only
+            && !self
+                .pool_manager
+                .pool_exists_for_pair(&params.input_mint, &params.output_mint)
+                .await?
+        {
+            return Err(MeteoraError::NoLiquidityPoolFound);
         }
         Ok(())
     }
 
-    async fn find_best_route(
+    /// Finds the best route through one intermediate mint when there is no direct pool
+    ///
+    /// WSOL and USDC are tried first, followed by the other mints paired with
+    /// the input token, up to `MAX_INTERMEDIATE_CANDIDATES` in total. The
+    /// pool pair with the highest final output wins.
+    async fn find_two_hop_route(
         &self,
-        input_mint: &Pubkey,
-        output_mint: &Pubkey,
-    ) -> Result<Vec<Pubkey>, MeteoraError> {
-        let pools = self
+        params: &TradeParams,
+    ) -> Result<Vec<MatchedPool>, MeteoraError> {
+        let mut candidates = vec![
+            spl_token::native_mint::ID,
+            Pubkey::from_str(USDC_MINT).unwrap(),
+        ];
+        for pool_address in self
             .pool_manager
-            .find_pools_by_tokens(input_mint, output_mint)
-            .await?;
-        let mut pool_liquidity = Vec::new();
-        for pool in &pools {
-            if let Ok(liquidity) = self.pool_manager.get_pool_liquidity(&pool.address).await {
-                pool_liquidity.push((liquidity, pool.address));
+            .find_token_pools(&params.input_mint)
+            .await?
+        {
+            if candidates.len() >= MAX_INTERMEDIATE_CANDIDATES {
+                break;
+            }
+            if let Ok(pool_info) = self.pool_manager.get_pool_info_cached(&pool_address).await {
+                let other_mint = if pool_info.token_a_mint == params.input_mint {
+                    pool_info.token_b_mint
+                } else {
+                    pool_info.token_a_mint
+                };
+                if !candidates.contains(&other_mint) {
+                    candidates.push(other_mint);
+                }
             }
         }
-        pool_liquidity.sort_by(|a, b| b.0.cmp(&a.0));
-        Ok(pool_liquidity.into_iter().map(|(_, addr)| addr).collect())
-    }
-
-    async fn select_best_pool(&self, pools: &[Pubkey]) -> Result<Pubkey, MeteoraError> {
-        let mut best_pool = None;
-        let mut best_score = 0.0;
-        for pool_address in pools {
-            if let Ok(pool_info) = self.pool_manager.get_pool_info(pool_address).await {
-                let liquidity = pool_info.token_a_reserve_amount + pool_info.token_b_reserve_amount;
-                let fee_score = 1.0 - (pool_info.trade_fee_bps as f64 / 10000.0);
-                let score = liquidity as f64 * fee_score;
-                if score > best_score {
-                    best_score = score;
-                    best_pool = Some(*pool_address);
+        let mut best: Option<(u64, Vec<MatchedPool>)> = None;
+        for intermediate in candidates {
+            if intermediate == params.input_mint || intermediate == params.output_mint {
+                continue;
+            }
+            let first_legs = self
+                .pool_manager
+                .find_matched_pools(&params.input_mint, &intermediate)
+                .await?;
+            if first_legs.is_empty() {
+                continue;
+            }
+            let second_legs = self
+                .pool_manager
+                .find_matched_pools(&intermediate, &params.output_mint)
+                .await?;
+            for first in &first_legs {
+                for second in &second_legs {
+                    let hops = vec![first.clone(), second.clone()];
+                    if let Ok(amount_out) = self.calculate_route_output(params.amount_in, &hops)
+                        && best
+                            .as_ref()
+                            .is_none_or(|(best_out, _)| amount_out > *best_out)
+                    {
+                        best = Some((amount_out, hops));
+                    }
                 }
             }
         }
-        best_pool.ok_or(MeteoraError::NoLiquidityPoolFound)
+        best.map(|(_, hops)| hops)
+            .ok_or(MeteoraError::NoLiquidityPoolFound)
+    }
+
+    /// Builds a quote for swapping through `hops` in order
+    ///
+    /// A referral fee is taken from the route's output, so `amount_out` and
+    /// `min_amount_out` are what the user receives after it.
+    fn quote_route(
+        &self,
+        params: &TradeParams,
+        hops: &[MatchedPool],
+    ) -> Result<TradeQuote, MeteoraError> {
+        let route_output = self.calculate_route_output(params.amount_in, hops)?;
+        let referral_fee_amount = params.referral.map_or(0, |referral| {
+            math::fee_amount(route_output, referral.fee_bps as u64)
+        });
+        let amount_out = route_output - referral_fee_amount;
+        let price_impact = self.calculate_route_price_impact(params.amount_in, hops)?;
+        let mut amount_after_fees = params.amount_in;
+        for hop in hops {
+            amount_after_fees -= math::fee_amount(amount_after_fees, hop.pool.trade_fee_bps);
+        }
+        Ok(TradeQuote {
+            amount_out,
+            min_amount_out: math::min_amount_out(amount_out, params.slippage_bps),
+            price_impact,
+            fee_amount: params.amount_in - amount_after_fees,
+            referral_fee_amount,
+            max_amount_in: params.amount_in,
+            route: hops.iter().map(|hop| hop.pool.address).collect(),
+            hops: self.quote_hops(params.amount_in, hops)?,
+        })
+    }
+
+    /// Breaks a route down into the amounts, fee and price impact of each hop,
+    /// each hop spending the previous hop's full output
+    fn quote_hops(
+        &self,
+        amount_in: u64,
+        hops: &[MatchedPool],
+    ) -> Result<Vec<HopInfo>, MeteoraError> {
+        let mut hop_amount_in = amount_in;
+        let mut hop_infos = Vec::with_capacity(hops.len());
+        for hop in hops {
+            let hop_amount_out = self.calculate_swap_output(hop_amount_in, hop)?;
+            hop_infos.push(HopInfo {
+                pool: hop.pool.address,
+                input_mint: hop.input_mint(),
+                output_mint: hop.output_mint(),
+                amount_in: hop_amount_in,
+                amount_out: hop_amount_out,
+                fee_amount: math::fee_amount(hop_amount_in, hop.pool.trade_fee_bps),
+                price_impact: self.calculate_price_impact(hop_amount_in, hop)?,
+            });
+            hop_amount_in = hop_amount_out;
+        }
+        Ok(hop_infos)
     }
 
     async fn simulate_swap(
@@ -175,45 +894,209 @@ impl Trade {
         params: &TradeParams,
         quote: &TradeQuote,
     ) -> Result<SwapSimulation, MeteoraError> {
-        let instructions = self.build_swap_instructions(params, quote).await?;
-        let recent_blockhash = self.get_recent_blockhash().await?;
-        let message =
-            Message::new_with_blockhash(&instructions, Some(&params.user), &recent_blockhash);
-        // build transaction
-        let transaction = Transaction::new_unsigned(message);
+        let transaction = self.build_transaction_for_quote(params, quote).await?;
         // Simulate trading using RPC
-        match self
+        let transaction = &transaction;
+        let result = self
             .client
-            .solana
-            .client_arc()
-            .simulate_transaction(&transaction)
-            .await
-        {
-            Ok(result) => {
-                let simulation = SwapSimulation {
-                    success: result.value.err.is_none(),
-                    logs: result.value.logs.unwrap_or_default(),
-                    units_consumed: result.value.units_consumed.unwrap_or(0),
-                    price_impact: quote.price_impact,
-                    actual_output: quote.amount_out,
-                };
-                Ok(simulation)
+            .rpc(|rpc| async move { rpc.simulate_transaction(transaction).await })
+            .await?;
+        let err = result.value.err.map(TransactionError::from);
+        let instruction_error = match &err {
+            Some(TransactionError::InstructionError(_, instruction_error)) => {
+                Some(instruction_error.clone())
+            }
+            _ => None,
+        };
+        Ok(SwapSimulation {
+            success: err.is_none(),
+            logs: result.value.logs.unwrap_or_default(),
+            units_consumed: result.value.units_consumed.unwrap_or(0),
+            price_impact: quote.price_impact,
+            actual_output: quote.amount_out,
+            error: err.map(|e| e.to_string()),
+            instruction_error,
+        })
+    }
+
+    /// Checks that every account the swap instructions reference exists and is
+    /// owned by the expected program
+    ///
+    /// For each pool of the route, the pool, its reserves and fee account, both
+    /// Meteora vaults with their token accounts and LP mints, the pool's vault
+    /// LP accounts and the protocol fee account of the hop's input mint are
+    /// checked. Token accounts must belong to the program of the mint they
+    /// hold, so pools of Token-2022 mints pass. The user's token accounts
+    /// aren't checked: the quote doesn't name the user, and the swap creates
+    /// missing output accounts itself.
+    ///
+    /// Returns `AccountNotFound` or `InvalidAccountOwner` naming the offending
+    /// account, so a bad pool layout is caught before anything is sent.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// let quote = trade.get_quote(&params).await?;
+    /// trade.validate_route_accounts(&quote).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn validate_route_accounts(&self, quote: &TradeQuote) -> Result<(), MeteoraError> {
+        let meteora_program = Pubkey::from_str(METEORA_PROGRAM_ID).unwrap();
+        let vault_program = Pubkey::from_str(METEORA_VAULT_PROGRAM_ID).unwrap();
+        let token_programs = vec![spl_token::id(), spl_token_2022_interface::id()];
+        for (index, pool_address) in quote.route.iter().enumerate() {
+            let hop = quote.hops.get(index).ok_or_else(|| {
+                MeteoraError::InvalidInput(format!("Quote has no hop for pool {}", pool_address))
+            })?;
+            let pool_info = self.pool_manager.get_pool_info(pool_address).await?;
+            let mints = [
+                (
+                    "token a mint",
+                    pool_info.token_a_mint,
+                    token_programs.clone(),
+                ),
+                (
+                    "token b mint",
+                    pool_info.token_b_mint,
+                    token_programs.clone(),
+                ),
+            ];
+            let mint_owners = self.check_route_accounts(pool_address, &mints).await?;
+            let (token_a_program, token_b_program) = (mint_owners[0], mint_owners[1]);
+            let input_program = if hop.input_mint == pool_info.token_a_mint {
+                token_a_program
+            } else {
+                token_b_program
+            };
+            let a_vault = pool::vault_address(&pool_info.token_a_mint);
+            let b_vault = pool::vault_address(&pool_info.token_b_mint);
+            let expected = [
+                ("pool", pool_info.address, vec![meteora_program]),
+                (
+                    "token a reserve",
+                    pool_info.token_a_reserve,
+                    vec![token_a_program],
+                ),
+                (
+                    "token b reserve",
+                    pool_info.token_b_reserve,
+                    vec![token_b_program],
+                ),
+                (
+                    "fee account",
+                    pool_info.fee_account,
+                    vec![token_a_program, token_b_program],
+                ),
+                ("token a vault", a_vault, vec![vault_program]),
+                ("token b vault", b_vault, vec![vault_program]),
+                (
+                    "token a vault token account",
+                    pool::vault_token_account(&a_vault),
+                    vec![token_a_program],
+                ),
+                (
+                    "token b vault token account",
+                    pool::vault_token_account(&b_vault),
+                    vec![token_b_program],
+                ),
+                (
+                    "token a vault LP mint",
+                    pool::vault_lp_mint(&a_vault),
+                    vec![spl_token::id()],
+                ),
+                (
+                    "token b vault LP mint",
+                    pool::vault_lp_mint(&b_vault),
+                    vec![spl_token::id()],
+                ),
+                (
+                    "token a vault LP account",
+                    pool::pool_vault_lp_account(&a_vault, pool_address),
+                    vec![spl_token::id()],
+                ),
+                (
+                    "token b vault LP account",
+                    pool::pool_vault_lp_account(&b_vault, pool_address),
+                    vec![spl_token::id()],
+                ),
+                (
+                    "protocol fee account",
+                    pool::protocol_fee_account(&hop.input_mint, pool_address),
+                    vec![input_program],
+                ),
+            ];
+            self.check_route_accounts(pool_address, &expected).await?;
+        }
+        Ok(())
+    }
+
+    /// Fetches `expected` accounts of `pool_address` and checks each exists
+    /// and is owned by one of its programs, returning the owners
+    async fn check_route_accounts(
+        &self,
+        pool_address: &Pubkey,
+        expected: &[(&str, Pubkey, Vec<Pubkey>)],
+    ) -> Result<Vec<Pubkey>, MeteoraError> {
+        let addresses: Vec<Pubkey> = expected.iter().map(|(_, address, _)| *address).collect();
+        let accounts = self.client.get_multiple_accounts(&addresses).await?;
+        let mut owners = Vec::with_capacity(expected.len());
+        for ((name, address, programs), account) in expected.iter().zip(accounts) {
+            let Some(account) = account else {
+                return Err(MeteoraError::AccountNotFound(format!(
+                    "{} {} of pool {} not found",
+                    name, address, pool_address
+                )));
+            };
+            if !programs.contains(&account.owner) {
+                let programs: Vec<String> = programs.iter().map(Pubkey::to_string).collect();
+                return Err(MeteoraError::InvalidAccountOwner(format!(
+                    "{} {} of pool {} is owned by {}, expected {}",
+                    name,
+                    address,
+                    pool_address,
+                    account.owner,
+                    programs.join(" or ")
+                )));
             }
-            Err(e) => Err(MeteoraError::RpcError(e.to_string())),
+            owners.push(account.owner);
         }
+        Ok(owners)
     }
 
+    /// Checks that `user` can spend `required_amount` of `mint`
+    ///
+    /// When native SOL is wrapped for the swap, the amount comes from the
+    /// wallet's lamports instead of a token account. The wallet must then
+    /// keep its own rent-exempt minimum and, if the WSOL account doesn't
+    /// exist yet, the rent for creating it.
     async fn check_user_balance(
         &self,
         user: &Pubkey,
         mint: &Pubkey,
         required_amount: u64,
     ) -> Result<(), MeteoraError> {
+        if self.wrap_sol && *mint == spl_token::native_mint::ID {
+            return self.check_native_sol_balance(user, required_amount).await;
+        }
         let token_account = get_associated_token_address(user, mint);
-        match self.client.get_account_data(&token_account).await {
-            Ok(account_data) => {
-                let account = spl_token::state::Account::unpack(&account_data)
-                    .map_err(|e| MeteoraError::DeserializationError(e.to_string()))?;
+        match self.client.get_account(&token_account).await {
+            Ok(account) => {
+                let account = unpack_token_account(&token_account, &account)?;
                 if account.amount < required_amount {
                     return Err(MeteoraError::InsufficientBalance);
                 }
@@ -225,126 +1108,655 @@ impl Trade {
         }
     }
 
+    async fn check_native_sol_balance(
+        &self,
+        user: &Pubkey,
+        required_lamports: u64,
+    ) -> Result<(), MeteoraError> {
+        let lamports = self
+            .client
+            .rpc(|rpc| async move { rpc.get_balance(user).await })
+            .await?;
+        let wsol_account = get_associated_token_address(user, &spl_token::native_mint::ID);
+        let wsol_exists = self.client.get_account(&wsol_account).await.is_ok();
+        let mut reserved = self
+            .client
+            .rpc(|rpc| async move { rpc.get_minimum_balance_for_rent_exemption(0).await })
+            .await?;
+        if !wsol_exists {
+            reserved += self
+                .client
+                .rpc(|rpc| async move {
+                    rpc.get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+                        .await
+                })
+                .await?;
+        }
+        if lamports.saturating_sub(reserved) < required_lamports {
+            return Err(MeteoraError::InsufficientBalance);
+        }
+        Ok(())
+    }
+
     async fn estimate_transaction_fees(&self) -> Result<u64, MeteoraError> {
-        match self.client.solana.client_arc().get_latest_blockhash().await {
+        match self.get_recent_blockhash().await {
             Ok(blockhash) => {
-                let message = Message::new_with_blockhash(&[], None, &blockhash);
+                let message = &Message::new_with_blockhash(&[], None, &blockhash);
                 match self
                     .client
-                    .solana
-                    .client_arc()
-                    .get_fee_for_message(&message)
+                    .rpc(|rpc| async move { rpc.get_fee_for_message(message).await })
                     .await
                 {
                     Ok(fee) => Ok(fee),
                     Err(e) => {
-                        log::warn!("Failed to get fee estimate: {}, using fallback", e);
+                        log::warn!("Failed to get fee estimate: {:?}, using fallback", e);
                         let fallback_fee = 5000;
                         Ok(fallback_fee)
                     }
                 }
             }
             Err(e) => {
-                log::warn!("Failed to get blockhash for fee estimation: {}", e);
+                log::warn!("Failed to get blockhash for fee estimation: {:?}", e);
                 Ok(10000)
             }
         }
     }
 
-    async fn send_transaction(
+    /// Submits a transaction and waits for confirmation, resending with a fresh
+    /// blockhash until it confirms, the resend budget is exhausted or
+    /// `deadline` passes
+    async fn submit_and_confirm(
         &self,
-        instructions: &[Instruction],
+        mut transaction: Transaction,
         user_keypair: &Keypair,
-        fee_estimate: u64,
+        deadline: Option<i64>,
     ) -> Result<String, MeteoraError> {
-        let message = Message::new_with_blockhash(
-            instructions,
-            Some(&user_keypair.pubkey()),
-            &self.get_recent_blockhash().await?,
-        );
-        let mut transaction = Transaction::new_unsigned(message);
-        transaction.sign(&[user_keypair], self.get_recent_blockhash().await?);
-        match self
-            .client
-            .solana
-            .client_arc()
-            .send_and_confirm_transaction(&transaction)
-            .await
-        {
-            Ok(signature) => Ok(signature.to_string()),
-            Err(e) => Err(MeteoraError::TransactionFailed(e.to_string())),
-        }
-    }
-
+        let attempts = self.confirm_config.max_resends + 1;
+        for attempt in 1..=attempts {
+            if deadline.is_some_and(|deadline| chrono::Utc::now().timestamp() >= deadline) {
+                return Err(MeteoraError::DeadlineExceeded);
+            }
+            if attempt > 1 {
+                transaction.message.recent_blockhash = self.get_recent_blockhash().await?;
+            }
+            let recent_blockhash = transaction.message.recent_blockhash;
+            transaction
+                .try_sign(&[user_keypair], recent_blockhash)
+                .map_err(|e| MeteoraError::InvalidInput(e.to_string()))?;
+            let transaction = &transaction;
+            let signature = self
+                .client
+                .rpc(|rpc| async move { rpc.send_transaction(transaction).await })
+                .await
+                .map_err(|e| match e {
+                    MeteoraError::RpcError(message) => MeteoraError::TransactionFailed(message),
+                    other => other,
+                })?;
+            if self
+                .confirm_transaction_with_timeout(&signature, &recent_blockhash)
+                .await?
+            {
+                return Ok(signature.to_string());
+            }
+            log::warn!(
+                "Transaction {} not confirmed (attempt {}/{})",
+                signature,
+                attempt,
+                attempts
+            );
+        }
+        Err(MeteoraError::TransactionTimeout)
+    }
+
     async fn get_recent_blockhash(&self) -> Result<solana_sdk::hash::Hash, MeteoraError> {
         self.client
-            .solana
-            .client_arc()
-            .get_latest_blockhash()
+            .rpc(|rpc| async move { rpc.get_latest_blockhash().await })
             .await
-            .map_err(|e| MeteoraError::RpcError(e.to_string()))
     }
 
+    /// Polls the signature until it reaches the configured commitment, its
+    /// blockhash expires or the configured timeout elapses
+    ///
+    /// Returns `Ok(false)` when the transaction never landed and can be resent.
     async fn confirm_transaction_with_timeout(
         &self,
-        signature: &str,
-        timeout_seconds: u64,
+        signature: &Signature,
+        recent_blockhash: &Hash,
     ) -> Result<bool, MeteoraError> {
-        let signature = signature
-            .parse()
-            .map_err(|_| MeteoraError::InvalidInput("Invalid signature".to_string()))?;
-        for _ in 0..timeout_seconds {
-            match self
+        let commitment = self.confirm_config.commitment;
+        let timeout = tokio::time::Duration::from_secs(self.confirm_config.timeout_secs);
+        let started = tokio::time::Instant::now();
+        let signatures = &[*signature];
+        let mut landed = false;
+        while started.elapsed() < timeout {
+            let status = self
                 .client
-                .solana
-                .client_arc()
-                .get_signature_status(&signature)
+                .rpc(|rpc| async move { rpc.get_signature_statuses(signatures).await })
                 .await
-            {
-                Ok(Some(status)) => {
-                    if status.err().is_none() {
-                        return Ok(true);
-                    } else {
-                        return Ok(false);
-                    }
+                .ok()
+                .and_then(|statuses| statuses.value.into_iter().next().flatten());
+            if let Some(status) = status {
+                if let Some(err) = status.err {
+                    return Err(MeteoraError::TransactionFailed(err.to_string()));
                 }
-                _ => {
-                    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                if status.satisfies_commitment(commitment) {
+                    return Ok(true);
                 }
+                // Landed but below the target commitment: wait for it rather
+                // than resending, even if the blockhash has since expired
+                landed = true;
+            } else if let Ok(false) = self
+                .client
+                .rpc(
+                    |rpc| async move { rpc.is_blockhash_valid(recent_blockhash, commitment).await },
+                )
+                .await
+            {
+                return Ok(false);
             }
+            tokio::time::sleep(self.confirm_config.poll_interval).await;
         }
-        Err(MeteoraError::TransactionFailed(
-            "Confirmation timeout".to_string(),
-        ))
+        if landed {
+            // Resending a landed transaction with a new blockhash could execute it twice
+            return Err(MeteoraError::TransactionTimeout);
+        }
+        Ok(false)
     }
 
     /// Gets a quick trade quote without extensive validation
     ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
     /// let quote = trade.get_quote(&params).await?;
     /// println!("Expected output: {}", quote.amount_out);
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_quote(&self, params: &TradeParams) -> Result<TradeQuote, MeteoraError> {
+        self.best_quote(params).await
+    }
+
+    /// Gets a quote whose output is what the Meteora program actually returns
+    ///
+    /// The swap is simulated with no minimum output, and `amount_out` is the
+    /// change in the user's output token account between now and the end of
+    /// the simulation, so curve type and fee structure are fully accounted
+    /// for. Falls back to the constant-product quote from `get_quote` when the
+    /// simulation fails or the output account can't be read from it, e.g.
+    /// when the user doesn't hold the input tokens yet.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// let quote = trade.simulate_quote(&params).await?;
+    /// println!("Simulated output: {}", quote.amount_out);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn simulate_quote(&self, params: &TradeParams) -> Result<TradeQuote, MeteoraError> {
+        let quote = self.get_quote(params).await?;
+        match self.simulate_output(params, &quote).await {
+            Ok(amount_out) => {
+                let mut hops = quote.hops.clone();
+                if let Some(last_hop) = hops.last_mut() {
+                    last_hop.amount_out = amount_out;
+                }
+                Ok(TradeQuote {
+                    amount_out,
+                    min_amount_out: math::min_amount_out(amount_out, params.slippage_bps),
+                    hops,
+                    ..quote
+                })
+            }
+            Err(e) => {
+                log::warn!("Swap simulation unavailable, using formula quote: {:?}", e);
+                Ok(quote)
+            }
+        }
+    }
+
+    /// Simulates the swap for `quote` and returns the amount credited to the
+    /// user's output token account
+    async fn simulate_output(
+        &self,
+        params: &TradeParams,
+        quote: &TradeQuote,
+    ) -> Result<u64, MeteoraError> {
+        let user_output_account =
+            self.get_associated_token_address(&params.user, &params.output_mint);
+        let balance_before = match self.client.get_account(&user_output_account).await {
+            Ok(account) => unpack_token_account(&user_output_account, &account)?.amount,
+            Err(MeteoraError::AccountNotFound(_)) => 0,
+            Err(e) => return Err(e),
+        };
+        let unbounded_quote = TradeQuote {
+            min_amount_out: 0,
+            ..quote.clone()
+        };
+        let transaction = self
+            .build_transaction_for_quote(params, &unbounded_quote)
+            .await?;
+        let transaction = &transaction;
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(self.client.commitment),
+            accounts: Some(RpcSimulateTransactionAccountsConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                addresses: vec![user_output_account.to_string()],
+            }),
+            ..RpcSimulateTransactionConfig::default()
+        };
+        let result = self
+            .client
+            .rpc(|rpc| {
+                let config = config.clone();
+                async move {
+                    rpc.simulate_transaction_with_config(transaction, config)
+                        .await
+                }
+            })
+            .await?;
+        if let Some(err) = result.value.err {
+            return Err(MeteoraError::SimulationFailed(format!("{:?}", err)));
+        }
+        let account = result
+            .value
+            .accounts
+            .and_then(|accounts| accounts.into_iter().next().flatten())
+            .and_then(|account| account.decode::<Account>())
+            .ok_or_else(|| {
+                MeteoraError::SimulationFailed(format!(
+                    "Simulation returned no state for output account {}",
+                    user_output_account
+                ))
+            })?;
+        let balance_after = unpack_token_account(&user_output_account, &account)?.amount;
+        Ok(balance_after.saturating_sub(balance_before))
+    }
+
+    /// Gets a quote for receiving exactly `amount_out` of `output_mint`
+    ///
+    /// The required input is rounded up so the swap never yields less than
+    /// `amount_out`. The quote's `max_amount_in` is the required input plus
+    /// `slippage_bps`, capped at the caller's `max_amount_in`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let (usdc_mint, sol_mint) = (solana_sdk::pubkey::Pubkey::new_unique(), spl_token::native_mint::ID);
+    /// # let user_pubkey = solana_sdk::pubkey::Pubkey::new_unique();
+    /// // Buy exactly 100 USDC, spending at most 1 SOL
+    /// let quote = trade
+    ///     .get_quote_exact_out(&sol_mint, &usdc_mint, 100_000_000, 1_000_000_000, 100, user_pubkey)
+    ///     .await?;
+    /// println!("Spend up to {} lamports", quote.max_amount_in);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_quote_exact_out(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        amount_out: u64,
+        max_amount_in: u64,
+        slippage_bps: u16,
+        user: Pubkey,
+    ) -> Result<TradeQuote, MeteoraError> {
+        if amount_out == 0 {
+            return Err(MeteoraError::InvalidInput(
+                "Amount cannot be zero".to_string(),
+            ));
+        }
+        let params = TradeParams {
+            input_mint: *input_mint,
+            output_mint: *output_mint,
+            amount_in: max_amount_in,
+            slippage_bps,
+            user,
+            deadline: None,
+            referral: None,
+        };
+        self.validate_trade_params(&params, true).await?;
         let pools = self
+            .pool_manager
+            .find_pools_by_tokens(input_mint, output_mint)
+            .await?;
+        let pool_info = best_pool(input_mint, &pools)
+            .cloned()
+            .ok_or(MeteoraError::NoLiquidityPoolFound)?;
+        let matched = &MatchedPool::new(pool_info, input_mint);
+        require_constant_product(&matched.pool)?;
+        let (input_reserve, output_reserve) = matched.reserve_amounts();
+        let amount_in = math::constant_product_in(
+            amount_out,
+            input_reserve,
+            output_reserve,
+            matched.pool.trade_fee_bps,
+        )
+        .ok_or_else(|| {
+            MeteoraError::CalculationError("Pool cannot provide the requested output".to_string())
+        })?;
+        let amount_in = u64::try_from(amount_in).map_err(|_| {
+            MeteoraError::CalculationError("Required input does not fit in u64".to_string())
+        })?;
+        if amount_in > max_amount_in {
+            return Err(MeteoraError::SlippageExceeded);
+        }
+        let price_impact = self.calculate_price_impact(amount_in, matched)?;
+        if price_impact > slippage_bps as f64 / 100.0 {
+            return Err(MeteoraError::SlippageExceeded);
+        }
+        let fee_amount = math::fee_amount(amount_in, matched.pool.trade_fee_bps);
+        Ok(TradeQuote {
+            amount_out,
+            min_amount_out: amount_out,
+            price_impact,
+            fee_amount,
+            referral_fee_amount: 0,
+            max_amount_in: math::max_amount_in(amount_in, slippage_bps).min(max_amount_in),
+            route: vec![matched.pool.address],
+            hops: vec![HopInfo {
+                pool: matched.pool.address,
+                input_mint: *input_mint,
+                output_mint: *output_mint,
+                amount_in,
+                amount_out,
+                fee_amount,
+                price_impact,
+            }],
+        })
+    }
+
+    /// Quotes an exact-input swap through a single pool without any RPC calls
+    ///
+    /// Uses the reserves, fee and curve already in `pool_info`, so the quote
+    /// is only as fresh as the pool information passed in. This makes it
+    /// suitable for backtests and simulations over recorded pool states.
+    ///
+    /// # Params
+    /// pool_info - The pool to swap through
+    /// amount_in - The amount of `input_mint` to sell, in base units
+    /// input_mint - The token to sell, one of the pool's two mints
+    /// slippage_bps - Slippage tolerance used for `min_amount_out`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let pool_manager = meteora_sdk::pool::PoolManager::new(client);
+    /// # let pool_address = solana_sdk::pubkey::Pubkey::new_unique();
+    /// let pool_info = pool_manager.get_pool_info(&pool_address).await?;
+    /// let quote = Trade::quote_from_pool(&pool_info, 1_000_000, &pool_info.token_a_mint, 50)?;
+    /// println!("Out: {}, fee: {}", quote.amount_out, quote.fee_amount);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn quote_from_pool(
+        pool_info: &PoolInfo,
+        amount_in: u64,
+        input_mint: &Pubkey,
+        slippage_bps: u16,
+    ) -> Result<TradeQuote, MeteoraError> {
+        if *input_mint != pool_info.token_a_mint && *input_mint != pool_info.token_b_mint {
+            return Err(MeteoraError::InvalidInput(format!(
+                "{} is not traded in pool {}",
+                input_mint, pool_info.address
+            )));
+        }
+        let matched = MatchedPool::new(pool_info.clone(), input_mint);
+        let (input_reserve, output_reserve) = matched.reserve_amounts();
+        if input_reserve == 0 || output_reserve == 0 {
+            return Err(MeteoraError::CalculationError(
+                "Pool has no liquidity".to_string(),
+            ));
+        }
+        let amount_out = matched.swap_output(amount_in)?;
+        let price_impact = matched.price_impact(amount_in)?;
+        let fee_amount = math::fee_amount(amount_in, pool_info.trade_fee_bps);
+        Ok(TradeQuote {
+            amount_out,
+            min_amount_out: math::min_amount_out(amount_out, slippage_bps),
+            price_impact,
+            fee_amount,
+            referral_fee_amount: 0,
+            max_amount_in: amount_in,
+            route: vec![pool_info.address],
+            hops: vec![HopInfo {
+                pool: pool_info.address,
+                input_mint: matched.input_mint(),
+                output_mint: matched.output_mint(),
+                amount_in,
+                amount_out,
+                fee_amount,
+                price_impact,
+            }],
+        })
+    }
+
+    /// Quotes a swap split across the deepest direct pools between two tokens
+    ///
+    /// The input is divided into `SPLIT_CHUNKS` equal chunks, and each chunk
+    /// goes to whichever of the `MAX_SPLIT_POOLS` deepest pools gives the most
+    /// additional output for it, i.e. the best marginal price. A single deep
+    /// pool therefore receives everything, while pools of similar depth share
+    /// the input and each move their price less. Referral fees are not
+    /// supported for split swaps.
+    ///
+    /// # Params
+    /// params - The trade to split
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// let split = trade.get_split_quote(&params).await?;
+    /// for allocation in &split.allocations {
+    ///     println!("{}: {} in, {} out", allocation.pool, allocation.amount_in, allocation.amount_out);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_split_quote(&self, params: &TradeParams) -> Result<SplitQuote, MeteoraError> {
+        self.validate_trade_params(params, true).await?;
+        if params.referral.is_some() {
+            return Err(MeteoraError::InvalidInput(
+                "Referral fees are not supported for split swaps".to_string(),
+            ));
+        }
+        let mut pools = self
             .pool_manager
             .find_pools_by_tokens(&params.input_mint, &params.output_mint)
             .await?;
+        pools.retain(|pool| {
+            pool.normalized_liquidity_in(&params.input_mint)
+                .is_some_and(|liquidity| liquidity > 0.0)
+        });
+        pools.sort_by(|a, b| {
+            let liquidity = |pool: &PoolInfo| {
+                pool.normalized_liquidity_in(&params.input_mint)
+                    .unwrap_or_default()
+            };
+            liquidity(b).total_cmp(&liquidity(a))
+        });
+        pools.truncate(MAX_SPLIT_POOLS);
         if pools.is_empty() {
             return Err(MeteoraError::NoLiquidityPoolFound);
         }
-        let pool_info = &pools[0];
-        let amount_out =
-            self.calculate_swap_output(params.amount_in, pool_info, &params.input_mint)?;
-        let min_amount_out = amount_out * (10000 - params.slippage_bps as u64) / 10000;
+        let matched: Vec<MatchedPool> = pools
+            .into_iter()
+            .map(|pool| MatchedPool::new(pool, &params.input_mint))
+            .collect();
+        let amounts = split_amount(params.amount_in, &matched)?;
+        let mut allocations = Vec::new();
+        for (hop, amount_in) in matched.iter().zip(amounts) {
+            if amount_in == 0 {
+                continue;
+            }
+            let amount_out = self.calculate_swap_output(amount_in, hop)?;
+            allocations.push(SplitAllocation {
+                pool: hop.pool.address,
+                amount_in,
+                amount_out,
+                min_amount_out: math::min_amount_out(amount_out, params.slippage_bps),
+                price_impact: self.calculate_price_impact(amount_in, hop)?,
+            });
+        }
+        let price_impact = allocations
+            .iter()
+            .map(|allocation| allocation.price_impact * allocation.amount_in as f64)
+            .sum::<f64>()
+            / params.amount_in as f64;
+        Ok(SplitQuote {
+            amount_out: allocations.iter().map(|a| a.amount_out).sum(),
+            min_amount_out: allocations.iter().map(|a| a.min_amount_out).sum(),
+            price_impact,
+            allocations,
+        })
+    }
+
+    /// Quotes a trade through a DLMM pool by walking its bins from the active bin
+    ///
+    /// The swap direction follows from `params.input_mint`, and the quote is
+    /// completed like `get_quote`'s: the referral fee is taken from the
+    /// output and `min_amount_out` applies the trade's slippage. Price impact
+    /// is the shortfall of the realized rate against the active bin's price.
+    /// At most `dlmm::DEFAULT_MAX_BINS` bins are traversed; larger trades fail
+    /// with `CalculationError`.
+    ///
+    /// # Params
+    /// pool - The DLMM pool's bins and configuration
+    /// params - The trade to quote
+    ///
+    /// # Example
+    /// ```
+    /// # use meteora_sdk::trade::Trade;
+    /// # use meteora_sdk::types::{DlmmBin, DlmmPool, TradeParams};
+    /// # use solana_sdk::pubkey::Pubkey;
+    /// # fn main() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let dlmm_pool = DlmmPool {
+    /// #     address: Pubkey::new_unique(),
+    /// #     token_x_mint: Pubkey::new_unique(),
+    /// #     token_y_mint: Pubkey::new_unique(),
+    /// #     active_bin_id: 0,
+    /// #     bin_step: 100,
+    /// #     fee_bps: 25,
+    /// #     bins: (-5..=5).map(|bin_id| DlmmBin { bin_id, amount_x: 1_000, amount_y: 1_000 }).collect(),
+    /// # };
+    /// # let params = TradeParams {
+    /// #     input_mint: dlmm_pool.token_x_mint,
+    /// #     output_mint: dlmm_pool.token_y_mint,
+    /// #     amount_in: 1_500,
+    /// #     slippage_bps: 100,
+    /// #     user: Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// let quote = Trade::get_dlmm_quote(&dlmm_pool, &params)?;
+    /// println!("Output: {} ({:.2}% impact)", quote.amount_out, quote.price_impact);
+    /// # assert!(quote.amount_out > 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_dlmm_quote(
+        pool: &DlmmPool,
+        params: &TradeParams,
+    ) -> Result<TradeQuote, MeteoraError> {
+        let swap_for_y = if (params.input_mint, params.output_mint)
+            == (pool.token_x_mint, pool.token_y_mint)
+        {
+            true
+        } else if (params.input_mint, params.output_mint) == (pool.token_y_mint, pool.token_x_mint)
+        {
+            false
+        } else {
+            return Err(MeteoraError::InvalidInput(format!(
+                "DLMM pool {} does not trade {} for {}",
+                pool.address, params.input_mint, params.output_mint
+            )));
+        };
+        let dlmm_quote = dlmm::quote_exact_in(
+            &pool.bins,
+            pool.active_bin_id,
+            pool.bin_step,
+            pool.fee_bps,
+            params.amount_in,
+            swap_for_y,
+            dlmm::DEFAULT_MAX_BINS,
+        )?;
+        let referral_fee_amount = params.referral.map_or(0, |referral| {
+            math::fee_amount(dlmm_quote.amount_out, referral.fee_bps as u64)
+        });
+        let amount_out = dlmm_quote.amount_out - referral_fee_amount;
         let price_impact =
-            self.calculate_price_impact(params.amount_in, pool_info, &params.input_mint)?;
+            dlmm::price_impact(&dlmm_quote, pool.active_bin_id, pool.bin_step, swap_for_y);
         Ok(TradeQuote {
             amount_out,
-            min_amount_out,
+            min_amount_out: math::min_amount_out(amount_out, params.slippage_bps),
             price_impact,
-            fee_amount: params.amount_in * pool_info.trade_fee_bps / 10000,
-            route: vec![pool_info.address],
+            fee_amount: dlmm_quote.fee_amount,
+            referral_fee_amount,
+            max_amount_in: params.amount_in,
+            route: vec![pool.address],
+            hops: vec![HopInfo {
+                pool: pool.address,
+                input_mint: params.input_mint,
+                output_mint: params.output_mint,
+                amount_in: params.amount_in,
+                amount_out: dlmm_quote.amount_out,
+                fee_amount: dlmm_quote.fee_amount,
+                price_impact,
+            }],
         })
     }
 
@@ -352,48 +1764,50 @@ impl Trade {
     fn calculate_swap_output(
         &self,
         amount_in: u64,
-        pool_info: &PoolInfo,
-        input_mint: &Pubkey,
+        matched: &MatchedPool,
     ) -> Result<u64, MeteoraError> {
-        let (input_reserve, output_reserve) = if *input_mint == pool_info.token_a_mint {
-            (
-                pool_info.token_a_reserve_amount,
-                pool_info.token_b_reserve_amount,
-            )
-        } else {
-            (
-                pool_info.token_b_reserve_amount,
-                pool_info.token_a_reserve_amount,
-            )
-        };
-        let amount_in_with_fee = amount_in * (10000 - pool_info.trade_fee_bps) / 10000;
-        let numerator = amount_in_with_fee * output_reserve;
-        let denominator = input_reserve * 10000 + amount_in_with_fee;
-        if denominator == 0 {
+        let (input_reserve, output_reserve) = matched.reserve_amounts();
+        if input_reserve == 0 || output_reserve == 0 {
             return Err(MeteoraError::CalculationError(
-                "Division by zero".to_string(),
+                "Pool has no liquidity".to_string(),
             ));
         }
-        Ok(numerator / denominator)
+        matched.swap_output(amount_in)
+    }
+
+    /// Calculates the output of swapping through every hop of a route in order
+    fn calculate_route_output(
+        &self,
+        amount_in: u64,
+        hops: &[MatchedPool],
+    ) -> Result<u64, MeteoraError> {
+        hops.iter().try_fold(amount_in, |amount, hop| {
+            self.calculate_swap_output(amount, hop)
+        })
+    }
+
+    /// Calculates the compounded price impact of swapping through a route
+    fn calculate_route_price_impact(
+        &self,
+        amount_in: u64,
+        hops: &[MatchedPool],
+    ) -> Result<f64, MeteoraError> {
+        let mut amount = amount_in;
+        let mut remaining = 1.0;
+        for hop in hops {
+            remaining *= 1.0 - self.calculate_price_impact(amount, hop)? / 100.0;
+            amount = self.calculate_swap_output(amount, hop)?;
+        }
+        Ok((1.0 - remaining) * 100.0)
     }
 
     /// Calculates price impact of the swap
     fn calculate_price_impact(
         &self,
         amount_in: u64,
-        pool_info: &PoolInfo,
-        input_mint: &Pubkey,
+        matched: &MatchedPool,
     ) -> Result<f64, MeteoraError> {
-        let input_reserve = if *input_mint == pool_info.token_a_mint {
-            pool_info.token_a_reserve_amount
-        } else {
-            pool_info.token_b_reserve_amount
-        };
-        if input_reserve == 0 {
-            return Ok(100.0);
-        }
-        let price_impact = (amount_in as f64) / (input_reserve as f64 + amount_in as f64) * 100.0;
-        Ok(price_impact)
+        matched.price_impact(amount_in)
     }
 
     async fn build_swap_instructions(
@@ -401,56 +1815,296 @@ impl Trade {
         params: &TradeParams,
         quote: &TradeQuote,
     ) -> Result<Vec<Instruction>, MeteoraError> {
-        let pool_info = self.pool_manager.get_pool_info(&quote.route[0]).await?;
         let user_input_account =
             self.get_associated_token_address(&params.user, &params.input_mint);
         let user_output_account =
             self.get_associated_token_address(&params.user, &params.output_mint);
-        let mut instructions = Vec::new();
-        if let Err(_) = self.client.get_account_data(&user_output_account).await {
-            instructions.push(
-                self.create_associated_token_account_instruction(&params.user, &params.output_mint),
-            );
+        let mut instructions = self.build_compute_budget_instructions();
+        let output_balance = match self.client.get_account(&user_output_account).await {
+            Ok(account) => unpack_token_account(&user_output_account, &account)?.amount,
+            Err(_) => {
+                instructions.push(self.create_associated_token_account_instruction(
+                    &params.user,
+                    &params.output_mint,
+                ));
+                0
+            }
+        };
+        let wrap_input = self.wrap_sol && params.input_mint == spl_token::native_mint::ID;
+        let unwrap_output = self.wrap_sol && params.output_mint == spl_token::native_mint::ID;
+        if wrap_input {
+            instructions.extend(self.build_wrap_sol_instructions(&params.user, params.amount_in)?);
         }
-        let swap_instruction = self.build_meteora_swap_instruction(
+        // Each hop spends exactly the previous hop's minimum output, so any
+        // surplus from an intermediate hop stays in the user's account. The
+        // last hop must also cover the referral fee paid after it.
+        let mut hop_input_mint = params.input_mint;
+        let mut hop_input_account = user_input_account;
+        let mut hop_amount_in = params.amount_in;
+        for (index, pool_address) in quote.route.iter().enumerate() {
+            let pool_info = self.pool_manager.get_pool_info(pool_address).await?;
+            let is_last_hop = index + 1 == quote.route.len();
+            let (hop_output_mint, hop_output_account, hop_min_amount_out) = if is_last_hop {
+                (
+                    params.output_mint,
+                    user_output_account,
+                    quote.min_amount_out + quote.referral_fee_amount,
+                )
+            } else {
+                let matched = MatchedPool::new(pool_info.clone(), &hop_input_mint);
+                let intermediate_mint = if matched.input_is_token_a {
+                    pool_info.token_b_mint
+                } else {
+                    pool_info.token_a_mint
+                };
+                instructions.push(create_associated_token_account_idempotent(
+                    &params.user,
+                    &params.user,
+                    &intermediate_mint,
+                    &spl_token::id(),
+                ));
+                let expected_out = self.calculate_swap_output(hop_amount_in, &matched)?;
+                (
+                    intermediate_mint,
+                    self.get_associated_token_address(&params.user, &intermediate_mint),
+                    math::min_amount_out(expected_out, params.slippage_bps),
+                )
+            };
+            instructions.push(Self::build_meteora_swap_instruction(
+                &params.user,
+                &pool_info,
+                &hop_input_mint,
+                &hop_input_account,
+                &hop_output_account,
+                hop_amount_in,
+                hop_min_amount_out,
+            )?);
+            hop_input_mint = hop_output_mint;
+            hop_input_account = hop_output_account;
+            hop_amount_in = hop_min_amount_out;
+        }
+        instructions.extend(Self::build_referral_fee_instruction(
             params,
-            quote,
-            &pool_info,
-            &user_input_account,
             &user_output_account,
-        )?;
-        instructions.push(swap_instruction);
+            quote.referral_fee_amount,
+        )?);
+        if let Some(guard_program) = self.output_guard_program {
+            instructions.push(self.build_output_guard_instruction(
+                &guard_program,
+                &user_output_account,
+                output_balance.saturating_add(quote.min_amount_out),
+            ));
+        }
+        if wrap_input {
+            instructions
+                .push(self.build_close_wsol_instruction(&params.user, &user_input_account)?);
+        }
+        if unwrap_output {
+            instructions
+                .push(self.build_close_wsol_instruction(&params.user, &user_output_account)?);
+        }
         Ok(instructions)
     }
 
-    fn build_meteora_swap_instruction(
+    /// Builds the transfer paying a trade's referral fee from the user's
+    /// output token account to the referral's fee account
+    ///
+    /// Returns `None` for trades without a referral or with a zero fee.
+    fn build_referral_fee_instruction(
+        params: &TradeParams,
+        user_output_account: &Pubkey,
+        referral_fee_amount: u64,
+    ) -> Result<Option<Instruction>, MeteoraError> {
+        let Some(referral) = &params.referral else {
+            return Ok(None);
+        };
+        if referral_fee_amount == 0 {
+            return Ok(None);
+        }
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            user_output_account,
+            &referral.fee_account,
+            &params.user,
+            &[],
+            referral_fee_amount,
+        )
+        .map(Some)
+        .map_err(|e| MeteoraError::Error(e.to_string()))
+    }
+
+    /// Builds the instructions for a split swap, with one swap per allocation
+    ///
+    /// Each swap spends its allocation from the user's input token account
+    /// and must return at least the allocation's `min_amount_out`. Compute
+    /// budget, SOL wrapping and the output guard are handled as for a single
+    /// swap.
+    ///
+    /// # Params
+    /// params - The trade that was split
+    /// split - The split quote from `get_split_quote`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let params = meteora_sdk::types::TradeParams {
+    /// #     input_mint: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     output_mint: spl_token::native_mint::ID,
+    /// #     amount_in: 100_000_000,
+    /// #     slippage_bps: 100,
+    /// #     user: solana_sdk::pubkey::Pubkey::new_unique(),
+    /// #     deadline: None,
+    /// #     referral: None,
+    /// # };
+    /// let split = trade.get_split_quote(&params).await?;
+    /// let instructions = trade.build_split_swap_instructions(&params, &split).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build_split_swap_instructions(
         &self,
         params: &TradeParams,
-        quote: &TradeQuote,
+        split: &SplitQuote,
+    ) -> Result<Vec<Instruction>, MeteoraError> {
+        let user_input_account =
+            self.get_associated_token_address(&params.user, &params.input_mint);
+        let user_output_account =
+            self.get_associated_token_address(&params.user, &params.output_mint);
+        let mut instructions = self.build_compute_budget_instructions();
+        let output_balance = match self.client.get_account(&user_output_account).await {
+            Ok(account) => unpack_token_account(&user_output_account, &account)?.amount,
+            Err(_) => {
+                instructions.push(self.create_associated_token_account_instruction(
+                    &params.user,
+                    &params.output_mint,
+                ));
+                0
+            }
+        };
+        let wrap_input = self.wrap_sol && params.input_mint == spl_token::native_mint::ID;
+        let unwrap_output = self.wrap_sol && params.output_mint == spl_token::native_mint::ID;
+        if wrap_input {
+            let total_in = split.allocations.iter().map(|a| a.amount_in).sum();
+            instructions.extend(self.build_wrap_sol_instructions(&params.user, total_in)?);
+        }
+        for allocation in &split.allocations {
+            let pool_info = self.pool_manager.get_pool_info(&allocation.pool).await?;
+            instructions.push(Self::build_meteora_swap_instruction(
+                &params.user,
+                &pool_info,
+                &params.input_mint,
+                &user_input_account,
+                &user_output_account,
+                allocation.amount_in,
+                allocation.min_amount_out,
+            )?);
+        }
+        if let Some(guard_program) = self.output_guard_program {
+            instructions.push(self.build_output_guard_instruction(
+                &guard_program,
+                &user_output_account,
+                output_balance.saturating_add(split.min_amount_out),
+            ));
+        }
+        if wrap_input {
+            instructions
+                .push(self.build_close_wsol_instruction(&params.user, &user_input_account)?);
+        }
+        if unwrap_output {
+            instructions
+                .push(self.build_close_wsol_instruction(&params.user, &user_output_account)?);
+        }
+        Ok(instructions)
+    }
+
+    /// Builds the instructions that move `lamports` of native SOL into the
+    /// user's WSOL account: create it if needed, transfer, then sync
+    fn build_wrap_sol_instructions(
+        &self,
+        user: &Pubkey,
+        lamports: u64,
+    ) -> Result<Vec<Instruction>, MeteoraError> {
+        let wsol_account = self.get_associated_token_address(user, &spl_token::native_mint::ID);
+        Ok(vec![
+            create_associated_token_account_idempotent(
+                user,
+                user,
+                &spl_token::native_mint::ID,
+                &spl_token::id(),
+            ),
+            solana_system_interface::instruction::transfer(user, &wsol_account, lamports),
+            spl_token::instruction::sync_native(&spl_token::id(), &wsol_account)
+                .map_err(|e| MeteoraError::Error(e.to_string()))?,
+        ])
+    }
+
+    /// Builds an instruction closing a WSOL account, unwrapping its whole
+    /// balance back to native SOL in the user's wallet
+    fn build_close_wsol_instruction(
+        &self,
+        user: &Pubkey,
+        wsol_account: &Pubkey,
+    ) -> Result<Instruction, MeteoraError> {
+        spl_token::instruction::close_account(&spl_token::id(), wsol_account, user, user, &[])
+            .map_err(|e| MeteoraError::Error(e.to_string()))
+    }
+
+    /// Builds the program's `swap` instruction
+    ///
+    /// Accounts follow the Dynamic AMM IDL order: pool, user source and
+    /// destination token accounts, the A and B vaults, their token vaults,
+    /// their LP mints, the pool's LP accounts in each vault, the protocol fee
+    /// account for `input_mint`, user, vault program and token program. All
+    /// but the pool and user token accounts are PDAs derived from the pool's
+    /// mints. The program infers the direction from the source account's
+    /// mint. Data is the 8-byte Anchor discriminator followed by `in_amount`
+    /// and `minimum_out_amount` as little-endian u64s.
+    fn build_meteora_swap_instruction(
+        user: &Pubkey,
         pool_info: &PoolInfo,
+        input_mint: &Pubkey,
         user_input_account: &Pubkey,
         user_output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
     ) -> Result<Instruction, MeteoraError> {
-        let (input_reserve, output_reserve) = if params.input_mint == pool_info.token_a_mint {
-            (&pool_info.token_a_reserve, &pool_info.token_b_reserve)
-        } else {
-            (&pool_info.token_b_reserve, &pool_info.token_a_reserve)
-        };
+        let a_vault = pool::vault_address(&pool_info.token_a_mint);
+        let b_vault = pool::vault_address(&pool_info.token_b_mint);
         let accounts = vec![
             AccountMeta::new(pool_info.address, false),
-            AccountMeta::new_readonly(self.get_pool_authority(&pool_info.address)?, false),
-            AccountMeta::new(params.user, true),
             AccountMeta::new(*user_input_account, false),
-            AccountMeta::new(*input_reserve, false),
-            AccountMeta::new(*output_reserve, false),
             AccountMeta::new(*user_output_account, false),
-            AccountMeta::new(pool_info.fee_account, false),
+            AccountMeta::new(a_vault, false),
+            AccountMeta::new(b_vault, false),
+            AccountMeta::new(pool::vault_token_account(&a_vault), false),
+            AccountMeta::new(pool::vault_token_account(&b_vault), false),
+            AccountMeta::new(pool::vault_lp_mint(&a_vault), false),
+            AccountMeta::new(pool::vault_lp_mint(&b_vault), false),
+            AccountMeta::new(
+                pool::pool_vault_lp_account(&a_vault, &pool_info.address),
+                false,
+            ),
+            AccountMeta::new(
+                pool::pool_vault_lp_account(&b_vault, &pool_info.address),
+                false,
+            ),
+            AccountMeta::new(
+                pool::protocol_fee_account(input_mint, &pool_info.address),
+                false,
+            ),
+            AccountMeta::new_readonly(*user, true),
+            AccountMeta::new_readonly(Pubkey::from_str(METEORA_VAULT_PROGRAM_ID).unwrap(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ];
-        let mut data = Vec::new();
-        data.push(9);
-        data.extend_from_slice(&params.amount_in.to_le_bytes());
-        data.extend_from_slice(&quote.min_amount_out.to_le_bytes());
+        let mut data = Vec::with_capacity(24);
+        data.extend_from_slice(&SWAP_DISCRIMINATOR);
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_amount_out.to_le_bytes());
         Ok(Instruction {
             program_id: Pubkey::from_str(METEORA_PROGRAM_ID).unwrap(),
             accounts,
@@ -458,13 +2112,50 @@ impl Trade {
         })
     }
 
-    fn get_pool_authority(&self, pool_address: &Pubkey) -> Result<Pubkey, MeteoraError> {
-        let (authority, _bump) = Pubkey::find_program_address(
-            &[b"amm", pool_address.as_ref()],
-            &Pubkey::from_str(METEORA_PROGRAM_ID).unwrap(),
-        );
-        Ok(authority)
+    /// Builds the compute unit limit and price instructions, in that order,
+    /// for whichever of the two are configured
+    fn build_compute_budget_instructions(&self) -> Vec<Instruction> {
+        let program_id = Pubkey::from_str(COMPUTE_BUDGET_PROGRAM_ID).unwrap();
+        let mut instructions = Vec::new();
+        if let Some(limit) = self.priority_fee_config.compute_unit_limit {
+            let mut data = vec![2];
+            data.extend_from_slice(&limit.to_le_bytes());
+            instructions.push(Instruction {
+                program_id,
+                accounts: vec![],
+                data,
+            });
+        }
+        if let Some(price) = self.priority_fee_config.compute_unit_price_micro_lamports {
+            let mut data = vec![3];
+            data.extend_from_slice(&price.to_le_bytes());
+            instructions.push(Instruction {
+                program_id,
+                accounts: vec![],
+                data,
+            });
+        }
+        instructions
     }
+
+    /// Builds an instruction that aborts the transaction when `token_account`
+    /// holds less than `min_balance`
+    ///
+    /// The guard program receives the token account as its only, read-only
+    /// account and `min_balance` as a little-endian u64 in the instruction data.
+    fn build_output_guard_instruction(
+        &self,
+        guard_program: &Pubkey,
+        token_account: &Pubkey,
+        min_balance: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: *guard_program,
+            accounts: vec![AccountMeta::new_readonly(*token_account, false)],
+            data: min_balance.to_le_bytes().to_vec(),
+        }
+    }
+
     fn get_associated_token_address(&self, wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
         get_associated_token_address(wallet, mint)
     }
@@ -479,13 +2170,23 @@ impl Trade {
     /// Builds a token approve instruction
     ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let (token_account, delegate_pubkey) = (solana_sdk::pubkey::Pubkey::new_unique(), solana_sdk::pubkey::Pubkey::new_unique());
+    /// # let user_pubkey = solana_sdk::pubkey::Pubkey::new_unique();
     /// let approve_ix = trade.build_approve_instruction(
     ///     &user_pubkey,
     ///     &token_account,
     ///     &delegate_pubkey,
     ///     100_000_000,
     /// )?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn build_approve_instruction(
         &self,
@@ -501,21 +2202,29 @@ impl Trade {
             owner,
             &[],
             amount,
-        )
-        .map_err(|e| MeteoraError::DeserializationError(e.to_string()))?;
+        )?;
         Ok(instruction)
     }
 
     /// Builds a token transfer instruction
     ///
     /// # Example
-    /// ```
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let (from_account, to_account, owner_pubkey) = (solana_sdk::pubkey::Pubkey::new_unique(), solana_sdk::pubkey::Pubkey::new_unique(), solana_sdk::pubkey::Pubkey::new_unique());
     /// let transfer_ix = trade.build_transfer_instruction(
     ///     &from_account,
     ///     &to_account,
     ///     &owner_pubkey,
     ///     50_000_000,
     /// )?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn build_transfer_instruction(
         &self,
@@ -525,36 +2234,1415 @@ impl Trade {
         amount: u64,
     ) -> Result<Instruction, MeteoraError> {
         let instruction =
-            spl_token::instruction::transfer(&spl_token::id(), from, to, owner, &[], amount)
-                .map_err(|e| MeteoraError::DeserializationError(e.to_string()))?;
+            spl_token::instruction::transfer(&spl_token::id(), from, to, owner, &[], amount)?;
         Ok(instruction)
     }
 
-    /// Confirms transaction status
+    /// Decodes the first Meteora swap of a confirmed transaction
+    ///
+    /// See `decode_swaps`, which also returns the later swaps of multi-hop
+    /// routes and transactions with several swaps.
+    ///
+    /// # Params
+    /// signature - The transaction signature
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let signature = String::new();
+    /// let swap = trade.decode_swap(&signature).await?;
+    /// println!("{} {} -> {} {}", swap.amount_in, swap.input_mint, swap.amount_out, swap.output_mint);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn decode_swap(&self, signature: &str) -> Result<DecodedSwap, MeteoraError> {
+        self.decode_swaps(signature)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                MeteoraError::DeserializationError("Transaction has no Meteora swap".to_string())
+            })
+    }
+
+    /// Decodes every Meteora swap of a confirmed transaction, in execution order
+    ///
+    /// Pools are found from the Meteora swap instructions, including ones
+    /// invoked by other programs such as aggregators, and instructions of
+    /// other programs are ignored. Amounts come from the balance changes of
+    /// each pool's reserves, so several swaps through the same pool in one
+    /// transaction are reported as their net swap. The fee is the pool's
+    /// current trade fee applied to the input amount.
+    ///
+    /// # Params
+    /// signature - The transaction signature
     ///
     /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let signature = String::new();
+    /// for swap in trade.decode_swaps(&signature).await? {
+    ///     println!("{}: {} in, {} out", swap.pool, swap.amount_in, swap.amount_out);
+    /// }
+    /// # Ok(())
+    /// # }
     /// ```
+    pub async fn decode_swaps(&self, signature: &str) -> Result<Vec<DecodedSwap>, MeteoraError> {
+        let signature: Signature = signature
+            .parse()
+            .map_err(|_| MeteoraError::Error("Invalid signature".to_string()))?;
+        let tx = self.client.get_transaction(&signature).await?;
+        let mut swaps = Vec::new();
+        for pool in meteora_swap_pools(&tx)? {
+            let pool_info = self.pool_manager.get_pool_info(&pool).await?;
+            // Pools whose reserves didn't move one in and one out, e.g. when
+            // the swap was netted out by another one, are skipped
+            let Ok(swap) = decode_pool_swap(&tx, &pool_info, &pool_info.token_a_mint, 0.0) else {
+                continue;
+            };
+            swaps.push(DecodedSwap {
+                pool,
+                input_mint: swap.input_mint,
+                output_mint: swap.output_mint,
+                amount_in: swap.input_amount,
+                amount_out: swap.output_amount,
+                fee: math::fee_amount(swap.input_amount, pool_info.trade_fee_bps),
+                timestamp: swap.timestamp,
+            });
+        }
+        Ok(swaps)
+    }
+
+    /// Confirms transaction status
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use meteora_sdk::{MeteoraClient, trade::Trade};
+    /// # use solana_network_sdk::types::Mode;
+    /// # async fn example() -> Result<(), meteora_sdk::types::MeteoraError> {
+    /// # let client = Arc::new(MeteoraClient::new(Mode::MAIN)?);
+    /// # let trade = Trade::new(client);
+    /// # let signature = String::new();
     /// let confirmed = trade.confirm_transaction(&signature).await?;
     /// if confirmed {
     ///     println!("Transaction confirmed!");
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn confirm_transaction(&self, signature: &str) -> Result<bool, MeteoraError> {
-        match self
+        let signatures = &[signature.parse().unwrap()];
+        let statuses = self
             .client
-            .solana
-            .client_arc()
-            .get_signature_statuses(&[signature.parse().unwrap()])
+            .rpc(|rpc| async move { rpc.get_signature_statuses(signatures).await })
+            .await?;
+        if let Some(status) = statuses.value.first().and_then(|s| s.as_ref()) {
+            Ok(status.err.is_none())
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+/// Every problem with `params` that can be found without RPC calls
+fn trade_param_issues(params: &TradeParams) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    if params.amount_in == 0 {
+        issues.push(ValidationIssue::ZeroAmount);
+    }
+    if params.slippage_bps > MAX_SLIPPAGE_BPS {
+        issues.push(ValidationIssue::SlippageTooHigh {
+            slippage_bps: params.slippage_bps,
+            max_bps: MAX_SLIPPAGE_BPS,
+        });
+    }
+    if params.input_mint == params.output_mint {
+        issues.push(ValidationIssue::SameToken);
+    }
+    if let Some(referral) = &params.referral
+        && referral.fee_bps > MAX_REFERRAL_FEE_BPS
+    {
+        issues.push(ValidationIssue::ReferralFeeTooHigh {
+            fee_bps: referral.fee_bps,
+            max_bps: MAX_REFERRAL_FEE_BPS,
+        });
+    }
+    issues
+}
+
+/// Fails for pools whose curve the closed-form constant-product math doesn't fit
+fn require_constant_product(pool: &PoolInfo) -> Result<(), MeteoraError> {
+    match pool.curve_type {
+        CurveType::ConstantProduct => Ok(()),
+        curve_type => Err(MeteoraError::CalculationError(format!(
+            "Unsupported curve {:?} for pool {}",
+            curve_type, pool.address
+        ))),
+    }
+}
+
+/// Splits `amount_in` across `pools` one chunk at a time, giving each chunk
+/// to the pool with the highest output for it
+///
+/// Returns the amount allocated to each pool, in the order of `pools`.
+fn split_amount(amount_in: u64, pools: &[MatchedPool]) -> Result<Vec<u64>, MeteoraError> {
+    let chunk = (amount_in / SPLIT_CHUNKS).max(1);
+    let mut allocated = vec![0u64; pools.len()];
+    let mut outputs = vec![0u64; pools.len()];
+    let mut remaining = amount_in;
+    while remaining > 0 {
+        // The last chunk also takes the remainder of the division
+        let size = if remaining < 2 * chunk {
+            remaining
+        } else {
+            chunk
+        };
+        let mut best: Option<(usize, u64)> = None;
+        for (index, pool) in pools.iter().enumerate() {
+            let output = pool.swap_output(allocated[index] + size)?;
+            let marginal = output.saturating_sub(outputs[index]);
+            if best.is_none_or(|(_, best_marginal)| marginal > best_marginal) {
+                best = Some((index, marginal));
+            }
+        }
+        let Some((index, marginal)) = best else {
+            return Err(MeteoraError::NoLiquidityPoolFound);
+        };
+        allocated[index] += size;
+        outputs[index] += marginal;
+        remaining -= size;
+    }
+    Ok(allocated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        layout::{PoolLayout, parse_pool_account},
+        test_util::{
+            MockReply, MockRpc, PoolFixture, program_account, signature_status, token_account,
+        },
+        types::{DlmmBin, ReferralConfig},
+    };
+    use serde_json::json;
+    use solana_commitment_config::CommitmentConfig;
+
+    fn trade_params(input_mint: Pubkey, output_mint: Pubkey, amount_in: u64) -> TradeParams {
+        TradeParams {
+            input_mint,
+            output_mint,
+            amount_in,
+            slippage_bps: 100,
+            user: Pubkey::new_unique(),
+            deadline: None,
+            referral: None,
+        }
+    }
+
+    fn dlmm_pool() -> DlmmPool {
+        DlmmPool {
+            address: Pubkey::new_unique(),
+            token_x_mint: Pubkey::new_unique(),
+            token_y_mint: Pubkey::new_unique(),
+            active_bin_id: 0,
+            bin_step: 100,
+            fee_bps: 0,
+            bins: (-5..=5)
+                .map(|bin_id| DlmmBin {
+                    bin_id,
+                    amount_x: 1_000,
+                    amount_y: 1_000,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn dlmm_quote_follows_the_input_mint() {
+        let pool = dlmm_pool();
+        let sell_x = trade_params(pool.token_x_mint, pool.token_y_mint, 2_510);
+        let quote = Trade::get_dlmm_quote(&pool, &sell_x).unwrap();
+        let expected = dlmm::quote_exact_in(&pool.bins, 0, 100, 0, 2_510, true, 70).unwrap();
+        assert_eq!(quote.amount_out, expected.amount_out);
+        assert_eq!(
+            quote.min_amount_out,
+            math::min_amount_out(expected.amount_out, 100)
+        );
+        assert_eq!(quote.route, vec![pool.address]);
+        assert_eq!(quote.hops[0].input_mint, pool.token_x_mint);
+        assert!(quote.price_impact > 0.0);
+
+        let sell_y = trade_params(pool.token_y_mint, pool.token_x_mint, 2_510);
+        let expected = dlmm::quote_exact_in(&pool.bins, 0, 100, 0, 2_510, false, 70).unwrap();
+        assert_eq!(
+            Trade::get_dlmm_quote(&pool, &sell_y).unwrap().amount_out,
+            expected.amount_out
+        );
+    }
+
+    #[test]
+    fn dlmm_quote_takes_the_referral_fee_from_output() {
+        let pool = dlmm_pool();
+        let mut params = trade_params(pool.token_x_mint, pool.token_y_mint, 500);
+        params.referral = Some(ReferralConfig {
+            fee_account: Pubkey::new_unique(),
+            fee_bps: 100,
+        });
+        let quote = Trade::get_dlmm_quote(&pool, &params).unwrap();
+        assert_eq!(quote.referral_fee_amount, 5);
+        assert_eq!(quote.amount_out, 495);
+    }
+
+    #[test]
+    fn dlmm_quote_rejects_other_mints() {
+        let pool = dlmm_pool();
+        let params = trade_params(pool.token_x_mint, Pubkey::new_unique(), 500);
+        assert!(matches!(
+            Trade::get_dlmm_quote(&pool, &params),
+            Err(MeteoraError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn direct_quotes_of_a_pair_without_pools_fail_before_loading_any() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000,
+            1_000_000,
+        );
+        let trade = Trade::new(rpc.client());
+        let lonely_mint = Pubkey::new_unique();
+        assert!(matches!(
+            trade
+                .get_quote_exact_out(
+                    &pool.token_a_mint,
+                    &lonely_mint,
+                    100,
+                    1_000,
+                    100,
+                    Pubkey::new_unique()
+                )
+                .await,
+            Err(MeteoraError::NoLiquidityPoolFound)
+        ));
+        let params = trade_params(pool.token_a_mint, lonely_mint, 1_000);
+        assert!(matches!(
+            trade.get_split_quote(&params).await,
+            Err(MeteoraError::NoLiquidityPoolFound)
+        ));
+        assert_eq!(rpc.call_count("getMultipleAccounts"), 0);
+
+        let quote = trade
+            .get_quote_exact_out(
+                &pool.token_a_mint,
+                &pool.token_b_mint,
+                100,
+                1_000,
+                100,
+                Pubkey::new_unique(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(quote.route, vec![pool.address]);
+    }
+
+    /// A pool with every account its swaps reference, and a quote selling token A into it
+    async fn routed_pool(rpc: &MockRpc) -> (PoolFixture, TradeQuote) {
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000,
+            1_000_000,
+        );
+        rpc.add_swap_accounts(&pool);
+        let pool_info = PoolManager::new(rpc.client())
+            .get_pool_info(&pool.address)
+            .await
+            .unwrap();
+        let quote = Trade::quote_from_pool(&pool_info, 1_000, &pool.token_a_mint, 100).unwrap();
+        (pool, quote)
+    }
+
+    #[tokio::test]
+    async fn route_accounts_pass_with_token_2022_mints() {
+        let rpc = MockRpc::new();
+        let (pool, quote) = routed_pool(&rpc).await;
+        let trade = Trade::new(rpc.client());
+        trade.validate_route_accounts(&quote).await.unwrap();
+
+        let b_vault = pool::vault_address(&pool.token_b_mint);
+        for address in [
+            pool.token_b_mint,
+            pool.token_b_reserve,
+            pool::vault_token_account(&b_vault),
+        ] {
+            let mut account = rpc.account(&address).unwrap();
+            account.owner = spl_token_2022_interface::id();
+            rpc.set_account(address, account);
+        }
+        trade.validate_route_accounts(&quote).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_fee_account_is_named() {
+        let rpc = MockRpc::new();
+        let (pool, quote) = routed_pool(&rpc).await;
+        rpc.remove_account(&pool::protocol_fee_account(
+            &pool.token_a_mint,
+            &pool.address,
+        ));
+        let trade = Trade::new(rpc.client());
+        match trade.validate_route_accounts(&quote).await {
+            Err(MeteoraError::AccountNotFound(message)) => {
+                assert!(message.starts_with("protocol fee account"), "{}", message)
+            }
+            other => panic!("expected a missing fee account, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn mis_owned_vault_is_named() {
+        let rpc = MockRpc::new();
+        let (pool, quote) = routed_pool(&rpc).await;
+        let a_vault = pool::vault_address(&pool.token_a_mint);
+        rpc.set_account(
+            a_vault,
+            crate::test_util::program_account(Pubkey::new_unique(), vec![0; 8]),
+        );
+        let trade = Trade::new(rpc.client());
+        match trade.validate_route_accounts(&quote).await {
+            Err(MeteoraError::InvalidAccountOwner(message)) => {
+                assert!(message.starts_with("token a vault "), "{}", message)
+            }
+            other => panic!("expected a mis-owned vault, got {:?}", other),
+        }
+    }
+
+    fn confirm_trade(rpc: &MockRpc) -> Trade {
+        Trade::new_with_confirm_config(
+            rpc.client(),
+            ConfirmConfig {
+                max_resends: 2,
+                timeout_secs: 1,
+                poll_interval: std::time::Duration::from_millis(10),
+                ..ConfirmConfig::default()
+            },
+        )
+    }
+
+    /// An unsigned transfer from `payer` with a preset blockhash
+    fn transfer_transaction(payer: &Keypair) -> Transaction {
+        let instruction = solana_system_interface::instruction::transfer(
+            &payer.pubkey(),
+            &Pubkey::new_unique(),
+            1_000,
+        );
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.message.recent_blockhash = Hash::new_from_array([7; 32]);
+        transaction
+    }
+
+    #[tokio::test]
+    async fn submit_resends_with_a_fresh_blockhash_after_the_first_expires() {
+        let rpc = MockRpc::new();
+        let sends = rpc.clone();
+        rpc.on("getSignatureStatuses", move |_| {
+            if sends.call_count("sendTransaction") > 1 {
+                signature_status("confirmed")
+            } else {
+                MockReply::Result(json!({ "context": { "slot": 1 }, "value": [null] }))
+            }
+        });
+        rpc.on("isBlockhashValid", |_| {
+            MockReply::Result(json!({ "context": { "slot": 1 }, "value": false }))
+        });
+        let payer = Keypair::new();
+        let signature = confirm_trade(&rpc)
+            .submit_and_confirm(transfer_transaction(&payer), &payer, None)
+            .await
+            .unwrap();
+
+        let sent = rpc.sent_transactions();
+        assert_eq!(sent.len(), 2);
+        assert_ne!(
+            sent[0].message.recent_blockhash,
+            sent[1].message.recent_blockhash
+        );
+        assert_eq!(signature, sent[1].signatures[0].to_string());
+    }
+
+    #[tokio::test]
+    async fn a_finalized_commitment_reaches_simulation_and_confirmation() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        );
+        let client = Arc::new(
+            rpc.client_builder()
+                .commitment(CommitmentConfig::finalized())
+                .build()
+                .unwrap(),
+        );
+        let trade = Trade::new_with_confirm_config(
+            client,
+            ConfirmConfig {
+                max_resends: 0,
+                timeout_secs: 1,
+                poll_interval: std::time::Duration::from_millis(10),
+                commitment: CommitmentConfig::finalized(),
+            },
+        );
+
+        let params = trade_params(pool.token_a_mint, pool.token_b_mint, 1_000_000);
+        trade.simulate_quote(&params).await.unwrap();
+        let simulations = rpc.calls("simulateTransaction");
+        assert_eq!(simulations.len(), 1);
+        assert_eq!(simulations[0][1]["commitment"], "finalized");
+
+        // A confirmed transaction hasn't reached the finalized target yet
+        rpc.on("getSignatureStatuses", |_| signature_status("confirmed"));
+        let payer = Keypair::new();
+        match trade
+            .submit_and_confirm(transfer_transaction(&payer), &payer, None)
             .await
         {
-            Ok(statuses) => {
-                if let Some(status) = statuses.value.get(0).and_then(|s| s.as_ref()) {
-                    Ok(status.err.is_none())
+            Err(MeteoraError::TransactionTimeout) => {}
+            other => panic!("expected a confirmation timeout, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn submit_signs_with_the_transactions_blockhash() {
+        let rpc = MockRpc::new();
+        rpc.on("getSignatureStatuses", |_| signature_status("confirmed"));
+        let payer = Keypair::new();
+        let transaction = transfer_transaction(&payer);
+        let blockhash = transaction.message.recent_blockhash;
+        confirm_trade(&rpc)
+            .submit_and_confirm(transaction, &payer, None)
+            .await
+            .unwrap();
+
+        let sent = rpc.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].message.recent_blockhash, blockhash);
+        assert!(sent[0].verify().is_ok());
+        assert_eq!(rpc.call_count("getLatestBlockhash"), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn submit_times_out_when_a_landed_transaction_never_confirms() {
+        let rpc = MockRpc::new();
+        rpc.on("getSignatureStatuses", |_| signature_status("processed"));
+        let payer = Keypair::new();
+        let result = confirm_trade(&rpc)
+            .submit_and_confirm(transfer_transaction(&payer), &payer, None)
+            .await;
+
+        assert!(matches!(result, Err(MeteoraError::TransactionTimeout)));
+        // A landed transaction must not be resent
+        assert_eq!(rpc.call_count("sendTransaction"), 1);
+    }
+
+    #[tokio::test]
+    async fn swap_past_its_deadline_is_never_sent() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        );
+        let payer = Keypair::new();
+        let mut params = trade_params(pool.token_a_mint, pool.token_b_mint, 1_000);
+        params.user = payer.pubkey();
+        params.deadline = Some(chrono::Utc::now().timestamp() - 1);
+        let result = confirm_trade(&rpc).execute_swap_safe(&params, &payer).await;
+
+        assert!(matches!(result, Err(MeteoraError::DeadlineExceeded)));
+        assert_eq!(rpc.call_count("sendTransaction"), 0);
+    }
+
+    #[test]
+    fn swap_instruction_follows_the_idl_account_order() {
+        let fixture = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let mut pool_info =
+            parse_pool_account(&fixture.account_data(PoolLayout::V1, 25, 10_000)).unwrap();
+        pool_info.address = fixture.address;
+        let user = Pubkey::new_unique();
+        let (source, destination) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let instruction = Trade::build_meteora_swap_instruction(
+            &user,
+            &pool_info,
+            &fixture.token_b_mint,
+            &source,
+            &destination,
+            1_000,
+            990,
+        )
+        .unwrap();
+
+        assert_eq!(instruction.data[..8], SWAP_DISCRIMINATOR);
+        assert_eq!(instruction.data[8..16], 1_000u64.to_le_bytes());
+        assert_eq!(instruction.data[16..24], 990u64.to_le_bytes());
+        let a_vault = pool::vault_address(&fixture.token_a_mint);
+        let b_vault = pool::vault_address(&fixture.token_b_mint);
+        let vault_program = Pubkey::from_str(METEORA_VAULT_PROGRAM_ID).unwrap();
+        let expected = [
+            fixture.address,
+            source,
+            destination,
+            a_vault,
+            b_vault,
+            pool::vault_token_account(&a_vault),
+            pool::vault_token_account(&b_vault),
+            pool::vault_lp_mint(&a_vault),
+            pool::vault_lp_mint(&b_vault),
+            pool::pool_vault_lp_account(&a_vault, &fixture.address),
+            pool::pool_vault_lp_account(&b_vault, &fixture.address),
+            pool::protocol_fee_account(&fixture.token_b_mint, &fixture.address),
+            user,
+            vault_program,
+            spl_token::id(),
+        ];
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|a| a.pubkey).collect();
+        assert_eq!(keys, expected);
+        let signers: Vec<Pubkey> = instruction
+            .accounts
+            .iter()
+            .filter(|a| a.is_signer)
+            .map(|a| a.pubkey)
+            .collect();
+        assert_eq!(signers, [user]);
+        assert!(instruction.accounts[..12].iter().all(|a| a.is_writable));
+        assert!(instruction.accounts[12..].iter().all(|a| !a.is_writable));
+    }
+
+    #[test]
+    fn referral_fee_is_transferred_only_when_set() {
+        let output_account = Pubkey::new_unique();
+        let mut params = trade_params(Pubkey::new_unique(), Pubkey::new_unique(), 1_000);
+        assert!(
+            Trade::build_referral_fee_instruction(&params, &output_account, 5)
+                .unwrap()
+                .is_none()
+        );
+
+        let fee_account = Pubkey::new_unique();
+        params.referral = Some(ReferralConfig {
+            fee_account,
+            fee_bps: 50,
+        });
+        assert!(
+            Trade::build_referral_fee_instruction(&params, &output_account, 0)
+                .unwrap()
+                .is_none()
+        );
+        let instruction = Trade::build_referral_fee_instruction(&params, &output_account, 5)
+            .unwrap()
+            .unwrap();
+        assert_eq!(instruction.program_id, spl_token::id());
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|a| a.pubkey).collect();
+        assert_eq!(keys, [output_account, fee_account, params.user]);
+        assert_eq!(
+            spl_token::instruction::TokenInstruction::unpack(&instruction.data).unwrap(),
+            spl_token::instruction::TokenInstruction::Transfer { amount: 5 }
+        );
+    }
+
+    #[tokio::test]
+    async fn swap_pays_the_referral_fee_after_the_last_hop() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        );
+        let trade = Trade::new(rpc.client());
+        let mut params = trade_params(pool.token_a_mint, pool.token_b_mint, 1_000_000);
+        let fee_account = Pubkey::new_unique();
+        let mentions_fee_account = |instructions: &[Instruction]| {
+            instructions
+                .iter()
+                .any(|i| i.accounts.iter().any(|a| a.pubkey == fee_account))
+        };
+
+        let quote = trade.get_quote(&params).await.unwrap();
+        let instructions = trade
+            .build_swap_instructions(&params, &quote)
+            .await
+            .unwrap();
+        assert!(!mentions_fee_account(&instructions));
+
+        params.referral = Some(ReferralConfig {
+            fee_account,
+            fee_bps: 50,
+        });
+        let quote = trade.get_quote(&params).await.unwrap();
+        assert!(quote.referral_fee_amount > 0);
+        let instructions = trade
+            .build_swap_instructions(&params, &quote)
+            .await
+            .unwrap();
+        assert!(mentions_fee_account(&instructions));
+        let swap_index = instructions
+            .iter()
+            .position(|i| i.data.starts_with(&SWAP_DISCRIMINATOR))
+            .unwrap();
+        let swap_min_out =
+            u64::from_le_bytes(instructions[swap_index].data[16..24].try_into().unwrap());
+        assert_eq!(
+            swap_min_out,
+            quote.min_amount_out + quote.referral_fee_amount
+        );
+        let transfer = &instructions[swap_index + 1];
+        assert_eq!(transfer.accounts[1].pubkey, fee_account);
+    }
+
+    #[tokio::test]
+    async fn pair_without_a_pool_routes_through_the_best_intermediate() {
+        let rpc = MockRpc::new();
+        let (input_mint, output_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let wsol = spl_token::native_mint::ID;
+        let first = rpc.add_pool(input_mint, wsol, 1_000_000_000, 2_000_000_000);
+        let second = rpc.add_pool(wsol, output_mint, 2_000_000_000, 3_000_000_000);
+        // A route through another mint paired with the input pays less
+        let other = Pubkey::new_unique();
+        rpc.add_pool(input_mint, other, 1_000_000_000, 1_000_000_000);
+        rpc.add_pool(other, output_mint, 1_000_000_000, 1_000_000_000);
+        let trade = Trade::new(rpc.client());
+        let params = trade_params(input_mint, output_mint, 1_000_000);
+
+        let quote = trade.get_quote(&params).await.unwrap();
+        assert_eq!(quote.route, [first.address, second.address]);
+        let intermediate = math::constant_product_out(1_000_000, 1_000_000_000, 2_000_000_000, 25);
+        let amount_out =
+            math::constant_product_out(intermediate as u64, 2_000_000_000, 3_000_000_000, 25);
+        assert_eq!(quote.amount_out as u128, amount_out);
+        assert_eq!(quote.hops.len(), 2);
+        assert_eq!(quote.hops[0].amount_out as u128, intermediate);
+        assert_eq!(quote.hops[1].input_mint, wsol);
+    }
+
+    #[tokio::test]
+    async fn two_hop_quote_breaks_down_each_hop() {
+        let rpc = MockRpc::new();
+        let (input_mint, output_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let wsol = spl_token::native_mint::ID;
+        let deep = rpc.add_pool(input_mint, wsol, 1_000_000_000, 2_000_000_000);
+        // The second pool is much shallower, so most of the impact is there
+        let shallow = rpc.add_pool(output_mint, wsol, 30_000_000, 20_000_000);
+        let trade = Trade::new(rpc.client());
+        let params = trade_params(input_mint, output_mint, 1_000_000);
+
+        let quote = trade.get_quote(&params).await.unwrap();
+        let pools: Vec<Pubkey> = quote.hops.iter().map(|hop| hop.pool).collect();
+        assert_eq!(pools, [deep.address, shallow.address]);
+        assert_eq!(quote.route, pools);
+        let (first, second) = (&quote.hops[0], &quote.hops[1]);
+        assert_eq!((first.input_mint, first.output_mint), (input_mint, wsol));
+        assert_eq!((second.input_mint, second.output_mint), (wsol, output_mint));
+        assert_eq!(first.amount_in, params.amount_in);
+        assert_eq!(second.amount_in, first.amount_out);
+        assert_eq!(second.amount_out, quote.amount_out);
+        for hop in &quote.hops {
+            assert_eq!(hop.fee_amount, hop.amount_in * 25 / 10_000);
+        }
+        assert!(first.price_impact > 0.0);
+        assert!(second.price_impact > first.price_impact);
+    }
+
+    #[tokio::test]
+    async fn suggested_slippage_grows_with_price_impact() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            1_000_000_000,
+        );
+        let mut trade = Trade::new(rpc.client());
+        let small = trade_params(pool.token_a_mint, pool.token_b_mint, 1_000);
+        let large = trade_params(pool.token_a_mint, pool.token_b_mint, 100_000_000);
+
+        let small_bps = trade.suggest_slippage_bps(&small).await.unwrap();
+        let large_bps = trade.suggest_slippage_bps(&large).await.unwrap();
+        // A negligible trade only gets the buffer, rounded up by its impact
+        assert!(small_bps >= DEFAULT_SLIPPAGE_BUFFER_BPS);
+        assert!(small_bps <= DEFAULT_SLIPPAGE_BUFFER_BPS + 1);
+        assert!(large_bps > small_bps + 500, "{}", large_bps);
+
+        trade.set_slippage_buffer_bps(200);
+        assert_eq!(
+            trade.suggest_slippage_bps(&large).await.unwrap(),
+            large_bps + 150
+        );
+        // Draining most of the pool is clamped to the maximum
+        let drain = trade_params(pool.token_a_mint, pool.token_b_mint, 100_000_000_000);
+        assert_eq!(
+            trade.suggest_slippage_bps(&drain).await.unwrap(),
+            MAX_SLIPPAGE_BPS
+        );
+    }
+
+    #[tokio::test]
+    async fn validation_report_lists_every_issue() {
+        let rpc = MockRpc::new();
+        let trade = Trade::new(rpc.client());
+        let mint = Pubkey::new_unique();
+        let mut params = trade_params(mint, mint, 0);
+        params.slippage_bps = MAX_SLIPPAGE_BPS + 1;
+        params.referral = Some(ReferralConfig {
+            fee_account: Pubkey::new_unique(),
+            fee_bps: MAX_REFERRAL_FEE_BPS + 1,
+        });
+
+        let report = trade.validate_report(&params).await;
+        assert!(!report.is_valid());
+        assert!(matches!(
+            report.issues[..],
+            [
+                ValidationIssue::ZeroAmount,
+                ValidationIssue::SlippageTooHigh { .. },
+                ValidationIssue::SameToken,
+                ValidationIssue::ReferralFeeTooHigh { .. },
+            ]
+        ));
+        // The quote path still stops at the first issue
+        assert!(matches!(
+            trade.get_quote_with_validation(&params).await,
+            Err(MeteoraError::InvalidInput(_))
+        ));
+
+        // Route checks run once the amount and mints are usable
+        let pool = rpc.add_pool(mint, Pubkey::new_unique(), 1_000_000, 1_000_000);
+        let shallow = TradeParams {
+            output_mint: pool.token_b_mint,
+            amount_in: 500_000,
+            slippage_bps: 100,
+            referral: None,
+            ..params
+        };
+        let report = trade.validate_report(&shallow).await;
+        assert!(
+            report
+                .issues
+                .iter()
+                .any(|issue| matches!(issue, ValidationIssue::PriceImpactTooHigh { .. }))
+        );
+        let unrouted = TradeParams {
+            output_mint: Pubkey::new_unique(),
+            ..shallow
+        };
+        let report = trade.validate_report(&unrouted).await;
+        assert!(
+            report
+                .issues
+                .iter()
+                .any(|issue| matches!(issue, ValidationIssue::NoRoute))
+        );
+
+        let valid = TradeParams {
+            amount_in: 1_000,
+            ..shallow
+        };
+        assert!(trade.validate_report(&valid).await.is_valid());
+    }
+
+    #[tokio::test]
+    async fn routed_swaps_decode_in_order_past_other_programs() {
+        let rpc = MockRpc::new();
+        let wsol = spl_token::native_mint::ID;
+        let (token_in, token_out) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let first = rpc.add_pool(token_in, wsol, 1_000_000_000, 2_000_000_000);
+        let second = rpc.add_pool(wsol, token_out, 1_000_000_000, 4_000_000_000);
+        let keys = [
+            Pubkey::new_unique(),
+            first.address,
+            first.token_a_reserve,
+            first.token_b_reserve,
+            second.address,
+            second.token_a_reserve,
+            second.token_b_reserve,
+            METEORA_PROGRAM_ID.parse().unwrap(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let swap_data =
+            bs58::encode([SWAP_DISCRIMINATOR.as_slice(), &[0; 16]].concat()).into_string();
+        let instruction = |program: u8, accounts: &[u8], data: &str| json!({ "programIdIndex": program, "accounts": accounts, "data": data });
+        let balance = |index: u8, mint: &Pubkey, amount: u64| {
+            json!({
+                "accountIndex": index,
+                "mint": mint.to_string(),
+                "uiTokenAmount": {
+                    "uiAmount": null,
+                    "decimals": 6,
+                    "amount": amount.to_string(),
+                    "uiAmountString": "",
+                },
+            })
+        };
+        // A memo, then an aggregator routing 1 token_in -> ~2 SOL -> ~8 token_out
+        // through both pools with inner instructions
+        let transaction = json!({
+            "slot": crate::test_util::MOCK_SLOT,
+            "blockTime": 1_700_000_000,
+            "transaction": {
+                "signatures": [Signature::new_unique().to_string()],
+                "message": {
+                    "accountKeys": keys.map(|key| key.to_string()),
+                    "header": {
+                        "numRequiredSignatures": 1,
+                        "numReadonlySignedAccounts": 0,
+                        "numReadonlyUnsignedAccounts": 3,
+                    },
+                    "recentBlockhash": Hash::default().to_string(),
+                    "instructions": [
+                        instruction(8, &[], "3yZe7d"),
+                        instruction(9, &[0, 1, 4], "2g"),
+                    ],
+                },
+            },
+            "meta": {
+                "err": null,
+                "status": { "Ok": null },
+                "fee": 5000,
+                "preBalances": [],
+                "postBalances": [],
+                "innerInstructions": [{
+                    "index": 1,
+                    "instructions": [
+                        instruction(7, &[1, 2, 3], &swap_data),
+                        instruction(7, &[4, 5, 6], &swap_data),
+                    ],
+                }],
+                "preTokenBalances": [
+                    balance(2, &token_in, 1_000_000_000),
+                    balance(3, &wsol, 2_000_000_000),
+                    balance(5, &wsol, 1_000_000_000),
+                    balance(6, &token_out, 4_000_000_000),
+                ],
+                "postTokenBalances": [
+                    balance(2, &token_in, 1_001_000_000),
+                    balance(3, &wsol, 1_998_000_000),
+                    balance(5, &wsol, 1_002_000_000),
+                    balance(6, &token_out, 3_992_000_000),
+                ],
+            },
+        });
+        rpc.on("getTransaction", move |_| {
+            MockReply::Result(transaction.clone())
+        });
+        let trade = Trade::new(rpc.client());
+        let signature = Signature::new_unique().to_string();
+
+        let swaps = trade.decode_swaps(&signature).await.unwrap();
+        let decoded: Vec<_> = swaps
+            .iter()
+            .map(|swap| {
+                (
+                    swap.pool,
+                    swap.input_mint,
+                    swap.output_mint,
+                    swap.amount_in,
+                    swap.amount_out,
+                    swap.fee,
+                )
+            })
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (first.address, token_in, wsol, 1_000_000, 2_000_000, 2_500),
+                (second.address, wsol, token_out, 2_000_000, 8_000_000, 5_000),
+            ]
+        );
+        assert!(swaps.iter().all(|swap| swap.timestamp == 1_700_000_000));
+        let first_swap = trade.decode_swap(&signature).await.unwrap();
+        assert_eq!(first_swap.pool, first.address);
+    }
+
+    #[tokio::test]
+    async fn transaction_without_a_meteora_swap_has_nothing_to_decode() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(Pubkey::new_unique(), spl_token::native_mint::ID, 1, 1);
+        // The stored fixture moves the reserves but has no swap instruction
+        let signature = rpc.add_swap(&pool, 1_700_000_000, 1_000, -1_000);
+        let trade = Trade::new(rpc.client());
+
+        let signature = signature.to_string();
+        assert!(trade.decode_swaps(&signature).await.unwrap().is_empty());
+        assert!(matches!(
+            trade.decode_swap(&signature).await,
+            Err(MeteoraError::DeserializationError(_))
+        ));
+        assert!(matches!(
+            trade.decode_swap("not a signature").await,
+            Err(MeteoraError::Error(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn native_sol_balance_keeps_rent_for_the_wallet_and_wsol_account() {
+        let rpc = MockRpc::new();
+        let user = Pubkey::new_unique();
+        let wsol = spl_token::native_mint::ID;
+        let rent = solana_sdk::rent::Rent::default();
+        let wallet_rent = rent.minimum_balance(0);
+        let wsol_rent = rent.minimum_balance(spl_token::state::Account::LEN);
+        let fund = |lamports: u64| {
+            let mut wallet = program_account(solana_system_interface::program::ID, vec![]);
+            wallet.lamports = lamports;
+            rpc.set_account(user, wallet);
+        };
+        let mut trade = Trade::new(rpc.client());
+        let amount = 1_000_000_000;
+
+        // Without a WSOL account yet, creating it costs rent too
+        fund(amount + wallet_rent + wsol_rent);
+        trade
+            .check_user_balance(&user, &wsol, amount)
+            .await
+            .unwrap();
+        fund(amount + wallet_rent + wsol_rent - 1);
+        assert!(matches!(
+            trade.check_user_balance(&user, &wsol, amount).await,
+            Err(MeteoraError::InsufficientBalance)
+        ));
+        // Once it exists only the wallet's own minimum is kept back
+        let wsol_account = get_associated_token_address(&user, &wsol);
+        rpc.set_account(wsol_account, token_account(wsol, user, 0));
+        trade
+            .check_user_balance(&user, &wsol, amount)
+            .await
+            .unwrap();
+
+        // Without wrapping, the WSOL balance is what gets spent
+        trade.set_wrap_sol(false);
+        assert!(matches!(
+            trade.check_user_balance(&user, &wsol, amount).await,
+            Err(MeteoraError::InsufficientBalance)
+        ));
+        rpc.set_account(wsol_account, token_account(wsol, user, amount));
+        trade
+            .check_user_balance(&user, &wsol, amount)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn spl_token_balance_comes_from_the_associated_account() {
+        let rpc = MockRpc::new();
+        let (user, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let trade = Trade::new(rpc.client());
+
+        assert!(matches!(
+            trade.check_user_balance(&user, &mint, 1_000).await,
+            Err(MeteoraError::AccountNotFound(_))
+        ));
+        let token_account_address = get_associated_token_address(&user, &mint);
+        rpc.set_account(token_account_address, token_account(mint, user, 999));
+        assert!(matches!(
+            trade.check_user_balance(&user, &mint, 1_000).await,
+            Err(MeteoraError::InsufficientBalance)
+        ));
+        rpc.set_account(token_account_address, token_account(mint, user, 1_000));
+        trade.check_user_balance(&user, &mint, 1_000).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn swap_transaction_is_built_unsigned_for_the_user() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        );
+        let trade = Trade::new(rpc.client());
+        let params = trade_params(pool.token_a_mint, pool.token_b_mint, 1_000_000);
+
+        let transaction = trade.build_swap_transaction(&params).await.unwrap();
+        // The user's output account doesn't exist yet, so it's created first
+        assert_eq!(transaction.message.instructions.len(), 2);
+        assert_eq!(transaction.message.header.num_required_signatures, 1);
+        assert_eq!(transaction.message.account_keys[0], params.user);
+        assert_eq!(transaction.signatures, [Signature::default()]);
+        assert!(!transaction.is_signed());
+        assert_eq!(
+            transaction.message.recent_blockhash,
+            Hash::new_from_array([1; 32])
+        );
+    }
+
+    #[tokio::test]
+    async fn versioned_swap_references_the_lookup_tables() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        );
+        let trade = Trade::new(rpc.client());
+        let params = trade_params(pool.token_a_mint, pool.token_b_mint, 1_000_000);
+        let a_vault = pool::vault_address(&pool.token_a_mint);
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![pool.address, a_vault],
+        };
+
+        let transaction = trade
+            .build_versioned_swap_transaction(&params, std::slice::from_ref(&lookup_table))
+            .await
+            .unwrap();
+        let VersionedMessage::V0(message) = &transaction.message else {
+            panic!("expected a v0 message");
+        };
+        assert_eq!(message.account_keys[0], params.user);
+        assert!(!message.account_keys.contains(&pool.address));
+        assert!(!message.account_keys.contains(&a_vault));
+        assert_eq!(message.address_table_lookups.len(), 1);
+        let lookup = &message.address_table_lookups[0];
+        assert_eq!(lookup.account_key, lookup_table.key);
+        assert_eq!(lookup.writable_indexes.len(), 2);
+        assert_eq!(transaction.signatures, [Signature::default()]);
+
+        let transaction = trade
+            .build_versioned_swap_transaction(&params, &[])
+            .await
+            .unwrap();
+        assert!(
+            transaction
+                .message
+                .address_table_lookups()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    /// A constant-product pool of two fresh mints holding the given reserves
+    fn matched_pool(token_a_amount: u64, token_b_amount: u64) -> MatchedPool {
+        let fixture = PoolFixture::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let pool_info = PoolInfo {
+            address: fixture.address,
+            token_a_reserve_amount: token_a_amount,
+            token_b_reserve_amount: token_b_amount,
+            ..parse_pool_account(&fixture.account_data(PoolLayout::V1, 25, 10_000)).unwrap()
+        };
+        MatchedPool::new(pool_info, &fixture.token_a_mint)
+    }
+
+    #[test]
+    fn offline_quotes_deduct_the_fee_in_both_directions() {
+        let pool_info = matched_pool(1_000_000_000, 2_000_000_000).pool;
+        let (token_a, token_b) = (pool_info.token_a_mint, pool_info.token_b_mint);
+
+        // The 0.25% fee is taken from the input before the constant-product swap
+        let sell_a = Trade::quote_from_pool(&pool_info, 1_000_000, &token_a, 100).unwrap();
+        assert_eq!(sell_a.fee_amount, 2_500);
+        assert_eq!(sell_a.amount_out, 1_993_011);
+        assert_eq!(sell_a.min_amount_out, 1_973_079);
+        assert_eq!(sell_a.route, vec![pool_info.address]);
+        assert_eq!(
+            (sell_a.hops[0].input_mint, sell_a.hops[0].output_mint),
+            (token_a, token_b)
+        );
+
+        let sell_b = Trade::quote_from_pool(&pool_info, 2_000_000, &token_b, 100).unwrap();
+        assert_eq!(sell_b.fee_amount, 5_000);
+        assert_eq!(sell_b.amount_out, 996_505);
+        assert_eq!(
+            (sell_b.hops[0].input_mint, sell_b.hops[0].output_mint),
+            (token_b, token_a)
+        );
+        // A 0.1% trade moves the price, but by well under its fee
+        assert!(sell_a.price_impact > 0.0 && sell_a.price_impact < 0.25);
+
+        assert!(matches!(
+            Trade::quote_from_pool(&pool_info, 1_000, &Pubkey::new_unique(), 100),
+            Err(MeteoraError::InvalidInput(_))
+        ));
+        let empty = matched_pool(0, 2_000_000_000).pool;
+        assert!(matches!(
+            Trade::quote_from_pool(&empty, 1_000, &empty.token_a_mint, 100),
+            Err(MeteoraError::CalculationError(_))
+        ));
+    }
+
+    #[test]
+    fn split_across_two_equal_pools_beats_a_single_pool() {
+        let pools = [
+            matched_pool(1_000_000_000, 2_000_000_000),
+            matched_pool(1_000_000_000, 2_000_000_000),
+        ];
+        let amount_in = 200_000_000;
+        let allocated = split_amount(amount_in, &pools).unwrap();
+
+        assert_eq!(allocated, [amount_in / 2, amount_in / 2]);
+        let split_out = pools[0].swap_output(allocated[0]).unwrap()
+            + pools[1].swap_output(allocated[1]).unwrap();
+        assert!(split_out > pools[0].swap_output(amount_in).unwrap());
+    }
+
+    #[test]
+    fn compute_unit_limit_comes_before_its_price() {
+        let rpc = MockRpc::new();
+        let mut trade = Trade::new(rpc.client());
+        assert!(trade.build_compute_budget_instructions().is_empty());
+
+        trade.set_priority_fee_config(PriorityFeeConfig {
+            compute_unit_limit: Some(200_000),
+            compute_unit_price_micro_lamports: Some(5_000),
+        });
+        let instructions = trade.build_compute_budget_instructions();
+        let compute_budget = Pubkey::from_str(COMPUTE_BUDGET_PROGRAM_ID).unwrap();
+        assert!(instructions.iter().all(|i| i.program_id == compute_budget));
+        let data: Vec<&[u8]> = instructions.iter().map(|i| i.data.as_slice()).collect();
+        let mut limit = vec![2];
+        limit.extend_from_slice(&200_000u32.to_le_bytes());
+        let mut price = vec![3];
+        price.extend_from_slice(&5_000u64.to_le_bytes());
+        assert_eq!(data, [limit.as_slice(), price.as_slice()]);
+
+        trade.set_priority_fee_config(PriorityFeeConfig {
+            compute_unit_limit: None,
+            compute_unit_price_micro_lamports: Some(5_000),
+        });
+        let instructions = trade.build_compute_budget_instructions();
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(instructions[0].data, price);
+    }
+
+    /// Names each instruction after what it does, for checking sequences
+    fn instruction_kinds(instructions: &[Instruction]) -> Vec<&'static str> {
+        use spl_token::instruction::TokenInstruction;
+        instructions
+            .iter()
+            .map(|instruction| {
+                if instruction.program_id == spl_associated_token_account::id() {
+                    "create_account"
+                } else if instruction.program_id == solana_system_interface::program::ID {
+                    "transfer_sol"
+                } else if instruction.program_id == Pubkey::from_str(METEORA_PROGRAM_ID).unwrap() {
+                    "swap"
                 } else {
-                    Ok(false)
+                    match TokenInstruction::unpack(&instruction.data).unwrap() {
+                        TokenInstruction::SyncNative => "sync_native",
+                        TokenInstruction::CloseAccount => "close_account",
+                        other => panic!("unexpected token instruction {:?}", other),
+                    }
                 }
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn output_guard_checks_the_balance_after_the_swap() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        );
+        let params = trade_params(pool.token_a_mint, pool.token_b_mint, 1_000_000);
+        let mut trade = Trade::new(rpc.client());
+        let output_account = trade.get_associated_token_address(&params.user, &pool.token_b_mint);
+        rpc.set_account(
+            output_account,
+            token_account(pool.token_b_mint, params.user, 500),
+        );
+        let quote = trade.get_quote(&params).await.unwrap();
+        let instructions = trade
+            .build_swap_instructions(&params, &quote)
+            .await
+            .unwrap();
+        assert_eq!(instruction_kinds(&instructions), ["swap"]);
+
+        let guard_program = Pubkey::new_unique();
+        trade.set_output_guard_program(Some(guard_program));
+        let instructions = trade
+            .build_swap_instructions(&params, &quote)
+            .await
+            .unwrap();
+        assert_eq!(instructions.len(), 2);
+        let guard = &instructions[1];
+        assert_eq!(guard.program_id, guard_program);
+        assert_eq!(
+            guard.accounts,
+            vec![AccountMeta::new_readonly(output_account, false)]
+        );
+        assert_eq!(guard.data, (500 + quote.min_amount_out).to_le_bytes());
+    }
+
+    #[tokio::test]
+    async fn sol_is_wrapped_before_and_unwrapped_after_the_swap() {
+        let rpc = MockRpc::new();
+        let wsol = spl_token::native_mint::ID;
+        let pool = rpc.add_pool(wsol, Pubkey::new_unique(), 1_000_000_000, 2_000_000_000);
+        let mut trade = Trade::new(rpc.client());
+        trade.set_wrap_sol(true);
+
+        // Selling SOL: wrap the input, swap, then close the emptied WSOL account
+        let params = trade_params(wsol, pool.token_b_mint, 1_000_000);
+        let wsol_account = trade.get_associated_token_address(&params.user, &wsol);
+        let quote = trade.get_quote(&params).await.unwrap();
+        let instructions = trade
+            .build_swap_instructions(&params, &quote)
+            .await
+            .unwrap();
+        assert_eq!(
+            instruction_kinds(&instructions),
+            [
+                "create_account",
+                "create_account",
+                "transfer_sol",
+                "sync_native",
+                "swap",
+                "close_account"
+            ]
+        );
+        assert_eq!(
+            instructions[2],
+            solana_system_interface::instruction::transfer(&params.user, &wsol_account, 1_000_000)
+        );
+        assert_eq!(instructions[3].accounts[0].pubkey, wsol_account);
+        assert_eq!(instructions[5].accounts[0].pubkey, wsol_account);
+
+        // Buying SOL: swap into the WSOL account, then close it to unwrap
+        let params = trade_params(pool.token_b_mint, wsol, 1_000_000);
+        let wsol_account = trade.get_associated_token_address(&params.user, &wsol);
+        let quote = trade.get_quote(&params).await.unwrap();
+        let instructions = trade
+            .build_swap_instructions(&params, &quote)
+            .await
+            .unwrap();
+        assert_eq!(
+            instruction_kinds(&instructions),
+            ["create_account", "swap", "close_account"]
+        );
+        assert_eq!(instructions[2].accounts[0].pubkey, wsol_account);
+        assert_eq!(instructions[2].accounts[1].pubkey, params.user);
+    }
+
+    #[tokio::test]
+    async fn simulated_quote_reads_the_output_accounts_balance_change() {
+        use solana_account_decoder::encode_ui_account;
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        );
+        let trade = Trade::new(rpc.client());
+        let params = trade_params(pool.token_a_mint, pool.token_b_mint, 1_000_000);
+        let formula_quote = trade.get_quote(&params).await.unwrap();
+
+        // Without simulation the formula quote is returned
+        let quote = trade.simulate_quote(&params).await.unwrap();
+        assert_eq!(quote.amount_out, formula_quote.amount_out);
+
+        let output_account = trade.get_associated_token_address(&params.user, &pool.token_b_mint);
+        rpc.set_account(
+            output_account,
+            token_account(pool.token_b_mint, params.user, 500),
+        );
+        let simulated = encode_ui_account(
+            &output_account,
+            &token_account(pool.token_b_mint, params.user, 500 + 1_234_567),
+            UiAccountEncoding::Base64,
+            None,
+            None,
+        );
+        rpc.on("simulateTransaction", move |_| {
+            MockReply::Result(json!({
+                "context": { "slot": 1 },
+                "value": {
+                    "err": null,
+                    "logs": [],
+                    "accounts": [simulated],
+                    "unitsConsumed": 40_000,
+                    "returnData": null,
+                },
+            }))
+        });
+        let quote = trade.simulate_quote(&params).await.unwrap();
+        assert_eq!(quote.amount_out, 1_234_567);
+        assert_eq!(quote.hops.last().unwrap().amount_out, 1_234_567);
+        assert_eq!(
+            quote.min_amount_out,
+            math::min_amount_out(1_234_567, params.slippage_bps)
+        );
+        let simulations = rpc.calls("simulateTransaction");
+        assert_eq!(
+            simulations[0][1]["accounts"]["addresses"],
+            json!([output_account.to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_simulation_reports_the_program_error_code() {
+        let rpc = MockRpc::new();
+        let pool = rpc.add_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            1_000_000_000,
+            2_000_000_000,
+        );
+        rpc.on("simulateTransaction", |_| {
+            MockReply::Result(json!({
+                "context": { "slot": 1 },
+                "value": {
+                    "err": { "InstructionError": [2, { "Custom": 6004 }] },
+                    "logs": ["Program log: Exceeded slippage tolerance"],
+                    "accounts": null,
+                    "unitsConsumed": 21_000,
+                    "returnData": null,
+                },
+            }))
+        });
+        let trade = Trade::new(rpc.client());
+        let payer = Keypair::new();
+        let mut params = trade_params(pool.token_a_mint, pool.token_b_mint, 1_000_000);
+        params.user = payer.pubkey();
+
+        let quote = trade.get_quote(&params).await.unwrap();
+        let simulation = trade.simulate_swap(&params, &quote).await.unwrap();
+        assert!(!simulation.success);
+        assert_eq!(simulation.units_consumed, 21_000);
+        assert_eq!(
+            simulation.instruction_error,
+            Some(solana_sdk::instruction::InstructionError::Custom(6004))
+        );
+        assert_eq!(simulation.custom_error_code(), Some(6004));
+
+        match trade.execute_swap_safe(&params, &payer).await {
+            Err(MeteoraError::SimulationFailed(message)) => {
+                assert!(message.contains("program error code 0x1774"), "{}", message)
             }
-            Err(e) => Err(MeteoraError::RpcError(e.to_string())),
+            other => panic!("expected a failed simulation, got {:?}", other),
         }
+        assert_eq!(rpc.call_count("sendTransaction"), 0);
     }
 }
@@ -1,19 +1,24 @@
+use crate::math;
 use serde::{Deserialize, Serialize};
-use solana_sdk::pubkey::Pubkey;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::{instruction::InstructionError, program_error::ProgramError, pubkey::Pubkey};
 use std::fmt;
+use std::time::Duration;
 
 /// Result type alias for Meteora operations
 pub type MeteoraResult<T> = Result<T, MeteoraError>;
 
 /// Error types for Meteora operations
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MeteoraError {
     RpcError(String),
     AccountNotFound(String),
-    InvalidPoolData,
+    InvalidPoolData(String),
     TransactionFailed(String),
     DeserializationError(String),
     InvalidAccountData,
+    InvalidAccountOwner(String),
     CalculationError(String),
     NoLiquidityPoolFound,
     Error(String),
@@ -24,6 +29,84 @@ pub enum MeteoraError {
     SimulationFailed(String),
     TransactionTimeout,
     InvalidPrice,
+    RateLimited,
+    StalePrice(String),
+    DeadlineExceeded,
+}
+
+impl MeteoraError {
+    /// Classifies an RPC failure message, returning `RateLimited` for
+    /// HTTP 429 / rate-limit responses and `RpcError` otherwise
+    pub fn from_rpc_error(message: String) -> Self {
+        let lower = message.to_lowercase();
+        if lower.contains("429")
+            || lower.contains("rate limit")
+            || lower.contains("too many requests")
+        {
+            MeteoraError::RateLimited
+        } else {
+            MeteoraError::RpcError(message)
+        }
+    }
+
+    /// Whether the error is a transient RPC failure worth retrying
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, MeteoraError::RpcError(_) | MeteoraError::RateLimited)
+    }
+}
+
+/// Transport failures (I/O, HTTP, node errors) become retryable `RpcError` or
+/// `RateLimited`, while failures retrying can't fix map to non-retryable variants
+impl From<ClientError> for MeteoraError {
+    fn from(error: ClientError) -> Self {
+        match error.kind() {
+            ClientErrorKind::TransactionError(e) => MeteoraError::TransactionFailed(e.to_string()),
+            ClientErrorKind::SigningError(e) => MeteoraError::TransactionFailed(e.to_string()),
+            ClientErrorKind::SerdeJson(e) => MeteoraError::DeserializationError(e.to_string()),
+            // Only preflight failures are left: the node simulated the
+            // transaction before sending and it failed
+            _ => match error.kind().get_transaction_error() {
+                Some(e) => MeteoraError::SimulationFailed(e.to_string()),
+                None => MeteoraError::from_rpc_error(error.to_string()),
+            },
+        }
+    }
+}
+
+impl From<ProgramError> for MeteoraError {
+    fn from(error: ProgramError) -> Self {
+        match error {
+            ProgramError::InvalidAccountData
+            | ProgramError::AccountDataTooSmall
+            | ProgramError::UninitializedAccount
+            | ProgramError::BorshIoError => MeteoraError::DeserializationError(error.to_string()),
+            ProgramError::InvalidArgument | ProgramError::InvalidInstructionData => {
+                MeteoraError::InvalidInput(error.to_string())
+            }
+            _ => MeteoraError::Error(error.to_string()),
+        }
+    }
+}
+
+/// Trading activity and returns of a pool over the last 24 hours
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolStats {
+    pub volume_24h_usd: f64,
+    pub fees_24h_usd: f64,
+    /// Yearly fee return on the pool's TVL at the last day's rate, as a percentage
+    pub fee_apr: f64,
+    pub tvl_usd: f64,
+}
+
+/// When a pool was created, from the oldest of its transactions that was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolCreationTime {
+    /// Unix timestamp of the oldest transaction found
+    pub timestamp: i64,
+    /// Whether the pool's history was too long to read to the start, in which
+    /// case the pool was created at or before `timestamp` and the age derived
+    /// from it is a lower bound
+    pub is_lower_bound: bool,
 }
 
 /// Token price information
@@ -32,8 +115,111 @@ pub struct TokenPrice {
     pub token_mint: Pubkey,
     pub sol_price: f64,
     pub usd_price: f64,
+    /// Unix timestamp at which the reserves behind the price were read
     pub timestamp: i64,
+    /// Liquidity of the pricing pools in base units of the token, see
+    /// `PoolInfo::liquidity_in`
     pub liquidity: u64,
+    /// Confidence in the price from 0 (none) to 1 (high), based on how many
+    /// pools agree on it and how deep they are
+    pub confidence: f64,
+    /// Where the SOL/USD rate behind `usd_price` came from
+    pub source: PriceSource,
+}
+
+/// Direction of a trade relative to the priced token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TradeSide {
+    /// Spend the pool's other token to receive the priced token
+    Buy,
+    /// Spend the priced token to receive the pool's other token
+    Sell,
+}
+
+/// Origin of a SOL/USD rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PriceSource {
+    /// Derived from the reserves of a WSOL/USDC pool
+    Pool,
+    /// Read from the configured Pyth price account
+    Oracle,
+    /// Neither was available, a hardcoded rate was used
+    Fallback,
+    /// Pinned with `PriceFeed::set_sol_usd_override`
+    Override,
+}
+
+impl TokenPrice {
+    /// Whether `usd_price` is backed by a pool, oracle or overridden rate
+    /// rather than the hardcoded fallback
+    pub fn has_reliable_usd_price(&self) -> bool {
+        self.source != PriceSource::Fallback
+    }
+
+    /// Whether the price is older than `max_age`
+    ///
+    /// `timestamp` is when the reserves behind the price were read, so prices
+    /// computed from cached pools age with the cache.
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        self.is_stale_at(max_age, chrono::Utc::now().timestamp())
+    }
+
+    /// Whether the price is older than `max_age` at unix timestamp `now`
+    pub(crate) fn is_stale_at(&self, max_age: Duration, now: i64) -> bool {
+        now - self.timestamp > max_age.as_secs() as i64
+    }
+}
+
+/// Lifecycle events emitted by the price listener
+#[derive(Debug, Clone)]
+pub enum ListenerEvent {
+    /// The listener loop started
+    Started,
+    /// Pricing a subscribed token failed
+    Error { token_mint: Pubkey, message: String },
+    /// Pricing a token succeeded again after a previous error
+    Reconnected { token_mint: Pubkey },
+    /// A subscription was removed because it had no receivers left
+    SubscriptionPruned { token_mint: Pubkey },
+    /// The WebSocket connection failed or dropped, the listener falls back
+    /// to polling until it reconnects
+    Disconnected { message: String },
+    /// The listener loop returned after a shutdown request
+    Stopped,
+    /// A price was sent while a subscriber's channel was full, so its oldest
+    /// unread price was dropped. `dropped` counts every drop for the token
+    /// since the listener started. Only emitted with `ListenerConfig::track_lag`.
+    SubscriberLagged { token_mint: Pubkey, dropped: u64 },
+}
+
+/// Market activity reported to `PriceListener::subscribe_events` receivers
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    /// The token's price moved past the change threshold
+    PriceChange(TokenPrice),
+    /// Liquidity was added to (positive) or removed from (negative) the token's pool
+    LiquidityChange { pool: Pubkey, delta_usd: f64 },
+    /// A swap of at least the large swap threshold went through the token's pool
+    LargeSwap { pool: Pubkey, amount_usd: f64 },
+}
+
+impl MarketEvent {
+    /// Returns the kind of the event, for filtering
+    pub fn kind(&self) -> MarketEventKind {
+        match self {
+            MarketEvent::PriceChange(_) => MarketEventKind::PriceChange,
+            MarketEvent::LiquidityChange { .. } => MarketEventKind::LiquidityChange,
+            MarketEvent::LargeSwap { .. } => MarketEventKind::LargeSwap,
+        }
+    }
+}
+
+/// Kinds of `MarketEvent` a subscriber can ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MarketEventKind {
+    PriceChange,
+    LiquidityChange,
+    LargeSwap,
 }
 
 /// Candlestick data for price charts
@@ -49,14 +235,18 @@ pub struct CandleStick {
 }
 
 /// Supported time frames for chart data
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum TimeFrame {
     M1,  // 1分钟
     M5,  // 5分钟
     M15, // 15分钟
+    M30, // 30分钟
     H1,  // 1小时
+    H2,  // 2小时
     H4,  // 4小时
+    H12, // 12小时
     D1,  // 1天
+    W1,  // 1周
 }
 
 impl fmt::Display for TimeFrame {
@@ -65,15 +255,19 @@ impl fmt::Display for TimeFrame {
             TimeFrame::M1 => write!(f, "1m"),
             TimeFrame::M5 => write!(f, "5m"),
             TimeFrame::M15 => write!(f, "15m"),
+            TimeFrame::M30 => write!(f, "30m"),
             TimeFrame::H1 => write!(f, "1h"),
+            TimeFrame::H2 => write!(f, "2h"),
             TimeFrame::H4 => write!(f, "4h"),
+            TimeFrame::H12 => write!(f, "12h"),
             TimeFrame::D1 => write!(f, "1d"),
+            TimeFrame::W1 => write!(f, "1w"),
         }
     }
 }
 
 /// Liquidity pool information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PoolInfo {
     pub address: Pubkey,
     pub token_a_mint: Pubkey,
@@ -88,6 +282,226 @@ pub struct PoolInfo {
     pub token_a_reserve_amount: u64,
     pub token_b_reserve_amount: u64,
     pub lp_supply: u64,
+    pub lp_decimals: u8,
+    #[serde(default)]
+    pub curve_type: CurveType,
+    /// Unix timestamp at which the reserve amounts were read, 0 if never
+    #[serde(default)]
+    pub fetched_at: i64,
+}
+
+/// Invariant a pool prices swaps with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CurveType {
+    /// `x * y = k`
+    #[default]
+    ConstantProduct,
+    /// StableSwap invariant with amplification coefficient `amp`, for pegged pairs
+    Stable { amp: u64 },
+}
+
+impl PoolInfo {
+    /// Returns the LP token supply in UI units
+    pub fn ui_lp_supply(&self) -> f64 {
+        self.lp_supply as f64 / 10f64.powi(self.lp_decimals as i32)
+    }
+
+    /// Returns the token A and token B reserves in UI units
+    pub fn ui_reserve_amounts(&self) -> (f64, f64) {
+        (
+            self.token_a_reserve_amount as f64 / 10f64.powi(self.token_a_decimals as i32),
+            self.token_b_reserve_amount as f64 / 10f64.powi(self.token_b_decimals as i32),
+        )
+    }
+
+    /// Returns the price of `token_mint` in units of the pool's other token
+    ///
+    /// Empty and one-sided pools have no meaningful price, so `InvalidPrice`
+    /// is returned when either reserve is zero instead of `inf` or `NaN`.
+    pub fn price_of(&self, token_mint: &Pubkey) -> Result<f64, MeteoraError> {
+        let (token_a, token_b) = self.ui_reserve_amounts();
+        if token_a == 0.0 || token_b == 0.0 {
+            return Err(MeteoraError::InvalidPrice);
+        }
+        if *token_mint == self.token_a_mint {
+            Ok(token_b / token_a)
+        } else {
+            Ok(token_a / token_b)
+        }
+    }
+
+    /// Returns the value of both reserves in base units of `quote_mint`
+    ///
+    /// The other reserve is converted at the pool's own price, which for a
+    /// constant product pool makes the total twice the `quote_mint` reserve.
+    /// Unlike adding the raw reserves, this is comparable across every pool
+    /// containing `quote_mint`. Returns `None` if the pool doesn't hold it.
+    pub fn liquidity_in(&self, quote_mint: &Pubkey) -> Option<u64> {
+        let reserve = if *quote_mint == self.token_a_mint {
+            self.token_a_reserve_amount
+        } else if *quote_mint == self.token_b_mint {
+            self.token_b_reserve_amount
+        } else {
+            return None;
+        };
+        Some(reserve.saturating_mul(2))
+    }
+
+    /// `liquidity_in` converted from base units to whole tokens of `quote_mint`
+    pub fn normalized_liquidity_in(&self, quote_mint: &Pubkey) -> Option<f64> {
+        let decimals = if *quote_mint == self.token_a_mint {
+            self.token_a_decimals
+        } else {
+            self.token_b_decimals
+        };
+        let liquidity = self.liquidity_in(quote_mint)?;
+        Some(liquidity as f64 / 10f64.powi(decimals as i32))
+    }
+
+    /// Returns the value of both reserves given each token's price
+    ///
+    /// When only one price is known the other side is valued at the pool's
+    /// own rate, i.e. the total is twice the known side. Returns `None` when
+    /// neither price is known.
+    pub fn tvl(&self, token_a_price: Option<f64>, token_b_price: Option<f64>) -> Option<f64> {
+        let (token_a, token_b) = self.ui_reserve_amounts();
+        match (token_a_price, token_b_price) {
+            (Some(price_a), Some(price_b)) => Some(token_a * price_a + token_b * price_b),
+            (Some(price_a), None) => Some(2.0 * token_a * price_a),
+            (None, Some(price_b)) => Some(2.0 * token_b * price_b),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Share of the input reserve swapped to find a stable pool's spot rate
+const STABLE_IMPACT_PROBE_DIVISOR: u64 = 1_000_000;
+
+/// A pool matched against a swap direction
+#[derive(Debug, Clone)]
+pub struct MatchedPool {
+    pub pool: PoolInfo,
+    pub input_is_token_a: bool,
+}
+
+impl MatchedPool {
+    /// Creates a MatchedPool for a swap selling `input_mint`
+    pub fn new(pool: PoolInfo, input_mint: &Pubkey) -> Self {
+        let input_is_token_a = pool.token_a_mint == *input_mint;
+        Self {
+            pool,
+            input_is_token_a,
+        }
+    }
+
+    /// Returns the mint sold into the pool
+    pub fn input_mint(&self) -> Pubkey {
+        if self.input_is_token_a {
+            self.pool.token_a_mint
+        } else {
+            self.pool.token_b_mint
+        }
+    }
+
+    /// Returns the mint bought from the pool
+    pub fn output_mint(&self) -> Pubkey {
+        if self.input_is_token_a {
+            self.pool.token_b_mint
+        } else {
+            self.pool.token_a_mint
+        }
+    }
+
+    /// Returns the output of swapping `amount_in` after the trade fee, on the
+    /// pool's curve
+    ///
+    /// Stable pools are computed with both reserves scaled to the larger of
+    /// the two token decimals, so pegged tokens with different decimals
+    /// balance at 1:1 in UI units.
+    pub fn swap_output(&self, amount_in: u64) -> Result<u64, MeteoraError> {
+        let (input_reserve, output_reserve) = self.reserve_amounts();
+        let amount_out = match self.pool.curve_type {
+            CurveType::ConstantProduct => math::constant_product_out(
+                amount_in,
+                input_reserve,
+                output_reserve,
+                self.pool.trade_fee_bps,
+            ),
+            CurveType::Stable { amp } => {
+                let (input_scale, output_scale) = self.stable_scales();
+                math::stable_swap_out(
+                    amount_in as u128 * input_scale,
+                    input_reserve as u128 * input_scale,
+                    output_reserve as u128 * output_scale,
+                    amp,
+                    self.pool.trade_fee_bps,
+                )
+                .ok_or_else(|| {
+                    MeteoraError::CalculationError(
+                        "Stable swap math failed for the pool's reserves".to_string(),
+                    )
+                })? / output_scale
+            }
+        };
+        u64::try_from(amount_out).map_err(|_| {
+            MeteoraError::CalculationError("Swap output does not fit in u64".to_string())
+        })
+    }
+
+    /// Returns the price impact of swapping `amount_in` as a percentage
+    ///
+    /// Stable pools have no closed form, so the swap's rate is compared to
+    /// that of a probe trade of a millionth of the input reserve.
+    pub fn price_impact(&self, amount_in: u64) -> Result<f64, MeteoraError> {
+        let (input_reserve, _) = self.reserve_amounts();
+        match self.pool.curve_type {
+            CurveType::ConstantProduct => Ok(math::price_impact(amount_in, input_reserve)),
+            CurveType::Stable { .. } => {
+                if amount_in == 0 {
+                    return Ok(0.0);
+                }
+                let probe = (input_reserve / STABLE_IMPACT_PROBE_DIVISOR).max(1);
+                let probe_out = self.swap_output(probe)?;
+                if probe_out == 0 {
+                    return Err(MeteoraError::CalculationError(
+                        "Pool is too shallow to price".to_string(),
+                    ));
+                }
+                let spot_rate = probe_out as f64 / probe as f64;
+                let rate = self.swap_output(amount_in)? as f64 / amount_in as f64;
+                Ok(((1.0 - rate / spot_rate) * 100.0).max(0.0))
+            }
+        }
+    }
+
+    /// Factors bringing the (input, output) amounts to a common precision
+    fn stable_scales(&self) -> (u128, u128) {
+        let (input_decimals, output_decimals) = if self.input_is_token_a {
+            (self.pool.token_a_decimals, self.pool.token_b_decimals)
+        } else {
+            (self.pool.token_b_decimals, self.pool.token_a_decimals)
+        };
+        let decimals = input_decimals.max(output_decimals);
+        (
+            10u128.pow((decimals - input_decimals) as u32),
+            10u128.pow((decimals - output_decimals) as u32),
+        )
+    }
+
+    /// Returns the (input, output) reserve amounts for the matched direction
+    pub fn reserve_amounts(&self) -> (u64, u64) {
+        if self.input_is_token_a {
+            (
+                self.pool.token_a_reserve_amount,
+                self.pool.token_b_reserve_amount,
+            )
+        } else {
+            (
+                self.pool.token_b_reserve_amount,
+                self.pool.token_a_reserve_amount,
+            )
+        }
+    }
 }
 
 /// Token information and metadata
@@ -98,6 +512,17 @@ pub struct TokenInfo {
     pub supply: u64,
     pub holder_count: u64,
     pub metadata: Option<TokenMetadata>,
+    /// Authority that can mint new tokens, `None` if the supply is fixed
+    pub mint_authority: Option<Pubkey>,
+    /// Authority that can freeze token accounts, `None` if there is none
+    pub freeze_authority: Option<Pubkey>,
+}
+
+impl TokenInfo {
+    /// Returns the token supply in UI units
+    pub fn ui_supply(&self) -> f64 {
+        self.supply as f64 / 10f64.powi(self.decimals as i32)
+    }
 }
 
 /// Token metadata from on-chain data
@@ -106,34 +531,450 @@ pub struct TokenMetadata {
     pub name: String,
     pub symbol: String,
     pub uri: String,
+    /// Royalty paid to creators on secondary sales, in basis points
+    pub seller_fee_basis_points: u16,
+    pub creators: Vec<Creator>,
+}
+
+/// A creator listed in a token's Metaplex metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Creator {
+    pub address: Pubkey,
+    /// Whether the creator signed the metadata
+    pub verified: bool,
+    /// Percentage of royalties paid to this creator
+    pub share: u8,
 }
 
 /// Parameters for executing a trade
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TradeParams {
     pub input_mint: Pubkey,
     pub output_mint: Pubkey,
     pub amount_in: u64,
     pub slippage_bps: u16,
     pub user: Pubkey,
+    /// Unix timestamp after which the trade must not be sent, `None` for no deadline
+    pub deadline: Option<i64>,
+    /// Partner fee taken from the output, `None` for no referral
+    pub referral: Option<ReferralConfig>,
 }
 
-/// Quote information for a proposed trade
+/// Referral fee collected on a swap's output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReferralConfig {
+    /// Token account of the output mint that receives the fee
+    pub fee_account: Pubkey,
+    /// Share of the output taken as the fee, in basis points
+    pub fee_bps: u16,
+}
+
+/// A problem found while validating trade parameters
 #[derive(Debug, Clone)]
+pub enum ValidationIssue {
+    ZeroAmount,
+    SlippageTooHigh {
+        slippage_bps: u16,
+        max_bps: u16,
+    },
+    SameToken,
+    ReferralFeeTooHigh {
+        fee_bps: u16,
+        max_bps: u16,
+    },
+    /// No direct or two-hop route connects the two mints
+    NoRoute,
+    /// The best route's price impact, as a percentage, exceeds the slippage
+    PriceImpactTooHigh {
+        price_impact: f64,
+        slippage_bps: u16,
+    },
+    /// Routing could not be checked, e.g. because of an RPC failure
+    RouteCheckFailed(MeteoraError),
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::ZeroAmount => write!(f, "Amount cannot be zero"),
+            ValidationIssue::SlippageTooHigh { .. } => write!(f, "Slippage too high"),
+            ValidationIssue::SameToken => write!(f, "Cannot swap same token"),
+            ValidationIssue::ReferralFeeTooHigh { fee_bps, max_bps } => write!(
+                f,
+                "Referral fee of {} bps exceeds the {} bps cap",
+                fee_bps, max_bps
+            ),
+            ValidationIssue::NoRoute => write!(f, "No liquidity pool found"),
+            ValidationIssue::PriceImpactTooHigh {
+                price_impact,
+                slippage_bps,
+            } => write!(
+                f,
+                "Price impact of {:.2}% exceeds the {:.2}% slippage",
+                price_impact,
+                *slippage_bps as f64 / 100.0
+            ),
+            ValidationIssue::RouteCheckFailed(e) => write!(f, "Route check failed: {:?}", e),
+        }
+    }
+}
+
+impl From<ValidationIssue> for MeteoraError {
+    fn from(issue: ValidationIssue) -> Self {
+        match issue {
+            ValidationIssue::NoRoute => MeteoraError::NoLiquidityPoolFound,
+            ValidationIssue::PriceImpactTooHigh { .. } => MeteoraError::SlippageExceeded,
+            ValidationIssue::RouteCheckFailed(e) => e,
+            other => MeteoraError::InvalidInput(other.to_string()),
+        }
+    }
+}
+
+/// Every problem found with a set of trade parameters
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether no issues were found
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Quote information for a proposed trade
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TradeQuote {
     pub amount_out: u64,
     pub min_amount_out: u64,
     pub price_impact: f64,
     pub fee_amount: u64,
+    /// Referral fee deducted from `amount_out`, in base units of the output mint
+    pub referral_fee_amount: u64,
+    /// Most input the trade may spend; equals the input amount for exact-input quotes
+    pub max_amount_in: u64,
     pub route: Vec<Pubkey>,
+    /// Per-pool breakdown of the route, in swap order
+    pub hops: Vec<HopInfo>,
 }
 
-/// Simulation results for a swap operation
+/// One pool swap within a quoted route
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HopInfo {
+    pub pool: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    /// Pool fee charged on `amount_in`, in base units of `input_mint`
+    pub fee_amount: u64,
+    /// Price impact of this hop alone, as a percentage
+    pub price_impact: f64,
+}
+
+/// The share of a split swap sent through one pool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitAllocation {
+    pub pool: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub min_amount_out: u64,
+    /// Price impact of this allocation in its pool, as a percentage
+    pub price_impact: f64,
+}
+
+/// Quote for a swap split across several direct pools
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitQuote {
+    /// One entry per pool that receives part of the input
+    pub allocations: Vec<SplitAllocation>,
+    /// Total output of all allocations
+    pub amount_out: u64,
+    /// Total minimum output of all allocations
+    pub min_amount_out: u64,
+    /// Price impact of the allocations weighted by their input, as a percentage
+    pub price_impact: f64,
+}
+
+/// A Meteora swap read back from a confirmed transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecodedSwap {
+    pub pool: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    /// Pool fee charged on `amount_in`, in base units of `input_mint`
+    pub fee: u64,
+    /// Block time of the transaction, in Unix seconds
+    pub timestamp: i64,
+}
+
+/// Settings for submitting and confirming transactions
+#[derive(Debug, Clone)]
+pub struct ConfirmConfig {
+    /// Number of times an unconfirmed transaction is resent with a fresh blockhash
+    pub max_resends: u32,
+    /// Seconds to wait for each attempt to confirm
+    pub timeout_secs: u64,
+    /// Commitment the transaction must reach to count as confirmed
+    pub commitment: CommitmentConfig,
+    /// Time between signature status checks
+    pub poll_interval: Duration,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        Self {
+            max_resends: 3,
+            timeout_secs: 30,
+            commitment: CommitmentConfig::confirmed(),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Polling settings for the price listener
+#[derive(Debug, Clone)]
+pub struct ListenerConfig {
+    /// Time between price checks
+    pub poll_interval: Duration,
+    /// Relative price change that triggers an update, e.g. `0.01` for 1%
+    pub change_threshold: f64,
+    /// Prices older than this are not sent to subscribers, `None` to send
+    /// prices of any age
+    pub max_price_age: Option<Duration>,
+    /// Smallest liquidity add or removal, in USD, reported as `LiquidityChange`
+    pub liquidity_change_threshold_usd: f64,
+    /// Smallest swap, in USD, reported as `LargeSwap`
+    pub large_swap_threshold_usd: f64,
+    /// Number of unread messages each subscription channel holds before the
+    /// oldest is dropped for a slow receiver
+    pub channel_capacity: usize,
+    /// Log and emit `SubscriberLagged` whenever a price update overflows a
+    /// subscriber's channel
+    pub track_lag: bool,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            change_threshold: 0.01,
+            max_price_age: None,
+            liquidity_change_threshold_usd: 10_000.0,
+            large_swap_threshold_usd: 50_000.0,
+            channel_capacity: 100,
+            track_lag: false,
+        }
+    }
+}
+
+/// Retry policy for transient RPC failures
 #[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on every further retry
+    pub base_delay: Duration,
+    /// Upper bound on the delay between retries
+    pub max_delay: Duration,
+}
+
+impl RetryConfig {
+    /// Delay before retry number `attempt` (starting at 0), with up to 50% jitter
+    pub fn backoff_delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        exponential.mul_f64(0.5 + rand::random::<f64>() * 0.5)
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Compute budget settings prepended to swap transactions
+#[derive(Debug, Clone, Default)]
+pub struct PriorityFeeConfig {
+    /// Maximum compute units the transaction may consume
+    pub compute_unit_limit: Option<u32>,
+    /// Price paid per compute unit, in micro-lamports
+    pub compute_unit_price_micro_lamports: Option<u64>,
+}
+
+/// Simulation results for a swap operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SwapSimulation {
     pub success: bool,
     pub logs: Vec<String>,
     pub units_consumed: u64,
     pub price_impact: f64,
     pub actual_output: u64,
+    /// Why the simulated transaction failed, `None` when it succeeded
+    pub error: Option<String>,
+    /// Error of the failing instruction, when the failure came from one
+    pub instruction_error: Option<InstructionError>,
+}
+
+impl SwapSimulation {
+    /// Program-specific error code of the failing instruction, e.g. a
+    /// Meteora slippage check, when it failed with `InstructionError::Custom`
+    pub fn custom_error_code(&self) -> Option<u32> {
+        match self.instruction_error {
+            Some(InstructionError::Custom(code)) => Some(code),
+            _ => None,
+        }
+    }
+}
+
+/// A single liquidity bin of a DLMM pool
+#[derive(Debug, Clone)]
+pub struct DlmmBin {
+    pub bin_id: i32,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+/// The state of a DLMM pool needed to quote a swap through it
+#[derive(Debug, Clone)]
+pub struct DlmmPool {
+    pub address: Pubkey,
+    pub token_x_mint: Pubkey,
+    pub token_y_mint: Pubkey,
+    pub active_bin_id: i32,
+    /// Price increment between adjacent bins, in basis points
+    pub bin_step: u16,
+    /// Swap fee charged on input, in basis points
+    pub fee_bps: u64,
+    /// The pool's bins, in any order
+    pub bins: Vec<DlmmBin>,
+}
+
+/// Quote for a DLMM swap walked bin-by-bin from the active bin
+#[derive(Debug, Clone)]
+pub struct DlmmQuote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+    pub average_price: f64,
+    pub bins_crossed: usize,
+    pub end_bin_id: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_price(timestamp: i64) -> TokenPrice {
+        TokenPrice {
+            token_mint: Pubkey::new_unique(),
+            sol_price: 1.0,
+            usd_price: 150.0,
+            timestamp,
+            liquidity: 1_000,
+            confidence: 1.0,
+            source: PriceSource::Pool,
+        }
+    }
+
+    #[test]
+    fn price_is_stale_only_past_max_age() {
+        let max_age = Duration::from_secs(10);
+        let price = token_price(1_000);
+        assert!(!price.is_stale_at(max_age, 1_000));
+        assert!(!price.is_stale_at(max_age, 1_010));
+        assert!(price.is_stale_at(max_age, 1_011));
+    }
+
+    #[test]
+    fn trade_quote_round_trips_through_json() {
+        let (pool, input_mint, output_mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let quote = TradeQuote {
+            amount_out: 1_955_016,
+            min_amount_out: 1_935_465,
+            price_impact: 1.96,
+            fee_amount: 2_500,
+            referral_fee_amount: 0,
+            max_amount_in: 1_000_000,
+            route: vec![pool],
+            hops: vec![HopInfo {
+                pool,
+                input_mint,
+                output_mint,
+                amount_in: 1_000_000,
+                amount_out: 1_955_016,
+                fee_amount: 2_500,
+                price_impact: 1.96,
+            }],
+        };
+        let json = serde_json::to_string(&quote).unwrap();
+        assert_eq!(serde_json::from_str::<TradeQuote>(&json).unwrap(), quote);
+    }
+
+    #[test]
+    fn client_errors_keep_their_retryability() {
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        let error = MeteoraError::from(ClientError::from(io));
+        assert!(matches!(error, MeteoraError::RpcError(_)));
+        assert!(error.is_retryable());
+
+        let error = MeteoraError::from(ClientError::from(ClientErrorKind::TransactionError(
+            solana_sdk::transaction::TransactionError::InsufficientFundsForFee,
+        )));
+        assert!(matches!(error, MeteoraError::TransactionFailed(_)));
+        assert!(!error.is_retryable());
+
+        let json = serde_json::from_str::<u64>("not json").unwrap_err();
+        let error = MeteoraError::from(ClientError::from(json));
+        assert!(matches!(error, MeteoraError::DeserializationError(_)));
+    }
+
+    #[test]
+    fn preflight_failures_map_to_simulation_failed() {
+        use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+        let simulation = serde_json::from_value(serde_json::json!({
+            "err": { "InstructionError": [0, { "Custom": 6004 }] },
+            "logs": ["Program log: Exceeded slippage tolerance"],
+        }))
+        .unwrap();
+        let error = MeteoraError::from(ClientError::from(ClientErrorKind::RpcError(
+            RpcError::RpcResponseError {
+                code: -32002,
+                message: "Transaction simulation failed".to_string(),
+                data: RpcResponseErrorData::SendTransactionPreflightFailure(simulation),
+            },
+        )));
+        assert!(matches!(error, MeteoraError::SimulationFailed(_)));
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn program_errors_map_to_matching_variants() {
+        assert!(matches!(
+            MeteoraError::from(ProgramError::InvalidAccountData),
+            MeteoraError::DeserializationError(_)
+        ));
+        assert!(matches!(
+            MeteoraError::from(ProgramError::InvalidArgument),
+            MeteoraError::InvalidInput(_)
+        ));
+        assert!(matches!(
+            MeteoraError::from(ProgramError::Custom(1)),
+            MeteoraError::Error(_)
+        ));
+    }
 }